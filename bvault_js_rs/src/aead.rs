@@ -0,0 +1,72 @@
+//! Password-based encryption shared by every AEAD cipher suite.
+//!
+//! Each suite module only picks the [`Cipher`]; salt handling, key
+//! derivation and encoding live here so all suites behave identically.
+
+use crate::{
+    b64_to_bytes, derive_key, new_salted_key, random_bytes, BVaultError, Cipher, EncryptOptions,
+    EncryptedBytes, EncryptedData, ErrorCode, KdfParams,
+};
+
+pub(crate) fn encrypt(
+    cipher: Cipher,
+    plaintext: &[u8],
+    password: &str,
+    options: Option<EncryptOptions>,
+) -> Result<EncryptedData, BVaultError> {
+    let options = options.unwrap_or_default();
+    Ok(encrypt_bytes(cipher, plaintext, password, &options)?.encode(options.encoding))
+}
+
+pub(crate) fn encrypt_bytes(
+    cipher: Cipher,
+    plaintext: &[u8],
+    password: &str,
+    options: &EncryptOptions,
+) -> Result<EncryptedBytes, BVaultError> {
+    let (salt, key) = new_salted_key(password, options.salt_length, &options.kdf, Some(cipher))?;
+    let nonce = random_bytes(cipher.nonce_length())?;
+    let ciphertext = cipher.encrypt_with_aad(&key[..], &nonce, plaintext, &options.aad)?;
+
+    Ok(EncryptedBytes {
+        ciphertext,
+        iv: nonce,
+        salt,
+        mac: None,
+    })
+}
+
+pub(crate) fn decrypt(
+    cipher: Cipher,
+    b64_ciphertext: &str,
+    password: &str,
+    b64_nonce: &str,
+    b64_salt: &str,
+    kdf: Option<KdfParams>,
+    aad: &[u8],
+) -> Result<Vec<u8>, BVaultError> {
+    let ciphertext = b64_to_bytes(b64_ciphertext)?;
+    let nonce = b64_to_bytes(b64_nonce)?;
+    let salt = b64_to_bytes(b64_salt)?;
+    decrypt_bytes(cipher, &ciphertext, password, &nonce, &salt, kdf, aad)
+}
+
+pub(crate) fn decrypt_bytes(
+    cipher: Cipher,
+    ciphertext: &[u8],
+    password: &str,
+    nonce: &[u8],
+    salt: &[u8],
+    kdf: Option<KdfParams>,
+    aad: &[u8],
+) -> Result<Vec<u8>, BVaultError> {
+    if nonce.len() != cipher.nonce_length() {
+        return Err(BVaultError::new(
+            ErrorCode::BadIvLength,
+            format!("nonce must be {} bytes", cipher.nonce_length()),
+        ));
+    }
+
+    let key = derive_key(password, salt, kdf)?;
+    cipher.decrypt_with_aad(&key[..], nonce, ciphertext, aad)
+}
@@ -0,0 +1,119 @@
+//! Raw AES-CTR, for payloads written by systems that use it (Ethereum
+//! keystores' `aes-128-ctr`, the CTR stage of some S3 client-side
+//! encryption schemes).
+//!
+//! CTR has no authentication: flipping a ciphertext bit flips the same
+//! plaintext bit and nothing notices. Only use it to read or write formats
+//! that carry their own MAC; new data belongs in an envelope.
+//!
+//! The caller passes the full 16-byte initial counter block and how many of
+//! its trailing bits form the big-endian counter: 128 (the whole block, as
+//! in Ethereum keystores and OpenSSL's `aes-*-ctr`), 64, or 32 (as in GCM,
+//! whose keystream starts at `nonce || 00000002`). The rest of the block
+//! stays fixed, and the counter wraps within its bits.
+
+use aes::{Aes128, Aes192, Aes256};
+use ctr::cipher::{KeyIvInit, StreamCipher};
+use ctr::{Ctr128BE, Ctr32BE, Ctr64BE};
+use wasm_bindgen::prelude::*;
+
+use crate::{BVaultError, ErrorCode};
+
+/// Length of the initial counter block.
+const BLOCK_SIZE: usize = 16;
+/// Default counter width, in bits.
+const DEFAULT_COUNTER_BITS: u32 = 128;
+
+fn keystream<C: KeyIvInit + StreamCipher>(
+    key: &[u8],
+    block: &[u8],
+    data: &mut [u8],
+) -> Result<(), BVaultError> {
+    C::new_from_slices(key, block)
+        .map_err(|_| BVaultError::new(ErrorCode::BadKeyLength, "invalid key length"))?
+        .try_apply_keystream(data)
+        .map_err(|_| {
+            BVaultError::new(
+                ErrorCode::BadLength,
+                "data is too long for the counter width",
+            )
+        })
+}
+
+fn apply(
+    key: &[u8],
+    counter_block: &[u8],
+    data: &[u8],
+    counter_bits: Option<u32>,
+) -> Result<Vec<u8>, BVaultError> {
+    if counter_block.len() != BLOCK_SIZE {
+        return Err(BVaultError::new(
+            ErrorCode::BadIvLength,
+            "counter block must be 16 bytes",
+        ));
+    }
+    let mut out = data.to_vec();
+    match (key.len(), counter_bits.unwrap_or(DEFAULT_COUNTER_BITS)) {
+        (16, 128) => keystream::<Ctr128BE<Aes128>>(key, counter_block, &mut out),
+        (24, 128) => keystream::<Ctr128BE<Aes192>>(key, counter_block, &mut out),
+        (32, 128) => keystream::<Ctr128BE<Aes256>>(key, counter_block, &mut out),
+        (16, 64) => keystream::<Ctr64BE<Aes128>>(key, counter_block, &mut out),
+        (24, 64) => keystream::<Ctr64BE<Aes192>>(key, counter_block, &mut out),
+        (32, 64) => keystream::<Ctr64BE<Aes256>>(key, counter_block, &mut out),
+        (16, 32) => keystream::<Ctr32BE<Aes128>>(key, counter_block, &mut out),
+        (24, 32) => keystream::<Ctr32BE<Aes192>>(key, counter_block, &mut out),
+        (32, 32) => keystream::<Ctr32BE<Aes256>>(key, counter_block, &mut out),
+        (16 | 24 | 32, _) => Err(BVaultError::new(
+            ErrorCode::InvalidArgument,
+            "counter width must be 32, 64 or 128 bits",
+        )),
+        _ => Err(BVaultError::new(
+            ErrorCode::BadKeyLength,
+            "AES keys must be 16, 24 or 32 bytes",
+        )),
+    }?;
+    Ok(out)
+}
+
+/// Encrypts `plaintext` with AES-CTR (AES-128, -192 or -256 by key length)
+/// starting from `counter_block`. `counter_bits` defaults to 128.
+///
+/// Never reuse a counter block with the same key.
+///
+/// # Errors
+///
+/// - If the key is not 16, 24 or 32 bytes, or the counter block not 16
+///   bytes, an error is returned.
+/// - If `counter_bits` is not 32, 64 or 128, an error is returned.
+/// - If the plaintext is longer than the counter can address (2^32 blocks
+///   for a 32-bit counter), an error is returned.
+#[wasm_bindgen]
+pub fn aes_ctr_encrypt(
+    plaintext: &[u8],
+    key: &[u8],
+    counter_block: &[u8],
+    counter_bits: Option<u32>,
+) -> Result<Vec<u8>, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    apply(key, counter_block, plaintext, counter_bits)
+}
+
+/// Decrypts an AES-CTR ciphertext; the inverse of [`aes_ctr_encrypt`]
+/// given the same key, counter block and counter width.
+///
+/// Nothing is verified: a wrong key or tampered data yields garbage rather
+/// than an error.
+///
+/// # Errors
+///
+/// See [`aes_ctr_encrypt`].
+#[wasm_bindgen]
+pub fn aes_ctr_decrypt(
+    ciphertext: &[u8],
+    key: &[u8],
+    counter_block: &[u8],
+    counter_bits: Option<u32>,
+) -> Result<Vec<u8>, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    apply(key, counter_block, ciphertext, counter_bits)
+}
@@ -0,0 +1,556 @@
+//! The age v1 file format (<https://age-encryption.org/v1>), so vault
+//! exports interoperate with `age`, `rage` and the rest of the ecosystem.
+//!
+//! A file is a text header followed by the binary payload:
+//!
+//! ```text
+//! age-encryption.org/v1
+//! -> scrypt <salt> <log2 N>          one stanza per recipient: the
+//! <wrapped file key, base64>         16-byte file key wrapped for it
+//! --- <header MAC, base64>
+//! <payload nonce, 16 bytes><STREAM chunks>
+//! ```
+//!
+//! The payload is ChaCha20-Poly1305 in the STREAM construction: 64 KiB
+//! chunks, each with an 11-byte big-endian counter and a final-chunk flag
+//! as its nonce. Passphrase files carry a single `scrypt` stanza; public
+//! key files carry one `X25519` stanza per recipient (`age1…` strings).
+//! Only the binary encoding is supported, not the ASCII armor.
+
+use std::convert::TryFrom;
+
+use base64::engine::general_purpose::STANDARD_NO_PAD;
+use base64::Engine;
+use bech32::primitives::decode::CheckedHrpstring;
+use bech32::{Bech32, Hrp};
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::ChaCha20Poly1305;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use wasm_bindgen::prelude::*;
+use x25519_dalek::{PublicKey, StaticSecret};
+use zeroize::Zeroizing;
+
+use crate::envelope::invalid;
+use crate::kcv::wrong_password;
+use crate::kdf::{hkdf_sha256, KEY_LENGTH};
+use crate::{random_bytes, BVaultError, ErrorCode};
+
+const VERSION_LINE: &str = "age-encryption.org/v1";
+const SCRYPT_LABEL: &[u8] = b"age-encryption.org/v1/scrypt";
+const X25519_LABEL: &[u8] = b"age-encryption.org/v1/X25519";
+
+const RECIPIENT_HRP: &str = "age";
+const IDENTITY_HRP: &str = "AGE-SECRET-KEY-";
+
+const FILE_KEY_LENGTH: usize = 16;
+const WRAPPED_KEY_LENGTH: usize = FILE_KEY_LENGTH + TAG_LENGTH;
+const SCRYPT_SALT_LENGTH: usize = 16;
+const PAYLOAD_NONCE_LENGTH: usize = 16;
+const TAG_LENGTH: usize = 16;
+const CHUNK_SIZE: usize = 64 * 1024;
+const LINE_WIDTH: usize = 64;
+
+/// scrypt work factor used by `age -p`.
+const DEFAULT_WORK_FACTOR: u8 = 18;
+/// Highest work factor accepted, so a hostile file cannot demand gigabytes
+/// of memory.
+const MAX_WORK_FACTOR: u8 = 22;
+
+type FileKey = Zeroizing<[u8; FILE_KEY_LENGTH]>;
+
+/// One recipient stanza: `-> tag args...` and its body.
+struct Stanza {
+    tag: String,
+    args: Vec<String>,
+    body: Vec<u8>,
+}
+
+/// A parsed header and where the payload starts.
+struct Header<'a> {
+    stanzas: Vec<Stanza>,
+    /// The bytes the MAC covers: everything up to and including `---`.
+    mac_input: &'a [u8],
+    mac: Vec<u8>,
+    payload: &'a [u8],
+}
+
+fn malformed(message: &str) -> BVaultError {
+    invalid(format!("malformed age header: {}", message))
+}
+
+fn decode_b64(string: &str) -> Result<Vec<u8>, BVaultError> {
+    STANDARD_NO_PAD
+        .decode(string)
+        .map_err(|_| BVaultError::new(ErrorCode::InvalidBase64, "invalid base64 in age header"))
+}
+
+impl<'a> Header<'a> {
+    fn parse(file: &'a [u8]) -> Result<Header<'a>, BVaultError> {
+        let mut pos = 0;
+        let mut next_line = || -> Result<&'a str, BVaultError> {
+            let end = file[pos..]
+                .iter()
+                .position(|&b| b == b'\n')
+                .ok_or_else(|| malformed("truncated"))?;
+            let line = std::str::from_utf8(&file[pos..pos + end])
+                .map_err(|_| malformed("not valid utf-8"))?;
+            pos += end + 1;
+            Ok(line)
+        };
+
+        if next_line()? != VERSION_LINE {
+            return Err(invalid("not an age v1 file"));
+        }
+
+        let mut stanzas = Vec::new();
+        let mut line = next_line()?;
+        while let Some(rest) = line.strip_prefix("-> ") {
+            let mut args = rest.split(' ').map(str::to_string);
+            let tag = args.next().unwrap_or_default();
+            let args: Vec<String> = args.collect();
+            if tag.is_empty() || args.iter().any(String::is_empty) {
+                return Err(malformed("empty stanza argument"));
+            }
+
+            let mut b64 = String::new();
+            loop {
+                let body_line = next_line()?;
+                if body_line.len() > LINE_WIDTH {
+                    return Err(malformed("stanza line too long"));
+                }
+                b64.push_str(body_line);
+                if body_line.len() < LINE_WIDTH {
+                    break;
+                }
+            }
+            stanzas.push(Stanza {
+                tag,
+                args,
+                body: decode_b64(&b64)?,
+            });
+            line = next_line()?;
+        }
+
+        let b64_mac = line
+            .strip_prefix("--- ")
+            .ok_or_else(|| malformed("expected a stanza or the MAC line"))?;
+        if stanzas.is_empty() {
+            return Err(malformed("no recipient stanzas"));
+        }
+        // `pos` is just past the MAC line's newline.
+        let mac_end = pos - 1 - b64_mac.len() - 1;
+
+        Ok(Header {
+            stanzas,
+            mac_input: &file[..mac_end],
+            mac: decode_b64(b64_mac)?,
+            payload: &file[pos..],
+        })
+    }
+}
+
+/// Writes the header up to and including `---`, ready to be MACed.
+fn write_header(stanzas: &[Stanza]) -> Vec<u8> {
+    let mut out = Vec::new();
+    out.extend_from_slice(VERSION_LINE.as_bytes());
+    out.push(b'\n');
+    for stanza in stanzas {
+        out.extend_from_slice(b"-> ");
+        out.extend_from_slice(stanza.tag.as_bytes());
+        for arg in &stanza.args {
+            out.push(b' ');
+            out.extend_from_slice(arg.as_bytes());
+        }
+        out.push(b'\n');
+
+        let b64 = STANDARD_NO_PAD.encode(&stanza.body);
+        for line in b64.as_bytes().chunks(LINE_WIDTH) {
+            out.extend_from_slice(line);
+            out.push(b'\n');
+        }
+        // The body ends with a line shorter than the full width.
+        if b64.len() % LINE_WIDTH == 0 {
+            out.push(b'\n');
+        }
+    }
+    out.extend_from_slice(b"---");
+    out
+}
+
+fn header_mac(file_key: &[u8], mac_input: &[u8]) -> Result<Hmac<Sha256>, BVaultError> {
+    let mut key = Zeroizing::new([0u8; KEY_LENGTH]);
+    hkdf_sha256(file_key, None, b"header", &mut key[..])?;
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(&key[..])
+        .map_err(|_| BVaultError::new(ErrorCode::BadKeyLength, "invalid HMAC key"))?;
+    mac.update(mac_input);
+    Ok(mac)
+}
+
+/// Wraps or unwraps a file key under a stanza's 32-byte key, with the
+/// all-zero nonce age uses for key wrapping.
+fn wrap_key(key: &[u8], file_key: &[u8]) -> Result<Vec<u8>, BVaultError> {
+    ChaCha20Poly1305::new_from_slice(key)
+        .map_err(|_| BVaultError::new(ErrorCode::BadKeyLength, "invalid key length"))?
+        .encrypt(&Default::default(), file_key)
+        .map_err(|_| BVaultError::new(ErrorCode::EncryptionFailed, "encryption error"))
+}
+
+fn unwrap_key(key: &[u8], body: &[u8]) -> Option<FileKey> {
+    if body.len() != WRAPPED_KEY_LENGTH {
+        return None;
+    }
+    let plaintext = Zeroizing::new(
+        ChaCha20Poly1305::new_from_slice(key)
+            .ok()?
+            .decrypt(&Default::default(), body)
+            .ok()?,
+    );
+    let mut file_key = FileKey::default();
+    file_key.copy_from_slice(&plaintext);
+    Some(file_key)
+}
+
+fn payload_cipher(file_key: &[u8], nonce: &[u8]) -> Result<ChaCha20Poly1305, BVaultError> {
+    let mut key = Zeroizing::new([0u8; KEY_LENGTH]);
+    hkdf_sha256(file_key, Some(nonce), b"payload", &mut key[..])?;
+    ChaCha20Poly1305::new_from_slice(&key[..])
+        .map_err(|_| BVaultError::new(ErrorCode::BadKeyLength, "invalid key length"))
+}
+
+fn chunk_nonce(counter: u64, last: bool) -> [u8; 12] {
+    let mut nonce = [0u8; 12];
+    nonce[3..11].copy_from_slice(&counter.to_be_bytes());
+    nonce[11] = last as u8;
+    nonce
+}
+
+/// Builds a complete age file around `file_key` and its recipient stanzas.
+fn encrypt_with(
+    file_key: &FileKey,
+    stanzas: &[Stanza],
+    plaintext: &[u8],
+) -> Result<Vec<u8>, BVaultError> {
+    let mut out = write_header(stanzas);
+    let mac = header_mac(&file_key[..], &out)?.finalize().into_bytes();
+    out.push(b' ');
+    out.extend_from_slice(STANDARD_NO_PAD.encode(mac).as_bytes());
+    out.push(b'\n');
+
+    let nonce = random_bytes(PAYLOAD_NONCE_LENGTH)?;
+    out.extend_from_slice(&nonce);
+    let cipher = payload_cipher(&file_key[..], &nonce)?;
+
+    // An empty plaintext still gets one (empty) final chunk.
+    let chunk_count = plaintext.len().div_ceil(CHUNK_SIZE).max(1);
+    for i in 0..chunk_count {
+        let end = ((i + 1) * CHUNK_SIZE).min(plaintext.len());
+        let chunk = &plaintext[i * CHUNK_SIZE..end];
+        let nonce = chunk_nonce(i as u64, i + 1 == chunk_count);
+        let sealed = cipher
+            .encrypt(&nonce.into(), chunk)
+            .map_err(|_| BVaultError::new(ErrorCode::EncryptionFailed, "encryption error"))?;
+        out.extend_from_slice(&sealed);
+    }
+    Ok(out)
+}
+
+/// Verifies the header MAC and decrypts the payload.
+fn decrypt_with(file_key: &FileKey, header: &Header<'_>) -> Result<Vec<u8>, BVaultError> {
+    header_mac(&file_key[..], header.mac_input)?
+        .verify_slice(&header.mac)
+        .map_err(|_| {
+            BVaultError::decryption_failed(ErrorCode::AuthFailed, "age header MAC mismatch")
+        })?;
+
+    if header.payload.len() < PAYLOAD_NONCE_LENGTH {
+        return Err(invalid("age payload is truncated"));
+    }
+    let (nonce, mut rest) = header.payload.split_at(PAYLOAD_NONCE_LENGTH);
+    let cipher = payload_cipher(&file_key[..], nonce)?;
+
+    let mut plaintext = Vec::with_capacity(rest.len());
+    let mut counter = 0u64;
+    loop {
+        let last = rest.len() <= CHUNK_SIZE + TAG_LENGTH;
+        let (chunk, tail) = rest.split_at(rest.len().min(CHUNK_SIZE + TAG_LENGTH));
+        let opened = cipher
+            .decrypt(&chunk_nonce(counter, last).into(), chunk)
+            .map_err(|_| {
+                BVaultError::decryption_failed(
+                    ErrorCode::AuthFailed,
+                    "age payload authentication failed",
+                )
+            })?;
+        if last && opened.is_empty() && counter > 0 {
+            return Err(invalid("age payload ends with an empty chunk"));
+        }
+        plaintext.extend_from_slice(&opened);
+        if last {
+            return Ok(plaintext);
+        }
+        rest = tail;
+        counter += 1;
+    }
+}
+
+fn new_file_key() -> Result<FileKey, BVaultError> {
+    let mut file_key = FileKey::default();
+    file_key.copy_from_slice(&random_bytes(FILE_KEY_LENGTH)?);
+    Ok(file_key)
+}
+
+fn scrypt_key(
+    passphrase: &str,
+    salt: &[u8],
+    log_n: u8,
+) -> Result<Zeroizing<[u8; KEY_LENGTH]>, BVaultError> {
+    let mut labeled = SCRYPT_LABEL.to_vec();
+    labeled.extend_from_slice(salt);
+    let params = scrypt::Params::new(log_n, 8, 1, KEY_LENGTH)
+        .map_err(|_| BVaultError::new(ErrorCode::WeakKdfParams, "invalid scrypt work factor"))?;
+    let mut key = Zeroizing::new([0u8; KEY_LENGTH]);
+    scrypt::scrypt(passphrase.as_bytes(), &labeled, &params, &mut key[..])
+        .map_err(|_| BVaultError::new(ErrorCode::KdfFailed, "key derivation failed"))?;
+    Ok(key)
+}
+
+/// Encrypts `plaintext` to a passphrase, like `age -p`.
+///
+/// `work_factor` is the scrypt log2(N), 1 to 22 (default 18, as `age`
+/// uses).
+///
+/// # Errors
+///
+/// - If `work_factor` is out of range, an error is returned.
+/// - If the random number generator is unavailable, an error is returned.
+#[wasm_bindgen]
+pub fn age_encrypt_passphrase(
+    plaintext: &[u8],
+    passphrase: &str,
+    work_factor: Option<u8>,
+) -> Result<Vec<u8>, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    let log_n = work_factor.unwrap_or(DEFAULT_WORK_FACTOR);
+    if log_n == 0 || log_n > MAX_WORK_FACTOR {
+        return Err(BVaultError::new(
+            ErrorCode::InvalidArgument,
+            "work factor must be 1 to 22",
+        ));
+    }
+
+    let file_key = new_file_key()?;
+    let salt = random_bytes(SCRYPT_SALT_LENGTH)?;
+    let key = scrypt_key(passphrase, &salt, log_n)?;
+    let stanza = Stanza {
+        tag: "scrypt".to_string(),
+        args: vec![STANDARD_NO_PAD.encode(&salt), log_n.to_string()],
+        body: wrap_key(&key[..], &file_key[..])?,
+    };
+    encrypt_with(&file_key, &[stanza], plaintext)
+}
+
+/// Decrypts a passphrase-encrypted age file.
+///
+/// # Errors
+///
+/// - If the file is malformed, is not passphrase-encrypted, or asks for a
+///   work factor above 22, an error is returned.
+/// - If the passphrase is wrong, an [`ErrorCode::WrongPassword`] error is
+///   returned.
+/// - If the header or payload fails authentication, an error is returned.
+#[wasm_bindgen]
+pub fn age_decrypt_passphrase(file: &[u8], passphrase: &str) -> Result<Vec<u8>, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    let header = Header::parse(file)?;
+    let stanza = match header.stanzas.as_slice() {
+        [stanza] if stanza.tag == "scrypt" => stanza,
+        stanzas if stanzas.iter().any(|s| s.tag == "scrypt") => {
+            return Err(malformed("an scrypt stanza must be the only one"))
+        }
+        _ => {
+            return Err(BVaultError::new(
+                ErrorCode::InvalidArgument,
+                "file is not passphrase-encrypted",
+            ))
+        }
+    };
+
+    let (salt, log_n) = match stanza.args.as_slice() {
+        [salt, log_n] => (decode_b64(salt)?, log_n),
+        _ => return Err(malformed("scrypt stanza needs a salt and work factor")),
+    };
+    if salt.len() != SCRYPT_SALT_LENGTH {
+        return Err(malformed("scrypt salt must be 16 bytes"));
+    }
+    let log_n: u8 = match log_n.parse() {
+        Ok(n) if !log_n.starts_with('0') && n > 0 => n,
+        _ => return Err(malformed("invalid scrypt work factor")),
+    };
+    if log_n > MAX_WORK_FACTOR {
+        return Err(BVaultError::new(
+            ErrorCode::WeakKdfParams,
+            "scrypt work factor is above 22",
+        ));
+    }
+
+    let key = scrypt_key(passphrase, &salt, log_n)?;
+    let file_key = unwrap_key(&key[..], &stanza.body).ok_or_else(wrong_password)?;
+    decrypt_with(&file_key, &header)
+}
+
+fn parse_bech32(string: &str, hrp: &str) -> Result<[u8; 32], BVaultError> {
+    let checked = CheckedHrpstring::new::<Bech32>(string)
+        .map_err(|_| BVaultError::new(ErrorCode::InvalidArgument, "invalid bech32 key"))?;
+    if !checked.hrp().as_str().eq_ignore_ascii_case(hrp) {
+        return Err(BVaultError::new(
+            ErrorCode::InvalidArgument,
+            format!("expected a key starting with {}1", hrp),
+        ));
+    }
+    let bytes: Vec<u8> = checked.byte_iter().collect();
+    <[u8; 32]>::try_from(bytes.as_slice())
+        .map_err(|_| BVaultError::new(ErrorCode::BadKeyLength, "age keys must be 32 bytes"))
+}
+
+fn encode_bech32(hrp: &str, bytes: &[u8], upper: bool) -> String {
+    // Both HRPs are valid constants and 32 bytes fit any bech32 length.
+    let hrp = Hrp::parse(hrp).expect("valid hrp");
+    if upper {
+        bech32::encode_upper::<Bech32>(hrp, bytes).expect("encodable")
+    } else {
+        bech32::encode_lower::<Bech32>(hrp, bytes).expect("encodable")
+    }
+}
+
+fn parse_identity(identity: &str) -> Result<StaticSecret, BVaultError> {
+    let mut bytes = parse_bech32(identity.trim(), IDENTITY_HRP)?;
+    let secret = StaticSecret::from(bytes);
+    zeroize::Zeroize::zeroize(&mut bytes);
+    Ok(secret)
+}
+
+/// Generates a new X25519 identity (`AGE-SECRET-KEY-1…`), like
+/// `age-keygen`.
+///
+/// # Errors
+///
+/// - If the random number generator is unavailable, an error is returned.
+#[wasm_bindgen]
+pub fn age_generate_identity() -> Result<String, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    let secret = Zeroizing::new(random_bytes(KEY_LENGTH)?);
+    Ok(encode_bech32(IDENTITY_HRP, &secret, true))
+}
+
+/// The recipient (`age1…`) for an identity, like `age-keygen -y`.
+///
+/// # Errors
+///
+/// - If the identity is not a valid age secret key, an error is returned.
+#[wasm_bindgen]
+pub fn age_identity_to_recipient(identity: &str) -> Result<String, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    let public = PublicKey::from(&parse_identity(identity)?);
+    Ok(encode_bech32(RECIPIENT_HRP, public.as_bytes(), false))
+}
+
+/// The key wrapping a file key for one X25519 recipient.
+fn x25519_wrap_key(
+    shared: &x25519_dalek::SharedSecret,
+    share: &PublicKey,
+    recipient: &PublicKey,
+) -> Result<Zeroizing<[u8; KEY_LENGTH]>, BVaultError> {
+    if !shared.was_contributory() {
+        return Err(invalid("age X25519 share is a low-order point"));
+    }
+    let mut salt = share.as_bytes().to_vec();
+    salt.extend_from_slice(recipient.as_bytes());
+    let mut key = Zeroizing::new([0u8; KEY_LENGTH]);
+    hkdf_sha256(shared.as_bytes(), Some(&salt), X25519_LABEL, &mut key[..])?;
+    Ok(key)
+}
+
+/// Encrypts `plaintext` to one or more X25519 recipients (`age1…`), like
+/// `age -r`.
+///
+/// # Errors
+///
+/// - If there are no recipients or one is invalid, an error is returned.
+/// - If the random number generator is unavailable, an error is returned.
+#[wasm_bindgen]
+pub fn age_encrypt(plaintext: &[u8], recipients: Vec<String>) -> Result<Vec<u8>, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    if recipients.is_empty() {
+        return Err(BVaultError::new(
+            ErrorCode::InvalidArgument,
+            "at least one recipient is required",
+        ));
+    }
+
+    let file_key = new_file_key()?;
+    let mut stanzas = Vec::with_capacity(recipients.len());
+    for recipient in &recipients {
+        let recipient = PublicKey::from(parse_bech32(recipient.trim(), RECIPIENT_HRP)?);
+        let mut ephemeral = Zeroizing::new([0u8; KEY_LENGTH]);
+        ephemeral.copy_from_slice(&random_bytes(KEY_LENGTH)?);
+        let ephemeral = StaticSecret::from(*ephemeral);
+        let share = PublicKey::from(&ephemeral);
+
+        let shared = ephemeral.diffie_hellman(&recipient);
+        let key = x25519_wrap_key(&shared, &share, &recipient)?;
+        stanzas.push(Stanza {
+            tag: "X25519".to_string(),
+            args: vec![STANDARD_NO_PAD.encode(share.as_bytes())],
+            body: wrap_key(&key[..], &file_key[..])?,
+        });
+    }
+    encrypt_with(&file_key, &stanzas, plaintext)
+}
+
+/// Decrypts an age file with an X25519 identity (`AGE-SECRET-KEY-1…`).
+///
+/// Stanzas for other recipient types are skipped.
+///
+/// # Errors
+///
+/// - If the file or identity is malformed, an error is returned.
+/// - If no stanza is addressed to the identity, an
+///   [`ErrorCode::WrongPassword`] error is returned.
+/// - If the header or payload fails authentication, an error is returned.
+#[wasm_bindgen]
+pub fn age_decrypt(file: &[u8], identity: &str) -> Result<Vec<u8>, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    let secret = parse_identity(identity)?;
+    let public = PublicKey::from(&secret);
+    let header = Header::parse(file)?;
+    if header.stanzas.iter().any(|s| s.tag == "scrypt") {
+        return Err(BVaultError::new(
+            ErrorCode::InvalidArgument,
+            "file is passphrase-encrypted",
+        ));
+    }
+
+    for stanza in header.stanzas.iter().filter(|s| s.tag == "X25519") {
+        let share = match stanza.args.as_slice() {
+            [share] => decode_b64(share)?,
+            _ => return Err(malformed("X25519 stanza needs one share")),
+        };
+        let share = <[u8; 32]>::try_from(share.as_slice())
+            .map_err(|_| malformed("X25519 share must be 32 bytes"))?;
+        let share = PublicKey::from(share);
+        if stanza.body.len() != WRAPPED_KEY_LENGTH {
+            return Err(malformed("X25519 body must be 32 bytes"));
+        }
+
+        let shared = secret.diffie_hellman(&share);
+        let key = x25519_wrap_key(&shared, &share, &public)?;
+        if let Some(file_key) = unwrap_key(&key[..], &stanza.body) {
+            return decrypt_with(&file_key, &header);
+        }
+    }
+    Err(BVaultError::new(
+        ErrorCode::WrongPassword,
+        "no stanza is addressed to this identity",
+    ))
+}
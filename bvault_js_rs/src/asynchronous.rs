@@ -0,0 +1,122 @@
+//! Promise-returning variants of the synchronous CBC API.
+//!
+//! Key derivation dominates the cost of a decryption and blocks the main
+//! thread for hundreds of milliseconds. These functions run the same
+//! computation but yield back to the event loop between KDF batches, so
+//! the UI keeps painting while a vault unlocks.
+
+use wasm_bindgen::prelude::*;
+use zeroize::Zeroizing;
+
+use crate::cancel::AbortSignal;
+use crate::legacy::{cbc_decrypt_with_key, cbc_encrypt_with_key, decode_cbc_inputs};
+use crate::{
+    b64_to_bytes, mac, new_salt, random_bytes, BVaultError, EncryptOptions, EncryptedBytes,
+    EncryptedData, KdfParams, TextDecoding, IV_LENGTH,
+};
+
+/// Asynchronous [`crate::encrypt_sync`]; resolves to the same
+/// [`EncryptedData`].
+///
+/// `on_progress`, if given, is called with the key derivation progress in
+/// percent (0 to 100). PBKDF2 reports as it goes; Argon2id and scrypt only
+/// report the start and the end.
+///
+/// `signal`, if given, cancels the call when aborted: the promise rejects
+/// with an [`crate::ErrorCode::Aborted`] error within one KDF batch.
+///
+/// # Errors
+///
+/// See [`crate::encrypt_sync`]; errors reject the returned promise.
+#[wasm_bindgen]
+pub async fn encrypt_async(
+    plaintext: String,
+    password: String,
+    options: Option<EncryptOptions>,
+    on_progress: Option<js_sys::Function>,
+    signal: Option<AbortSignal>,
+) -> Result<EncryptedData, BVaultError> {
+    crate::utils::in_operation(async move {
+        let password = Zeroizing::new(password);
+        let plaintext = Zeroizing::new(plaintext);
+        let options = options.unwrap_or_default();
+        options.reject_aad()?;
+
+        let salt = new_salt(&password, options.salt_length, &options.kdf, None)?;
+        let iv = random_bytes(IV_LENGTH)?;
+        let key = options
+            .kdf
+            .derive_async(
+                password.as_bytes(),
+                &salt,
+                on_progress.as_ref(),
+                signal.as_ref(),
+            )
+            .await?;
+        let ciphertext = cbc_encrypt_with_key(&key[..], &iv, plaintext.as_bytes())?;
+        let tag = mac::tag(&key[..], &iv, &ciphertext)?;
+
+        Ok(EncryptedBytes {
+            ciphertext,
+            iv,
+            salt,
+            mac: Some(tag),
+        }
+        .encode(options.encoding))
+    })
+    .await
+}
+
+/// Asynchronous [`crate::decrypt_sync`].
+///
+/// `on_progress` and `signal` behave as in [`encrypt_async`], and `b64_mac`
+/// and `decoding` as in [`crate::decrypt_sync`].
+///
+/// # Errors
+///
+/// See [`crate::decrypt_sync`]; errors reject the returned promise.
+// Positional to match `decrypt_sync` for JS callers.
+#[allow(clippy::too_many_arguments)]
+#[wasm_bindgen]
+pub async fn decrypt_async(
+    b64_ciphertext: String,
+    password: String,
+    b64_iv: String,
+    b64_salt: String,
+    kdf: Option<KdfParams>,
+    on_progress: Option<js_sys::Function>,
+    b64_mac: Option<String>,
+    signal: Option<AbortSignal>,
+    decoding: Option<TextDecoding>,
+) -> Result<String, BVaultError> {
+    crate::utils::in_operation(async move {
+        let password = Zeroizing::new(password);
+
+        crate::policy::check_input(|| {
+            crate::validation::diagnose_legacy(
+                &b64_ciphertext,
+                &b64_iv,
+                &b64_salt,
+                kdf.as_ref(),
+                b64_mac.as_deref(),
+            )
+        })?;
+        let inputs = decode_cbc_inputs(&b64_ciphertext, &b64_iv, &b64_salt)?;
+        let kdf = kdf.unwrap_or_default();
+        crate::policy::check_decrypt(&password, inputs.salt.len(), &kdf);
+        let key = kdf
+            .derive_async(
+                password.as_bytes(),
+                &inputs.salt,
+                on_progress.as_ref(),
+                signal.as_ref(),
+            )
+            .await?;
+        let tag = b64_mac.as_deref().map(b64_to_bytes).transpose()?;
+        let plaintext =
+            cbc_decrypt_with_key(&key[..], &inputs.iv, inputs.ciphertext, tag.as_deref())?;
+
+        crate::text::decode_plaintext(plaintext, decoding)
+    })
+    .await
+}
@@ -0,0 +1,281 @@
+//! Self-contained backup archives of a vault and its attachments, for
+//! offline copies users can keep anywhere.
+//!
+//! A backup holds the vault blob as [`Vault::to_bytes`] writes it (its
+//! header names the KDF parameters and salt, so the password alone restores
+//! it), the app's attachments, and a manifest of every section's SHA-256.
+//! The manifest is authenticated with HMAC-SHA256 under a key derived from
+//! the vault's master key, so [`import_backup`] detects any change to any
+//! section before it returns anything. All integers are big-endian:
+//!
+//! ```text
+//! magic     4      "BVLB"
+//! version   1      currently 1
+//! created   8      ms since the Unix epoch
+//! count     4      number of sections, then per section:
+//!   name    2 + n  utf-8, length-prefixed; empty for the vault
+//!   length  4
+//!   sha256  32     of the section's bytes
+//! mac       32     HMAC-SHA256 of everything above
+//! sections  rest   the vault first, then the attachments in name order,
+//!                  each a raw-key envelope under a second derived key
+//! ```
+
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+use wasm_bindgen::prelude::*;
+use zeroize::Zeroizing;
+
+use crate::envelope::{check_version, invalid, Reader};
+use crate::{open_with_raw_key, seal_with_raw_key, BVaultError, ErrorCode, OpenOptions, Vault};
+
+const MAGIC: &[u8; 4] = b"BVLB";
+const VERSION: u8 = 1;
+
+const MAC_KEY_INFO: &[u8] = b"bvault-backup-manifest";
+const ATTACHMENT_KEY_INFO: &[u8] = b"bvault-backup-attachments";
+const MAC_LENGTH: usize = 32;
+const HASH_LENGTH: usize = 32;
+
+/// Named files stored in a backup alongside the vault, such as the
+/// attachments of its entries.
+#[wasm_bindgen]
+#[derive(Clone, Default)]
+pub struct BackupAttachments {
+    files: BTreeMap<String, Zeroizing<Vec<u8>>>,
+}
+
+#[wasm_bindgen]
+impl BackupAttachments {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> BackupAttachments {
+        BackupAttachments::default()
+    }
+
+    /// Adds the file `name` with contents `data`.
+    ///
+    /// # Errors
+    ///
+    /// - If `name` is empty, longer than 65 535 bytes or already added, an
+    ///   [`ErrorCode::InvalidArgument`] error is returned.
+    pub fn add(&mut self, name: &str, data: &[u8]) -> Result<(), BVaultError> {
+        if name.is_empty() || name.len() > u16::MAX as usize {
+            return Err(BVaultError::new(
+                ErrorCode::InvalidArgument,
+                "attachment names must be 1 to 65535 bytes",
+            ));
+        }
+        if self.files.contains_key(name) {
+            return Err(BVaultError::new(
+                ErrorCode::InvalidArgument,
+                format!("attachment {} was already added", name),
+            ));
+        }
+        self.files
+            .insert(name.to_string(), Zeroizing::new(data.to_vec()));
+        Ok(())
+    }
+
+    /// The names of the files, in order.
+    pub fn names(&self) -> Vec<String> {
+        self.files.keys().cloned().collect()
+    }
+
+    /// The contents of the file `name`.
+    ///
+    /// # Errors
+    ///
+    /// - If there is no such file, an [`ErrorCode::EntryNotFound`] error is
+    ///   returned.
+    pub fn get(&self, name: &str) -> Result<Vec<u8>, BVaultError> {
+        self.files
+            .get(name)
+            .map(|data| data.to_vec())
+            .ok_or_else(|| {
+                BVaultError::new(
+                    ErrorCode::EntryNotFound,
+                    format!("no attachment named {}", name),
+                )
+            })
+    }
+}
+
+/// What [`import_backup`] restored.
+#[wasm_bindgen]
+pub struct RestoredBackup {
+    vault: Option<Vault>,
+    attachments: BackupAttachments,
+    created_at: f64,
+}
+
+#[wasm_bindgen]
+impl RestoredBackup {
+    /// The unlocked vault. It can only be taken once.
+    ///
+    /// # Errors
+    ///
+    /// - If it was already taken, an [`ErrorCode::InvalidArgument`] error
+    ///   is returned.
+    pub fn take_vault(&mut self) -> Result<Vault, BVaultError> {
+        self.vault.take().ok_or_else(|| {
+            BVaultError::new(
+                ErrorCode::InvalidArgument,
+                "the vault was already taken from this backup",
+            )
+        })
+    }
+
+    /// The attachments, decrypted.
+    #[wasm_bindgen(getter)]
+    pub fn attachments(&self) -> BackupAttachments {
+        self.attachments.clone()
+    }
+
+    /// When the backup was made, in ms since the Unix epoch.
+    #[wasm_bindgen(getter)]
+    pub fn created_at(&self) -> f64 {
+        self.created_at
+    }
+}
+
+fn manifest_mac(vault: &Vault) -> Result<Hmac<Sha256>, BVaultError> {
+    let key = vault.subkey(MAC_KEY_INFO)?;
+    Ok(Hmac::<Sha256>::new_from_slice(&key[..]).expect("HMAC takes any key length"))
+}
+
+fn section_length(section: &[u8]) -> Result<u32, BVaultError> {
+    u32::try_from(section.len()).map_err(|_| {
+        BVaultError::new(
+            ErrorCode::InvalidArgument,
+            "backup sections must be under 4 GiB",
+        )
+    })
+}
+
+/// Writes a backup of `vault` and `attachments`. `password` must be the
+/// vault's, so an unlocked vault left unattended cannot be exported.
+///
+/// # Errors
+///
+/// - If `password` is not the vault's, an [`ErrorCode::WrongPassword`]
+///   error is returned.
+/// - If a section is 4 GiB or larger, an [`ErrorCode::InvalidArgument`]
+///   error is returned.
+#[wasm_bindgen]
+pub fn export_backup(
+    vault: &Vault,
+    password: &str,
+    attachments: Option<BackupAttachments>,
+) -> Result<Vec<u8>, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    vault.check_password(password)?;
+    let attachment_key = vault.subkey(ATTACHMENT_KEY_INFO)?;
+
+    let mut sections = vec![(String::new(), vault.to_bytes()?)];
+    for (name, data) in attachments.unwrap_or_default().files {
+        let body = seal_with_raw_key(&data, &attachment_key[..], None)?;
+        sections.push((name, body));
+    }
+
+    let mut out = MAGIC.to_vec();
+    out.push(VERSION);
+    out.extend_from_slice(&(crate::utils::now_ms() as u64).to_be_bytes());
+    out.extend_from_slice(&(sections.len() as u32).to_be_bytes());
+    for (name, section) in &sections {
+        // Names are bounded by `BackupAttachments::add`.
+        out.extend_from_slice(&(name.len() as u16).to_be_bytes());
+        out.extend_from_slice(name.as_bytes());
+        out.extend_from_slice(&section_length(section)?.to_be_bytes());
+        out.extend_from_slice(&Sha256::digest(section));
+    }
+    let mut mac = manifest_mac(vault)?;
+    mac.update(&out);
+    out.extend_from_slice(&mac.finalize().into_bytes());
+    for (_, section) in sections {
+        out.extend_from_slice(&section);
+    }
+    Ok(out)
+}
+
+/// Verifies a backup written by [`export_backup`] and restores it.
+/// `options` carries the keyfile the vault was sealed with, if any.
+///
+/// # Errors
+///
+/// - If the backup is malformed or truncated, an error is returned.
+/// - If the password (or keyfile) is wrong, an [`ErrorCode::WrongPassword`]
+///   error is returned.
+/// - If the manifest or any section was modified, an
+///   [`ErrorCode::AuthFailed`] error is returned.
+#[wasm_bindgen]
+pub fn import_backup(
+    backup: &[u8],
+    password: &str,
+    options: Option<OpenOptions>,
+) -> Result<RestoredBackup, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    let mut reader = Reader::new(backup);
+    if reader.take(MAGIC.len())? != MAGIC {
+        return Err(invalid("not a bvault backup"));
+    }
+    check_version("backup", reader.u8()?, VERSION)?;
+    let created_at = reader.u64()?;
+    let mut manifest = Vec::new();
+    for _ in 0..reader.u32()? {
+        let name_len = reader.u16()? as usize;
+        let name = std::str::from_utf8(reader.take(name_len)?)
+            .map_err(|_| invalid("attachment name is not valid utf-8"))?
+            .to_string();
+        let length = reader.u32()? as usize;
+        let hash = reader.take(HASH_LENGTH)?;
+        manifest.push((name, length, hash));
+    }
+    let signed = &backup[..backup.len() - reader.rest().len()];
+    let mut reader = Reader::new(&backup[signed.len()..]);
+    let tag = reader.take(MAC_LENGTH)?;
+    let mut sections = Vec::with_capacity(manifest.len());
+    for (name, length, hash) in &manifest {
+        sections.push((name, reader.take(*length)?, hash));
+    }
+    if !reader.rest().is_empty() {
+        return Err(invalid("trailing bytes after the backup"));
+    }
+    let (vault_blob, vault_hash) = match sections.first() {
+        Some(&(name, blob, hash)) if name.is_empty() => (blob, hash),
+        _ => return Err(invalid("backup does not start with a vault")),
+    };
+
+    // The vault's own key check value verifies the password; its key then
+    // authenticates the manifest, which binds every section.
+    let vault = Vault::unlock(vault_blob, password, options)?;
+    let mut mac = manifest_mac(&vault)?;
+    mac.update(signed);
+    let damaged = |what: &str| {
+        BVaultError::new(
+            ErrorCode::AuthFailed,
+            format!("backup {} was modified", what),
+        )
+    };
+    mac.verify_slice(tag).map_err(|_| damaged("manifest"))?;
+    if !crate::constant_time_eq(&Sha256::digest(vault_blob), vault_hash) {
+        return Err(damaged("vault"));
+    }
+
+    let attachment_key = vault.subkey(ATTACHMENT_KEY_INFO)?;
+    let mut attachments = BackupAttachments::new();
+    for &(name, body, hash) in &sections[1..] {
+        if !crate::constant_time_eq(&Sha256::digest(body), hash) {
+            return Err(damaged(&format!("attachment {}", name)));
+        }
+        let data = Zeroizing::new(open_with_raw_key(body, &attachment_key[..], None)?);
+        attachments.add(name, &data)?;
+    }
+    Ok(RestoredBackup {
+        vault: Some(vault),
+        attachments,
+        created_at: created_at as f64,
+    })
+}
@@ -0,0 +1,86 @@
+//! bcrypt password hashes (`$2b$12$…`), for verifying logins against a
+//! legacy user database while it migrates to [`crate::hash_password`].
+//!
+//! `$2a$`, `$2b$`, `$2x$` and `$2y$` hashes all verify. bcrypt only reads
+//! the first 72 bytes of a password; longer passwords are truncated, as by
+//! every other implementation.
+
+use std::convert::TryInto;
+
+use wasm_bindgen::prelude::*;
+
+use crate::{random_bytes, BVaultError, ErrorCode};
+
+/// Cost [`bcrypt_hash`] uses when the caller does not say.
+const DEFAULT_COST: u32 = 12;
+
+/// Lowest cost [`bcrypt_hash`] accepts.
+const MIN_COST: u32 = 10;
+
+/// Highest cost accepted, so a hostile hash cannot stall the page.
+const MAX_COST: u32 = 16;
+
+/// Length of a bcrypt salt, in bytes.
+const SALT_LENGTH: usize = 16;
+
+fn invalid_hash() -> BVaultError {
+    BVaultError::new(ErrorCode::InvalidArgument, "malformed bcrypt hash")
+}
+
+/// Reads the cost out of `hash` without the rest of the parsing, which
+/// [`bcrypt::verify`] does.
+fn cost(hash: &str) -> Result<u32, BVaultError> {
+    hash.split('$')
+        .nth(2)
+        .and_then(|cost| cost.parse().ok())
+        .ok_or_else(invalid_hash)
+}
+
+/// Whether `password` matches the bcrypt `hash`, compared in constant time.
+///
+/// # Errors
+///
+/// - If `hash` is not a bcrypt hash, an [`ErrorCode::InvalidArgument`]
+///   error is returned.
+/// - If its cost is above 16, an [`ErrorCode::WeakKdfParams`] error is
+///   returned.
+#[wasm_bindgen]
+pub fn bcrypt_verify(password: &str, hash: &str) -> Result<bool, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    if cost(hash)? > MAX_COST {
+        return Err(BVaultError::new(
+            ErrorCode::WeakKdfParams,
+            "bcrypt cost is above 16",
+        ));
+    }
+    bcrypt::verify(password, hash).map_err(|_| invalid_hash())
+}
+
+/// Hashes `password` with bcrypt at `cost` (default 12) under a fresh salt,
+/// as a `$2b$` hash.
+///
+/// Prefer [`crate::hash_password`] for new hashes; this is for systems that
+/// still only read bcrypt.
+///
+/// # Errors
+///
+/// - If `cost` is outside 10..=16, an [`ErrorCode::WeakKdfParams`] error is
+///   returned.
+/// - If the random number generator is unavailable, an error is returned.
+#[wasm_bindgen]
+pub fn bcrypt_hash(password: &str, cost: Option<u32>) -> Result<String, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    let cost = cost.unwrap_or(DEFAULT_COST);
+    if !(MIN_COST..=MAX_COST).contains(&cost) {
+        return Err(BVaultError::new(
+            ErrorCode::WeakKdfParams,
+            "bcrypt cost must be between 10 and 16",
+        ));
+    }
+    let salt: [u8; SALT_LENGTH] = random_bytes(SALT_LENGTH)?
+        .try_into()
+        .expect("random_bytes returns the requested length");
+    let parts = bcrypt::hash_with_salt(password, cost, salt)
+        .map_err(|_| BVaultError::new(ErrorCode::KdfFailed, "bcrypt hashing failed"))?;
+    Ok(parts.format_for_version(bcrypt::Version::TwoB))
+}
@@ -0,0 +1,363 @@
+//! Importing Bitwarden `.json` exports into a [`Vault`].
+//!
+//! A password-protected export wraps the plain export in one encrypted
+//! string:
+//!
+//! ```text
+//! {
+//!   "encrypted": true,
+//!   "passwordProtected": true,
+//!   "salt": "<text>",
+//!   "kdfType": 0 | 1,                       PBKDF2-SHA256 or Argon2id
+//!   "kdfIterations": …, "kdfMemory": <MiB>, "kdfParallelism": …,
+//!   "encKeyValidation_DO_NOT_EDIT": "2.<iv>|<ciphertext>|<mac>",
+//!   "data": "2.<iv>|<ciphertext>|<mac>"
+//! }
+//! ```
+//!
+//! The password is stretched with PBKDF2 over the salt text, or Argon2id
+//! over its SHA-256, then expanded with HKDF-Expand-SHA256 into an
+//! AES-256-CBC key (info "enc") and an HMAC-SHA256 key (info "mac"). Each
+//! encrypted string is AES-256-CBC with PKCS#7 padding, and its mac is
+//! HMAC-SHA256 over iv || ciphertext, all base64. Plain exports
+//! (`"encrypted": false`) are accepted as they are; exports encrypted with
+//! the account key cannot be opened without the account and are rejected.
+//!
+//! Every item becomes one vault entry, a JSON object:
+//!
+//! ```text
+//! {
+//!   "source": "bitwarden",
+//!   "type": "login" | "note" | "card" | "identity" | "ssh-key",
+//!   "name": …, "folder": <name> | null, "favorite": …, "notes": … | null,
+//!   "data": <the item's login, card, identity or sshKey object> | null,
+//!   "fields": [<the item's custom fields>]
+//! }
+//! ```
+
+use hkdf::Hkdf;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use wasm_bindgen::prelude::*;
+use zeroize::Zeroizing;
+
+use crate::encoding::decode_base64;
+use crate::kcv::wrong_password;
+use crate::kdf::Key;
+use crate::legacy::cbc_decrypt_with_key;
+use crate::{BVaultError, ErrorCode, KdfParams, Vault};
+
+const KDF_PBKDF2: u8 = 0;
+const KDF_ARGON2ID: u8 = 1;
+/// Encrypted string type 2: AES-256-CBC with HMAC-SHA256, base64.
+const ENC_TYPE_AES_CBC_HMAC: &str = "2";
+
+/// Highest costs Bitwarden itself allows, so a hostile file cannot stall
+/// the page.
+const MAX_PBKDF2_ITERATIONS: u32 = 2_000_000;
+const MAX_ARGON2_MEMORY_MIB: u32 = 1024;
+const MAX_ARGON2_ITERATIONS: u32 = 10;
+const MAX_ARGON2_PARALLELISM: u32 = 16;
+
+const IV_LENGTH: usize = 16;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ExportJson {
+    encrypted: bool,
+    #[serde(default)]
+    password_protected: bool,
+    salt: Option<String>,
+    kdf_type: Option<u8>,
+    kdf_iterations: Option<u32>,
+    kdf_memory: Option<u32>,
+    kdf_parallelism: Option<u32>,
+    #[serde(rename = "encKeyValidation_DO_NOT_EDIT")]
+    key_validation: Option<String>,
+    data: Option<String>,
+    #[serde(default)]
+    folders: Vec<FolderJson>,
+    #[serde(default)]
+    items: Vec<ItemJson>,
+}
+
+#[derive(Deserialize)]
+struct FolderJson {
+    id: String,
+    name: String,
+}
+
+/// An item of the plain export. Members the entry does not keep
+/// (`id`, `revisionDate`, `passwordHistory`, …) are ignored.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ItemJson {
+    #[serde(rename = "type")]
+    kind: u8,
+    name: String,
+    notes: Option<String>,
+    #[serde(default)]
+    favorite: bool,
+    folder_id: Option<String>,
+    login: Option<Value>,
+    card: Option<Value>,
+    identity: Option<Value>,
+    ssh_key: Option<Value>,
+    #[serde(default)]
+    fields: Vec<Value>,
+}
+
+#[derive(Serialize)]
+struct Entry<'a> {
+    source: &'static str,
+    #[serde(rename = "type")]
+    kind: &'static str,
+    name: &'a str,
+    folder: Option<&'a str>,
+    favorite: bool,
+    notes: Option<&'a str>,
+    data: Option<&'a Value>,
+    fields: &'a [Value],
+}
+
+/// The AES-256-CBC and HMAC-SHA256 keys of an export.
+struct ExportKeys {
+    enc: Key,
+    mac: Key,
+}
+
+impl ExportJson {
+    fn kdf(&self) -> Result<(KdfParams, Vec<u8>), BVaultError> {
+        let salt = self
+            .salt
+            .as_deref()
+            .ok_or_else(|| invalid_json("salt", "missing"))?;
+        let iterations = self
+            .kdf_iterations
+            .ok_or_else(|| invalid_json("kdfIterations", "missing"))?;
+        match self.kdf_type {
+            Some(KDF_PBKDF2) => {
+                if iterations > MAX_PBKDF2_ITERATIONS {
+                    return Err(BVaultError::new(
+                        ErrorCode::WeakKdfParams,
+                        "PBKDF2 iterations are above 2 000 000",
+                    ));
+                }
+                Ok((KdfParams::pbkdf2(iterations), salt.as_bytes().to_vec()))
+            }
+            Some(KDF_ARGON2ID) => {
+                let memory = self
+                    .kdf_memory
+                    .ok_or_else(|| invalid_json("kdfMemory", "missing"))?;
+                let parallelism = self
+                    .kdf_parallelism
+                    .ok_or_else(|| invalid_json("kdfParallelism", "missing"))?;
+                if memory > MAX_ARGON2_MEMORY_MIB
+                    || iterations > MAX_ARGON2_ITERATIONS
+                    || parallelism > MAX_ARGON2_PARALLELISM
+                {
+                    return Err(BVaultError::new(
+                        ErrorCode::WeakKdfParams,
+                        "Argon2 parameters are above Bitwarden's maximums",
+                    ));
+                }
+                let kdf = KdfParams::argon2id(
+                    Some(memory.saturating_mul(1024)),
+                    Some(iterations),
+                    Some(parallelism),
+                );
+                Ok((kdf, Sha256::digest(salt.as_bytes()).to_vec()))
+            }
+            Some(other) => Err(BVaultError::new(
+                ErrorCode::UnsupportedAlgorithm,
+                format!("unsupported Bitwarden KDF type {}", other),
+            )),
+            None => Err(invalid_json("kdfType", "missing")),
+        }
+    }
+
+    fn keys(&self, password: &str) -> Result<ExportKeys, BVaultError> {
+        let (kdf, salt) = self.kdf()?;
+        let master = kdf.derive(password.as_bytes(), &salt)?;
+        let hkdf = Hkdf::<Sha256>::from_prk(&master[..]).expect("32-byte PRK");
+        let mut keys = ExportKeys {
+            enc: Key::default(),
+            mac: Key::default(),
+        };
+        hkdf.expand(b"enc", &mut keys.enc[..])
+            .expect("valid HKDF length");
+        hkdf.expand(b"mac", &mut keys.mac[..])
+            .expect("valid HKDF length");
+        Ok(keys)
+    }
+}
+
+/// Decrypts the encrypted string at `path`. `on_bad_mac` is the error for
+/// a mac mismatch.
+fn decrypt_string(
+    path: &str,
+    text: &str,
+    keys: &ExportKeys,
+    on_bad_mac: fn() -> BVaultError,
+) -> Result<Zeroizing<Vec<u8>>, BVaultError> {
+    let (kind, parts) = text
+        .split_once('.')
+        .ok_or_else(|| invalid_json(path, "not an encrypted string"))?;
+    if kind != ENC_TYPE_AES_CBC_HMAC {
+        return Err(BVaultError::new(
+            ErrorCode::UnsupportedAlgorithm,
+            format!("unsupported Bitwarden encryption type {}", kind),
+        ));
+    }
+    let parts = parts
+        .split('|')
+        .map(decode_base64)
+        .collect::<Result<Vec<_>, _>>()?;
+    let (iv, ciphertext, tag) = match parts.as_slice() {
+        [iv, ciphertext, tag] => (iv, ciphertext, tag),
+        _ => return Err(invalid_json(path, "expected iv|ciphertext|mac")),
+    };
+    if iv.len() != IV_LENGTH {
+        return Err(invalid_json(path, "the iv must be 16 bytes"));
+    }
+    let mut mac = Hmac::<Sha256>::new_from_slice(&keys.mac[..]).expect("HMAC takes any key length");
+    mac.update(iv);
+    mac.update(ciphertext);
+    mac.verify_slice(tag).map_err(|_| on_bad_mac())?;
+    Ok(Zeroizing::new(cbc_decrypt_with_key(
+        &keys.enc[..],
+        iv,
+        ciphertext.clone(),
+        None,
+    )?))
+}
+
+fn data_modified() -> BVaultError {
+    BVaultError::new(ErrorCode::AuthFailed, "Bitwarden export data was modified")
+}
+
+fn parse<T: serde::de::DeserializeOwned>(json: &[u8]) -> Result<T, BVaultError> {
+    let deserializer = &mut serde_json::Deserializer::from_slice(json);
+    serde_path_to_error::deserialize(deserializer).map_err(|e| {
+        let path = e.path().to_string();
+        invalid_json(&path, e.into_inner().to_string())
+    })
+}
+
+fn invalid_json(path: &str, message: impl std::fmt::Display) -> BVaultError {
+    BVaultError::new(ErrorCode::InvalidJson, format!("{}: {}", path, message))
+}
+
+/// Imports a Bitwarden `.json` export into `vault`, one entry per item (see
+/// the module docs for their layout), and returns the new entry ids in the
+/// export's order. `password` is the export password; it is ignored for
+/// plain exports. Either every item is added or none is.
+///
+/// # Errors
+///
+/// - If the JSON does not match the export schema, or an item has an
+///   unknown type, an [`ErrorCode::InvalidJson`] error naming the
+///   offending field is returned.
+/// - If the export is encrypted with the account key, an
+///   [`ErrorCode::InvalidArgument`] error is returned.
+/// - If the KDF parameters are below the crate's minimums or above
+///   Bitwarden's maximums, an [`ErrorCode::WeakKdfParams`] error is
+///   returned.
+/// - If the password is wrong, an [`ErrorCode::WrongPassword`] error is
+///   returned; if the data was modified, an [`ErrorCode::AuthFailed`]
+///   error.
+#[wasm_bindgen]
+pub fn bitwarden_import(
+    vault: &mut Vault,
+    json: &str,
+    password: Option<String>,
+) -> Result<Vec<String>, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    let outer: ExportJson = parse(json.as_bytes())?;
+    let export =
+        match (outer.encrypted, outer.password_protected) {
+            (false, _) => outer,
+            (true, false) => return Err(BVaultError::new(
+                ErrorCode::InvalidArgument,
+                "this export is encrypted with the account key; export it again with a password",
+            )),
+            (true, true) => {
+                let password = password.ok_or_else(|| {
+                    BVaultError::new(
+                        ErrorCode::InvalidArgument,
+                        "a password-protected export needs its password",
+                    )
+                })?;
+                let keys = outer.keys(&password)?;
+                let validation = outer
+                    .key_validation
+                    .as_deref()
+                    .ok_or_else(|| invalid_json("encKeyValidation_DO_NOT_EDIT", "missing"))?;
+                decrypt_string(
+                    "encKeyValidation_DO_NOT_EDIT",
+                    validation,
+                    &keys,
+                    wrong_password,
+                )?;
+                let data = outer
+                    .data
+                    .as_deref()
+                    .ok_or_else(|| invalid_json("data", "missing"))?;
+                parse(&decrypt_string("data", data, &keys, data_modified)?)?
+            }
+        };
+
+    let folder = |id: &Option<String>| {
+        id.as_deref().and_then(|id| {
+            export
+                .folders
+                .iter()
+                .find(|folder| folder.id == id)
+                .map(|folder| folder.name.as_str())
+        })
+    };
+    let mut entries = Vec::with_capacity(export.items.len());
+    for (index, item) in export.items.iter().enumerate() {
+        let (kind, data) = match item.kind {
+            1 => ("login", item.login.as_ref()),
+            2 => ("note", None),
+            3 => ("card", item.card.as_ref()),
+            4 => ("identity", item.identity.as_ref()),
+            5 => ("ssh-key", item.ssh_key.as_ref()),
+            other => {
+                return Err(invalid_json(
+                    &format!("items[{}].type", index),
+                    format!("unknown item type {}", other),
+                ))
+            }
+        };
+        let entry = Entry {
+            source: "bitwarden",
+            kind,
+            name: &item.name,
+            folder: folder(&item.folder_id),
+            favorite: item.favorite,
+            notes: item.notes.as_deref(),
+            data,
+            fields: &item.fields,
+        };
+        entries.push(Zeroizing::new(
+            serde_json::to_vec(&entry).expect("entries serialize"),
+        ));
+    }
+    let mut ids = Vec::with_capacity(entries.len());
+    for entry in &entries {
+        match vault.add_entry(entry) {
+            Ok(id) => ids.push(id),
+            Err(err) => {
+                for id in &ids {
+                    vault.delete_entry(id)?;
+                }
+                return Err(err);
+            }
+        }
+    }
+    Ok(ids)
+}
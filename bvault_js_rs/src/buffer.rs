@@ -0,0 +1,96 @@
+//! Buffers in WASM memory, for decrypting very large payloads without
+//! copying them across the JS boundary.
+//!
+//! Passing a `Uint8Array` to any other function copies it into WASM memory,
+//! and returning one copies it back out. Instead, [`alloc_buffer`] reserves
+//! the memory up front and [`WasmBuffer::view`] hands JS a `Uint8Array`
+//! over it; JS writes the ciphertext straight into the view,
+//! [`decrypt_in_place`] turns it into plaintext where it lies, and JS reads
+//! the result through a fresh view.
+//!
+//! A view is only valid until WASM memory next grows, which any call into
+//! this module may cause. Take a new view after every call, and never hold
+//! one across calls.
+
+use wasm_bindgen::prelude::*;
+use zeroize::Zeroizing;
+
+use crate::{derive_key, BVaultError, Cipher, KdfParams};
+
+/// A byte buffer living in WASM memory. Its contents are wiped when it is
+/// freed.
+#[wasm_bindgen]
+pub struct WasmBuffer {
+    data: Zeroizing<Vec<u8>>,
+}
+
+#[wasm_bindgen]
+impl WasmBuffer {
+    /// The current length, in bytes. Decryption shrinks the buffer by the
+    /// length of the authentication tag.
+    #[wasm_bindgen(getter)]
+    pub fn length(&self) -> usize {
+        self.data.len()
+    }
+
+    /// A `Uint8Array` over the buffer's bytes, without copying. Writes
+    /// through it change the buffer.
+    ///
+    /// The view is detached as soon as WASM memory grows; take a new one
+    /// after every call into the library.
+    pub fn view(&self) -> js_sys::Uint8Array {
+        let memory: js_sys::WebAssembly::Memory = wasm_bindgen::memory().unchecked_into();
+        js_sys::Uint8Array::new_with_byte_offset_and_length(
+            &memory.buffer(),
+            self.data.as_ptr() as u32,
+            self.data.len() as u32,
+        )
+    }
+
+    /// Copies the contents out into a regular `Uint8Array`.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.data.to_vec()
+    }
+}
+
+/// Allocates a zero-filled buffer of `length` bytes in WASM memory.
+#[wasm_bindgen]
+pub fn alloc_buffer(length: usize) -> WasmBuffer {
+    let _operation = crate::utils::set_panic_hook();
+    WasmBuffer {
+        data: Zeroizing::new(vec![0u8; length]),
+    }
+}
+
+/// Decrypts the ciphertext (tag appended, as from the `encrypt_*` functions)
+/// held in `buffer` in place, under the key derived from `password`,
+/// `salt` and `kdf`, and the associated data `aad` it was bound to, if
+/// any. Afterwards the buffer holds the plaintext.
+///
+/// # Errors
+///
+/// - If the nonce has the wrong length for `cipher`, an error is returned.
+/// - If the KDF parameters are below the accepted minimum, an error is
+///   returned.
+/// - If authentication fails (wrong password or tampered data), an
+///   [`crate::ErrorCode::AuthFailed`] error is returned and the buffer is
+///   wiped and emptied.
+#[wasm_bindgen]
+pub fn decrypt_in_place(
+    buffer: &mut WasmBuffer,
+    cipher: Cipher,
+    password: &str,
+    nonce: &[u8],
+    salt: &[u8],
+    kdf: Option<KdfParams>,
+    aad: Option<Vec<u8>>,
+) -> Result<(), BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    let key = derive_key(password, salt, kdf)?;
+    cipher.decrypt_in_place(
+        &key[..],
+        nonce,
+        &mut buffer.data,
+        aad.as_deref().unwrap_or_default(),
+    )
+}
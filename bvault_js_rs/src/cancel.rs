@@ -0,0 +1,38 @@
+//! Cancellation of the async and streaming APIs with an `AbortSignal`:
+//!
+//! ```text
+//! const controller = new AbortController();
+//! cancelButton.onclick = () => controller.abort();
+//! const plaintext = await decrypt_async(..., controller.signal);
+//! ```
+//!
+//! The signal is checked between units of work (PBKDF2 batches, stream
+//! chunks), so an abort takes effect within one unit. Argon2id and scrypt
+//! run as one unit and are only checked before and after. Aborted work
+//! fails with [`ErrorCode::Aborted`]; the keys and partial plaintexts it
+//! held are zeroized as they are dropped.
+
+use wasm_bindgen::prelude::*;
+
+use crate::{BVaultError, ErrorCode};
+
+#[wasm_bindgen]
+extern "C" {
+    /// The DOM `AbortSignal` of an `AbortController`.
+    #[derive(Clone, Debug)]
+    pub type AbortSignal;
+
+    #[wasm_bindgen(method, getter)]
+    fn aborted(this: &AbortSignal) -> bool;
+}
+
+/// Fails with [`ErrorCode::Aborted`] if `signal` has been aborted.
+pub(crate) fn check(signal: Option<&AbortSignal>) -> Result<(), BVaultError> {
+    match signal {
+        Some(signal) if signal.aborted() => Err(BVaultError::new(
+            ErrorCode::Aborted,
+            "the operation was aborted",
+        )),
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,287 @@
+//! A small canonical CBOR (RFC 8949) codec for the container formats.
+//!
+//! Only the subset the containers need is supported: unsigned integers,
+//! byte strings, text strings, arrays and maps with unsigned integer keys.
+//! Encoding follows the core deterministic rules of RFC 8949 §4.2.1
+//! (shortest-form heads, definite lengths, keys in ascending order), and
+//! decoding rejects anything that is not already in that form, so a value
+//! has exactly one encoding and MACs or signatures over it stay stable.
+
+use std::convert::TryFrom;
+
+use crate::envelope::invalid;
+use crate::BVaultError;
+
+const MAJOR_UINT: u8 = 0;
+const MAJOR_BYTES: u8 = 2;
+const MAJOR_TEXT: u8 = 3;
+const MAJOR_ARRAY: u8 = 4;
+const MAJOR_MAP: u8 = 5;
+
+/// Nesting limit, so hostile input cannot exhaust the stack.
+const MAX_DEPTH: usize = 16;
+
+/// A decoded CBOR data item.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Value {
+    Uint(u64),
+    Bytes(Vec<u8>),
+    Text(String),
+    Array(Vec<Value>),
+    /// Entries are kept in ascending key order.
+    Map(Vec<(u64, Value)>),
+}
+
+impl Value {
+    /// Builds a map, sorting the entries into canonical order.
+    pub(crate) fn map(mut entries: Vec<(u64, Value)>) -> Value {
+        entries.sort_by_key(|(key, _)| *key);
+        Value::Map(entries)
+    }
+
+    /// Encodes the value canonically.
+    pub(crate) fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        self.write(&mut out);
+        out
+    }
+
+    fn write(&self, out: &mut Vec<u8>) {
+        match self {
+            Value::Uint(n) => write_head(out, MAJOR_UINT, *n),
+            Value::Bytes(bytes) => {
+                write_head(out, MAJOR_BYTES, bytes.len() as u64);
+                out.extend_from_slice(bytes);
+            }
+            Value::Text(text) => {
+                write_head(out, MAJOR_TEXT, text.len() as u64);
+                out.extend_from_slice(text.as_bytes());
+            }
+            Value::Array(items) => {
+                write_head(out, MAJOR_ARRAY, items.len() as u64);
+                for item in items {
+                    item.write(out);
+                }
+            }
+            Value::Map(entries) => {
+                write_head(out, MAJOR_MAP, entries.len() as u64);
+                for (key, value) in entries {
+                    write_head(out, MAJOR_UINT, *key);
+                    value.write(out);
+                }
+            }
+        }
+    }
+
+    /// Decodes exactly one canonical data item spanning all of `bytes`.
+    pub(crate) fn parse(bytes: &[u8]) -> Result<Value, BVaultError> {
+        let mut decoder = Decoder { bytes, pos: 0 };
+        let value = decoder.value(0)?;
+        if decoder.pos != bytes.len() {
+            return Err(invalid("trailing bytes after CBOR item"));
+        }
+        Ok(value)
+    }
+
+    pub(crate) fn into_uint(self) -> Result<u64, BVaultError> {
+        match self {
+            Value::Uint(n) => Ok(n),
+            _ => Err(invalid("expected a CBOR unsigned integer")),
+        }
+    }
+
+    pub(crate) fn into_bytes(self) -> Result<Vec<u8>, BVaultError> {
+        match self {
+            Value::Bytes(bytes) => Ok(bytes),
+            _ => Err(invalid("expected a CBOR byte string")),
+        }
+    }
+
+    pub(crate) fn into_text(self) -> Result<String, BVaultError> {
+        match self {
+            Value::Text(text) => Ok(text),
+            _ => Err(invalid("expected a CBOR text string")),
+        }
+    }
+
+    pub(crate) fn into_array(self) -> Result<Vec<Value>, BVaultError> {
+        match self {
+            Value::Array(items) => Ok(items),
+            _ => Err(invalid("expected a CBOR array")),
+        }
+    }
+
+    pub(crate) fn into_map(self) -> Result<Fields, BVaultError> {
+        match self {
+            Value::Map(entries) => Ok(Fields { entries }),
+            _ => Err(invalid("expected a CBOR map")),
+        }
+    }
+}
+
+impl From<u64> for Value {
+    fn from(n: u64) -> Value {
+        Value::Uint(n)
+    }
+}
+
+impl From<u32> for Value {
+    fn from(n: u32) -> Value {
+        Value::Uint(n.into())
+    }
+}
+
+impl From<u8> for Value {
+    fn from(n: u8) -> Value {
+        Value::Uint(n.into())
+    }
+}
+
+/// The entries of a decoded map, taken out one key at a time.
+pub(crate) struct Fields {
+    entries: Vec<(u64, Value)>,
+}
+
+impl Fields {
+    /// Removes and returns the value under `key`, if present.
+    pub(crate) fn take(&mut self, key: u64) -> Option<Value> {
+        let index = self.entries.iter().position(|(k, _)| *k == key)?;
+        Some(self.entries.remove(index).1)
+    }
+
+    /// Like [`Fields::take`], but the key must be present.
+    pub(crate) fn require(&mut self, key: u64) -> Result<Value, BVaultError> {
+        self.take(key)
+            .ok_or_else(|| invalid(format!("missing CBOR field {}", key)))
+    }
+
+    /// Fails if any key was not taken, so unknown fields are not ignored.
+    pub(crate) fn finish(self) -> Result<(), BVaultError> {
+        match self.entries.first() {
+            Some((key, _)) => Err(invalid(format!("unknown CBOR field {}", key))),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Narrows a decoded integer to a smaller type.
+pub(crate) fn narrow<T: TryFrom<u64>>(value: Value) -> Result<T, BVaultError> {
+    T::try_from(value.into_uint()?).map_err(|_| invalid("CBOR integer out of range"))
+}
+
+fn write_head(out: &mut Vec<u8>, major: u8, n: u64) {
+    let major = major << 5;
+    if n < 24 {
+        out.push(major | n as u8);
+    } else if n <= u8::MAX as u64 {
+        out.push(major | 24);
+        out.push(n as u8);
+    } else if n <= u16::MAX as u64 {
+        out.push(major | 25);
+        out.extend_from_slice(&(n as u16).to_be_bytes());
+    } else if n <= u32::MAX as u64 {
+        out.push(major | 26);
+        out.extend_from_slice(&(n as u32).to_be_bytes());
+    } else {
+        out.push(major | 27);
+        out.extend_from_slice(&n.to_be_bytes());
+    }
+}
+
+struct Decoder<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Decoder<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], BVaultError> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|&end| end <= self.bytes.len())
+            .ok_or_else(|| invalid("CBOR item is truncated"))?;
+        let slice = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(slice)
+    }
+
+    /// Reads a head, rejecting indefinite lengths and non-shortest forms.
+    fn head(&mut self) -> Result<(u8, u64), BVaultError> {
+        let initial = self.take(1)?[0];
+        let (major, info) = (initial >> 5, initial & 0x1f);
+        let (n, min) = match info {
+            0..=23 => return Ok((major, info.into())),
+            24 => (u64::from(self.take(1)?[0]), 24),
+            25 => {
+                let b = self.take(2)?;
+                (u64::from(u16::from_be_bytes([b[0], b[1]])), 0x100)
+            }
+            26 => {
+                let b = self.take(4)?;
+                (
+                    u64::from(u32::from_be_bytes([b[0], b[1], b[2], b[3]])),
+                    0x1_0000,
+                )
+            }
+            27 => {
+                let mut b = [0; 8];
+                b.copy_from_slice(self.take(8)?);
+                (u64::from_be_bytes(b), 0x1_0000_0000)
+            }
+            _ => return Err(invalid("indefinite or reserved CBOR length")),
+        };
+        if n < min {
+            return Err(invalid("CBOR integer is not in shortest form"));
+        }
+        Ok((major, n))
+    }
+
+    fn len(&mut self, n: u64) -> Result<usize, BVaultError> {
+        usize::try_from(n).map_err(|_| invalid("CBOR length out of range"))
+    }
+
+    fn value(&mut self, depth: usize) -> Result<Value, BVaultError> {
+        if depth > MAX_DEPTH {
+            return Err(invalid("CBOR nesting is too deep"));
+        }
+        let (major, n) = self.head()?;
+        match major {
+            MAJOR_UINT => Ok(Value::Uint(n)),
+            MAJOR_BYTES => {
+                let len = self.len(n)?;
+                Ok(Value::Bytes(self.take(len)?.to_vec()))
+            }
+            MAJOR_TEXT => {
+                let len = self.len(n)?;
+                let text = std::str::from_utf8(self.take(len)?)
+                    .map_err(|_| invalid("CBOR text is not valid utf-8"))?;
+                Ok(Value::Text(text.to_string()))
+            }
+            MAJOR_ARRAY => {
+                // Every item takes at least one byte, which bounds the
+                // allocation by the input size.
+                let len = self.len(n)?.min(self.bytes.len() - self.pos);
+                let mut items = Vec::with_capacity(len);
+                for _ in 0..n {
+                    items.push(self.value(depth + 1)?);
+                }
+                Ok(Value::Array(items))
+            }
+            MAJOR_MAP => {
+                let mut entries: Vec<(u64, Value)> = Vec::new();
+                for _ in 0..n {
+                    let (key_major, key) = self.head()?;
+                    if key_major != MAJOR_UINT {
+                        return Err(invalid("CBOR map keys must be unsigned integers"));
+                    }
+                    if entries.last().is_some_and(|(last, _)| *last >= key) {
+                        return Err(invalid("CBOR map keys are not in canonical order"));
+                    }
+                    entries.push((key, self.value(depth + 1)?));
+                }
+                Ok(Value::Map(entries))
+            }
+            _ => Err(invalid("unsupported CBOR type")),
+        }
+    }
+}
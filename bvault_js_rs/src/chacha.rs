@@ -0,0 +1,209 @@
+//! ChaCha20-Poly1305 and XChaCha20-Poly1305 authenticated encryption.
+//!
+//! ChaCha20 is a pure software cipher, so it usually outperforms AES in WASM
+//! where no AES hardware instructions are available. Key derivation is the
+//! same PBKDF2 setup used by every other suite.
+//!
+//! The XChaCha20 variant takes a 24-byte nonce, which is large enough to be
+//! drawn at random for every entry under one key without risking reuse.
+
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    aead, BVaultError, Cipher, EncryptOptions, EncryptedBytes, EncryptedData, KdfParams,
+    TextDecoding,
+};
+
+/// Synchronously encrypts a plaintext string with ChaCha20-Poly1305.
+///
+/// A random salt and 12-byte nonce are generated for every call.
+///
+/// # Errors
+///
+/// See [`crate::encrypt_gcm`].
+#[wasm_bindgen]
+pub fn encrypt_chacha(
+    plaintext: &str,
+    password: &str,
+    options: Option<EncryptOptions>,
+) -> Result<EncryptedData, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    aead::encrypt(
+        Cipher::ChaCha20Poly1305,
+        plaintext.as_bytes(),
+        password,
+        options,
+    )
+}
+
+/// Synchronously decrypts and verifies a ChaCha20-Poly1305 ciphertext.
+///
+/// # Errors
+///
+/// See [`crate::decrypt_gcm`].
+#[wasm_bindgen]
+pub fn decrypt_chacha(
+    b64_ciphertext: &str,
+    password: &str,
+    b64_nonce: &str,
+    b64_salt: &str,
+    kdf: Option<KdfParams>,
+    aad: Option<Vec<u8>>,
+    decoding: Option<TextDecoding>,
+) -> Result<String, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    let plaintext = aead::decrypt(
+        Cipher::ChaCha20Poly1305,
+        b64_ciphertext,
+        password,
+        b64_nonce,
+        b64_salt,
+        kdf,
+        aad.as_deref().unwrap_or_default(),
+    )?;
+    crate::text::decode_plaintext(plaintext, decoding)
+}
+
+/// Synchronously encrypts a plaintext string with XChaCha20-Poly1305.
+///
+/// A random salt and 24-byte nonce are generated for every call.
+///
+/// # Errors
+///
+/// See [`crate::encrypt_gcm`].
+#[wasm_bindgen]
+pub fn encrypt_xchacha(
+    plaintext: &str,
+    password: &str,
+    options: Option<EncryptOptions>,
+) -> Result<EncryptedData, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    aead::encrypt(
+        Cipher::XChaCha20Poly1305,
+        plaintext.as_bytes(),
+        password,
+        options,
+    )
+}
+
+/// Synchronously decrypts and verifies an XChaCha20-Poly1305 ciphertext.
+///
+/// # Errors
+///
+/// See [`crate::decrypt_gcm`]; the nonce must be 24 bytes.
+#[wasm_bindgen]
+pub fn decrypt_xchacha(
+    b64_ciphertext: &str,
+    password: &str,
+    b64_nonce: &str,
+    b64_salt: &str,
+    kdf: Option<KdfParams>,
+    aad: Option<Vec<u8>>,
+    decoding: Option<TextDecoding>,
+) -> Result<String, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    let plaintext = aead::decrypt(
+        Cipher::XChaCha20Poly1305,
+        b64_ciphertext,
+        password,
+        b64_nonce,
+        b64_salt,
+        kdf,
+        aad.as_deref().unwrap_or_default(),
+    )?;
+    crate::text::decode_plaintext(plaintext, decoding)
+}
+
+/// Like [`encrypt_chacha`], but takes and returns raw bytes.
+///
+/// # Errors
+///
+/// See [`crate::encrypt_gcm`].
+#[wasm_bindgen]
+pub fn encrypt_chacha_binary(
+    plaintext: &[u8],
+    password: &str,
+    options: Option<EncryptOptions>,
+) -> Result<EncryptedBytes, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    aead::encrypt_bytes(
+        Cipher::ChaCha20Poly1305,
+        plaintext,
+        password,
+        &options.unwrap_or_default(),
+    )
+}
+
+/// Like [`decrypt_chacha`], but takes raw bytes and returns the
+/// plaintext bytes without utf-8 validation.
+///
+/// # Errors
+///
+/// See [`crate::decrypt_gcm`], minus the base64 and utf-8 checks.
+#[wasm_bindgen]
+pub fn decrypt_chacha_binary(
+    ciphertext: &[u8],
+    password: &str,
+    nonce: &[u8],
+    salt: &[u8],
+    kdf: Option<KdfParams>,
+    aad: Option<Vec<u8>>,
+) -> Result<Vec<u8>, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    aead::decrypt_bytes(
+        Cipher::ChaCha20Poly1305,
+        ciphertext,
+        password,
+        nonce,
+        salt,
+        kdf,
+        aad.as_deref().unwrap_or_default(),
+    )
+}
+
+/// Like [`encrypt_xchacha`], but takes and returns raw bytes.
+///
+/// # Errors
+///
+/// See [`crate::encrypt_gcm`].
+#[wasm_bindgen]
+pub fn encrypt_xchacha_binary(
+    plaintext: &[u8],
+    password: &str,
+    options: Option<EncryptOptions>,
+) -> Result<EncryptedBytes, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    aead::encrypt_bytes(
+        Cipher::XChaCha20Poly1305,
+        plaintext,
+        password,
+        &options.unwrap_or_default(),
+    )
+}
+
+/// Like [`decrypt_xchacha`], but takes raw bytes and returns the
+/// plaintext bytes without utf-8 validation.
+///
+/// # Errors
+///
+/// See [`decrypt_xchacha`], minus the base64 and utf-8 checks.
+#[wasm_bindgen]
+pub fn decrypt_xchacha_binary(
+    ciphertext: &[u8],
+    password: &str,
+    nonce: &[u8],
+    salt: &[u8],
+    kdf: Option<KdfParams>,
+    aad: Option<Vec<u8>>,
+) -> Result<Vec<u8>, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    aead::decrypt_bytes(
+        Cipher::XChaCha20Poly1305,
+        ciphertext,
+        password,
+        nonce,
+        salt,
+        kdf,
+        aad.as_deref().unwrap_or_default(),
+    )
+}
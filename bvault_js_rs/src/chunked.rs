@@ -0,0 +1,527 @@
+//! Chunked authenticated encryption for large files, such as attachments
+//! kept in object storage.
+//!
+//! The plaintext is split into chunks of a fixed size, each sealed on its
+//! own with the STREAM construction (Hoang et al., 2015): a chunk's nonce
+//! is a random per-file prefix followed by the chunk's index and a flag
+//! marking the final chunk. Reordered, dropped or duplicated chunks fail
+//! authentication, and so does a file cut short, even at a chunk boundary,
+//! because its new last chunk was not sealed as final. The file is
+//! (big-endian):
+//!
+//! ```text
+//! magic       4      "BVCF"
+//! version     1      currently 1
+//! cipher      1      Cipher ID
+//! kdf         1 + …  as in envelopes; "none" for a raw key
+//! salt        1 + n  length-prefixed
+//! kcv         8      key check value of the file key
+//! chunk size  4      plaintext bytes per chunk
+//! prefix      n      nonce prefix: the cipher's nonce length minus 5
+//! chunks             each the chunk's ciphertext with the cipher's tag
+//! ```
+//!
+//! Nonces are `prefix || index (4) || final (1)`. Every chunk but the last
+//! holds exactly `chunk size` bytes and the last holds 1 to `chunk size`
+//! (0 only for an empty file), so chunk `i` starts at a known offset and can
+//! be fetched and checked on its own with [`ChunkedDecryptor::decrypt_chunk`].
+//! The header is every chunk's associated data.
+//!
+//! With a password the file key comes from the KDF; with a raw key it is
+//! `HKDF-SHA256(key, salt, "bvault-chunked-file")`, so every file has its
+//! own key either way.
+
+use wasm_bindgen::prelude::*;
+use zeroize::Zeroizing;
+
+use crate::envelope::{
+    check_salt_length, check_version, cipher_from_id, invalid, read_kdf, write_kdf, Reader,
+};
+use crate::kcv::{self, KCV_LENGTH};
+use crate::kdf::{hkdf_sha256, Key, KEY_LENGTH};
+use crate::{
+    new_salted_key, random_bytes, BVaultError, Cipher, ErrorCode, KdfParams, OpenOptions,
+    SealOptions, SALT_LENGTH,
+};
+
+const MAGIC: &[u8; 4] = b"BVCF";
+const VERSION: u8 = 1;
+
+const RAW_KEY_INFO: &[u8] = b"bvault-chunked-file";
+
+/// Nonce bytes taken by the chunk index and final flag.
+const COUNTER_LENGTH: usize = 5;
+
+const DEFAULT_CHUNK_SIZE: u32 = 64 * 1024;
+const MIN_CHUNK_SIZE: u32 = 1024;
+const MAX_CHUNK_SIZE: u32 = 16 * 1024 * 1024;
+
+fn finished() -> BVaultError {
+    BVaultError::new(ErrorCode::StreamFinished, "stream was already finalized")
+}
+
+fn truncated() -> BVaultError {
+    BVaultError::decryption_failed(ErrorCode::AuthFailed, "chunked file is truncated")
+}
+
+/// The per-file parameters and key shared by both directions.
+struct File {
+    cipher: Cipher,
+    key: Key,
+    chunk_size: u32,
+    prefix: Vec<u8>,
+    header: Vec<u8>,
+}
+
+impl File {
+    fn nonce(&self, index: u32, last: bool) -> Vec<u8> {
+        let mut nonce = self.prefix.clone();
+        nonce.extend_from_slice(&index.to_be_bytes());
+        nonce.push(last as u8);
+        nonce
+    }
+
+    fn seal(&self, index: u32, last: bool, chunk: &[u8]) -> Result<Vec<u8>, BVaultError> {
+        self.cipher
+            .encrypt_with_aad(&self.key[..], &self.nonce(index, last), chunk, &self.header)
+    }
+
+    fn open(&self, index: u32, last: bool, sealed: &[u8]) -> Result<Vec<u8>, BVaultError> {
+        self.cipher.decrypt_with_aad(
+            &self.key[..],
+            &self.nonce(index, last),
+            sealed,
+            &self.header,
+        )
+    }
+
+    fn sealed_chunk_size(&self) -> usize {
+        self.chunk_size as usize + self.cipher.tag_length()
+    }
+
+    /// Builds the header for a fresh file under `key`.
+    fn create(
+        cipher: Cipher,
+        kdf: Option<&KdfParams>,
+        salt: Vec<u8>,
+        key: Key,
+        chunk_size: Option<u32>,
+    ) -> Result<File, BVaultError> {
+        let chunk_size = chunk_size.unwrap_or(DEFAULT_CHUNK_SIZE);
+        if !(MIN_CHUNK_SIZE..=MAX_CHUNK_SIZE).contains(&chunk_size) {
+            return Err(BVaultError::new(
+                ErrorCode::InvalidArgument,
+                "chunk size must be between 1 KiB and 16 MiB",
+            ));
+        }
+        let prefix = random_bytes(cipher.nonce_length() - COUNTER_LENGTH)?;
+        let mut header = Vec::new();
+        header.extend_from_slice(MAGIC);
+        header.push(VERSION);
+        header.push(cipher.id());
+        write_kdf(&mut header, kdf);
+        header.push(salt.len() as u8);
+        header.extend_from_slice(&salt);
+        header.extend_from_slice(&kcv::compute(&key[..])?);
+        header.extend_from_slice(&chunk_size.to_be_bytes());
+        header.extend_from_slice(&prefix);
+        Ok(File {
+            cipher,
+            key,
+            chunk_size,
+            prefix,
+            header,
+        })
+    }
+}
+
+fn raw_file_key(key: &[u8], salt: &[u8]) -> Result<Key, BVaultError> {
+    if key.len() != KEY_LENGTH {
+        return Err(BVaultError::new(
+            ErrorCode::BadKeyLength,
+            "raw key must be 32 bytes",
+        ));
+    }
+    let mut file_key = Zeroizing::new([0u8; KEY_LENGTH]);
+    hkdf_sha256(key, Some(salt), RAW_KEY_INFO, &mut file_key[..])?;
+    Ok(file_key)
+}
+
+/// A parsed header, before the key is known.
+struct Header<'a> {
+    cipher: Cipher,
+    kdf: Option<KdfParams>,
+    salt: &'a [u8],
+    kcv: &'a [u8],
+    chunk_size: u32,
+    prefix: &'a [u8],
+    header: &'a [u8],
+}
+
+impl<'a> Header<'a> {
+    /// Reads the header at the start of `bytes`, which may continue with
+    /// chunks.
+    fn parse(bytes: &'a [u8]) -> Result<Header<'a>, BVaultError> {
+        let mut reader = Reader::new(bytes);
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err(invalid("not a bvault chunked file"));
+        }
+        check_version("chunked file", reader.u8()?, VERSION)?;
+        let cipher = cipher_from_id(reader.u8()?)?;
+        let kdf = read_kdf(&mut reader)?;
+        let salt_length = reader.u8()? as usize;
+        check_salt_length(salt_length)?;
+        let salt = reader.take(salt_length)?;
+        let kcv = reader.take(KCV_LENGTH)?;
+        let chunk_size = reader.u32()?;
+        if !(MIN_CHUNK_SIZE..=MAX_CHUNK_SIZE).contains(&chunk_size) {
+            return Err(invalid("chunk size out of range"));
+        }
+        let prefix = reader.take(cipher.nonce_length() - COUNTER_LENGTH)?;
+        let header_length = bytes.len() - reader.rest().len();
+        Ok(Header {
+            cipher,
+            kdf,
+            salt,
+            kcv,
+            chunk_size,
+            prefix,
+            header: &bytes[..header_length],
+        })
+    }
+
+    fn into_file(self, key: Key) -> Result<File, BVaultError> {
+        if !kcv::matches(&key[..], self.kcv)? {
+            return Err(kcv::wrong_password());
+        }
+        Ok(File {
+            cipher: self.cipher,
+            key,
+            chunk_size: self.chunk_size,
+            prefix: self.prefix.to_vec(),
+            header: self.header.to_vec(),
+        })
+    }
+}
+
+/// Whether `bytes` hold a complete header.
+#[cfg(feature = "streams")]
+pub(crate) fn header_complete(bytes: &[u8]) -> bool {
+    Header::parse(bytes).is_ok()
+}
+
+/// Encrypts a file chunk by chunk.
+#[wasm_bindgen]
+pub struct ChunkedEncryptor {
+    file: File,
+    pending: Zeroizing<Vec<u8>>,
+    index: u32,
+    done: bool,
+}
+
+#[wasm_bindgen]
+impl ChunkedEncryptor {
+    /// Derives a file key from `password` under a fresh salt. `chunk_size`
+    /// defaults to 64 KiB and must be between 1 KiB and 16 MiB; `options`
+    /// pick the cipher, KDF and salt length as for [`crate::seal`].
+    ///
+    /// # Errors
+    ///
+    /// - If `chunk_size` is out of range, an [`ErrorCode::InvalidArgument`]
+    ///   error is returned.
+    /// - Otherwise see [`crate::seal`].
+    #[wasm_bindgen(constructor)]
+    pub fn new(
+        password: &str,
+        chunk_size: Option<u32>,
+        options: Option<SealOptions>,
+    ) -> Result<ChunkedEncryptor, BVaultError> {
+        let _operation = crate::utils::set_panic_hook();
+        let options = options.unwrap_or_default();
+        check_salt_length(options.salt_length())?;
+        let kdf = options.kdf();
+        let (salt, key) = new_salted_key(
+            password,
+            options.salt_length(),
+            &kdf,
+            Some(options.cipher()),
+        )?;
+        let file = File::create(options.cipher(), Some(&kdf), salt, key, chunk_size)?;
+        Ok(ChunkedEncryptor::start(file))
+    }
+
+    /// Encrypts under a file key derived from a caller-supplied 32-byte key,
+    /// such as a [`crate::DataKey`]. `cipher` defaults to AES-256-GCM.
+    ///
+    /// # Errors
+    ///
+    /// - If `key` is not 32 bytes, an error is returned.
+    /// - If `chunk_size` is out of range, an [`ErrorCode::InvalidArgument`]
+    ///   error is returned.
+    /// - If the random number generator is unavailable, an error is returned.
+    pub fn with_raw_key(
+        key: &[u8],
+        chunk_size: Option<u32>,
+        cipher: Option<Cipher>,
+    ) -> Result<ChunkedEncryptor, BVaultError> {
+        let _operation = crate::utils::set_panic_hook();
+        let salt = random_bytes(SALT_LENGTH)?;
+        let file_key = raw_file_key(key, &salt)?;
+        let file = File::create(cipher.unwrap_or_default(), None, salt, file_key, chunk_size)?;
+        Ok(ChunkedEncryptor::start(file))
+    }
+
+    /// The file header; write it before the chunks.
+    #[wasm_bindgen(getter)]
+    pub fn header(&self) -> Vec<u8> {
+        self.file.header.clone()
+    }
+
+    /// Feeds the next part of the plaintext, of any length, and returns the
+    /// sealed chunks now complete. The last chunk is held back until
+    /// [`ChunkedEncryptor::finish`].
+    ///
+    /// # Errors
+    ///
+    /// - If the stream was already finished, an
+    ///   [`ErrorCode::StreamFinished`] error is returned.
+    /// - If the file would exceed 2^32 chunks, an error is returned.
+    pub fn push(&mut self, data: &[u8]) -> Result<Vec<u8>, BVaultError> {
+        if self.done {
+            return Err(finished());
+        }
+        self.pending.extend_from_slice(data);
+        let chunk_size = self.file.chunk_size as usize;
+        let mut out = Vec::new();
+        let mut start = 0;
+        while self.pending.len() - start > chunk_size {
+            let sealed =
+                self.file
+                    .seal(self.index, false, &self.pending[start..start + chunk_size])?;
+            out.extend_from_slice(&sealed);
+            self.index = self.index.checked_add(1).ok_or_else(|| {
+                BVaultError::new(ErrorCode::BadLength, "file exceeds 2^32 chunks")
+            })?;
+            start += chunk_size;
+        }
+        self.pending.drain(..start);
+        Ok(out)
+    }
+
+    /// Seals and returns the final chunk.
+    ///
+    /// # Errors
+    ///
+    /// - If the stream was already finished, an
+    ///   [`ErrorCode::StreamFinished`] error is returned.
+    pub fn finish(&mut self) -> Result<Vec<u8>, BVaultError> {
+        if self.done {
+            return Err(finished());
+        }
+        self.done = true;
+        let sealed = self.file.seal(self.index, true, &self.pending)?;
+        self.pending.clear();
+        Ok(sealed)
+    }
+}
+
+impl ChunkedEncryptor {
+    fn start(file: File) -> ChunkedEncryptor {
+        ChunkedEncryptor {
+            file,
+            pending: Zeroizing::new(Vec::new()),
+            index: 0,
+            done: false,
+        }
+    }
+}
+
+/// Decrypts a file from [`ChunkedEncryptor`], either streamed from the
+/// start or one chunk at a time.
+#[wasm_bindgen]
+pub struct ChunkedDecryptor {
+    file: File,
+    pending: Vec<u8>,
+    index: u32,
+    done: bool,
+}
+
+#[wasm_bindgen]
+impl ChunkedDecryptor {
+    /// Reads the header at the start of `file` (the rest of the file may
+    /// follow, or not) and derives the key from `password`.
+    ///
+    /// # Errors
+    ///
+    /// - If the header is malformed or uses an unknown version, cipher or
+    ///   KDF, an error is returned.
+    /// - If the file was encrypted under a raw key, an
+    ///   [`ErrorCode::InvalidArgument`] error is returned.
+    /// - If its cipher or KDF is below the floor in `options`, an
+    ///   [`ErrorCode::BelowMinimum`] error is returned.
+    /// - If the password (or keyfile) is wrong, an
+    ///   [`ErrorCode::WrongPassword`] error is returned.
+    #[wasm_bindgen(constructor)]
+    pub fn new(
+        file: &[u8],
+        password: &str,
+        options: Option<OpenOptions>,
+    ) -> Result<ChunkedDecryptor, BVaultError> {
+        let _operation = crate::utils::set_panic_hook();
+        let header = Header::parse(file)?;
+        let kdf = header.kdf.clone().ok_or_else(|| {
+            BVaultError::new(
+                ErrorCode::InvalidArgument,
+                "file was encrypted under a raw key; use with_raw_key",
+            )
+        })?;
+        let options = options.unwrap_or_default();
+        options.check_floor(header.cipher, &kdf)?;
+        let kdf = match options.keyfile() {
+            Some(keyfile) => kdf.with_keyfile(keyfile),
+            None => kdf,
+        };
+        crate::policy::check_decrypt(password, header.salt.len(), &kdf);
+        let blob = header.header;
+        let file = crate::throttle::attempt(blob, || {
+            let key = kdf.derive(password.as_bytes(), header.salt)?;
+            header.into_file(key)
+        })?;
+        Ok(ChunkedDecryptor::start(file))
+    }
+
+    /// Reads the header at the start of `file` for a file encrypted with
+    /// [`ChunkedEncryptor::with_raw_key`].
+    ///
+    /// # Errors
+    ///
+    /// - If the header is malformed, an error is returned.
+    /// - If `key` is not 32 bytes, an error is returned; if it is the wrong
+    ///   key, an [`ErrorCode::WrongPassword`] error.
+    pub fn with_raw_key(file: &[u8], key: &[u8]) -> Result<ChunkedDecryptor, BVaultError> {
+        let _operation = crate::utils::set_panic_hook();
+        let header = Header::parse(file)?;
+        if header.kdf.is_some() {
+            return Err(BVaultError::new(
+                ErrorCode::InvalidArgument,
+                "file was encrypted under a password",
+            ));
+        }
+        let file_key = raw_file_key(key, header.salt)?;
+        Ok(ChunkedDecryptor::start(header.into_file(file_key)?))
+    }
+
+    /// Length of the header, where the first chunk starts.
+    #[wasm_bindgen(getter)]
+    pub fn header_length(&self) -> u32 {
+        self.file.header.len() as u32
+    }
+
+    /// Plaintext bytes per chunk.
+    #[wasm_bindgen(getter)]
+    pub fn chunk_size(&self) -> u32 {
+        self.file.chunk_size
+    }
+
+    /// Bytes per sealed chunk, the last excepted: the chunk size plus the
+    /// cipher's tag (16 bytes, or 32 for [`Cipher::Cascade`]). Chunk `i`
+    /// starts at `header_length + i * sealed_chunk_size`.
+    #[wasm_bindgen(getter)]
+    pub fn sealed_chunk_size(&self) -> u32 {
+        self.file.sealed_chunk_size() as u32
+    }
+
+    /// Number of chunks in a file of `file_length` bytes, header included.
+    pub fn chunk_count(&self, file_length: f64) -> f64 {
+        let body = (file_length - self.file.header.len() as f64).max(0.0);
+        (body / self.file.sealed_chunk_size() as f64)
+            .ceil()
+            .max(1.0)
+    }
+
+    /// Decrypts chunk `index` on its own. `last` must say whether it is the
+    /// file's final chunk; see [`ChunkedDecryptor::chunk_count`].
+    ///
+    /// # Errors
+    ///
+    /// - If the chunk is not chunk `index`, or `last` is wrong, or it was
+    ///   tampered with, an [`ErrorCode::AuthFailed`] error is returned.
+    pub fn decrypt_chunk(
+        &self,
+        index: u32,
+        sealed: &[u8],
+        last: bool,
+    ) -> Result<Vec<u8>, BVaultError> {
+        if !last && sealed.len() != self.file.sealed_chunk_size() {
+            return Err(truncated());
+        }
+        self.file.open(index, last, sealed)
+    }
+
+    /// Feeds the next part of the chunks, of any length, and returns the
+    /// plaintext of the chunks now complete. The header must not be fed
+    /// again. Every returned byte is authenticated, but the file is only
+    /// known to be complete when [`ChunkedDecryptor::finish`] succeeds.
+    ///
+    /// # Errors
+    ///
+    /// - If the stream was already finished, an
+    ///   [`ErrorCode::StreamFinished`] error is returned.
+    /// - If a chunk fails authentication, an [`ErrorCode::AuthFailed`] error
+    ///   is returned.
+    pub fn push(&mut self, data: &[u8]) -> Result<Vec<u8>, BVaultError> {
+        if self.done {
+            return Err(finished());
+        }
+        self.pending.extend_from_slice(data);
+        let sealed_size = self.file.sealed_chunk_size();
+        let mut out = Vec::new();
+        let mut start = 0;
+        while self.pending.len() - start > sealed_size {
+            let chunk =
+                self.file
+                    .open(self.index, false, &self.pending[start..start + sealed_size])?;
+            out.extend_from_slice(&chunk);
+            self.index = self.index.checked_add(1).ok_or_else(truncated)?;
+            start += sealed_size;
+        }
+        self.pending.drain(..start);
+        Ok(out)
+    }
+
+    /// Decrypts the final chunk and returns its plaintext.
+    ///
+    /// # Errors
+    ///
+    /// - If the stream was already finished, an
+    ///   [`ErrorCode::StreamFinished`] error is returned.
+    /// - If the file was cut short or the final chunk fails authentication,
+    ///   an [`ErrorCode::AuthFailed`] error is returned.
+    pub fn finish(&mut self) -> Result<Vec<u8>, BVaultError> {
+        if self.done {
+            return Err(finished());
+        }
+        self.done = true;
+        if self.pending.len() < self.file.cipher.tag_length() {
+            return Err(truncated());
+        }
+        let pending = std::mem::take(&mut self.pending);
+        self.file.open(self.index, true, &pending).map_err(|_| {
+            BVaultError::decryption_failed(
+                ErrorCode::AuthFailed,
+                "final chunk failed authentication; the file may be truncated",
+            )
+        })
+    }
+}
+
+impl ChunkedDecryptor {
+    fn start(file: File) -> ChunkedDecryptor {
+        ChunkedDecryptor {
+            file,
+            pending: Vec::new(),
+            index: 0,
+            done: false,
+        }
+    }
+}
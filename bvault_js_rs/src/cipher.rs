@@ -0,0 +1,299 @@
+//! Registry of the AEAD cipher suites usable with a raw 256-bit key.
+//!
+//! The discriminant of each [`Cipher`] is the suite ID written into
+//! envelopes, so existing values must never be renumbered.
+//!
+//! AES-256-GCM needs the `gcm` feature and the two ChaCha20 suites the
+//! `chacha` feature; a build without them still parses their IDs but fails
+//! with [`ErrorCode::UnsupportedAlgorithm`] when asked to use them.
+//! AES-256-GCM-SIV is always built in, so envelopes work in every build.
+//!
+//! [`Cipher::Cascade`] layers AES-256-GCM under XChaCha20-Poly1305 and
+//! needs both features. Its layer keys are derived from the 256-bit key
+//! with HKDF-SHA256: the outer key once, the inner key per nonce (the nonce
+//! is the HKDF salt), so the inner layer's nonce never repeats under a key
+//! even when callers such as chunked files vary only the last bytes of the
+//! 24-byte nonce. Each layer authenticates the associated data.
+
+use aes_gcm_siv::aead::generic_array::GenericArray;
+use aes_gcm_siv::aead::{Aead, AeadInPlace, KeyInit, Payload};
+use aes_gcm_siv::Aes256GcmSiv;
+use wasm_bindgen::prelude::*;
+use zeroize::Zeroize;
+
+use crate::kdf::{hkdf_sha256, Key, KEY_LENGTH};
+use crate::{BVaultError, ErrorCode};
+
+/// Runs `$body` with `$suite` naming the implementation of `$cipher`, or
+/// fails if the suite is not compiled into this build.
+macro_rules! with_suite {
+    ($cipher:expr, $suite:ident => $body:expr) => {{
+        let cipher: Cipher = $cipher;
+        #[allow(unreachable_patterns)]
+        let result = match cipher {
+            #[cfg(feature = "gcm")]
+            Cipher::Aes256Gcm => {
+                type $suite = aes_gcm::Aes256Gcm;
+                $body
+            }
+            #[cfg(feature = "chacha")]
+            Cipher::ChaCha20Poly1305 => {
+                type $suite = chacha20poly1305::ChaCha20Poly1305;
+                $body
+            }
+            #[cfg(feature = "chacha")]
+            Cipher::XChaCha20Poly1305 => {
+                type $suite = chacha20poly1305::XChaCha20Poly1305;
+                $body
+            }
+            Cipher::Aes256GcmSiv => {
+                type $suite = Aes256GcmSiv;
+                $body
+            }
+            cipher => Err(BVaultError::new(
+                ErrorCode::UnsupportedAlgorithm,
+                format!("{:?} is not enabled in this build", cipher),
+            )),
+        };
+        result.map_err(|err: BVaultError| err.with_suite(cipher.name()))
+    }};
+}
+
+/// An authenticated cipher suite.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Cipher {
+    /// AES-256-GCM with a 12-byte nonce. The default.
+    Aes256Gcm = 1,
+    /// ChaCha20-Poly1305 with a 12-byte nonce.
+    ChaCha20Poly1305 = 2,
+    /// XChaCha20-Poly1305 with a 24-byte nonce.
+    XChaCha20Poly1305 = 3,
+    /// AES-256-GCM-SIV (RFC 8452) with a 12-byte nonce. Reusing a nonce
+    /// only reveals whether two messages are identical, instead of breaking
+    /// confidentiality and authenticity as with the other suites.
+    Aes256GcmSiv = 4,
+    /// AES-256-GCM, then XChaCha20-Poly1305 over its output, under
+    /// independent subkeys, with a 24-byte nonce. Data stays safe as long
+    /// as either suite holds, at the cost of two passes and 32 bytes of
+    /// tags.
+    Cascade = 5,
+}
+
+impl Default for Cipher {
+    /// AES-256-GCM, or in builds without the `gcm` feature the first suite
+    /// that is available.
+    fn default() -> Self {
+        if cfg!(feature = "gcm") {
+            Cipher::Aes256Gcm
+        } else if cfg!(feature = "chacha") {
+            Cipher::XChaCha20Poly1305
+        } else {
+            Cipher::Aes256GcmSiv
+        }
+    }
+}
+
+impl Cipher {
+    pub(crate) fn id(self) -> u8 {
+        self as u8
+    }
+
+    /// The suite's name, as in vault JSON exports: `"aes-256-gcm"`,
+    /// `"chacha20-poly1305"`, `"xchacha20-poly1305"`, `"aes-256-gcm-siv"` or
+    /// `"aes-256-gcm+xchacha20-poly1305"`.
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Cipher::Aes256Gcm => "aes-256-gcm",
+            Cipher::ChaCha20Poly1305 => "chacha20-poly1305",
+            Cipher::XChaCha20Poly1305 => "xchacha20-poly1305",
+            Cipher::Aes256GcmSiv => "aes-256-gcm-siv",
+            Cipher::Cascade => "aes-256-gcm+xchacha20-poly1305",
+        }
+    }
+
+    pub(crate) fn from_id(id: u8) -> Option<Cipher> {
+        match id {
+            1 => Some(Cipher::Aes256Gcm),
+            2 => Some(Cipher::ChaCha20Poly1305),
+            3 => Some(Cipher::XChaCha20Poly1305),
+            4 => Some(Cipher::Aes256GcmSiv),
+            5 => Some(Cipher::Cascade),
+            _ => None,
+        }
+    }
+
+    /// Length of the nonce this suite expects, in bytes.
+    pub(crate) fn nonce_length(self) -> usize {
+        match self {
+            Cipher::Aes256Gcm | Cipher::ChaCha20Poly1305 | Cipher::Aes256GcmSiv => 12,
+            Cipher::XChaCha20Poly1305 | Cipher::Cascade => 24,
+        }
+    }
+
+    /// Length of the authentication tag this suite appends, in bytes.
+    pub(crate) fn tag_length(self) -> usize {
+        match self {
+            Cipher::Cascade => 32,
+            _ => 16,
+        }
+    }
+
+    /// Encrypts `plaintext` and authenticates `aad` (which must be supplied
+    /// again to decrypt), returning the ciphertext with the tag appended.
+    pub(crate) fn encrypt_with_aad(
+        self,
+        key: &[u8],
+        nonce: &[u8],
+        plaintext: &[u8],
+        aad: &[u8],
+    ) -> Result<Vec<u8>, BVaultError> {
+        self.check_nonce(nonce)?;
+        if self == Cipher::Cascade {
+            return cascade_encrypt(key, nonce, plaintext, aad);
+        }
+        let payload = Payload {
+            msg: plaintext,
+            aad,
+        };
+        with_suite!(self, Suite => encrypt_with::<Suite>(key, nonce, payload))
+    }
+
+    /// Verifies and decrypts a ciphertext produced by
+    /// [`Cipher::encrypt_with_aad`].
+    pub(crate) fn decrypt_with_aad(
+        self,
+        key: &[u8],
+        nonce: &[u8],
+        ciphertext: &[u8],
+        aad: &[u8],
+    ) -> Result<Vec<u8>, BVaultError> {
+        self.check_nonce(nonce)?;
+        if self == Cipher::Cascade {
+            return cascade_decrypt(key, nonce, ciphertext, aad);
+        }
+        let payload = Payload {
+            msg: ciphertext,
+            aad,
+        };
+        with_suite!(self, Suite => decrypt_with::<Suite>(key, nonce, payload))
+    }
+
+    /// Verifies and decrypts `buffer` (ciphertext with the tag appended)
+    /// in place against `aad`, leaving only the plaintext. On failure the
+    /// buffer is wiped and emptied, so no unauthenticated plaintext
+    /// survives.
+    pub(crate) fn decrypt_in_place(
+        self,
+        key: &[u8],
+        nonce: &[u8],
+        buffer: &mut Vec<u8>,
+        aad: &[u8],
+    ) -> Result<(), BVaultError> {
+        self.check_nonce(nonce)?;
+        let result = if self == Cipher::Cascade {
+            cascade_decrypt(key, nonce, buffer, aad).map(|plaintext| {
+                buffer.zeroize();
+                *buffer = plaintext;
+            })
+        } else {
+            with_suite!(self, Suite => decrypt_in_place_with::<Suite>(key, nonce, buffer, aad))
+        };
+        if result.is_err() {
+            buffer.zeroize();
+        }
+        result
+    }
+
+    fn check_nonce(self, nonce: &[u8]) -> Result<(), BVaultError> {
+        if nonce.len() != self.nonce_length() {
+            return Err(BVaultError::new(
+                ErrorCode::BadIvLength,
+                format!("nonce must be {} bytes", self.nonce_length()),
+            ));
+        }
+        Ok(())
+    }
+}
+
+const CASCADE_INNER_INFO: &[u8] = b"bvault cascade aes-256-gcm";
+const CASCADE_OUTER_INFO: &[u8] = b"bvault cascade xchacha20-poly1305";
+
+/// The inner (AES-256-GCM) and outer (XChaCha20-Poly1305) keys and the
+/// inner nonce of a [`Cipher::Cascade`] message.
+fn cascade_layers(key: &[u8], nonce: &[u8]) -> Result<(Key, Key, [u8; 12]), BVaultError> {
+    if key.len() != KEY_LENGTH {
+        return Err(BVaultError::new(
+            ErrorCode::BadKeyLength,
+            "invalid key length",
+        ));
+    }
+    let mut inner = Key::default();
+    hkdf_sha256(key, Some(nonce), CASCADE_INNER_INFO, &mut inner[..])?;
+    let mut outer = Key::default();
+    hkdf_sha256(key, None, CASCADE_OUTER_INFO, &mut outer[..])?;
+    let mut inner_nonce = [0u8; 12];
+    inner_nonce.copy_from_slice(&nonce[..12]);
+    Ok((inner, outer, inner_nonce))
+}
+
+fn cascade_encrypt(
+    key: &[u8],
+    nonce: &[u8],
+    plaintext: &[u8],
+    aad: &[u8],
+) -> Result<Vec<u8>, BVaultError> {
+    let (inner, outer, inner_nonce) = cascade_layers(key, nonce)?;
+    let mut layer = Cipher::Aes256Gcm.encrypt_with_aad(&inner[..], &inner_nonce, plaintext, aad)?;
+    let sealed = Cipher::XChaCha20Poly1305.encrypt_with_aad(&outer[..], nonce, &layer, aad);
+    layer.zeroize();
+    sealed
+}
+
+fn cascade_decrypt(
+    key: &[u8],
+    nonce: &[u8],
+    ciphertext: &[u8],
+    aad: &[u8],
+) -> Result<Vec<u8>, BVaultError> {
+    let (inner, outer, inner_nonce) = cascade_layers(key, nonce)?;
+    let mut layer =
+        Cipher::XChaCha20Poly1305.decrypt_with_aad(&outer[..], nonce, ciphertext, aad)?;
+    let plaintext = Cipher::Aes256Gcm.decrypt_with_aad(&inner[..], &inner_nonce, &layer, aad);
+    layer.zeroize();
+    plaintext
+}
+
+fn encrypt_with<C: Aead + KeyInit>(
+    key: &[u8],
+    nonce: &[u8],
+    plaintext: Payload<'_, '_>,
+) -> Result<Vec<u8>, BVaultError> {
+    C::new_from_slice(key)
+        .map_err(|_| BVaultError::new(ErrorCode::BadKeyLength, "invalid key length"))?
+        .encrypt(GenericArray::from_slice(nonce), plaintext)
+        .map_err(|_| BVaultError::new(ErrorCode::EncryptionFailed, "encryption error"))
+}
+
+fn decrypt_with<C: Aead + KeyInit>(
+    key: &[u8],
+    nonce: &[u8],
+    ciphertext: Payload<'_, '_>,
+) -> Result<Vec<u8>, BVaultError> {
+    C::new_from_slice(key)
+        .map_err(|_| BVaultError::new(ErrorCode::BadKeyLength, "invalid key length"))?
+        .decrypt(GenericArray::from_slice(nonce), ciphertext)
+        .map_err(|_| BVaultError::decryption_failed(ErrorCode::AuthFailed, "authentication failed"))
+}
+
+fn decrypt_in_place_with<C: AeadInPlace + KeyInit>(
+    key: &[u8],
+    nonce: &[u8],
+    buffer: &mut Vec<u8>,
+    aad: &[u8],
+) -> Result<(), BVaultError> {
+    C::new_from_slice(key)
+        .map_err(|_| BVaultError::new(ErrorCode::BadKeyLength, "invalid key length"))?
+        .decrypt_in_place(GenericArray::from_slice(nonce), aad, buffer)
+        .map_err(|_| BVaultError::decryption_failed(ErrorCode::AuthFailed, "authentication failed"))
+}
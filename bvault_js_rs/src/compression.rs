@@ -0,0 +1,98 @@
+//! Optional compression of envelope plaintexts.
+//!
+//! Large JSON exports shrink five- to tenfold under DEFLATE, so
+//! [`SealOptions`](crate::SealOptions) can compress the plaintext before it
+//! is encrypted. The choice is recorded in the envelope and [`crate::open`]
+//! decompresses transparently.
+//!
+//! Compression leaks information through the ciphertext length: if an
+//! attacker can influence part of the plaintext and observe the size of the
+//! result, they can recover secrets stored next to their input (as in the
+//! CRIME and BREACH attacks on TLS and HTTP). Only compress data that mixes
+//! no attacker-influenced content with secrets, such as a user's own vault
+//! export. Compression is off by default.
+
+use wasm_bindgen::prelude::*;
+use zeroize::Zeroizing;
+
+use crate::{BVaultError, ErrorCode};
+
+/// Largest plaintext a compressed envelope may expand to (256 MiB), so a
+/// crafted body cannot exhaust WASM memory.
+#[cfg(feature = "compression")]
+const MAX_DECOMPRESSED_LENGTH: usize = 256 * 1024 * 1024;
+
+/// DEFLATE compression level, on miniz's 0 to 10 scale.
+#[cfg(feature = "compression")]
+const DEFLATE_LEVEL: u8 = 6;
+
+/// A compression algorithm applied to the plaintext before encryption.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Compression {
+    /// No compression. The default.
+    #[default]
+    None = 0,
+    /// Raw DEFLATE (RFC 1951).
+    Deflate = 1,
+}
+
+impl Compression {
+    pub(crate) fn id(self) -> u8 {
+        self as u8
+    }
+
+    pub(crate) fn from_id(id: u8) -> Option<Compression> {
+        match id {
+            0 => Some(Compression::None),
+            1 => Some(Compression::Deflate),
+            _ => None,
+        }
+    }
+
+    /// Compresses `plaintext`; a copy of it for [`Compression::None`].
+    pub(crate) fn compress(self, plaintext: &[u8]) -> Result<Zeroizing<Vec<u8>>, BVaultError> {
+        match self {
+            Compression::None => Ok(Zeroizing::new(plaintext.to_vec())),
+            #[cfg(feature = "compression")]
+            Compression::Deflate => Ok(Zeroizing::new(miniz_oxide::deflate::compress_to_vec(
+                plaintext,
+                DEFLATE_LEVEL,
+            ))),
+            #[cfg(not(feature = "compression"))]
+            Compression::Deflate => Err(not_enabled()),
+        }
+    }
+
+    /// Reverses [`Compression::compress`], wiping `data` either way.
+    pub(crate) fn decompress(self, data: Vec<u8>) -> Result<Vec<u8>, BVaultError> {
+        match self {
+            Compression::None => Ok(data),
+            #[cfg(feature = "compression")]
+            Compression::Deflate => {
+                let data = Zeroizing::new(data);
+                miniz_oxide::inflate::decompress_to_vec_with_limit(&data, MAX_DECOMPRESSED_LENGTH)
+                    .map_err(|mut err| {
+                        zeroize::Zeroize::zeroize(&mut err.output);
+                        BVaultError::new(
+                            ErrorCode::InvalidEnvelope,
+                            "compressed plaintext is corrupt or too large",
+                        )
+                    })
+            }
+            #[cfg(not(feature = "compression"))]
+            Compression::Deflate => {
+                drop(Zeroizing::new(data));
+                Err(not_enabled())
+            }
+        }
+    }
+}
+
+#[cfg(not(feature = "compression"))]
+fn not_enabled() -> BVaultError {
+    BVaultError::new(
+        ErrorCode::UnsupportedAlgorithm,
+        "compression is not enabled in this build",
+    )
+}
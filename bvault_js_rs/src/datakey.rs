@@ -0,0 +1,124 @@
+//! Envelope encryption with per-item data keys.
+//!
+//! Each item is encrypted under its own random data key (DEK), and only the
+//! DEK is encrypted under the password-derived key (the KEK). The wrapped
+//! DEK is an ordinary password envelope (see `envelope`) around the 32 key
+//! bytes, and payloads are raw-key envelopes under the DEK, so changing the
+//! password means re-wrapping each DEK with [`crate::reencrypt`] while the
+//! payloads stay untouched.
+
+use wasm_bindgen::prelude::*;
+use zeroize::Zeroizing;
+
+use crate::envelope::invalid;
+use crate::kdf::KEY_LENGTH;
+use crate::{
+    open, open_with_raw_key, random_bytes, seal, seal_with_raw_key, BVaultError, Cipher,
+    OpenOptions, SealOptions,
+};
+
+/// A fresh data key, in the clear and wrapped under a password.
+#[wasm_bindgen]
+#[derive(Clone)]
+pub struct DataKey {
+    key: Zeroizing<Vec<u8>>,
+    wrapped: Vec<u8>,
+}
+
+#[wasm_bindgen]
+impl DataKey {
+    /// The 32-byte data key. Use it with [`crate::seal_with_raw_key`] while
+    /// it is in memory, and never store it.
+    #[wasm_bindgen(getter)]
+    pub fn key(&self) -> Vec<u8> {
+        self.key.to_vec()
+    }
+
+    /// The data key sealed under the password; store this next to the item.
+    #[wasm_bindgen(getter)]
+    pub fn wrapped(&self) -> Vec<u8> {
+        self.wrapped.clone()
+    }
+}
+
+/// Generates a random data key and wraps it under `password`. `options`
+/// choose the cipher and KDF of the wrapping, as for [`crate::seal`].
+///
+/// # Errors
+///
+/// See [`crate::seal`].
+#[wasm_bindgen]
+pub fn generate_data_key(
+    password: &str,
+    options: Option<SealOptions>,
+) -> Result<DataKey, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    let key = Zeroizing::new(random_bytes(KEY_LENGTH)?);
+    let wrapped = seal(&key, password, options)?;
+    Ok(DataKey { key, wrapped })
+}
+
+/// Recovers the data key from its wrapped form.
+///
+/// # Errors
+///
+/// - If the password is wrong, an [`crate::ErrorCode::WrongPassword`] error
+///   is returned.
+/// - If `wrapped_key` is not a wrapped data key, an error is returned.
+#[wasm_bindgen]
+pub fn unwrap_data_key(
+    wrapped_key: &[u8],
+    password: &str,
+    options: Option<OpenOptions>,
+) -> Result<Vec<u8>, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    let key = Zeroizing::new(open(wrapped_key, password, options)?);
+    if key.len() != KEY_LENGTH {
+        return Err(invalid("wrapped data key must hold 32 bytes"));
+    }
+    Ok(key.to_vec())
+}
+
+/// Encrypts `plaintext` under the data key in `wrapped_key`, producing a
+/// raw-key envelope. `cipher` defaults to AES-256-GCM.
+///
+/// Every call runs the KDF to unwrap the data key; to encrypt many items
+/// under one key, unwrap it once with [`unwrap_data_key`] and call
+/// [`crate::seal_with_raw_key`] instead.
+///
+/// # Errors
+///
+/// See [`unwrap_data_key`] and [`crate::seal_with_raw_key`].
+#[wasm_bindgen]
+pub fn encrypt_with_data_key(
+    plaintext: &[u8],
+    wrapped_key: &[u8],
+    password: &str,
+    cipher: Option<Cipher>,
+) -> Result<Vec<u8>, BVaultError> {
+    let key = Zeroizing::new(unwrap_data_key(wrapped_key, password, None)?);
+    seal_with_raw_key(
+        plaintext,
+        &key,
+        Some(SealOptions::for_cipher(cipher.unwrap_or_default())),
+    )
+}
+
+/// Decrypts an envelope from [`encrypt_with_data_key`].
+///
+/// # Errors
+///
+/// - See [`unwrap_data_key`].
+/// - If the envelope was encrypted under a different data key, an
+///   [`crate::ErrorCode::WrongPassword`] error is returned.
+/// - If the envelope is malformed or fails authentication, an error is
+///   returned.
+#[wasm_bindgen]
+pub fn decrypt_with_data_key(
+    envelope: &[u8],
+    wrapped_key: &[u8],
+    password: &str,
+) -> Result<Vec<u8>, BVaultError> {
+    let key = Zeroizing::new(unwrap_data_key(wrapped_key, password, None)?);
+    open_with_raw_key(envelope, &key, None)
+}
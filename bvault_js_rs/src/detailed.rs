@@ -0,0 +1,192 @@
+//! Variants of [`crate::seal`] and [`crate::open_any`] that return what was
+//! done alongside the bytes: the format version, cipher, KDF and framing,
+//! whether the item was legacy, and how long it took. The plain functions
+//! stay as they are; use these where the metadata is wanted, such as to
+//! spot items due for re-encryption.
+
+use wasm_bindgen::prelude::*;
+use zeroize::Zeroizing;
+
+use crate::envelope::Envelope;
+use crate::{
+    open_any, seal, BVaultError, Cipher, Compression, KdfParams, OpenOptions, Padding, SealOptions,
+};
+
+/// The result of [`seal_detailed`].
+#[wasm_bindgen]
+#[derive(Clone)]
+pub struct SealResult {
+    envelope: Vec<u8>,
+    meta: Envelope,
+    elapsed_ms: f64,
+}
+
+#[wasm_bindgen]
+impl SealResult {
+    /// The envelope, as [`crate::seal`] returns it.
+    #[wasm_bindgen(getter)]
+    pub fn envelope(&self) -> Vec<u8> {
+        self.envelope.clone()
+    }
+
+    /// The envelope format version.
+    #[wasm_bindgen(getter)]
+    pub fn version(&self) -> u8 {
+        self.meta.version
+    }
+
+    /// The cipher used.
+    #[wasm_bindgen(getter)]
+    pub fn cipher(&self) -> Cipher {
+        self.meta.cipher
+    }
+
+    /// The KDF parameters used.
+    #[wasm_bindgen(getter)]
+    pub fn kdf(&self) -> Option<KdfParams> {
+        self.meta.kdf.clone()
+    }
+
+    /// Length of the generated salt, in bytes.
+    #[wasm_bindgen(getter)]
+    pub fn salt_length(&self) -> usize {
+        self.meta.salt.len()
+    }
+
+    /// Whether a key check value was stored.
+    #[wasm_bindgen(getter)]
+    pub fn key_check(&self) -> bool {
+        self.meta.kcv.is_some()
+    }
+
+    /// Milliseconds taken, key derivation included.
+    #[wasm_bindgen(getter)]
+    pub fn elapsed_ms(&self) -> f64 {
+        self.elapsed_ms
+    }
+}
+
+/// The result of [`open_detailed`].
+#[wasm_bindgen]
+#[derive(Clone)]
+pub struct OpenResult {
+    plaintext: Zeroizing<Vec<u8>>,
+    /// `None` for legacy items, which have no header.
+    meta: Option<Envelope>,
+    kdf: KdfParams,
+    elapsed_ms: f64,
+}
+
+#[wasm_bindgen]
+impl OpenResult {
+    /// The decrypted bytes.
+    #[wasm_bindgen(getter)]
+    pub fn plaintext(&self) -> Vec<u8> {
+        self.plaintext.to_vec()
+    }
+
+    /// Whether the item was in the legacy CBC format; see
+    /// [`crate::OpenedItem::legacy`].
+    #[wasm_bindgen(getter)]
+    pub fn legacy(&self) -> bool {
+        self.meta.is_none()
+    }
+
+    /// The envelope format version; `undefined` for legacy items.
+    #[wasm_bindgen(getter)]
+    pub fn version(&self) -> Option<u8> {
+        self.meta.as_ref().map(|meta| meta.version)
+    }
+
+    /// The cipher the envelope used; `undefined` for legacy items, which
+    /// are AES-256-CBC with HMAC-SHA256.
+    #[wasm_bindgen(getter)]
+    pub fn cipher(&self) -> Option<Cipher> {
+        self.meta.as_ref().map(|meta| meta.cipher)
+    }
+
+    /// The KDF parameters the key was derived with, without any keyfile.
+    #[wasm_bindgen(getter)]
+    pub fn kdf(&self) -> KdfParams {
+        self.kdf.clone()
+    }
+
+    /// The compression the envelope used.
+    #[wasm_bindgen(getter)]
+    pub fn compression(&self) -> Compression {
+        self.meta
+            .as_ref()
+            .map_or(Compression::None, |meta| meta.framing.compression)
+    }
+
+    /// The padding the envelope used.
+    #[wasm_bindgen(getter)]
+    pub fn padding(&self) -> Padding {
+        self.meta
+            .as_ref()
+            .map_or(Padding::None, |meta| meta.framing.padding)
+    }
+
+    /// Milliseconds taken, key derivation included.
+    #[wasm_bindgen(getter)]
+    pub fn elapsed_ms(&self) -> f64 {
+        self.elapsed_ms
+    }
+}
+
+/// [`crate::seal`], reporting the parameters it sealed with.
+///
+/// # Errors
+///
+/// See [`crate::seal`].
+#[wasm_bindgen]
+pub fn seal_detailed(
+    plaintext: &[u8],
+    password: &str,
+    options: Option<SealOptions>,
+) -> Result<SealResult, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    let start = crate::utils::now_ms();
+    let envelope = seal(plaintext, password, options)?;
+    let elapsed_ms = crate::utils::now_ms() - start;
+    let mut meta = Envelope::parse(&envelope)?;
+    meta.body = Vec::new();
+    Ok(SealResult {
+        envelope,
+        meta,
+        elapsed_ms,
+    })
+}
+
+/// [`crate::open_any`], reporting the format and parameters of the item.
+///
+/// # Errors
+///
+/// See [`crate::open_any`].
+#[wasm_bindgen]
+pub fn open_detailed(
+    data: &[u8],
+    password: &str,
+    options: Option<OpenOptions>,
+) -> Result<OpenResult, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    let legacy_kdf = options.as_ref().map(OpenOptions::legacy_kdf_params);
+    let start = crate::utils::now_ms();
+    let item = open_any(data, password, options)?;
+    let elapsed_ms = crate::utils::now_ms() - start;
+
+    let (meta, kdf) = if item.legacy() {
+        (None, legacy_kdf.flatten().unwrap_or_default())
+    } else {
+        let mut meta = Envelope::parse(data)?;
+        meta.body = Vec::new();
+        let kdf = meta.kdf.clone().unwrap_or_default();
+        (Some(meta), kdf)
+    };
+    Ok(OpenResult {
+        plaintext: Zeroizing::new(item.plaintext()),
+        meta,
+        kdf,
+        elapsed_ms,
+    })
+}
@@ -0,0 +1,103 @@
+//! Deterministic encryption with AES-SIV (RFC 5297), for fields a server
+//! must be able to compare without decrypting, such as detecting the same
+//! breached password across a team.
+//!
+//! Equal plaintexts under the same key and context give equal ciphertexts;
+//! that is the point, and also all it leaks beyond the length. Everything
+//! else in the crate encrypts with a random nonce and should be preferred
+//! wherever duplicates need not be found.
+//!
+//! The SIV key is `HKDF-SHA256(key, none, "bvault-deterministic")`, 64
+//! bytes, so the caller's key can also serve other purposes without its
+//! deterministic use weakening them. Ciphertexts are:
+//!
+//! ```text
+//! version  1   currently 1
+//! siv      16  the synthetic IV, which is also the tag
+//! body     n   the plaintext under AES-CTR
+//! ```
+
+use aes_siv::siv::Aes256Siv;
+use aes_siv::KeyInit;
+use wasm_bindgen::prelude::*;
+use zeroize::Zeroizing;
+
+use crate::envelope::{check_version, invalid, Reader};
+use crate::kdf::{hkdf_sha256, KEY_LENGTH};
+use crate::{BVaultError, ErrorCode};
+
+const VERSION: u8 = 1;
+
+const SIV_KEY_INFO: &[u8] = b"bvault-deterministic";
+const SIV_KEY_LENGTH: usize = 64;
+const SIV_LENGTH: usize = 16;
+
+fn siv(key: &[u8]) -> Result<Aes256Siv, BVaultError> {
+    if key.len() != KEY_LENGTH {
+        return Err(BVaultError::new(
+            ErrorCode::BadKeyLength,
+            "deterministic encryption keys must be 32 bytes",
+        ));
+    }
+    let mut siv_key = Zeroizing::new([0u8; SIV_KEY_LENGTH]);
+    hkdf_sha256(key, None, SIV_KEY_INFO, &mut siv_key[..])?;
+    Ok(Aes256Siv::new((&*siv_key).into()))
+}
+
+/// Encrypts `plaintext` deterministically under the 32-byte `key`. The
+/// same plaintext, key and `context` always give the same ciphertext.
+///
+/// `context` is authenticated but not stored, like the associated data of
+/// the randomized APIs. Pass the field name, such as `"password"`, so equal
+/// values in different fields do not compare equal.
+///
+/// # Errors
+///
+/// - If `key` is not 32 bytes, an [`ErrorCode::BadKeyLength`] error is
+///   returned.
+#[wasm_bindgen]
+pub fn deterministic_encrypt(
+    plaintext: &[u8],
+    key: &[u8],
+    context: Option<Vec<u8>>,
+) -> Result<Vec<u8>, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    let context = context.unwrap_or_default();
+    let body = siv(key)?
+        .encrypt([&context[..]], plaintext)
+        .map_err(|_| BVaultError::new(ErrorCode::EncryptionFailed, "AES-SIV failed"))?;
+    let mut out = Vec::with_capacity(1 + body.len());
+    out.push(VERSION);
+    out.extend_from_slice(&body);
+    Ok(out)
+}
+
+/// Decrypts the output of [`deterministic_encrypt`] with the same key and
+/// `context`.
+///
+/// # Errors
+///
+/// - If `key` is not 32 bytes, an [`ErrorCode::BadKeyLength`] error is
+///   returned.
+/// - If the ciphertext is truncated or uses an unknown version, an error is
+///   returned.
+/// - If authentication fails (wrong key or context, or tampered data), an
+///   error is returned.
+#[wasm_bindgen]
+pub fn deterministic_decrypt(
+    ciphertext: &[u8],
+    key: &[u8],
+    context: Option<Vec<u8>>,
+) -> Result<Vec<u8>, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    let context = context.unwrap_or_default();
+    let mut siv = siv(key)?;
+    let mut reader = Reader::new(ciphertext);
+    check_version("deterministic ciphertext", reader.u8()?, VERSION)?;
+    let body = reader.rest();
+    if body.len() < SIV_LENGTH {
+        return Err(invalid("deterministic ciphertext is truncated"));
+    }
+    siv.decrypt([&context[..]], body)
+        .map_err(|_| BVaultError::decryption_failed(ErrorCode::AuthFailed, "AES-SIV tag mismatch"))
+}
@@ -0,0 +1,130 @@
+//! Entry-level comparison of two vaults, for sync and audit UIs.
+//!
+//! [`diff_vaults`] decrypts both vaults inside WASM and reports only entry
+//! IDs, and for entries holding JSON objects the names of the top-level
+//! fields that changed; no plaintext crosses to JS.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use wasm_bindgen::prelude::*;
+use zeroize::Zeroizing;
+
+use crate::{BVaultError, OpenOptions, Vault};
+
+/// The entries added, removed and modified from one vault to another.
+#[wasm_bindgen]
+pub struct VaultDiff {
+    added: Vec<String>,
+    removed: Vec<String>,
+    modified: Vec<String>,
+    fields: BTreeMap<String, Vec<String>>,
+}
+
+#[wasm_bindgen]
+impl VaultDiff {
+    /// IDs of entries only in the second vault, in sorted order.
+    #[wasm_bindgen(getter)]
+    pub fn added(&self) -> Vec<String> {
+        self.added.clone()
+    }
+
+    /// IDs of entries only in the first vault, in sorted order.
+    #[wasm_bindgen(getter)]
+    pub fn removed(&self) -> Vec<String> {
+        self.removed.clone()
+    }
+
+    /// IDs of entries in both vaults whose plaintexts differ, in sorted
+    /// order.
+    #[wasm_bindgen(getter)]
+    pub fn modified(&self) -> Vec<String> {
+        self.modified.clone()
+    }
+
+    /// Whether the vaults hold the same entries.
+    #[wasm_bindgen(getter)]
+    pub fn unchanged(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.modified.is_empty()
+    }
+
+    /// For a modified entry that is a JSON object in both vaults, the
+    /// top-level fields added, removed or changed, in sorted order;
+    /// `undefined` for any other entry.
+    pub fn changed_fields(&self, id: &str) -> Option<Vec<String>> {
+        self.fields.get(id).cloned()
+    }
+}
+
+/// The names of the top-level fields that differ between two JSON
+/// objects, or `None` if either plaintext is not one.
+fn changed_fields(a: &[u8], b: &[u8]) -> Option<Vec<String>> {
+    let a: serde_json::Map<String, serde_json::Value> = serde_json::from_slice(a).ok()?;
+    let b: serde_json::Map<String, serde_json::Value> = serde_json::from_slice(b).ok()?;
+    let names: BTreeSet<&String> = a.keys().chain(b.keys()).collect();
+    Some(
+        names
+            .into_iter()
+            .filter(|name| a.get(*name) != b.get(*name))
+            .cloned()
+            .collect(),
+    )
+}
+
+/// Compares vault `a` with vault `b`: which entries `b` adds, removes or
+/// modifies. Entries are matched by ID and compared by plaintext, so
+/// re-encrypting an entry without changing it is not a modification.
+///
+/// Both vaults must open with `password` (and the keyfile in `options`, if
+/// any), though their salts may differ.
+///
+/// # Errors
+///
+/// - If a vault is malformed or the password is wrong for either, an error
+///   is returned.
+/// - If an entry fails authentication, an error is returned.
+#[wasm_bindgen]
+pub fn diff_vaults(
+    a: &[u8],
+    b: &[u8],
+    password: &str,
+    options: Option<OpenOptions>,
+) -> Result<VaultDiff, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    let a = Vault::unlock(a, password, options.clone())?;
+    let b = Vault::unlock(b, password, options)?;
+
+    let mut diff = VaultDiff {
+        added: Vec::new(),
+        removed: Vec::new(),
+        modified: Vec::new(),
+        fields: BTreeMap::new(),
+    };
+    for (id, entry) in a.entries() {
+        let theirs = match b.entries().get(id) {
+            Some(theirs) => theirs,
+            None => {
+                diff.removed.push(id.clone());
+                continue;
+            }
+        };
+        let metadata_changed = a.open_entry_metadata(id, entry)?.map(Zeroizing::new)
+            != b.open_entry_metadata(id, theirs)?.map(Zeroizing::new);
+        let ours = Zeroizing::new(a.open_entry(id, entry)?);
+        let theirs = Zeroizing::new(b.open_entry(id, theirs)?);
+        if ours[..] != theirs[..] {
+            if let Some(fields) = changed_fields(&ours, &theirs) {
+                diff.fields.insert(id.clone(), fields);
+            }
+            diff.modified.push(id.clone());
+        } else if metadata_changed {
+            diff.modified.push(id.clone());
+        }
+    }
+    diff.added = b
+        .entries()
+        .keys()
+        .filter(|id| !a.entries().contains_key(*id))
+        .cloned()
+        .collect();
+    Ok(diff)
+}
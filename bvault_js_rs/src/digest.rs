@@ -0,0 +1,362 @@
+//! Hashing and HMAC for callers outside the crate's own formats: attachment
+//! checksums, content addressing, request signatures, blind indexes and the
+//! like.
+//!
+//! Results come back as a [`DigestOutput`], which hands out the same value
+//! as bytes, lowercase hex, standard base64 or base64url, so every caller
+//! encodes it the same way.
+
+use std::convert::TryInto;
+
+use hmac::digest::KeyInit;
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256, Sha512};
+use subtle::ConstantTimeEq;
+use wasm_bindgen::prelude::*;
+
+use crate::utils::to_hex;
+use crate::{bytes_to_b64, BVaultError, Encoding, ErrorCode};
+
+/// Length of an HMAC-SHA256 tag, in bytes.
+const SHA256_LENGTH: usize = 32;
+/// Length of an HMAC-SHA512 tag, in bytes.
+const SHA512_LENGTH: usize = 64;
+
+/// Length of a BLAKE3 key, in bytes.
+const BLAKE3_KEY_LENGTH: usize = 32;
+/// Longest output [`blake3_derive_key`] produces, in bytes.
+const BLAKE3_MAX_DERIVE_LENGTH: usize = 1024;
+
+/// Hash functions accepted by [`hash`].
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HashAlgorithm {
+    /// SHA-256, 32 bytes.
+    Sha256 = 1,
+    /// SHA-512, 64 bytes.
+    Sha512 = 2,
+    /// BLAKE3 with its default 32-byte output.
+    Blake3 = 3,
+}
+
+/// A MAC or digest, in the encoding the caller needs.
+#[wasm_bindgen]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DigestOutput {
+    bytes: Vec<u8>,
+}
+
+#[wasm_bindgen]
+impl DigestOutput {
+    /// The raw bytes.
+    #[wasm_bindgen(getter)]
+    pub fn bytes(&self) -> Vec<u8> {
+        self.bytes.clone()
+    }
+
+    /// Lowercase hex.
+    #[wasm_bindgen(getter)]
+    pub fn hex(&self) -> String {
+        to_hex(&self.bytes)
+    }
+
+    /// Standard base64 with padding.
+    #[wasm_bindgen(getter)]
+    pub fn base64(&self) -> String {
+        bytes_to_b64(&self.bytes)
+    }
+
+    /// Unpadded base64url.
+    #[wasm_bindgen(getter)]
+    pub fn base64url(&self) -> String {
+        Encoding::Base64Url.encode(&self.bytes)
+    }
+}
+
+fn mac<M: Mac + KeyInit>(key: &[u8], data: &[u8]) -> M {
+    let mut mac = <M as Mac>::new_from_slice(key).expect("HMAC takes keys of any length");
+    mac.update(data);
+    mac
+}
+
+/// HMAC-SHA256 of `data` under `key`.
+#[wasm_bindgen]
+pub fn hmac_sha256(key: &[u8], data: &[u8]) -> DigestOutput {
+    let _operation = crate::utils::set_panic_hook();
+    DigestOutput {
+        bytes: mac::<Hmac<Sha256>>(key, data)
+            .finalize()
+            .into_bytes()
+            .to_vec(),
+    }
+}
+
+/// HMAC-SHA512 of `data` under `key`.
+#[wasm_bindgen]
+pub fn hmac_sha512(key: &[u8], data: &[u8]) -> DigestOutput {
+    let _operation = crate::utils::set_panic_hook();
+    DigestOutput {
+        bytes: mac::<Hmac<Sha512>>(key, data)
+            .finalize()
+            .into_bytes()
+            .to_vec(),
+    }
+}
+
+/// Whether `tag` is the HMAC of `data` under `key`, compared in constant
+/// time. The hash follows from the tag length: 32 bytes for HMAC-SHA256,
+/// 64 for HMAC-SHA512. Tags of any other length never verify.
+#[wasm_bindgen]
+pub fn hmac_verify(key: &[u8], data: &[u8], tag: &[u8]) -> bool {
+    let _operation = crate::utils::set_panic_hook();
+    match tag.len() {
+        SHA256_LENGTH => mac::<Hmac<Sha256>>(key, data).verify_slice(tag).is_ok(),
+        SHA512_LENGTH => mac::<Hmac<Sha512>>(key, data).verify_slice(tag).is_ok(),
+        _ => false,
+    }
+}
+
+/// Shortest index key [`blind_index`] accepts, in bytes.
+const MIN_INDEX_KEY_LENGTH: usize = 16;
+
+/// The first `bits` bits of the HMAC-SHA256 of `value`, with the rest of
+/// the last byte zeroed.
+fn truncated_index(index_key: &[u8], value: &str, bits: u32) -> Vec<u8> {
+    let tag = mac::<Hmac<Sha256>>(index_key, value.as_bytes())
+        .finalize()
+        .into_bytes();
+    let mut index = tag[..(bits as usize).div_ceil(8)].to_vec();
+    if !bits.is_multiple_of(8) {
+        *index.last_mut().expect("bits is at least 1") &= 0xff << (8 - bits % 8);
+    }
+    index
+}
+
+fn check_index_args(index_key: &[u8], bits: u32) -> Result<(), BVaultError> {
+    if index_key.len() < MIN_INDEX_KEY_LENGTH {
+        return Err(BVaultError::new(
+            ErrorCode::BadKeyLength,
+            "index keys must be at least 16 bytes",
+        ));
+    }
+    if bits == 0 || bits as usize > SHA256_LENGTH * 8 {
+        return Err(BVaultError::new(
+            ErrorCode::InvalidArgument,
+            "bits must be between 1 and 256",
+        ));
+    }
+    Ok(())
+}
+
+/// A blind index of `value`: the HMAC-SHA256 of it under `index_key`,
+/// truncated to `bits` bits (trailing bits of the last byte are zero).
+///
+/// Equal values give equal indexes, so a server can look up encrypted
+/// records by index without learning the values. Fewer bits make
+/// collisions, and so false positives, more likely, but leak less about
+/// which records share a value. Normalize values (case, whitespace)
+/// before indexing, and derive a separate `index_key` per field with
+/// [`crate::hkdf_derive`], e.g. under `info` `"search-index:username"`.
+///
+/// # Errors
+///
+/// - If `index_key` is shorter than 16 bytes, an
+///   [`ErrorCode::BadKeyLength`] error is returned.
+/// - If `bits` is outside 1..=256, an [`ErrorCode::InvalidArgument`] error
+///   is returned.
+#[wasm_bindgen]
+pub fn blind_index(value: &str, index_key: &[u8], bits: u32) -> Result<DigestOutput, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    check_index_args(index_key, bits)?;
+    Ok(DigestOutput {
+        bytes: truncated_index(index_key, value, bits),
+    })
+}
+
+/// [`blind_index`] of each of `values`, encoded as `encoding` (default
+/// base64), in the same order.
+///
+/// # Errors
+///
+/// See [`blind_index`].
+#[wasm_bindgen]
+pub fn blind_index_batch(
+    values: Vec<String>,
+    index_key: &[u8],
+    bits: u32,
+    encoding: Option<Encoding>,
+) -> Result<Vec<String>, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    check_index_args(index_key, bits)?;
+    let encoding = encoding.unwrap_or_default();
+    Ok(values
+        .iter()
+        .map(|value| encoding.encode(&truncated_index(index_key, value, bits)))
+        .collect())
+}
+
+/// Whether `a` and `b` hold the same bytes, compared in constant time, for
+/// MACs, tokens and other secrets that JS `===` would compare with a
+/// timing leak. Only the lengths are compared in variable time: inputs of
+/// different lengths are never equal.
+#[wasm_bindgen]
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    let _operation = crate::utils::set_panic_hook();
+    a.ct_eq(b).into()
+}
+
+/// The digest of `data` under `algorithm`.
+#[wasm_bindgen]
+pub fn hash(algorithm: HashAlgorithm, data: &[u8]) -> DigestOutput {
+    let _operation = crate::utils::set_panic_hook();
+    let bytes = match algorithm {
+        HashAlgorithm::Sha256 => Sha256::digest(data).to_vec(),
+        HashAlgorithm::Sha512 => Sha512::digest(data).to_vec(),
+        HashAlgorithm::Blake3 => blake3::hash(data).as_bytes().to_vec(),
+    };
+    DigestOutput { bytes }
+}
+
+/// Keyed BLAKE3 of `data`, a fast MAC for content addressing that must not
+/// be guessable without the key.
+///
+/// # Errors
+///
+/// - If `key` is not 32 bytes, an error is returned.
+#[wasm_bindgen]
+pub fn blake3_keyed_hash(key: &[u8], data: &[u8]) -> Result<DigestOutput, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    let key: &[u8; BLAKE3_KEY_LENGTH] = key
+        .try_into()
+        .map_err(|_| BVaultError::new(ErrorCode::BadKeyLength, "BLAKE3 keys must be 32 bytes"))?;
+    Ok(DigestOutput {
+        bytes: blake3::keyed_hash(key, data).as_bytes().to_vec(),
+    })
+}
+
+/// Derives `length` bytes (32 by default) from `material` with BLAKE3's
+/// key derivation mode, a faster alternative to [`crate::hkdf_derive`].
+///
+/// `context` should be a hardcoded, globally unique string naming the app
+/// and purpose, such as `"example.com 2024-06-01 sync feed tags"`, so keys
+/// for different purposes are independent. Like HKDF, this is for key
+/// material that is already random: use a [`crate::KdfParams`] KDF for
+/// passwords.
+///
+/// # Errors
+///
+/// - If `context` is empty, an [`ErrorCode::InvalidArgument`] error is
+///   returned.
+/// - If `length` is 0 or greater than 1024, an [`ErrorCode::BadLength`]
+///   error is returned.
+#[wasm_bindgen]
+pub fn blake3_derive_key(
+    context: &str,
+    material: &[u8],
+    length: Option<usize>,
+) -> Result<DigestOutput, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    let mut hasher = derive_key_hasher(context)?;
+    let length = length.unwrap_or(BLAKE3_KEY_LENGTH);
+    if length == 0 || length > BLAKE3_MAX_DERIVE_LENGTH {
+        return Err(BVaultError::new(
+            ErrorCode::BadLength,
+            "BLAKE3 derived keys must be between 1 and 1024 bytes",
+        ));
+    }
+    let mut bytes = vec![0u8; length];
+    hasher.update(material).finalize_xof().fill(&mut bytes);
+    Ok(DigestOutput { bytes })
+}
+
+fn derive_key_hasher(context: &str) -> Result<blake3::Hasher, BVaultError> {
+    if context.is_empty() {
+        return Err(BVaultError::new(
+            ErrorCode::InvalidArgument,
+            "BLAKE3 key derivation needs a context string",
+        ));
+    }
+    Ok(blake3::Hasher::new_derive_key(context))
+}
+
+#[derive(Clone)]
+enum HasherState {
+    Sha256(Sha256),
+    Sha512(Sha512),
+    Blake3(Box<blake3::Hasher>),
+}
+
+/// Incremental hashing, for inputs too large to hold in memory at once.
+/// Gives the same results as [`hash`], [`blake3_keyed_hash`] and
+/// [`blake3_derive_key`] (with the default length) over the
+/// concatenation of every chunk.
+#[wasm_bindgen]
+#[derive(Clone)]
+pub struct Hasher {
+    state: HasherState,
+}
+
+#[wasm_bindgen]
+impl Hasher {
+    /// Starts an empty hash under `algorithm`.
+    #[wasm_bindgen(constructor)]
+    pub fn new(algorithm: HashAlgorithm) -> Hasher {
+        let _operation = crate::utils::set_panic_hook();
+        let state = match algorithm {
+            HashAlgorithm::Sha256 => HasherState::Sha256(Sha256::new()),
+            HashAlgorithm::Sha512 => HasherState::Sha512(Sha512::new()),
+            HashAlgorithm::Blake3 => HasherState::Blake3(Box::new(blake3::Hasher::new())),
+        };
+        Hasher { state }
+    }
+
+    /// Starts an empty keyed BLAKE3 hash.
+    ///
+    /// # Errors
+    ///
+    /// - If `key` is not 32 bytes, an error is returned.
+    pub fn blake3_keyed(key: &[u8]) -> Result<Hasher, BVaultError> {
+        let _operation = crate::utils::set_panic_hook();
+        let key: &[u8; BLAKE3_KEY_LENGTH] = key.try_into().map_err(|_| {
+            BVaultError::new(ErrorCode::BadKeyLength, "BLAKE3 keys must be 32 bytes")
+        })?;
+        Ok(Hasher {
+            state: HasherState::Blake3(Box::new(blake3::Hasher::new_keyed(key))),
+        })
+    }
+
+    /// Starts an empty BLAKE3 key derivation under `context`, for key
+    /// material fed in chunks.
+    ///
+    /// # Errors
+    ///
+    /// - If `context` is empty, an error is returned.
+    pub fn blake3_derive_key(context: &str) -> Result<Hasher, BVaultError> {
+        let _operation = crate::utils::set_panic_hook();
+        Ok(Hasher {
+            state: HasherState::Blake3(Box::new(derive_key_hasher(context)?)),
+        })
+    }
+
+    /// Feeds the next chunk of input.
+    pub fn update(&mut self, chunk: &[u8]) {
+        match &mut self.state {
+            HasherState::Sha256(hasher) => hasher.update(chunk),
+            HasherState::Sha512(hasher) => hasher.update(chunk),
+            HasherState::Blake3(hasher) => {
+                hasher.update(chunk);
+            }
+        }
+    }
+
+    /// The digest of everything fed so far. The hasher is left as it was,
+    /// so more chunks can follow.
+    pub fn digest(&self) -> DigestOutput {
+        let bytes = match &self.state {
+            HasherState::Sha256(hasher) => hasher.clone().finalize().to_vec(),
+            HasherState::Sha512(hasher) => hasher.clone().finalize().to_vec(),
+            HasherState::Blake3(hasher) => hasher.finalize().as_bytes().to_vec(),
+        };
+        DigestOutput { bytes }
+    }
+}
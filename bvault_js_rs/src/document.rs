@@ -0,0 +1,69 @@
+//! Encrypted JSON documents, handed to JS as objects instead of strings.
+//!
+//! Decrypting to a string and calling `JSON.parse` leaves the whole
+//! plaintext behind in an immutable JS string that nothing can wipe.
+//! [`decrypt_json`] parses inside WASM, wipes the plaintext bytes and only
+//! hands JS the resulting object. [`encrypt_object`] goes the other way,
+//! serializing a JS value inside WASM so every client produces the same
+//! bytes for the same document.
+
+use serde::Serialize;
+use wasm_bindgen::prelude::*;
+use zeroize::Zeroizing;
+
+use crate::{open, seal, BVaultError, ErrorCode, OpenOptions, SealOptions};
+
+fn invalid_json(err: impl ToString) -> BVaultError {
+    BVaultError::new(ErrorCode::InvalidJson, err.to_string())
+}
+
+/// Opens an envelope whose plaintext is a JSON document and returns it as
+/// a JS value (object, array, string, number, boolean or `null`).
+///
+/// Integers outside the JS safe range (±2^53) are rejected rather than
+/// rounded.
+///
+/// # Errors
+///
+/// - If the plaintext is not valid JSON, an [`ErrorCode::InvalidJson`]
+///   error is returned.
+/// - Otherwise see [`open`].
+#[wasm_bindgen]
+pub fn decrypt_json(
+    envelope: &[u8],
+    password: &str,
+    options: Option<OpenOptions>,
+) -> Result<JsValue, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    let plaintext = Zeroizing::new(open(envelope, password, options)?);
+    let document: serde_json::Value = serde_json::from_slice(&plaintext).map_err(invalid_json)?;
+    document
+        .serialize(&serde_wasm_bindgen::Serializer::json_compatible())
+        .map_err(invalid_json)
+}
+
+/// Serializes `value` to canonical JSON inside WASM and seals it into an
+/// envelope under `password`, as [`seal`] does.
+///
+/// The serialization has no whitespace and object keys in sorted order, so
+/// equal documents always produce equal plaintexts. [`decrypt_json`] reads
+/// the result back.
+///
+/// # Errors
+///
+/// - If `value` cannot be represented as JSON (e.g. it is or holds a
+///   function or a symbol), an [`ErrorCode::InvalidArgument`] error is
+///   returned.
+/// - Otherwise see [`seal`].
+#[wasm_bindgen]
+pub fn encrypt_object(
+    value: JsValue,
+    password: &str,
+    options: Option<SealOptions>,
+) -> Result<Vec<u8>, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    let document: serde_json::Value = serde_wasm_bindgen::from_value(value)
+        .map_err(|e| BVaultError::new(ErrorCode::InvalidArgument, e.to_string()))?;
+    let plaintext = Zeroizing::new(serde_json::to_vec(&document).map_err(invalid_json)?);
+    seal(&plaintext, password, options)
+}
@@ -0,0 +1,179 @@
+//! Text encodings for binary values.
+//!
+//! Every function that takes base64 accepts both the standard and the
+//! URL-safe alphabet, with or without padding, so base64url from URLs and
+//! QR codes can be passed straight back in. Output defaults to standard
+//! padded base64; the `encrypt_*` functions follow
+//! [`crate::EncryptOptions::encoding`], and [`encode`], [`decode`] and
+//! [`transcode`] convert anything else, hex included.
+
+use base64::alphabet;
+use base64::engine::general_purpose::{GeneralPurpose, GeneralPurposeConfig, URL_SAFE_NO_PAD};
+use base64::engine::DecodePaddingMode;
+use base64::Engine;
+use wasm_bindgen::prelude::*;
+
+use crate::utils::{from_hex, to_hex};
+use crate::{bytes_to_b64, BVaultError, ErrorCode};
+
+const LENIENT: GeneralPurposeConfig =
+    GeneralPurposeConfig::new().with_decode_padding_mode(DecodePaddingMode::Indifferent);
+const STANDARD_LENIENT: GeneralPurpose = GeneralPurpose::new(&alphabet::STANDARD, LENIENT);
+const URL_SAFE_LENIENT: GeneralPurpose = GeneralPurpose::new(&alphabet::URL_SAFE, LENIENT);
+
+/// A text encoding for binary values.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Encoding {
+    /// Standard base64 with padding (RFC 4648 section 4).
+    #[default]
+    Base64 = 1,
+    /// URL- and filename-safe base64 without padding (RFC 4648 section 5).
+    Base64Url = 2,
+    /// Lowercase hex; either case, and a `0x` prefix, are accepted.
+    Hex = 3,
+}
+
+impl Encoding {
+    pub(crate) fn encode(self, bytes: &[u8]) -> String {
+        match self {
+            Encoding::Base64 => bytes_to_b64(bytes),
+            Encoding::Base64Url => URL_SAFE_NO_PAD.encode(bytes),
+            Encoding::Hex => to_hex(bytes),
+        }
+    }
+
+    pub(crate) fn decode(self, text: &str) -> Result<Vec<u8>, BVaultError> {
+        match self {
+            Encoding::Base64 | Encoding::Base64Url => decode_base64(text),
+            Encoding::Hex => {
+                from_hex(text).ok_or_else(|| BVaultError::new(ErrorCode::InvalidHex, "invalid hex"))
+            }
+        }
+    }
+}
+
+/// Decodes base64 in either alphabet, padded or not.
+pub(crate) fn decode_base64(text: &str) -> Result<Vec<u8>, BVaultError> {
+    STANDARD_LENIENT
+        .decode(text)
+        .or_else(|_| URL_SAFE_LENIENT.decode(text))
+        .map_err(|_| invalid_base64())
+}
+
+/// Encodes `bytes` as text; `encoding` defaults to standard base64.
+#[wasm_bindgen]
+pub fn encode(bytes: &[u8], encoding: Option<Encoding>) -> String {
+    let _operation = crate::utils::set_panic_hook();
+    encoding.unwrap_or_default().encode(bytes)
+}
+
+/// Decodes text produced by [`encode`] (or any other encoder of the same
+/// encoding); `encoding` defaults to base64, in either alphabet.
+///
+/// # Errors
+///
+/// - If `text` is not valid in the encoding, an
+///   [`ErrorCode::InvalidBase64`] or [`ErrorCode::InvalidHex`] error is
+///   returned.
+#[wasm_bindgen]
+pub fn decode(text: &str, encoding: Option<Encoding>) -> Result<Vec<u8>, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    encoding.unwrap_or_default().decode(text)
+}
+
+/// Re-encodes `text` from one encoding to another, e.g. a hex value from a
+/// debugging tool into the base64 the rest of the API takes.
+///
+/// # Errors
+///
+/// See [`decode`].
+#[wasm_bindgen]
+pub fn transcode(text: &str, from: Encoding, to: Encoding) -> Result<String, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    Ok(to.encode(&from.decode(text)?))
+}
+
+/// Decodes base64 text fed in slices, so a huge base64 string never has to
+/// sit in WASM memory whole next to its bytes. Like [`decode`], it accepts
+/// either alphabet, with or without padding, and also skips whitespace, so
+/// line-wrapped base64 can be fed as it is.
+///
+/// Slices may split the text anywhere; at most three characters are held
+/// back between calls.
+#[wasm_bindgen]
+#[derive(Default)]
+pub struct Base64Decoder {
+    pending: Vec<u8>,
+    padded: bool,
+    finished: bool,
+}
+
+#[wasm_bindgen]
+impl Base64Decoder {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Base64Decoder {
+        Base64Decoder::default()
+    }
+
+    /// Feeds the next slice of text and returns the bytes decoded so far.
+    ///
+    /// # Errors
+    ///
+    /// - If the text is not valid base64, an [`ErrorCode::InvalidBase64`]
+    ///   error is returned.
+    /// - If the decoder was already finalized, an
+    ///   [`ErrorCode::StreamFinished`] error is returned.
+    pub fn update(&mut self, chunk: &str) -> Result<Vec<u8>, BVaultError> {
+        if self.finished {
+            return Err(decoder_finished());
+        }
+        for byte in chunk.bytes().filter(|byte| !byte.is_ascii_whitespace()) {
+            // Padding may only be followed by more padding.
+            if self.padded && byte != b'=' {
+                return Err(invalid_base64());
+            }
+            self.padded = byte == b'=';
+            // Fold the URL-safe alphabet into the standard one.
+            self.pending.push(match byte {
+                b'-' => b'+',
+                b'_' => b'/',
+                byte => byte,
+            });
+        }
+        let ready = self.pending.len() / 4 * 4;
+        let out = STANDARD_LENIENT
+            .decode(&self.pending[..ready])
+            .map_err(|_| invalid_base64())?;
+        self.pending.drain(..ready);
+        Ok(out)
+    }
+
+    /// Decodes the last, unpadded characters and ends the stream.
+    ///
+    /// # Errors
+    ///
+    /// - If the text ends in a partial quantum that cannot be decoded, an
+    ///   [`ErrorCode::InvalidBase64`] error is returned.
+    /// - If the decoder was already finalized, an
+    ///   [`ErrorCode::StreamFinished`] error is returned.
+    pub fn finalize(&mut self) -> Result<Vec<u8>, BVaultError> {
+        if self.finished {
+            return Err(decoder_finished());
+        }
+        self.finished = true;
+        let out = STANDARD_LENIENT
+            .decode(&self.pending)
+            .map_err(|_| invalid_base64());
+        self.pending.clear();
+        out
+    }
+}
+
+fn invalid_base64() -> BVaultError {
+    BVaultError::new(ErrorCode::InvalidBase64, "invalid base64")
+}
+
+fn decoder_finished() -> BVaultError {
+    BVaultError::new(ErrorCode::StreamFinished, "decoder was already finalized")
+}
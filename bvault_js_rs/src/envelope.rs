@@ -0,0 +1,1312 @@
+//! Versioned, self-describing ciphertext envelopes.
+//!
+//! An envelope packs everything needed to decrypt an item into one blob, so
+//! callers no longer shuttle ciphertext, IV and salt around separately and
+//! parameters can change without breaking old data. All integers are
+//! big-endian:
+//!
+//! ```text
+//! magic      4  "BVLT"
+//! version    1  currently 2
+//! cipher     1  Cipher ID
+//! kdf        1  KDF ID, followed by its parameters:
+//!                 0 none           (sealed under a raw key, salt is empty)
+//!                 1 PBKDF2-SHA256  iterations u32
+//!                 2 Argon2id       memory_kib u32, passes u32, lanes u32
+//!                 3 scrypt         log_n u8, r u32, p u32
+//!                 4 PBKDF2-SHA512  iterations u32
+//! salt       1 + n  length-prefixed
+//! nonce      1 + n  length-prefixed
+//! extensions 1 + …  count, then (tag u8, length u16, value) per entry
+//! body       rest   ciphertext with the AEAD tag appended
+//! ```
+//!
+//! From version 2 the header (everything before the body, in this binary
+//! form even when the envelope is stored as CBOR) is authenticated as
+//! associated data, followed by any caller-supplied associated data (see
+//! [`SealOptions::aad`]). Version 1 envelopes did not authenticate their
+//! header; they still open, but cannot be bound to associated data.
+//!
+//! Extensions carry optional header fields; decoders reject tags they do not
+//! know. Defined tags:
+//!
+//! ```text
+//! 1  key check value  8 bytes, see `kcv`
+//! 2  compression      1 byte: 1 DEFLATE; absent means uncompressed
+//! 3  padding          1 byte: 1 Padmé, 2 power of two; absent means none
+//! 4  rotation         20 bytes: key generation u32, created-at u64 and
+//!                     rotate-after u64 in ms since the Unix epoch, 0 for
+//!                     no deadline; see `rotation`
+//! ```
+//!
+//! Envelopes can also be written as canonical CBOR (see `cbor`), a map with
+//! unsigned integer keys:
+//!
+//! ```text
+//! 0  magic    text "BVLT"
+//! 1  version  2 (or 1)
+//! 2  cipher   Cipher ID
+//! 3  kdf      [KDF ID, parameters...] as above; omitted for a raw key
+//! 4  salt     bytes
+//! 5  nonce    bytes
+//! 6  kcv      bytes, optional
+//! 7  body     bytes
+//! 8  compression  ID as above, optional
+//! 9  padding      ID as above, optional
+//! 10 rotation     [generation, created-at, rotate-after] as above, optional
+//! ```
+//!
+//! [`open`] and the other readers accept either form.
+
+use std::convert::{TryFrom, TryInto};
+
+use wasm_bindgen::prelude::*;
+use zeroize::Zeroizing;
+
+use crate::cbor::{narrow, Value};
+use crate::compression::Compression;
+use crate::kcv::{self, KCV_LENGTH};
+use crate::kdf::{Kdf, Pbkdf2Hash, KEY_LENGTH};
+use crate::padding::Padding;
+use crate::{
+    b64_to_bytes, bytes_to_b64, new_salted_key, random_bytes, BVaultError, Cipher, ErrorCode,
+    KdfParams, TextDecoding, SALT_LENGTH,
+};
+
+pub(crate) const MAGIC: &[u8; 4] = b"BVLT";
+pub(crate) const VERSION: u8 = 2;
+/// The first version, whose header is not authenticated.
+pub(crate) const VERSION_UNBOUND: u8 = 1;
+
+const KDF_NONE: u8 = 0;
+const KDF_PBKDF2_SHA256: u8 = 1;
+const KDF_ARGON2ID: u8 = 2;
+const KDF_SCRYPT: u8 = 3;
+const KDF_PBKDF2_SHA512: u8 = 4;
+
+pub(crate) const EXT_KEY_CHECK: u8 = 1;
+pub(crate) const EXT_COMPRESSION: u8 = 2;
+pub(crate) const EXT_PADDING: u8 = 3;
+pub(crate) const EXT_ROTATION: u8 = 4;
+
+pub(crate) const ROTATION_LENGTH: usize = 20;
+
+pub(crate) const FIELD_MAGIC: u64 = 0;
+pub(crate) const FIELD_VERSION: u64 = 1;
+pub(crate) const FIELD_CIPHER: u64 = 2;
+pub(crate) const FIELD_KDF: u64 = 3;
+pub(crate) const FIELD_SALT: u64 = 4;
+pub(crate) const FIELD_NONCE: u64 = 5;
+const FIELD_KCV: u64 = 6;
+pub(crate) const FIELD_BODY: u64 = 7;
+const FIELD_COMPRESSION: u64 = 8;
+const FIELD_PADDING: u64 = 9;
+const FIELD_ROTATION: u64 = 10;
+
+/// A parsed envelope.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Envelope {
+    pub(crate) version: u8,
+    pub(crate) cipher: Cipher,
+    /// How the key was derived; `None` for envelopes sealed under a raw key.
+    pub(crate) kdf: Option<KdfParams>,
+    pub(crate) salt: Vec<u8>,
+    pub(crate) nonce: Vec<u8>,
+    /// Key check value of the derived key, if one was stored.
+    pub(crate) kcv: Option<Vec<u8>>,
+    pub(crate) framing: Framing,
+    /// Key rotation metadata, if any was recorded.
+    pub(crate) rotation: Option<Rotation>,
+    pub(crate) body: Vec<u8>,
+}
+
+/// When an envelope's key was made and is due for replacement.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct Rotation {
+    /// Starts at whatever the app sets and grows by one per rotation.
+    pub(crate) generation: u32,
+    /// Ms since the Unix epoch.
+    pub(crate) created_at: u64,
+    /// Ms since the Unix epoch; 0 for no deadline.
+    pub(crate) rotate_after: u64,
+}
+
+impl Rotation {
+    fn to_bytes(self) -> [u8; ROTATION_LENGTH] {
+        let mut out = [0u8; ROTATION_LENGTH];
+        out[..4].copy_from_slice(&self.generation.to_be_bytes());
+        out[4..12].copy_from_slice(&self.created_at.to_be_bytes());
+        out[12..].copy_from_slice(&self.rotate_after.to_be_bytes());
+        out
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Result<Rotation, BVaultError> {
+        let mut reader = Reader::new(bytes);
+        Ok(Rotation {
+            generation: reader.u32()?,
+            created_at: reader.u64()?,
+            rotate_after: reader.u64()?,
+        })
+    }
+
+    fn to_cbor(self) -> Value {
+        Value::Array(vec![
+            self.generation.into(),
+            self.created_at.into(),
+            self.rotate_after.into(),
+        ])
+    }
+
+    fn from_cbor(value: Value) -> Result<Rotation, BVaultError> {
+        match <[Value; 3]>::try_from(value.into_array()?) {
+            Ok([generation, created_at, rotate_after]) => Ok(Rotation {
+                generation: narrow(generation)?,
+                created_at: created_at.into_uint()?,
+                rotate_after: rotate_after.into_uint()?,
+            }),
+            Err(_) => Err(invalid("malformed rotation metadata")),
+        }
+    }
+}
+
+/// How the plaintext is transformed before encryption: compressed, then
+/// padded.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct Framing {
+    pub(crate) compression: Compression,
+    pub(crate) padding: Padding,
+}
+
+impl Framing {
+    fn apply(self, plaintext: &[u8]) -> Result<Zeroizing<Vec<u8>>, BVaultError> {
+        self.padding.pad(self.compression.compress(plaintext)?)
+    }
+
+    fn remove(self, data: Vec<u8>) -> Result<Vec<u8>, BVaultError> {
+        self.compression.decompress(self.padding.unpad(data)?)
+    }
+}
+
+impl Envelope {
+    /// Serializes the envelope into its binary form.
+    pub(crate) fn to_bytes(&self) -> Vec<u8> {
+        let mut out = self.header();
+        out.extend_from_slice(&self.body);
+        out
+    }
+
+    /// The binary form without the body.
+    fn header(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(64 + self.body.len());
+        out.extend_from_slice(MAGIC);
+        out.push(self.version);
+        out.push(self.cipher.id());
+
+        write_kdf(&mut out, self.kdf.as_ref());
+
+        // Lengths are bounded by `SealOptions` (salt) and `Cipher` (nonce).
+        out.push(self.salt.len() as u8);
+        out.extend_from_slice(&self.salt);
+        out.push(self.nonce.len() as u8);
+        out.extend_from_slice(&self.nonce);
+
+        let mut extensions: Vec<(u8, &[u8])> = Vec::new();
+        if let Some(kcv) = &self.kcv {
+            extensions.push((EXT_KEY_CHECK, kcv));
+        }
+        let compression = [self.framing.compression.id()];
+        if self.framing.compression != Compression::None {
+            extensions.push((EXT_COMPRESSION, &compression));
+        }
+        let padding = [self.framing.padding.id()];
+        if self.framing.padding != Padding::None {
+            extensions.push((EXT_PADDING, &padding));
+        }
+        let rotation = self.rotation.map(Rotation::to_bytes);
+        if let Some(rotation) = &rotation {
+            extensions.push((EXT_ROTATION, rotation));
+        }
+        out.push(extensions.len() as u8);
+        for (tag, value) in extensions {
+            out.push(tag);
+            out.extend_from_slice(&(value.len() as u16).to_be_bytes());
+            out.extend_from_slice(value);
+        }
+        out
+    }
+
+    /// The associated data the body is authenticated with: the header,
+    /// then `aad`.
+    fn associated_data(&self, aad: &[u8]) -> Result<Vec<u8>, BVaultError> {
+        if self.version == VERSION_UNBOUND {
+            if !aad.is_empty() {
+                return Err(BVaultError::new(
+                    ErrorCode::InvalidArgument,
+                    "version 1 envelopes cannot carry associated data",
+                ));
+            }
+            return Ok(Vec::new());
+        }
+        let mut out = self.header();
+        out.extend_from_slice(aad);
+        Ok(out)
+    }
+
+    /// Serializes the envelope into its canonical CBOR form.
+    pub(crate) fn to_cbor(&self) -> Vec<u8> {
+        let mut fields = vec![
+            (FIELD_MAGIC, Value::Text(text_magic(MAGIC))),
+            (FIELD_VERSION, self.version.into()),
+            (FIELD_CIPHER, self.cipher.id().into()),
+            (FIELD_SALT, Value::Bytes(self.salt.clone())),
+            (FIELD_NONCE, Value::Bytes(self.nonce.clone())),
+            (FIELD_BODY, Value::Bytes(self.body.clone())),
+        ];
+        if let Some(kdf) = &self.kdf {
+            fields.push((FIELD_KDF, kdf_to_cbor(kdf)));
+        }
+        if let Some(kcv) = &self.kcv {
+            fields.push((FIELD_KCV, Value::Bytes(kcv.clone())));
+        }
+        if self.framing.compression != Compression::None {
+            fields.push((FIELD_COMPRESSION, self.framing.compression.id().into()));
+        }
+        if self.framing.padding != Padding::None {
+            fields.push((FIELD_PADDING, self.framing.padding.id().into()));
+        }
+        if let Some(rotation) = self.rotation {
+            fields.push((FIELD_ROTATION, rotation.to_cbor()));
+        }
+        Value::map(fields).to_bytes()
+    }
+
+    /// Decrypts the body under `key` with the caller's associated data
+    /// `aad`, and undoes any compression and padding.
+    pub(crate) fn decrypt(&self, key: &[u8], aad: &[u8]) -> Result<Vec<u8>, BVaultError> {
+        let plaintext = self.cipher.decrypt_with_aad(
+            key,
+            &self.nonce,
+            &self.body,
+            &self.associated_data(aad)?,
+        )?;
+        self.framing.remove(plaintext)
+    }
+
+    /// Parses and validates an envelope in either its binary or CBOR form.
+    pub(crate) fn parse(bytes: &[u8]) -> Result<Envelope, BVaultError> {
+        if is_cbor(bytes) {
+            Envelope::parse_cbor(bytes)
+        } else {
+            Envelope::parse_binary(bytes)
+        }
+    }
+
+    fn parse_cbor(bytes: &[u8]) -> Result<Envelope, BVaultError> {
+        let mut fields = Value::parse(bytes)?.into_map()?;
+        if fields.require(FIELD_MAGIC)?.into_text()?.as_bytes() != MAGIC {
+            return Err(invalid("not a bvault envelope"));
+        }
+        let version = check_envelope_version(narrow(fields.require(FIELD_VERSION)?)?)?;
+        let cipher = cipher_from_id(narrow(fields.require(FIELD_CIPHER)?)?)?;
+        let kdf = fields.take(FIELD_KDF).map(kdf_from_cbor).transpose()?;
+        let salt = fields.require(FIELD_SALT)?.into_bytes()?;
+        check_salt_length(salt.len()).map_err(|_| invalid("salt is too long"))?;
+        let nonce = fields.require(FIELD_NONCE)?.into_bytes()?;
+        check_nonce_length(cipher, &nonce)?;
+        let kcv = fields.take(FIELD_KCV).map(Value::into_bytes).transpose()?;
+        if kcv.as_ref().is_some_and(|kcv| kcv.len() != KCV_LENGTH) {
+            return Err(invalid("malformed key check value"));
+        }
+        let body = fields.require(FIELD_BODY)?.into_bytes()?;
+        let compression = match fields.take(FIELD_COMPRESSION) {
+            Some(id) => compression_from_id(narrow(id)?)?,
+            None => Compression::None,
+        };
+        let padding = match fields.take(FIELD_PADDING) {
+            Some(id) => padding_from_id(narrow(id)?)?,
+            None => Padding::None,
+        };
+        let rotation = fields
+            .take(FIELD_ROTATION)
+            .map(Rotation::from_cbor)
+            .transpose()?;
+        fields.finish()?;
+
+        Ok(Envelope {
+            version,
+            cipher,
+            kdf,
+            salt,
+            nonce,
+            kcv,
+            framing: Framing {
+                compression,
+                padding,
+            },
+            rotation,
+            body,
+        })
+    }
+
+    fn parse_binary(bytes: &[u8]) -> Result<Envelope, BVaultError> {
+        let mut reader = Reader::new(bytes);
+
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err(invalid("not a bvault envelope"));
+        }
+
+        let version = check_envelope_version(reader.u8()?)?;
+        let cipher = cipher_from_id(reader.u8()?)?;
+        let kdf = read_kdf(&mut reader)?;
+
+        let salt_len = reader.u8()? as usize;
+        let salt = reader.take(salt_len)?.to_vec();
+        let nonce_len = reader.u8()? as usize;
+        let nonce = reader.take(nonce_len)?.to_vec();
+        check_nonce_length(cipher, &nonce)?;
+
+        let mut kcv = None;
+        let mut compression = None;
+        let mut padding = None;
+        let mut rotation = None;
+        for _ in 0..reader.u8()? {
+            let tag = reader.u8()?;
+            let len = reader.u16()? as usize;
+            let value = reader.take(len)?;
+            match tag {
+                EXT_KEY_CHECK if kcv.is_none() && len == KCV_LENGTH => {
+                    kcv = Some(value.to_vec());
+                }
+                EXT_KEY_CHECK => return Err(invalid("malformed key check value")),
+                EXT_COMPRESSION if compression.is_none() && len == 1 => {
+                    compression = Some(compression_from_id(value[0])?);
+                }
+                EXT_COMPRESSION => return Err(invalid("malformed compression extension")),
+                EXT_PADDING if padding.is_none() && len == 1 => {
+                    padding = Some(padding_from_id(value[0])?);
+                }
+                EXT_PADDING => return Err(invalid("malformed padding extension")),
+                EXT_ROTATION if rotation.is_none() && len == ROTATION_LENGTH => {
+                    rotation = Some(Rotation::from_bytes(value)?);
+                }
+                EXT_ROTATION => return Err(invalid("malformed rotation extension")),
+                tag => return Err(invalid(format!("unknown envelope extension {}", tag))),
+            }
+        }
+
+        Ok(Envelope {
+            version,
+            cipher,
+            kdf,
+            salt,
+            nonce,
+            kcv,
+            framing: Framing {
+                compression: compression.unwrap_or_default(),
+                padding: padding.unwrap_or_default(),
+            },
+            rotation,
+            body: reader.rest().to_vec(),
+        })
+    }
+}
+
+fn pbkdf2_id(hash: Pbkdf2Hash) -> u8 {
+    match hash {
+        Pbkdf2Hash::Sha256 => KDF_PBKDF2_SHA256,
+        Pbkdf2Hash::Sha512 => KDF_PBKDF2_SHA512,
+    }
+}
+
+/// Writes a KDF ID and its parameters; `None` stands for a raw key.
+pub(crate) fn write_kdf(out: &mut Vec<u8>, kdf: Option<&KdfParams>) {
+    match kdf.map(KdfParams::kdf) {
+        None => out.push(KDF_NONE),
+        Some(Kdf::Pbkdf2 { iterations, hash }) => {
+            out.push(pbkdf2_id(hash));
+            out.extend_from_slice(&iterations.to_be_bytes());
+        }
+        Some(Kdf::Argon2id {
+            memory_kib,
+            iterations,
+            parallelism,
+        }) => {
+            out.push(KDF_ARGON2ID);
+            out.extend_from_slice(&memory_kib.to_be_bytes());
+            out.extend_from_slice(&iterations.to_be_bytes());
+            out.extend_from_slice(&parallelism.to_be_bytes());
+        }
+        Some(Kdf::Scrypt { log_n, r, p }) => {
+            out.push(KDF_SCRYPT);
+            out.push(log_n);
+            out.extend_from_slice(&r.to_be_bytes());
+            out.extend_from_slice(&p.to_be_bytes());
+        }
+    }
+}
+
+/// Reads what [`write_kdf`] wrote, rejecting costs above the maximum (see
+/// [`KdfParams::check_cost`]) before anything is derived with them.
+pub(crate) fn read_kdf(reader: &mut Reader<'_>) -> Result<Option<KdfParams>, BVaultError> {
+    let kdf = read_kdf_unchecked(reader)?;
+    if let Some(kdf) = &kdf {
+        kdf.check_cost()?;
+    }
+    Ok(kdf)
+}
+
+/// [`read_kdf`] without the cost check, for reporting every problem of a
+/// header at once.
+pub(crate) fn read_kdf_unchecked(
+    reader: &mut Reader<'_>,
+) -> Result<Option<KdfParams>, BVaultError> {
+    let kdf = match reader.u8()? {
+        KDF_NONE => None,
+        KDF_PBKDF2_SHA256 => Some(KdfParams::pbkdf2(reader.u32()?)),
+        KDF_PBKDF2_SHA512 => Some(KdfParams::pbkdf2_sha512(reader.u32()?)),
+        KDF_ARGON2ID => Some(KdfParams::argon2id(
+            Some(reader.u32()?),
+            Some(reader.u32()?),
+            Some(reader.u32()?),
+        )),
+        KDF_SCRYPT => Some(KdfParams::scrypt(
+            Some(reader.u8()?),
+            Some(reader.u32()?),
+            Some(reader.u32()?),
+        )),
+        id => {
+            return Err(BVaultError::new(
+                ErrorCode::UnsupportedAlgorithm,
+                format!("unknown kdf id {}", id),
+            ))
+        }
+    };
+    Ok(kdf)
+}
+
+/// Encodes KDF parameters as a CBOR array: the KDF ID, then its parameters.
+pub(crate) fn kdf_to_cbor(kdf: &KdfParams) -> Value {
+    Value::Array(match kdf.kdf() {
+        Kdf::Pbkdf2 { iterations, hash } => vec![pbkdf2_id(hash).into(), iterations.into()],
+        Kdf::Argon2id {
+            memory_kib,
+            iterations,
+            parallelism,
+        } => vec![
+            KDF_ARGON2ID.into(),
+            memory_kib.into(),
+            iterations.into(),
+            parallelism.into(),
+        ],
+        Kdf::Scrypt { log_n, r, p } => vec![KDF_SCRYPT.into(), log_n.into(), r.into(), p.into()],
+    })
+}
+
+/// Decodes what [`kdf_to_cbor`] wrote, rejecting costs above the maximum
+/// like [`read_kdf`].
+pub(crate) fn kdf_from_cbor(value: Value) -> Result<KdfParams, BVaultError> {
+    let kdf = kdf_from_cbor_unchecked(value)?;
+    kdf.check_cost()?;
+    Ok(kdf)
+}
+
+/// [`kdf_from_cbor`] without the cost check.
+pub(crate) fn kdf_from_cbor_unchecked(value: Value) -> Result<KdfParams, BVaultError> {
+    let mut items = value.into_array()?.into_iter();
+    let mut next = || {
+        items
+            .next()
+            .ok_or_else(|| invalid("truncated kdf parameters"))
+    };
+    let kdf = match narrow::<u8>(next()?)? {
+        KDF_PBKDF2_SHA256 => KdfParams::pbkdf2(narrow(next()?)?),
+        KDF_PBKDF2_SHA512 => KdfParams::pbkdf2_sha512(narrow(next()?)?),
+        KDF_ARGON2ID => KdfParams::argon2id(
+            Some(narrow(next()?)?),
+            Some(narrow(next()?)?),
+            Some(narrow(next()?)?),
+        ),
+        KDF_SCRYPT => KdfParams::scrypt(
+            Some(narrow(next()?)?),
+            Some(narrow(next()?)?),
+            Some(narrow(next()?)?),
+        ),
+        id => {
+            return Err(BVaultError::new(
+                ErrorCode::UnsupportedAlgorithm,
+                format!("unknown kdf id {}", id),
+            ))
+        }
+    };
+    if items.next().is_some() {
+        return Err(invalid("too many kdf parameters"));
+    }
+    Ok(kdf)
+}
+
+/// Whether `bytes` look like a CBOR container (a map) rather than the
+/// binary form, which starts with an ASCII magic.
+pub(crate) fn is_cbor(bytes: &[u8]) -> bool {
+    bytes.first().is_some_and(|&b| b >> 5 == 5)
+}
+
+/// The magic as CBOR text.
+pub(crate) fn text_magic(magic: &[u8; 4]) -> String {
+    String::from_utf8_lossy(magic).into_owned()
+}
+
+pub(crate) fn check_envelope_version(version: u8) -> Result<u8, BVaultError> {
+    if version == VERSION_UNBOUND {
+        return Ok(version);
+    }
+    check_version("envelope", version, VERSION)?;
+    Ok(version)
+}
+
+pub(crate) fn check_version(what: &str, version: u8, supported: u8) -> Result<(), BVaultError> {
+    if version != supported {
+        return Err(BVaultError::new(
+            ErrorCode::UnsupportedVersion,
+            format!("unsupported {} version {}", what, version),
+        ));
+    }
+    Ok(())
+}
+
+pub(crate) fn cipher_from_id(id: u8) -> Result<Cipher, BVaultError> {
+    Cipher::from_id(id).ok_or_else(|| {
+        BVaultError::new(
+            ErrorCode::UnsupportedAlgorithm,
+            format!("unknown cipher id {}", id),
+        )
+    })
+}
+
+fn compression_from_id(id: u8) -> Result<Compression, BVaultError> {
+    Compression::from_id(id).ok_or_else(|| {
+        BVaultError::new(
+            ErrorCode::UnsupportedAlgorithm,
+            format!("unknown compression id {}", id),
+        )
+    })
+}
+
+fn padding_from_id(id: u8) -> Result<Padding, BVaultError> {
+    Padding::from_id(id).ok_or_else(|| {
+        BVaultError::new(
+            ErrorCode::UnsupportedAlgorithm,
+            format!("unknown padding id {}", id),
+        )
+    })
+}
+
+pub(crate) fn check_nonce_length(cipher: Cipher, nonce: &[u8]) -> Result<(), BVaultError> {
+    if nonce.len() != cipher.nonce_length() {
+        return Err(BVaultError::new(
+            ErrorCode::BadIvLength,
+            format!("nonce must be {} bytes", cipher.nonce_length()),
+        ));
+    }
+    Ok(())
+}
+
+/// Bounds-checked cursor over an encoded envelope.
+pub(crate) struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) fn new(bytes: &'a [u8]) -> Reader<'a> {
+        Reader { bytes, pos: 0 }
+    }
+
+    pub(crate) fn take(&mut self, len: usize) -> Result<&'a [u8], BVaultError> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|&end| end <= self.bytes.len())
+            .ok_or_else(|| invalid("envelope is truncated"))?;
+        let slice = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(slice)
+    }
+
+    pub(crate) fn u8(&mut self) -> Result<u8, BVaultError> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub(crate) fn u16(&mut self) -> Result<u16, BVaultError> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    pub(crate) fn u32(&mut self) -> Result<u32, BVaultError> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    pub(crate) fn u64(&mut self) -> Result<u64, BVaultError> {
+        let bytes = self.take(8)?;
+        Ok(u64::from_be_bytes(bytes.try_into().expect("8 bytes")))
+    }
+
+    pub(crate) fn rest(&mut self) -> &'a [u8] {
+        let rest = &self.bytes[self.pos..];
+        self.pos = self.bytes.len();
+        rest
+    }
+}
+
+/// Rejects salts too long for the one-byte length prefix.
+pub(crate) fn check_salt_length(salt_length: usize) -> Result<(), BVaultError> {
+    if salt_length > u8::MAX as usize {
+        return Err(BVaultError::new(
+            ErrorCode::BadSaltLength,
+            "salt must be at most 255 bytes",
+        ));
+    }
+    Ok(())
+}
+
+pub(crate) fn invalid(message: impl Into<String>) -> BVaultError {
+    BVaultError::new(ErrorCode::InvalidEnvelope, message)
+}
+
+/// Options accepted by [`seal`] and [`seal_string`].
+#[wasm_bindgen]
+#[derive(Clone, Debug)]
+pub struct SealOptions {
+    cipher: Cipher,
+    kdf: KdfParams,
+    salt_length: usize,
+    key_check: bool,
+    compression: Compression,
+    padding: Padding,
+    aad: Vec<u8>,
+    generation: Option<u32>,
+    rotate_after_ms: Option<f64>,
+}
+
+impl Default for SealOptions {
+    fn default() -> Self {
+        SealOptions {
+            cipher: Cipher::default(),
+            kdf: KdfParams::default(),
+            salt_length: SALT_LENGTH,
+            key_check: true,
+            compression: Compression::None,
+            padding: Padding::None,
+            aad: Vec::new(),
+            generation: None,
+            rotate_after_ms: None,
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl SealOptions {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> SealOptions {
+        SealOptions::default()
+    }
+
+    /// Cipher suite to encrypt with (default AES-256-GCM).
+    #[wasm_bindgen(getter)]
+    pub fn cipher(&self) -> Cipher {
+        self.cipher
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_cipher(&mut self, cipher: Cipher) {
+        self.cipher = cipher;
+    }
+
+    /// Key derivation parameters (default PBKDF2, 100 000 iterations).
+    #[wasm_bindgen(getter)]
+    pub fn kdf(&self) -> KdfParams {
+        self.kdf.clone()
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_kdf(&mut self, kdf: &KdfParams) {
+        self.kdf = kdf.clone();
+    }
+
+    /// Length of the random salt to generate, in bytes (8 to 255, default 16).
+    #[wasm_bindgen(getter)]
+    pub fn salt_length(&self) -> usize {
+        self.salt_length
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_salt_length(&mut self, salt_length: usize) {
+        self.salt_length = salt_length;
+    }
+
+    /// Store a key check value so [`open`] can report a wrong password
+    /// separately from damaged data (default `true`).
+    #[wasm_bindgen(getter)]
+    pub fn key_check(&self) -> bool {
+        self.key_check
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_key_check(&mut self, key_check: bool) {
+        self.key_check = key_check;
+    }
+
+    /// Compress the plaintext before encrypting it (default
+    /// [`Compression::None`]). The ciphertext length then depends on the
+    /// content; never compress data an attacker can partly choose alongside
+    /// secrets (see the `compression` module).
+    #[wasm_bindgen(getter)]
+    pub fn compression(&self) -> Compression {
+        self.compression
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_compression(&mut self, compression: Compression) {
+        self.compression = compression;
+    }
+
+    /// Pad the plaintext to hide its exact length (default
+    /// [`Padding::None`]; see the `padding` module).
+    #[wasm_bindgen(getter)]
+    pub fn padding(&self) -> Padding {
+        self.padding
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_padding(&mut self, padding: Padding) {
+        self.padding = padding;
+    }
+
+    /// Associated data to bind the envelope to, such as an entry or vault
+    /// ID (default none). It is authenticated but not stored: [`open`]
+    /// fails unless the same bytes are passed through
+    /// [`OpenOptions::with_aad`], so envelopes cannot be swapped between
+    /// contexts.
+    #[wasm_bindgen(getter)]
+    pub fn aad(&self) -> Vec<u8> {
+        self.aad.clone()
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_aad(&mut self, aad: &[u8]) {
+        self.aad = aad.to_vec();
+    }
+
+    /// Key generation number to record (default none). Setting this or
+    /// [`SealOptions::rotate_after_ms`] stores rotation metadata with the
+    /// creation time; see [`crate::needs_rotation`].
+    #[wasm_bindgen(getter)]
+    pub fn generation(&self) -> Option<u32> {
+        self.generation
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_generation(&mut self, generation: Option<u32>) {
+        self.generation = generation;
+    }
+
+    /// How long after sealing the envelope is due for rotation, in ms
+    /// (default no deadline).
+    #[wasm_bindgen(getter)]
+    pub fn rotate_after_ms(&self) -> Option<f64> {
+        self.rotate_after_ms
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_rotate_after_ms(&mut self, rotate_after_ms: Option<f64>) {
+        self.rotate_after_ms = rotate_after_ms;
+    }
+}
+
+impl SealOptions {
+    /// The defaults with `cipher`, for sealing under an existing key.
+    pub(crate) fn for_cipher(cipher: Cipher) -> SealOptions {
+        SealOptions {
+            cipher,
+            ..SealOptions::default()
+        }
+    }
+
+    /// The rotation metadata to record, created now; `None` unless asked
+    /// for.
+    fn rotation(&self) -> Option<Rotation> {
+        if self.generation.is_none() && self.rotate_after_ms.is_none() {
+            return None;
+        }
+        let created_at = crate::utils::now_ms() as u64;
+        Some(Rotation {
+            generation: self.generation.unwrap_or(0),
+            created_at,
+            rotate_after: self
+                .rotate_after_ms
+                .map_or(0, |ms| created_at.saturating_add(ms as u64)),
+        })
+    }
+}
+
+/// Options accepted by [`open`], [`open_string`] and [`crate::open_any`].
+#[wasm_bindgen]
+#[derive(Clone, Debug, Default)]
+pub struct OpenOptions {
+    keyfile: Option<Zeroizing<Vec<u8>>>,
+    legacy_kdf: Option<KdfParams>,
+    aad: Vec<u8>,
+    min_version: u8,
+    min_kdf: Option<KdfParams>,
+    denied_ciphers: Vec<Cipher>,
+}
+
+#[wasm_bindgen]
+impl OpenOptions {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> OpenOptions {
+        OpenOptions::default()
+    }
+
+    /// Returns a copy of these options with the keyfile the envelope was
+    /// sealed with (see [`KdfParams::with_keyfile`]).
+    pub fn with_keyfile(&self, keyfile: &[u8]) -> OpenOptions {
+        OpenOptions {
+            keyfile: Some(Zeroizing::new(keyfile.to_vec())),
+            ..self.clone()
+        }
+    }
+
+    /// Returns a copy of these options with the KDF parameters legacy CBC
+    /// items were encrypted with, for [`crate::open_any`]. Legacy items do
+    /// not record them; the default is PBKDF2 with 100 000 iterations.
+    pub fn with_legacy_kdf(&self, kdf: &KdfParams) -> OpenOptions {
+        OpenOptions {
+            legacy_kdf: Some(kdf.clone()),
+            ..self.clone()
+        }
+    }
+
+    /// Returns a copy of these options with the associated data the
+    /// envelope was sealed with (see [`SealOptions::aad`]).
+    pub fn with_aad(&self, aad: &[u8]) -> OpenOptions {
+        OpenOptions {
+            aad: aad.to_vec(),
+            ..self.clone()
+        }
+    }
+
+    /// Returns a copy of these options that reject envelopes older than
+    /// `version`. Pass 2 to refuse version 1 envelopes, whose header is not
+    /// authenticated.
+    pub fn with_min_version(&self, version: u8) -> OpenOptions {
+        OpenOptions {
+            min_version: version,
+            ..self.clone()
+        }
+    }
+
+    /// Returns a copy of these options that reject envelopes, vaults and
+    /// chunked files whose KDF is cheaper than `kdf`: the same KDF with a
+    /// lower cost parameter, or PBKDF2 when `kdf` is memory-hard. Argon2id
+    /// and scrypt both meet a PBKDF2 floor.
+    pub fn with_min_kdf(&self, kdf: &KdfParams) -> OpenOptions {
+        OpenOptions {
+            min_kdf: Some(kdf.clone()),
+            ..self.clone()
+        }
+    }
+
+    /// Returns a copy of these options that reject envelopes, vaults and
+    /// chunked files encrypted with `cipher`.
+    pub fn with_denied_cipher(&self, cipher: Cipher) -> OpenOptions {
+        let mut options = self.clone();
+        if !options.denied_ciphers.contains(&cipher) {
+            options.denied_ciphers.push(cipher);
+        }
+        options
+    }
+}
+
+impl OpenOptions {
+    /// Rejects a cipher and KDF below the floor of these options. Runs
+    /// before key derivation, so a downgraded header costs nothing to
+    /// refuse.
+    pub(crate) fn check_floor(&self, cipher: Cipher, kdf: &KdfParams) -> Result<(), BVaultError> {
+        self.check_cipher(cipher)?;
+        match &self.min_kdf {
+            Some(floor) if !kdf.at_least(floor) => Err(BVaultError::new(
+                ErrorCode::BelowMinimum,
+                format!(
+                    "{} parameters are below the accepted minimum",
+                    kdf.algorithm()
+                ),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Rejects a cipher these options deny.
+    fn check_cipher(&self, cipher: Cipher) -> Result<(), BVaultError> {
+        if self.denied_ciphers.contains(&cipher) {
+            return Err(BVaultError::new(
+                ErrorCode::BelowMinimum,
+                format!("cipher {:?} is not accepted", cipher),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Rejects an envelope version below [`OpenOptions::with_min_version`].
+    fn check_version(&self, version: u8) -> Result<(), BVaultError> {
+        if version < self.min_version {
+            return Err(BVaultError::new(
+                ErrorCode::BelowMinimum,
+                format!("envelope version {} is not accepted", version),
+            ));
+        }
+        Ok(())
+    }
+
+    pub(crate) fn aad(&self) -> &[u8] {
+        &self.aad
+    }
+
+    pub(crate) fn keyfile(&self) -> Option<&[u8]> {
+        self.keyfile.as_deref().map(Vec::as_slice)
+    }
+
+    /// The legacy KDF parameters as given, without the keyfile.
+    pub(crate) fn legacy_kdf_params(&self) -> Option<KdfParams> {
+        self.legacy_kdf.clone()
+    }
+
+    /// The legacy KDF parameters with the keyfile, if any, mixed in.
+    #[cfg_attr(not(feature = "cbc-legacy"), allow(dead_code))]
+    pub(crate) fn legacy_kdf(&self) -> KdfParams {
+        let kdf = self.legacy_kdf.clone().unwrap_or_default();
+        match self.keyfile() {
+            Some(keyfile) => kdf.with_keyfile(keyfile),
+            None => kdf,
+        }
+    }
+}
+
+/// Encrypts `plaintext` under `password` into a self-describing envelope.
+///
+/// A fresh salt and nonce are generated for every call; the cipher and KDF
+/// parameters are recorded in the envelope so [`open`] needs nothing but
+/// the password. The cipher is always an AEAD suite, AES-256-GCM unless
+/// `options` pick another.
+///
+/// # Errors
+///
+/// - If the salt length is outside 8..=255 bytes, an error is returned.
+/// - If the parameters or the password fall short of the security policy
+///   (see [`crate::set_policy`]), an error is returned.
+/// - If the KDF parameters are below the accepted minimum, an error is returned.
+/// - If the random number generator is unavailable, an error is returned.
+#[wasm_bindgen]
+pub fn seal(
+    plaintext: &[u8],
+    password: &str,
+    options: Option<SealOptions>,
+) -> Result<Vec<u8>, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    let options = options.unwrap_or_default();
+
+    check_salt_length(options.salt_length)?;
+
+    let (salt, key) = new_salted_key(
+        password,
+        options.salt_length,
+        &options.kdf,
+        Some(options.cipher),
+    )?;
+    seal_with_key(
+        Some(options.kdf.clone()),
+        salt,
+        &key[..],
+        &options,
+        plaintext,
+    )
+}
+
+/// Encrypts `plaintext` under an already derived `key` with a fresh nonce,
+/// recording `kdf` and `salt` so the envelope can also be opened with the
+/// password. The cipher, key check, framing and associated data come from
+/// `options`; its KDF and salt length are ignored.
+pub(crate) fn seal_with_key(
+    kdf: Option<KdfParams>,
+    salt: Vec<u8>,
+    key: &[u8],
+    options: &SealOptions,
+    plaintext: &[u8],
+) -> Result<Vec<u8>, BVaultError> {
+    let nonce = random_bytes(options.cipher.nonce_length())?;
+    let envelope = seal_with_nonce(VERSION, kdf, salt, nonce, key, options, plaintext)?;
+    Ok(envelope.to_bytes())
+}
+
+/// [`seal_with_key`] with a given format version and nonce, for
+/// deterministic output such as test vectors. Never reuse a nonce.
+pub(crate) fn seal_with_nonce(
+    version: u8,
+    kdf: Option<KdfParams>,
+    salt: Vec<u8>,
+    nonce: Vec<u8>,
+    key: &[u8],
+    options: &SealOptions,
+    plaintext: &[u8],
+) -> Result<Envelope, BVaultError> {
+    let cipher = options.cipher;
+    let framing = Framing {
+        compression: options.compression,
+        padding: options.padding,
+    };
+    let kcv = if options.key_check {
+        Some(kcv::compute(key)?.to_vec())
+    } else {
+        None
+    };
+    let mut envelope = Envelope {
+        version,
+        cipher,
+        kdf,
+        salt,
+        nonce,
+        kcv,
+        framing,
+        rotation: options.rotation(),
+        body: Vec::new(),
+    };
+
+    envelope.body = cipher.encrypt_with_aad(
+        key,
+        &envelope.nonce,
+        &framing.apply(plaintext)?,
+        &envelope.associated_data(&options.aad)?,
+    )?;
+    Ok(envelope)
+}
+
+/// Decrypts an envelope produced by [`seal`].
+///
+/// Keyfiles are not recorded in the envelope; pass the one used at sealing
+/// time through `options`. The header names the cipher and KDF and is
+/// authenticated, so rewriting it fails to decrypt; `options` can also set
+/// a floor ([`OpenOptions::with_min_kdf`] and friends) so a rewritten
+/// header is refused before deriving any key.
+///
+/// # Errors
+///
+/// - If the envelope is malformed or truncated, an error is returned; with
+///   [`crate::SecurityPolicy::strict_validation`] on, an
+///   [`ErrorCode::ValidationFailed`] error listing every problem.
+/// - If the envelope uses an unknown version, cipher or KDF, an error is
+///   returned.
+/// - If its version, cipher or KDF is below the floor in `options`, an
+///   [`ErrorCode::BelowMinimum`] error is returned.
+/// - If the envelope has a key check value and the password does not match
+///   it, an [`ErrorCode::WrongPassword`] error is returned.
+/// - If authentication fails (wrong password or tampered data), an error
+///   is returned.
+/// - If throttling is on and the envelope has seen too many failed
+///   attempts, an [`ErrorCode::Throttled`] error is returned (see
+///   [`crate::enable_throttling`]).
+#[wasm_bindgen]
+pub fn open(
+    envelope: &[u8],
+    password: &str,
+    options: Option<OpenOptions>,
+) -> Result<Vec<u8>, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    let options = options.unwrap_or_default();
+    let bytes = envelope;
+    crate::policy::check_input(|| crate::validation::diagnose_envelope(bytes))?;
+    let envelope = Envelope::parse(bytes)?;
+
+    let kdf = envelope.kdf.as_ref().ok_or_else(|| {
+        BVaultError::new(
+            ErrorCode::InvalidArgument,
+            "envelope was sealed under a raw key; use open_with_raw_key",
+        )
+    })?;
+    options.check_version(envelope.version)?;
+    options.check_floor(envelope.cipher, kdf)?;
+    let kdf = match options.keyfile() {
+        Some(keyfile) => kdf.with_keyfile(keyfile),
+        None => kdf.clone(),
+    };
+    crate::policy::check_decrypt(password, envelope.salt.len(), &kdf);
+    crate::throttle::attempt(bytes, || {
+        let key = kdf.derive(password.as_bytes(), &envelope.salt)?;
+        open_with_key(&envelope, &key[..], &options.aad)
+    })
+}
+
+/// Re-encrypts an envelope under a new password without the plaintext ever
+/// leaving WASM memory.
+///
+/// The envelope is opened with `old_password` (and the keyfile in
+/// `open_options`, if any) and sealed again with `new_password` under a
+/// fresh salt and nonce. `options` picks the new cipher and KDF; when
+/// omitted the current defaults are used, which also upgrades old
+/// parameters. The intermediate plaintext is wiped before returning.
+///
+/// # Errors
+///
+/// See [`open`] and [`seal`].
+#[wasm_bindgen]
+pub fn reencrypt(
+    envelope: &[u8],
+    old_password: &str,
+    new_password: &str,
+    options: Option<SealOptions>,
+    open_options: Option<OpenOptions>,
+) -> Result<Vec<u8>, BVaultError> {
+    let plaintext = Zeroizing::new(open(envelope, old_password, open_options)?);
+    seal(&plaintext, new_password, options)
+}
+
+/// Checks the key check value, if any, and decrypts the body.
+fn open_with_key(envelope: &Envelope, key: &[u8], aad: &[u8]) -> Result<Vec<u8>, BVaultError> {
+    if let Some(kcv) = &envelope.kcv {
+        if !kcv::matches(key, kcv)? {
+            return Err(kcv::wrong_password());
+        }
+    }
+    envelope.decrypt(key, aad)
+}
+
+fn check_raw_key(key: &[u8]) -> Result<(), BVaultError> {
+    if key.len() != KEY_LENGTH {
+        return Err(BVaultError::new(
+            ErrorCode::BadKeyLength,
+            "raw key must be 32 bytes",
+        ));
+    }
+    Ok(())
+}
+
+/// Encrypts `plaintext` into an envelope under a caller-supplied 32-byte
+/// key, skipping key derivation entirely.
+///
+/// Meant for keys that do not come from a password, such as a WebAuthn PRF
+/// output or a hardware token secret. The key must be uniformly random; use
+/// [`seal`] for passwords. The cipher, key check, compression, padding,
+/// rotation and associated data come from `options`, as for [`seal`]; its
+/// KDF and salt length are ignored.
+///
+/// # Errors
+///
+/// - If `key` is not 32 bytes, an error is returned.
+/// - If the random number generator is unavailable, an error is returned.
+#[wasm_bindgen]
+pub fn seal_with_raw_key(
+    plaintext: &[u8],
+    key: &[u8],
+    options: Option<SealOptions>,
+) -> Result<Vec<u8>, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    check_raw_key(key)?;
+    seal_with_key(
+        None,
+        Vec::new(),
+        key,
+        &options.unwrap_or_default(),
+        plaintext,
+    )
+}
+
+/// Decrypts an envelope with a caller-supplied 32-byte key.
+///
+/// Works for envelopes from [`seal_with_raw_key`], and for password
+/// envelopes when given the derived key (see [`crate::derive_key_raw`]).
+/// The associated data and floors in `options` apply as in [`open`]; the
+/// KDF floor only to envelopes that record a KDF. Keyfiles play no part,
+/// since `key` is already final.
+///
+/// # Errors
+///
+/// - If `key` is not 32 bytes, an error is returned.
+/// - If the envelope is malformed or uses an unknown version or cipher, an
+///   error is returned.
+/// - If its version, cipher or KDF is below the floor in `options`, an
+///   [`ErrorCode::BelowMinimum`] error is returned.
+/// - If the key does not match the key check value, an
+///   [`ErrorCode::WrongPassword`] error is returned.
+/// - If authentication fails, an error is returned.
+#[wasm_bindgen]
+pub fn open_with_raw_key(
+    envelope: &[u8],
+    key: &[u8],
+    options: Option<OpenOptions>,
+) -> Result<Vec<u8>, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    let options = options.unwrap_or_default();
+    check_raw_key(key)?;
+    let envelope = Envelope::parse(envelope)?;
+    options.check_version(envelope.version)?;
+    match &envelope.kdf {
+        Some(kdf) => options.check_floor(envelope.cipher, kdf)?,
+        None => options.check_cipher(envelope.cipher)?,
+    }
+    open_with_key(&envelope, key, &options.aad)
+}
+
+/// Converts an envelope, in either form, to canonical CBOR.
+///
+/// # Errors
+///
+/// - If the envelope is malformed or uses an unknown version, cipher or
+///   KDF, an error is returned.
+#[wasm_bindgen]
+pub fn envelope_to_cbor(envelope: &[u8]) -> Result<Vec<u8>, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    Ok(Envelope::parse(envelope)?.to_cbor())
+}
+
+/// Converts an envelope, in either form, to the compact binary form.
+///
+/// # Errors
+///
+/// See [`envelope_to_cbor`].
+#[wasm_bindgen]
+pub fn envelope_from_cbor(envelope: &[u8]) -> Result<Vec<u8>, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    Ok(Envelope::parse(envelope)?.to_bytes())
+}
+
+/// Like [`seal`], but takes a string and returns the envelope as base64.
+///
+/// # Errors
+///
+/// See [`seal`].
+#[wasm_bindgen]
+pub fn seal_string(
+    plaintext: &str,
+    password: &str,
+    options: Option<SealOptions>,
+) -> Result<String, BVaultError> {
+    seal(plaintext.as_bytes(), password, options).map(|envelope| bytes_to_b64(&envelope))
+}
+
+/// Like [`open`], but takes a base64 envelope and returns a string.
+///
+/// # Errors
+///
+/// See [`open`]; additionally fails if the input is not base64 or the
+/// plaintext is not valid in `decoding`, which defaults to strict utf-8
+/// (see [`TextDecoding`]).
+#[wasm_bindgen]
+pub fn open_string(
+    b64_envelope: &str,
+    password: &str,
+    options: Option<OpenOptions>,
+    decoding: Option<TextDecoding>,
+) -> Result<String, BVaultError> {
+    crate::policy::check_input(|| crate::validation::diagnose_envelope_string(b64_envelope))?;
+    let plaintext = open(&b64_to_bytes(b64_envelope)?, password, options)?;
+    crate::text::decode_plaintext(plaintext, decoding)
+}
@@ -0,0 +1,270 @@
+//! Secrets that wipe themselves, for copy-to-clipboard flows.
+//!
+//! An [`EphemeralSecret`] is a [`crate::Secret`] that can be revealed a
+//! limited number of times (once by default) and only until its TTL runs
+//! out. The bytes are wiped after the last read, by a `setTimeout` timer
+//! when the TTL is up, or by [`EphemeralSecret::destroy`], whichever comes
+//! first. Every access also checks the deadline itself, so a timer delayed
+//! in a background tab cannot extend the secret's life.
+//!
+//! The optional `on_expire` callback is called once, right after the bytes
+//! are wiped, with the reason: `"read"`, `"expired"` or `"destroyed"`, so
+//! the UI can clear the clipboard or the field that showed the secret.
+//! Freeing the handle from JS wipes the bytes without calling it.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use zeroize::Zeroizing;
+
+use crate::{BVaultError, ErrorCode, Secret};
+
+const READ: &str = "read";
+const EXPIRED: &str = "expired";
+const DESTROYED: &str = "destroyed";
+
+struct State {
+    /// `None` once wiped.
+    bytes: Option<Zeroizing<Vec<u8>>>,
+    reads_left: u32,
+    /// When the TTL runs out, in ms since the epoch.
+    deadline_ms: f64,
+    /// Why the bytes were wiped.
+    reason: Option<&'static str>,
+    on_expire: Option<js_sys::Function>,
+}
+
+/// Wipes the bytes, unless already wiped, and then calls the callback. The
+/// state is not borrowed during the call, so the callback may use the
+/// secret.
+fn wipe(state: &RefCell<State>, reason: &'static str) {
+    let callback = {
+        let mut state = state.borrow_mut();
+        if state.bytes.is_none() {
+            return;
+        }
+        state.bytes = None;
+        state.reason = Some(reason);
+        state.on_expire.take()
+    };
+    if let Some(callback) = callback {
+        let _ = callback.call1(&JsValue::NULL, &JsValue::from_str(reason));
+    }
+}
+
+/// A pending `setTimeout` and the closure it will call.
+struct Timer {
+    handle: JsValue,
+    _callback: Closure<dyn FnMut()>,
+}
+
+impl Drop for Timer {
+    fn drop(&mut self) {
+        let global = js_sys::global();
+        if let Some(clear) = js_sys::Reflect::get(&global, &JsValue::from_str("clearTimeout"))
+            .ok()
+            .and_then(|f| f.dyn_into::<js_sys::Function>().ok())
+        {
+            let _ = clear.call1(&global, &self.handle);
+        }
+    }
+}
+
+/// Schedules the TTL wipe; `None` where there is no `setTimeout`, which
+/// leaves expiry to the checks on access.
+fn schedule(state: &Rc<RefCell<State>>, ttl_ms: f64) -> Option<Timer> {
+    let weak = Rc::downgrade(state);
+    let callback = Closure::<dyn FnMut()>::new(move || {
+        if let Some(state) = weak.upgrade() {
+            wipe(&state, EXPIRED);
+        }
+    });
+    let global = js_sys::global();
+    let set_timeout = js_sys::Reflect::get(&global, &JsValue::from_str("setTimeout"))
+        .ok()?
+        .dyn_into::<js_sys::Function>()
+        .ok()?;
+    let handle = set_timeout
+        .call2(&global, callback.as_ref(), &JsValue::from_f64(ttl_ms))
+        .ok()?;
+    Some(Timer {
+        handle,
+        _callback: callback,
+    })
+}
+
+/// A secret held in WASM memory for a limited number of reads and a
+/// limited time; see the module documentation.
+#[wasm_bindgen]
+pub struct EphemeralSecret {
+    state: Rc<RefCell<State>>,
+    _timer: Option<Timer>,
+}
+
+impl EphemeralSecret {
+    pub(crate) fn create(
+        bytes: Vec<u8>,
+        ttl_ms: f64,
+        max_reads: Option<u32>,
+        on_expire: Option<js_sys::Function>,
+    ) -> Result<EphemeralSecret, BVaultError> {
+        let bytes = Zeroizing::new(bytes);
+        if !(ttl_ms.is_finite() && ttl_ms >= 0.0) {
+            return Err(BVaultError::new(
+                ErrorCode::InvalidArgument,
+                "ttl_ms must be a non-negative number",
+            ));
+        }
+        let reads_left = max_reads.unwrap_or(1);
+        if reads_left == 0 {
+            return Err(BVaultError::new(
+                ErrorCode::InvalidArgument,
+                "max_reads must be at least 1",
+            ));
+        }
+        let state = Rc::new(RefCell::new(State {
+            bytes: Some(bytes),
+            reads_left,
+            deadline_ms: crate::utils::now_ms() + ttl_ms,
+            reason: None,
+            on_expire,
+        }));
+        let timer = schedule(&state, ttl_ms);
+        Ok(EphemeralSecret {
+            state,
+            _timer: timer,
+        })
+    }
+
+    /// Wipes the bytes if the TTL has run out.
+    fn check_deadline(&self) {
+        let expired = crate::utils::now_ms() >= self.state.borrow().deadline_ms;
+        if expired {
+            wipe(&self.state, EXPIRED);
+        }
+    }
+
+    /// Copies the bytes out, counting one read.
+    fn read(&self) -> Result<Zeroizing<Vec<u8>>, BVaultError> {
+        self.check_deadline();
+        let (bytes, last) = {
+            let mut state = self.state.borrow_mut();
+            let bytes = match &state.bytes {
+                Some(bytes) => Zeroizing::new(bytes.to_vec()),
+                None => {
+                    let message = match state.reason {
+                        Some(READ) => "the secret was already read",
+                        Some(EXPIRED) => "the secret expired",
+                        _ => "the secret was destroyed",
+                    };
+                    return Err(BVaultError::new(ErrorCode::SecretDestroyed, message));
+                }
+            };
+            state.reads_left -= 1;
+            (bytes, state.reads_left == 0)
+        };
+        if last {
+            wipe(&self.state, READ);
+        }
+        Ok(bytes)
+    }
+}
+
+#[wasm_bindgen]
+impl EphemeralSecret {
+    /// Wraps a copy of `bytes` that can be revealed `max_reads` times
+    /// (default 1) within `ttl_ms` milliseconds.
+    ///
+    /// # Errors
+    ///
+    /// - If `ttl_ms` is negative or not finite, or `max_reads` is 0, an
+    ///   [`ErrorCode::InvalidArgument`] error is returned.
+    #[wasm_bindgen(constructor)]
+    pub fn new(
+        bytes: &[u8],
+        ttl_ms: f64,
+        max_reads: Option<u32>,
+        on_expire: Option<js_sys::Function>,
+    ) -> Result<EphemeralSecret, BVaultError> {
+        let _operation = crate::utils::set_panic_hook();
+        EphemeralSecret::create(bytes.to_vec(), ttl_ms, max_reads, on_expire)
+    }
+
+    /// Copies the secret out as a string, counting one read; the last read
+    /// wipes it.
+    ///
+    /// # Errors
+    ///
+    /// - If the secret was wiped, an [`ErrorCode::SecretDestroyed`] error
+    ///   saying why is returned.
+    /// - If it is not valid utf-8, an [`ErrorCode::Utf8Error`] error is
+    ///   returned; the read still counts.
+    pub fn reveal(&self) -> Result<String, BVaultError> {
+        let bytes = self.read()?;
+        std::str::from_utf8(&bytes)
+            .map(str::to_string)
+            .map_err(|_| BVaultError::new(ErrorCode::Utf8Error, "the secret is not valid utf-8"))
+    }
+
+    /// Copies the secret out as bytes; see [`EphemeralSecret::reveal`].
+    ///
+    /// # Errors
+    ///
+    /// - If the secret was wiped, an [`ErrorCode::SecretDestroyed`] error
+    ///   saying why is returned.
+    pub fn reveal_bytes(&self) -> Result<Vec<u8>, BVaultError> {
+        Ok(self.read()?.to_vec())
+    }
+
+    /// Reads left before the secret is wiped; 0 once wiped.
+    #[wasm_bindgen(getter)]
+    pub fn reads_left(&self) -> u32 {
+        self.check_deadline();
+        let state = self.state.borrow();
+        if state.bytes.is_some() {
+            state.reads_left
+        } else {
+            0
+        }
+    }
+
+    /// When the TTL runs out, in ms since the Unix epoch.
+    #[wasm_bindgen(getter)]
+    pub fn expires_at(&self) -> f64 {
+        self.state.borrow().deadline_ms
+    }
+
+    /// Whether the secret has been wiped, for any reason.
+    #[wasm_bindgen(getter)]
+    pub fn destroyed(&self) -> bool {
+        self.check_deadline();
+        self.state.borrow().bytes.is_none()
+    }
+
+    /// Wipes the secret now, calling `on_expire` with `"destroyed"`.
+    pub fn destroy(&self) {
+        wipe(&self.state, DESTROYED);
+    }
+}
+
+#[wasm_bindgen]
+impl Secret {
+    /// An [`EphemeralSecret`] holding a copy of this secret; see
+    /// [`EphemeralSecret::new`]. This secret is left as it is.
+    ///
+    /// # Errors
+    ///
+    /// - If this secret was destroyed, an [`ErrorCode::SecretDestroyed`]
+    ///   error is returned.
+    /// - Otherwise see [`EphemeralSecret::new`].
+    pub fn to_ephemeral(
+        &self,
+        ttl_ms: f64,
+        max_reads: Option<u32>,
+        on_expire: Option<js_sys::Function>,
+    ) -> Result<EphemeralSecret, BVaultError> {
+        EphemeralSecret::create(self.reveal_bytes()?, ttl_ms, max_reads, on_expire)
+    }
+}
@@ -0,0 +1,251 @@
+//! The error type thrown by every exported function.
+//!
+//! Each failure carries an [`ErrorCode`] so callers can branch on the kind of
+//! failure (e.g. show "wrong password" for [`ErrorCode::AuthFailed`] but
+//! "file is damaged" for [`ErrorCode::InvalidBase64`]) instead of matching
+//! on message strings. Discriminants are part of the public API and must
+//! never be renumbered.
+//!
+//! Failed decryptions are deliberately uninformative: a padding error, a
+//! bad tag and invalid utf-8 all surface as [`ErrorCode::AuthFailed`] with
+//! the same message, so ciphertexts from untrusted parties cannot be used
+//! as an oracle. [`set_debug_errors`] restores the specific codes while
+//! debugging.
+//!
+//! Errors also record the source module of the exported function that
+//! failed (`operation`), the cipher suite involved (`suite`) and the error
+//! that led to them (`cause`). Logging tools that only understand `Error`
+//! instances can be given [`BVaultError::to_error`], which carries the same
+//! properties.
+
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use wasm_bindgen::prelude::*;
+
+/// Stable, machine-readable failure codes.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ErrorCode {
+    /// An input was not valid base64.
+    InvalidBase64 = 1,
+    /// The IV or nonce has the wrong length for the cipher.
+    BadIvLength = 2,
+    /// The salt is shorter than the accepted minimum.
+    BadSaltLength = 3,
+    /// The KDF parameters are invalid, below the accepted minimum or above
+    /// the maximum cost.
+    WeakKdfParams = 4,
+    /// The key derivation itself failed.
+    KdfFailed = 5,
+    /// Authentication failed: wrong password/key or tampered ciphertext.
+    AuthFailed = 6,
+    /// CBC padding was invalid: wrong password/key or corrupted ciphertext.
+    /// Only reported with [`set_debug_errors`] on; otherwise
+    /// [`ErrorCode::AuthFailed`].
+    PaddingError = 7,
+    /// The decrypted bytes are not valid utf-8. Only reported with
+    /// [`set_debug_errors`] on; otherwise [`ErrorCode::AuthFailed`].
+    Utf8Error = 8,
+    /// The platform random number generator is unavailable.
+    RandomUnavailable = 9,
+    /// A key has the wrong length for the cipher.
+    BadKeyLength = 10,
+    /// A requested output length is out of range.
+    BadLength = 11,
+    /// Encryption failed.
+    EncryptionFailed = 12,
+    /// The input is not a well-formed envelope.
+    InvalidEnvelope = 13,
+    /// The envelope uses a format version this build does not understand.
+    UnsupportedVersion = 14,
+    /// The envelope names a cipher or KDF this build does not support.
+    UnsupportedAlgorithm = 15,
+    /// The envelope was not sealed under the session's salt and KDF.
+    SessionMismatch = 16,
+    /// The stream was already finalized.
+    StreamFinished = 17,
+    /// An argument is outside its accepted range.
+    InvalidArgument = 18,
+    /// The password or key is wrong: the key check value does not match or
+    /// no key slot opens with it.
+    WrongPassword = 19,
+    /// No vault entry has the given ID.
+    EntryNotFound = 20,
+    /// A JSON document does not match the expected schema.
+    InvalidJson = 21,
+    /// The token's `exp` claim is in the past.
+    TokenExpired = 22,
+    /// The token's `nbf` claim is in the future.
+    TokenNotYetValid = 23,
+    /// A BIP39 mnemonic has an unknown word, wrong length or bad checksum.
+    InvalidMnemonic = 24,
+    /// A secret share is corrupted, or the shares do not belong together.
+    InvalidShare = 25,
+    /// A public or private key could not be parsed.
+    InvalidKey = 26,
+    /// An input was not valid hex.
+    InvalidHex = 27,
+    /// The parameters fall short of the policy set by
+    /// [`crate::set_policy`].
+    PolicyViolation = 28,
+    /// An input was not valid base32.
+    InvalidBase32 = 29,
+    /// Too many failed attempts on a blob; see
+    /// [`crate::enable_throttling`].
+    Throttled = 30,
+    /// The session's key has been wiped by `lock()` or by expiring.
+    SessionLocked = 31,
+    /// The operation's `AbortSignal` was aborted.
+    Aborted = 32,
+    /// The envelope's version, cipher or KDF is below the floor set in
+    /// [`crate::OpenOptions`], as when a header was rewritten to a weaker
+    /// suite.
+    BelowMinimum = 33,
+    /// The [`crate::Secret`] was wiped by `destroy()`.
+    SecretDestroyed = 34,
+    /// Strict validation found problems with an input; the message lists
+    /// them. See [`crate::validate_envelope`].
+    ValidationFailed = 35,
+}
+
+/// The error thrown to JS by every exported function.
+#[wasm_bindgen]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BVaultError {
+    code: ErrorCode,
+    message: String,
+    operation: Option<String>,
+    suite: Option<String>,
+    cause: Option<Box<BVaultError>>,
+}
+
+#[wasm_bindgen]
+impl BVaultError {
+    /// The machine-readable failure code.
+    #[wasm_bindgen(getter)]
+    pub fn code(&self) -> ErrorCode {
+        self.code
+    }
+
+    /// A human-readable description of the failure.
+    #[wasm_bindgen(getter)]
+    pub fn message(&self) -> String {
+        self.message.clone()
+    }
+
+    /// The source module of the exported function that failed, such as
+    /// `"vault"`.
+    #[wasm_bindgen(getter)]
+    pub fn operation(&self) -> Option<String> {
+        self.operation.clone()
+    }
+
+    /// The cipher suite that failed, such as `"aes-256-gcm"`, if the
+    /// failure came from one.
+    #[wasm_bindgen(getter)]
+    pub fn suite(&self) -> Option<String> {
+        self.suite.clone()
+    }
+
+    /// The error that caused this one, if any.
+    #[wasm_bindgen(getter)]
+    pub fn cause(&self) -> Option<BVaultError> {
+        self.cause.as_deref().cloned()
+    }
+
+    /// A JS `Error` with this error's message, named `"BVaultError"`, with
+    /// `code`, `operation` and `suite` properties and the cause (converted
+    /// the same way) as its standard `cause`. Unlike this class, it has a
+    /// stack trace, which error trackers such as Sentry group on.
+    #[wasm_bindgen(js_name = toError)]
+    pub fn to_error(&self) -> js_sys::Error {
+        let error = js_sys::Error::new(&self.message);
+        error.set_name("BVaultError");
+        let set = |name: &str, value: JsValue| {
+            let _ = js_sys::Reflect::set(&error, &JsValue::from_str(name), &value);
+        };
+        set("code", JsValue::from(self.code as u32));
+        set("operation", self.operation.clone().into());
+        set("suite", self.suite.clone().into());
+        if let Some(cause) = &self.cause {
+            set("cause", cause.to_error().into());
+        }
+        error
+    }
+
+    #[wasm_bindgen(js_name = toString)]
+    pub fn to_js_string(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl BVaultError {
+    pub(crate) fn new(code: ErrorCode, message: impl Into<String>) -> BVaultError {
+        BVaultError {
+            code,
+            message: message.into(),
+            operation: crate::utils::current_operation().map(str::to_string),
+            suite: None,
+            cause: None,
+        }
+    }
+
+    /// Records `suite` as the cipher suite that failed, unless one is
+    /// recorded already.
+    pub(crate) fn with_suite(mut self, suite: &str) -> BVaultError {
+        self.suite.get_or_insert_with(|| suite.to_string());
+        self
+    }
+
+    /// Records `cause` as the error that led to this one.
+    #[cfg_attr(not(feature = "cbc-legacy"), allow(dead_code))]
+    pub(crate) fn caused_by(mut self, cause: BVaultError) -> BVaultError {
+        self.cause = Some(Box::new(cause));
+        self
+    }
+
+    /// The error for a ciphertext that failed to decrypt: `code` and
+    /// `message` with debug errors on, a generic
+    /// [`ErrorCode::AuthFailed`] otherwise.
+    pub(crate) fn decryption_failed(code: ErrorCode, message: &str) -> BVaultError {
+        if DEBUG_ERRORS.load(Ordering::Relaxed) {
+            BVaultError::new(code, message)
+        } else {
+            BVaultError::new(
+                ErrorCode::AuthFailed,
+                "decryption failed: wrong password or tampered data",
+            )
+        }
+    }
+}
+
+static DEBUG_ERRORS: AtomicBool = AtomicBool::new(false);
+
+/// Makes failed decryptions report why they failed (bad padding, bad tag,
+/// invalid utf-8) instead of a uniform [`ErrorCode::AuthFailed`].
+///
+/// The detail turns every decryption into an oracle for whoever supplies the
+/// ciphertext; only enable it while debugging, never in production.
+#[wasm_bindgen]
+pub fn set_debug_errors(enabled: bool) {
+    DEBUG_ERRORS.store(enabled, Ordering::Relaxed);
+}
+
+impl fmt::Display for BVaultError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "BVaultError({:?}): {}", self.code, self.message)?;
+        if let Some(cause) = &self.cause {
+            write!(f, " (caused by {})", cause)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for BVaultError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.cause
+            .as_deref()
+            .map(|cause| cause as &(dyn std::error::Error + 'static))
+    }
+}
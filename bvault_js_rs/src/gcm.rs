@@ -0,0 +1,118 @@
+//! AES-256-GCM authenticated encryption.
+//!
+//! This is the recommended mode for new data: unlike the CBC functions it
+//! detects any tampering with the ciphertext. The output layout (12-byte
+//! nonce, tag appended to the ciphertext) matches WebCrypto's `AES-GCM`.
+
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    aead, BVaultError, Cipher, EncryptOptions, EncryptedBytes, EncryptedData, KdfParams,
+    TextDecoding,
+};
+
+/// Synchronously encrypts a plaintext string with AES-256-GCM.
+///
+/// A random salt and 12-byte nonce are generated for every call and the key
+/// is derived with PBKDF2-HMAC-SHA256, exactly as in [`crate::encrypt_sync`].
+///
+/// # Errors
+///
+/// - If the requested salt length is shorter than 8 bytes, an error is returned.
+/// - If the parameters or the password fall short of the security policy
+///   (see [`crate::set_policy`]), an error is returned.
+/// - If the KDF parameters are below the accepted minimum, an error is returned.
+/// - If the random number generator is unavailable, an error is returned.
+#[wasm_bindgen]
+pub fn encrypt_gcm(
+    plaintext: &str,
+    password: &str,
+    options: Option<EncryptOptions>,
+) -> Result<EncryptedData, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    aead::encrypt(Cipher::Aes256Gcm, plaintext.as_bytes(), password, options)
+}
+
+/// Synchronously decrypts and verifies an AES-256-GCM ciphertext.
+///
+/// `aad` must be the associated data given through
+/// [`EncryptOptions::aad`] at encryption time, if any.
+///
+/// # Errors
+///
+/// - If the inputs are invalid base64, an error is returned.
+/// - If the nonce is not 12 bytes, an error is returned.
+/// - If the KDF parameters are below the accepted minimum, an error is returned.
+/// - If the tag does not verify (wrong password, wrong `aad` or tampered
+///   data), an error is returned.
+/// - If the decrypted bytes are not valid in `decoding` (strict utf-8 by
+///   default; see [`crate::TextDecoding`]), an error is returned.
+#[wasm_bindgen]
+pub fn decrypt_gcm(
+    b64_ciphertext: &str,
+    password: &str,
+    b64_nonce: &str,
+    b64_salt: &str,
+    kdf: Option<KdfParams>,
+    aad: Option<Vec<u8>>,
+    decoding: Option<TextDecoding>,
+) -> Result<String, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    let plaintext = aead::decrypt(
+        Cipher::Aes256Gcm,
+        b64_ciphertext,
+        password,
+        b64_nonce,
+        b64_salt,
+        kdf,
+        aad.as_deref().unwrap_or_default(),
+    )?;
+    crate::text::decode_plaintext(plaintext, decoding)
+}
+
+/// Like [`encrypt_gcm`], but takes and returns raw bytes.
+///
+/// # Errors
+///
+/// See [`encrypt_gcm`].
+#[wasm_bindgen]
+pub fn encrypt_gcm_binary(
+    plaintext: &[u8],
+    password: &str,
+    options: Option<EncryptOptions>,
+) -> Result<EncryptedBytes, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    aead::encrypt_bytes(
+        Cipher::Aes256Gcm,
+        plaintext,
+        password,
+        &options.unwrap_or_default(),
+    )
+}
+
+/// Like [`decrypt_gcm`], but takes raw bytes and returns the
+/// plaintext bytes without utf-8 validation.
+///
+/// # Errors
+///
+/// See [`decrypt_gcm`], minus the base64 and utf-8 checks.
+#[wasm_bindgen]
+pub fn decrypt_gcm_binary(
+    ciphertext: &[u8],
+    password: &str,
+    nonce: &[u8],
+    salt: &[u8],
+    kdf: Option<KdfParams>,
+    aad: Option<Vec<u8>>,
+) -> Result<Vec<u8>, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    aead::decrypt_bytes(
+        Cipher::Aes256Gcm,
+        ciphertext,
+        password,
+        nonce,
+        salt,
+        kdf,
+        aad.as_deref().unwrap_or_default(),
+    )
+}
@@ -0,0 +1,227 @@
+//! Password and passphrase generation.
+//!
+//! Every character and word is picked with [`random_below`], so the output
+//! is uniformly distributed over the selected alphabet or wordlist.
+//!
+//! Passphrases use the EFF large wordlist (7 776 words, about 12.9 bits per
+//! word) by the Electronic Frontier Foundation, licensed under CC BY 3.0 US:
+//! <https://www.eff.org/dice>.
+
+use wasm_bindgen::prelude::*;
+
+use crate::random::random_below;
+use crate::{BVaultError, ErrorCode};
+
+const LOWERCASE: &str = "abcdefghijklmnopqrstuvwxyz";
+const UPPERCASE: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+const DIGITS: &str = "0123456789";
+const SYMBOLS: &str = "!@#$%^&*()-_=+[]{};:,.<>/?~";
+
+/// Characters easily confused with one another in many fonts.
+const AMBIGUOUS: &str = "Il1|O0o";
+
+/// Longest password [`generate_password`] produces.
+const MAX_PASSWORD_LENGTH: usize = 1024;
+
+/// Most words [`generate_passphrase`] produces.
+const MAX_PASSPHRASE_WORDS: usize = 64;
+
+/// The EFF large wordlist, one word per line.
+pub(crate) const WORDLIST: &str = include_str!("eff_large_wordlist.txt");
+
+/// Character classes used by [`generate_password`].
+#[wasm_bindgen]
+#[derive(Clone, Debug)]
+pub struct PasswordOptions {
+    lowercase: bool,
+    uppercase: bool,
+    digits: bool,
+    symbols: bool,
+    exclude_ambiguous: bool,
+}
+
+impl Default for PasswordOptions {
+    fn default() -> Self {
+        PasswordOptions {
+            lowercase: true,
+            uppercase: true,
+            digits: true,
+            symbols: true,
+            exclude_ambiguous: false,
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl PasswordOptions {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> PasswordOptions {
+        PasswordOptions::default()
+    }
+
+    /// Include `a`-`z` (default `true`).
+    #[wasm_bindgen(getter)]
+    pub fn lowercase(&self) -> bool {
+        self.lowercase
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_lowercase(&mut self, lowercase: bool) {
+        self.lowercase = lowercase;
+    }
+
+    /// Include `A`-`Z` (default `true`).
+    #[wasm_bindgen(getter)]
+    pub fn uppercase(&self) -> bool {
+        self.uppercase
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_uppercase(&mut self, uppercase: bool) {
+        self.uppercase = uppercase;
+    }
+
+    /// Include `0`-`9` (default `true`).
+    #[wasm_bindgen(getter)]
+    pub fn digits(&self) -> bool {
+        self.digits
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_digits(&mut self, digits: bool) {
+        self.digits = digits;
+    }
+
+    /// Include punctuation such as `!@#$%` (default `true`).
+    #[wasm_bindgen(getter)]
+    pub fn symbols(&self) -> bool {
+        self.symbols
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_symbols(&mut self, symbols: bool) {
+        self.symbols = symbols;
+    }
+
+    /// Leave out look-alike characters (`I l 1 | O 0 o`, default `false`).
+    #[wasm_bindgen(getter)]
+    pub fn exclude_ambiguous(&self) -> bool {
+        self.exclude_ambiguous
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_exclude_ambiguous(&mut self, exclude_ambiguous: bool) {
+        self.exclude_ambiguous = exclude_ambiguous;
+    }
+}
+
+impl PasswordOptions {
+    /// The enabled character classes, with ambiguous characters removed if
+    /// requested.
+    fn classes(&self) -> Vec<Vec<char>> {
+        [
+            (self.lowercase, LOWERCASE),
+            (self.uppercase, UPPERCASE),
+            (self.digits, DIGITS),
+            (self.symbols, SYMBOLS),
+        ]
+        .iter()
+        .filter(|(enabled, _)| *enabled)
+        .map(|(_, class)| {
+            class
+                .chars()
+                .filter(|c| !(self.exclude_ambiguous && AMBIGUOUS.contains(*c)))
+                .collect()
+        })
+        .collect()
+    }
+}
+
+fn pick<T: Copy>(items: &[T]) -> Result<T, BVaultError> {
+    Ok(items[random_below(items.len() as u32)? as usize])
+}
+
+/// Generates a random password of `length` characters.
+///
+/// The password contains at least one character from every enabled class.
+///
+/// # Errors
+///
+/// - If `length` is 0, greater than 1024 or smaller than the number of
+///   enabled classes, an error is returned.
+/// - If no character class is enabled, an error is returned.
+/// - If the random number generator is unavailable, an error is returned.
+#[wasm_bindgen]
+pub fn generate_password(
+    length: usize,
+    options: Option<PasswordOptions>,
+) -> Result<String, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    let classes = options.unwrap_or_default().classes();
+
+    if classes.is_empty() {
+        return Err(BVaultError::new(
+            ErrorCode::InvalidArgument,
+            "at least one character class must be enabled",
+        ));
+    }
+    if length == 0 || length > MAX_PASSWORD_LENGTH || length < classes.len() {
+        return Err(BVaultError::new(
+            ErrorCode::InvalidArgument,
+            format!(
+                "password length must be between {} and {}",
+                classes.len(),
+                MAX_PASSWORD_LENGTH
+            ),
+        ));
+    }
+
+    // One character from each class, the rest from all of them.
+    let alphabet: Vec<char> = classes.iter().flatten().copied().collect();
+    let mut password = Vec::with_capacity(length);
+    for class in &classes {
+        password.push(pick(class)?);
+    }
+    while password.len() < length {
+        password.push(pick(&alphabet)?);
+    }
+
+    // Fisher-Yates, so the guaranteed characters are not always in front.
+    for i in (1..password.len()).rev() {
+        let j = random_below(i as u32 + 1)? as usize;
+        password.swap(i, j);
+    }
+
+    Ok(password.into_iter().collect())
+}
+
+/// Generates a diceware-style passphrase of `word_count` words from the EFF
+/// large wordlist, joined by `separator` (default `"-"`).
+///
+/// Six words give about 77 bits of entropy.
+///
+/// # Errors
+///
+/// - If `word_count` is 0 or greater than 64, an error is returned.
+/// - If the random number generator is unavailable, an error is returned.
+#[wasm_bindgen]
+pub fn generate_passphrase(
+    word_count: usize,
+    separator: Option<String>,
+) -> Result<String, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+
+    if word_count == 0 || word_count > MAX_PASSPHRASE_WORDS {
+        return Err(BVaultError::new(
+            ErrorCode::InvalidArgument,
+            "word count must be between 1 and 64",
+        ));
+    }
+
+    let words: Vec<&str> = WORDLIST.lines().collect();
+    let chosen = (0..word_count)
+        .map(|_| pick(&words))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(chosen.join(separator.as_deref().unwrap_or("-")))
+}
@@ -0,0 +1,247 @@
+//! Hierarchical deterministic key derivation: BIP32 for secp256k1 and
+//! SLIP-10 for Ed25519.
+//!
+//! One recovery seed (see [`crate::mnemonic_to_seed`]) deterministically
+//! yields a tree of keys addressed by paths such as `m/44'/0'/0'/0/7`. An
+//! index followed by `'`, `h` or `H` is hardened. Ed25519 supports hardened
+//! derivation only.
+//!
+//! Each step computes `I = HMAC-SHA512(chain code, data || index)` and
+//! splits it into the child key material (left half) and chain code (right
+//! half). For secp256k1 the child key is the left half plus the parent key
+//! modulo the group order; for Ed25519 it is the left half itself. When
+//! the left half is not a valid secp256k1 key, derivation retries as
+//! SLIP-10 specifies.
+
+use hmac::{Hmac, Mac};
+use k256::elliptic_curve::sec1::ToEncodedPoint;
+use k256::elliptic_curve::PrimeField;
+use k256::{FieldBytes, Scalar, SecretKey};
+use sha2::Sha512;
+use wasm_bindgen::prelude::*;
+use zeroize::Zeroizing;
+
+use crate::{BVaultError, ErrorCode};
+
+/// Offset of the first hardened index (2^31).
+const HARDENED: u32 = 0x8000_0000;
+
+/// Longest path accepted, in components.
+const MAX_DEPTH: usize = 255;
+
+/// Curve a key tree is derived for.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HdCurve {
+    /// SLIP-10 Ed25519 (hardened indices only).
+    Ed25519 = 1,
+    /// BIP32 secp256k1.
+    Secp256k1 = 2,
+}
+
+impl HdCurve {
+    fn master_label(self) -> &'static [u8] {
+        match self {
+            HdCurve::Ed25519 => b"ed25519 seed",
+            HdCurve::Secp256k1 => b"Bitcoin seed",
+        }
+    }
+}
+
+/// A private key and chain code at some node of a key tree.
+#[wasm_bindgen]
+#[derive(Clone)]
+pub struct ExtendedKey {
+    curve: HdCurve,
+    private_key: Zeroizing<[u8; 32]>,
+    chain_code: [u8; 32],
+}
+
+#[wasm_bindgen]
+impl ExtendedKey {
+    /// The curve this key belongs to.
+    #[wasm_bindgen(getter)]
+    pub fn curve(&self) -> HdCurve {
+        self.curve
+    }
+
+    /// The 32-byte private key (an Ed25519 seed or a secp256k1 scalar).
+    #[wasm_bindgen(getter)]
+    pub fn private_key(&self) -> Vec<u8> {
+        self.private_key.to_vec()
+    }
+
+    /// The 32-byte chain code.
+    #[wasm_bindgen(getter)]
+    pub fn chain_code(&self) -> Vec<u8> {
+        self.chain_code.to_vec()
+    }
+
+    /// The public key: 32 bytes for Ed25519, 33 compressed SEC1 bytes for
+    /// secp256k1.
+    #[wasm_bindgen(getter)]
+    pub fn public_key(&self) -> Vec<u8> {
+        match self.curve {
+            HdCurve::Ed25519 => ed25519_dalek::SigningKey::from_bytes(&self.private_key)
+                .verifying_key()
+                .to_bytes()
+                .to_vec(),
+            HdCurve::Secp256k1 => secp256k1_public_key(&self.private_key).to_vec(),
+        }
+    }
+}
+
+fn secp256k1_public_key(private_key: &[u8; 32]) -> [u8; 33] {
+    // Every ExtendedKey holds a valid, non-zero scalar.
+    let secret = SecretKey::from_bytes(FieldBytes::from_slice(private_key)).expect("valid scalar");
+    let mut out = [0u8; 33];
+    out.copy_from_slice(secret.public_key().to_encoded_point(true).as_bytes());
+    out
+}
+
+fn hmac_sha512(key: &[u8], parts: &[&[u8]]) -> Zeroizing<[u8; 64]> {
+    let mut mac = Hmac::<Sha512>::new_from_slice(key).expect("HMAC takes keys of any length");
+    for part in parts {
+        mac.update(part);
+    }
+    Zeroizing::new(mac.finalize().into_bytes().into())
+}
+
+/// The left half of `i` as a secp256k1 scalar, if it is below the order.
+fn scalar(i: &[u8]) -> Option<Scalar> {
+    Option::from(Scalar::from_repr(*FieldBytes::from_slice(&i[..32])))
+}
+
+fn split(i: &[u8; 64]) -> (Zeroizing<[u8; 32]>, [u8; 32]) {
+    let mut key = Zeroizing::new([0u8; 32]);
+    key.copy_from_slice(&i[..32]);
+    let mut chain_code = [0u8; 32];
+    chain_code.copy_from_slice(&i[32..]);
+    (key, chain_code)
+}
+
+impl ExtendedKey {
+    fn master(seed: &[u8], curve: HdCurve) -> ExtendedKey {
+        let mut i = hmac_sha512(curve.master_label(), &[seed]);
+        if curve == HdCurve::Secp256k1 {
+            while scalar(&i[..]).is_none_or(|k| bool::from(k.is_zero())) {
+                let retry = *i;
+                i = hmac_sha512(curve.master_label(), &[&retry[..]]);
+            }
+        }
+        let (private_key, chain_code) = split(&i);
+        ExtendedKey {
+            curve,
+            private_key,
+            chain_code,
+        }
+    }
+
+    fn child(&self, index: u32) -> Result<ExtendedKey, BVaultError> {
+        let index_bytes = index.to_be_bytes();
+        let hardened = index >= HARDENED;
+        match self.curve {
+            HdCurve::Ed25519 => {
+                if !hardened {
+                    return Err(BVaultError::new(
+                        ErrorCode::InvalidArgument,
+                        "Ed25519 supports hardened derivation only",
+                    ));
+                }
+                let i = hmac_sha512(
+                    &self.chain_code,
+                    &[&[0], &self.private_key[..], &index_bytes],
+                );
+                let (private_key, chain_code) = split(&i);
+                Ok(ExtendedKey {
+                    curve: self.curve,
+                    private_key,
+                    chain_code,
+                })
+            }
+            HdCurve::Secp256k1 => {
+                let parent = scalar(&self.private_key[..]).expect("valid scalar");
+                let mut i = if hardened {
+                    hmac_sha512(
+                        &self.chain_code,
+                        &[&[0], &self.private_key[..], &index_bytes],
+                    )
+                } else {
+                    let public_key = secp256k1_public_key(&self.private_key);
+                    hmac_sha512(&self.chain_code, &[&public_key, &index_bytes])
+                };
+                loop {
+                    if let Some(tweak) = scalar(&i[..]) {
+                        let key = tweak + parent;
+                        if !bool::from(key.is_zero()) {
+                            let (_, chain_code) = split(&i);
+                            let mut private_key = Zeroizing::new([0u8; 32]);
+                            private_key.copy_from_slice(&key.to_repr());
+                            return Ok(ExtendedKey {
+                                curve: self.curve,
+                                private_key,
+                                chain_code,
+                            });
+                        }
+                    }
+                    let right = Zeroizing::new(i[32..].to_vec());
+                    i = hmac_sha512(&self.chain_code, &[&[1], &right[..], &index_bytes]);
+                }
+            }
+        }
+    }
+}
+
+/// Parses `m/44'/0'/0` into child indices.
+fn parse_path(path: &str) -> Result<Vec<u32>, BVaultError> {
+    let invalid = |message: String| BVaultError::new(ErrorCode::InvalidArgument, message);
+    let mut components = path.trim().split('/');
+    if components.next() != Some("m") {
+        return Err(invalid(
+            "a derivation path must start with \"m\"".to_string(),
+        ));
+    }
+    let indices = components
+        .map(|component| {
+            let (number, hardened) = match component.strip_suffix(['\'', 'h', 'H']) {
+                Some(number) => (number, true),
+                None => (component, false),
+            };
+            let index = number
+                .parse::<u32>()
+                .ok()
+                .filter(|&i| i < HARDENED && number.bytes().all(|b| b.is_ascii_digit()))
+                .ok_or_else(|| invalid(format!("invalid path component \"{}\"", component)))?;
+            Ok(if hardened { index | HARDENED } else { index })
+        })
+        .collect::<Result<Vec<u32>, BVaultError>>()?;
+    if indices.len() > MAX_DEPTH {
+        return Err(invalid("derivation path is deeper than 255".to_string()));
+    }
+    Ok(indices)
+}
+
+/// Derives the key at `path` (e.g. `m/44'/60'/0'/0/0`) from a 16- to
+/// 64-byte seed, such as the output of [`crate::mnemonic_to_seed`].
+///
+/// # Errors
+///
+/// - If the seed is shorter than 16 or longer than 64 bytes, an error is
+///   returned.
+/// - If the path is malformed, or has a non-hardened index for Ed25519, an
+///   error is returned.
+#[wasm_bindgen]
+pub fn derive_path(seed: &[u8], path: &str, curve: HdCurve) -> Result<ExtendedKey, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    if !(16..=64).contains(&seed.len()) {
+        return Err(BVaultError::new(
+            ErrorCode::InvalidArgument,
+            "seeds must be 16 to 64 bytes",
+        ));
+    }
+    parse_path(path)?
+        .into_iter()
+        .try_fold(ExtendedKey::master(seed, curve), |key, index| {
+            key.child(index)
+        })
+}
@@ -0,0 +1,350 @@
+//! Containers with a hidden second vault, for users under coercion.
+//!
+//! A hidden container holds two equally sized slots. The outer slot is
+//! sealed under one password and holds a plausible decoy; the other holds
+//! either the real data under a second password or random filler. An AEAD
+//! ciphertext is indistinguishable from random bytes, so nothing in the
+//! container shows whether a hidden vault exists: a user made to reveal a
+//! password can reveal the outer one and plausibly claim the rest is
+//! filler. Which slot is the outer one is chosen at random. All integers
+//! are big-endian:
+//!
+//! ```text
+//! magic     4     "BVLH"
+//! version   1     currently 1
+//! cipher    1     Cipher ID
+//! kdf       1 + … as in envelopes, shared by both slots
+//! salt len  1     n
+//! capacity  4     c, the largest plaintext a slot holds
+//! slot × 2:
+//!   salt    n
+//!   nonce   as the cipher requires
+//!   body    4 + c + t   sealed (length u32, plaintext, zeros), where t
+//!                       is the cipher's tag length
+//! ```
+//!
+//! Each body is authenticated against the header and its slot index.
+//! [`open_hidden`] always derives a key and attempts a decryption for both
+//! slots, so its timing does not depend on which one the password opens.
+//!
+//! The capacity is visible, so pick one that also suits the decoy.
+//! Updating one slot with [`reseal_hidden`] leaves the other byte for byte
+//! unchanged; an attacker holding several copies of a container taken over
+//! time can see which slot changed.
+
+use wasm_bindgen::prelude::*;
+use zeroize::Zeroizing;
+
+use crate::envelope::{
+    check_salt_length, check_version, cipher_from_id, invalid, read_kdf, write_kdf, Reader,
+};
+use crate::kdf::Key;
+use crate::{new_salted_key, random_bytes, BVaultError, Cipher, ErrorCode, KdfParams, SealOptions};
+
+const MAGIC: &[u8; 4] = b"BVLH";
+const VERSION: u8 = 1;
+
+/// Length of the plaintext length prefix inside a slot, in bytes.
+const LENGTH_PREFIX: usize = 4;
+
+/// Capacity used when the caller picks none, and the smallest one picked
+/// automatically.
+const MIN_DEFAULT_CAPACITY: usize = 4096;
+
+/// Largest slot capacity accepted (64 MiB).
+const MAX_CAPACITY: usize = 64 * 1024 * 1024;
+
+/// One sealed (or filler) slot.
+#[derive(Clone)]
+struct Slot {
+    salt: Vec<u8>,
+    nonce: Vec<u8>,
+    body: Vec<u8>,
+}
+
+/// A parsed container.
+struct Container {
+    cipher: Cipher,
+    kdf: KdfParams,
+    salt_length: usize,
+    capacity: usize,
+    slots: [Slot; 2],
+}
+
+impl Container {
+    fn header(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(32);
+        out.extend_from_slice(MAGIC);
+        out.push(VERSION);
+        out.push(self.cipher.id());
+        write_kdf(&mut out, Some(&self.kdf));
+        // Bounded by `check_salt_length` and `check_capacity`.
+        out.push(self.salt_length as u8);
+        out.extend_from_slice(&(self.capacity as u32).to_be_bytes());
+        out
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut out = self.header();
+        for slot in &self.slots {
+            out.extend_from_slice(&slot.salt);
+            out.extend_from_slice(&slot.nonce);
+            out.extend_from_slice(&slot.body);
+        }
+        out
+    }
+
+    fn parse(bytes: &[u8]) -> Result<Container, BVaultError> {
+        let mut reader = Reader::new(bytes);
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err(invalid("not a bvault hidden container"));
+        }
+        check_version("hidden container", reader.u8()?, VERSION)?;
+        let cipher = cipher_from_id(reader.u8()?)?;
+        let kdf = read_kdf(&mut reader)?.ok_or_else(|| invalid("container has no KDF"))?;
+        let salt_length = reader.u8()? as usize;
+        let capacity = reader.u32()? as usize;
+        if capacity > MAX_CAPACITY {
+            return Err(invalid("container capacity is too large"));
+        }
+
+        let body_length = LENGTH_PREFIX + capacity + cipher.tag_length();
+        let mut read_slot = || -> Result<Slot, BVaultError> {
+            Ok(Slot {
+                salt: reader.take(salt_length)?.to_vec(),
+                nonce: reader.take(cipher.nonce_length())?.to_vec(),
+                body: reader.take(body_length)?.to_vec(),
+            })
+        };
+        let slots = [read_slot()?, read_slot()?];
+        if !reader.rest().is_empty() {
+            return Err(invalid("trailing bytes after container"));
+        }
+
+        Ok(Container {
+            cipher,
+            kdf,
+            salt_length,
+            capacity,
+            slots,
+        })
+    }
+
+    /// The associated data slot `index` is authenticated with.
+    fn associated_data(&self, index: usize) -> Vec<u8> {
+        let mut out = self.header();
+        out.push(index as u8);
+        out
+    }
+
+    /// Seals `plaintext` into slot `index` under `key`, with `salt`.
+    fn seal_slot(
+        &mut self,
+        index: usize,
+        salt: Vec<u8>,
+        key: &Key,
+        plaintext: &[u8],
+    ) -> Result<(), BVaultError> {
+        if plaintext.len() > self.capacity {
+            return Err(BVaultError::new(
+                ErrorCode::BadLength,
+                format!(
+                    "plaintext must be at most {} bytes for this container",
+                    self.capacity
+                ),
+            ));
+        }
+        let mut framed = Zeroizing::new(Vec::with_capacity(LENGTH_PREFIX + self.capacity));
+        framed.extend_from_slice(&(plaintext.len() as u32).to_be_bytes());
+        framed.extend_from_slice(plaintext);
+        framed.resize(LENGTH_PREFIX + self.capacity, 0);
+
+        let nonce = random_bytes(self.cipher.nonce_length())?;
+        let aad = self.associated_data(index);
+        let body = self
+            .cipher
+            .encrypt_with_aad(&key[..], &nonce, &framed, &aad)?;
+        self.slots[index] = Slot { salt, nonce, body };
+        Ok(())
+    }
+
+    /// Attempts to open slot `index` under `password`.
+    fn open_slot(&self, index: usize, password: &str) -> Result<Vec<u8>, BVaultError> {
+        let slot = &self.slots[index];
+        crate::policy::check_decrypt(password, slot.salt.len(), &self.kdf);
+        let key = self.kdf.derive(password.as_bytes(), &slot.salt)?;
+        let framed = Zeroizing::new(self.cipher.decrypt_with_aad(
+            &key[..],
+            &slot.nonce,
+            &slot.body,
+            &self.associated_data(index),
+        )?);
+
+        let (prefix, rest) = framed.split_at(LENGTH_PREFIX);
+        let length = u32::from_be_bytes([prefix[0], prefix[1], prefix[2], prefix[3]]) as usize;
+        if length > rest.len() {
+            return Err(invalid("malformed slot length"));
+        }
+        Ok(rest[..length].to_vec())
+    }
+
+    /// The index of the slot `password` opens, and its plaintext. Both
+    /// slots are always tried.
+    fn open(&self, password: &str) -> Result<(usize, Vec<u8>), BVaultError> {
+        let first = self.open_slot(0, password);
+        let second = self.open_slot(1, password);
+        match (first, second) {
+            (Ok(plaintext), _) => Ok((0, plaintext)),
+            (_, Ok(plaintext)) => Ok((1, plaintext)),
+            (Err(err), Err(_)) => Err(err),
+        }
+    }
+}
+
+fn check_capacity(capacity: usize) -> Result<(), BVaultError> {
+    if capacity == 0 || capacity > MAX_CAPACITY {
+        return Err(BVaultError::new(
+            ErrorCode::BadLength,
+            "capacity must be between 1 byte and 64 MiB",
+        ));
+    }
+    Ok(())
+}
+
+/// Random bytes that look exactly like a sealed slot.
+fn filler(salt_length: usize, cipher: Cipher, capacity: usize) -> Result<Slot, BVaultError> {
+    Ok(Slot {
+        salt: random_bytes(salt_length)?,
+        nonce: random_bytes(cipher.nonce_length())?,
+        body: random_bytes(LENGTH_PREFIX + capacity + cipher.tag_length())?,
+    })
+}
+
+/// Creates a hidden container whose outer slot holds `outer` under
+/// `outer_password` and whose other slot holds `hidden` under
+/// `hidden_password`, or random filler when both are omitted.
+///
+/// Both slots hold up to `capacity` bytes; by default the next power of
+/// two that fits the larger plaintext, and at least 4 KiB. The cipher, KDF
+/// and salt length come from `options`; compression, padding, key checks
+/// and associated data are not used.
+///
+/// # Errors
+///
+/// - If only one of `hidden` and `hidden_password` is given, or both
+///   passwords are the same, an [`ErrorCode::InvalidArgument`] error is
+///   returned.
+/// - If a plaintext exceeds the capacity, or the capacity is 0 or above
+///   64 MiB, an [`ErrorCode::BadLength`] error is returned.
+/// - Otherwise see [`crate::seal`].
+#[wasm_bindgen]
+pub fn seal_hidden(
+    outer: &[u8],
+    outer_password: &str,
+    hidden: Option<Vec<u8>>,
+    hidden_password: Option<String>,
+    capacity: Option<usize>,
+    options: Option<SealOptions>,
+) -> Result<Vec<u8>, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    let hidden = Zeroizing::new(hidden);
+    let hidden = match (hidden.as_deref(), hidden_password.as_deref()) {
+        (Some(_), Some(password)) if password == outer_password => {
+            return Err(BVaultError::new(
+                ErrorCode::InvalidArgument,
+                "the hidden password must differ from the outer one",
+            ))
+        }
+        (Some(plaintext), Some(password)) => Some((plaintext, password)),
+        (None, None) => None,
+        _ => {
+            return Err(BVaultError::new(
+                ErrorCode::InvalidArgument,
+                "hidden and hidden_password must be given together",
+            ))
+        }
+    };
+    let options = options.unwrap_or_default();
+    check_salt_length(options.salt_length())?;
+    let capacity = capacity.unwrap_or_else(|| {
+        let largest = outer
+            .len()
+            .max(hidden.map_or(0, |(plaintext, _)| plaintext.len()));
+        largest
+            .checked_next_power_of_two()
+            .unwrap_or(usize::MAX)
+            .max(MIN_DEFAULT_CAPACITY)
+    });
+    check_capacity(capacity)?;
+
+    let cipher = options.cipher();
+    let kdf = options.kdf();
+    let salt_length = options.salt_length();
+    let mut container = Container {
+        cipher,
+        kdf: kdf.clone(),
+        salt_length,
+        capacity,
+        slots: [
+            filler(salt_length, cipher, capacity)?,
+            filler(salt_length, cipher, capacity)?,
+        ],
+    };
+
+    let outer_index = (random_bytes(1)?[0] & 1) as usize;
+    let (salt, key) = new_salted_key(outer_password, salt_length, &kdf, Some(cipher))?;
+    container.seal_slot(outer_index, salt, &key, outer)?;
+    if let Some((plaintext, password)) = hidden {
+        let (salt, key) = new_salted_key(password, salt_length, &kdf, Some(cipher))?;
+        container.seal_slot(1 - outer_index, salt, &key, plaintext)?;
+    }
+    Ok(container.to_bytes())
+}
+
+/// Opens whichever slot of a hidden container `password` unlocks.
+///
+/// # Errors
+///
+/// - If the container is malformed or uses an unknown version, cipher or
+///   KDF, an error is returned.
+/// - If the password opens neither slot, an [`ErrorCode::AuthFailed`]
+///   error is returned.
+#[wasm_bindgen]
+pub fn open_hidden(container: &[u8], password: &str) -> Result<Vec<u8>, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    let (_, plaintext) = Container::parse(container)?.open(password)?;
+    Ok(plaintext)
+}
+
+/// Replaces the contents of the slot `password` unlocks with `plaintext`,
+/// under a fresh salt and nonce, and leaves the other slot untouched.
+///
+/// Neither the other password nor whether the other slot is in use needs
+/// to be known.
+///
+/// # Errors
+///
+/// - If `plaintext` exceeds the container's capacity, an
+///   [`ErrorCode::BadLength`] error is returned.
+/// - Otherwise see [`open_hidden`] and [`crate::seal`].
+#[wasm_bindgen]
+pub fn reseal_hidden(
+    container: &[u8],
+    password: &str,
+    plaintext: &[u8],
+) -> Result<Vec<u8>, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    let mut container = Container::parse(container)?;
+    let (index, old) = container.open(password)?;
+    drop(Zeroizing::new(old));
+
+    let kdf = container.kdf.clone();
+    let (salt, key) = new_salted_key(
+        password,
+        container.salt_length,
+        &kdf,
+        Some(container.cipher),
+    )?;
+    container.seal_slot(index, salt, &key, plaintext)?;
+    Ok(container.to_bytes())
+}
@@ -0,0 +1,190 @@
+//! HPKE (RFC 9180) base mode, single-shot, for the standards-based sharing
+//! protocol the server speaks.
+//!
+//! Only one suite is implemented: DHKEM(X25519, HKDF-SHA256) (KEM 0x0020),
+//! HKDF-SHA256 (KDF 0x0001) and ChaCha20-Poly1305 (AEAD 0x0003). Key pairs
+//! are ordinary X25519 keys, see [`crate::generate_x25519_keypair`].
+//!
+//! [`hpke_seal`] returns the encapsulated key `enc` and the ciphertext
+//! separately, as other HPKE implementations expect them; each seal uses a
+//! fresh context, so the ciphertext is always sequence number 0.
+
+use hkdf::Hkdf;
+use sha2::Sha256;
+use wasm_bindgen::prelude::*;
+use x25519_dalek::PublicKey;
+use zeroize::Zeroizing;
+
+use crate::kdf::{Key, KEY_LENGTH};
+use crate::x25519::{diffie_hellman, public_key, secret_key};
+use crate::{random_bytes, BVaultError, Cipher};
+
+const MODE_BASE: u8 = 0x00;
+const KEM_ID: u16 = 0x0020;
+const KDF_ID: u16 = 0x0001;
+const AEAD_ID: u16 = 0x0003;
+
+const NONCE_LENGTH: usize = 12;
+
+/// `"KEM" || I2OSP(kem_id, 2)`.
+fn kem_suite_id() -> Vec<u8> {
+    let mut id = b"KEM".to_vec();
+    id.extend_from_slice(&KEM_ID.to_be_bytes());
+    id
+}
+
+/// `"HPKE" || I2OSP(kem_id, 2) || I2OSP(kdf_id, 2) || I2OSP(aead_id, 2)`.
+fn hpke_suite_id() -> Vec<u8> {
+    let mut id = b"HPKE".to_vec();
+    id.extend_from_slice(&KEM_ID.to_be_bytes());
+    id.extend_from_slice(&KDF_ID.to_be_bytes());
+    id.extend_from_slice(&AEAD_ID.to_be_bytes());
+    id
+}
+
+/// `LabeledExtract`, returning the PRK and an HKDF instance keyed with it.
+fn labeled_extract(
+    suite_id: &[u8],
+    salt: &[u8],
+    label: &[u8],
+    ikm: &[u8],
+) -> (Zeroizing<[u8; 32]>, Hkdf<Sha256>) {
+    let mut labeled = Zeroizing::new(b"HPKE-v1".to_vec());
+    labeled.extend_from_slice(suite_id);
+    labeled.extend_from_slice(label);
+    labeled.extend_from_slice(ikm);
+    let (prk, hkdf) = Hkdf::<Sha256>::extract(Some(salt), &labeled);
+    (Zeroizing::new(prk.into()), hkdf)
+}
+
+fn labeled_expand(suite_id: &[u8], prk: &Hkdf<Sha256>, label: &[u8], info: &[u8], out: &mut [u8]) {
+    let mut labeled = (out.len() as u16).to_be_bytes().to_vec();
+    labeled.extend_from_slice(b"HPKE-v1");
+    labeled.extend_from_slice(suite_id);
+    labeled.extend_from_slice(label);
+    labeled.extend_from_slice(info);
+    // Every output here is at most 32 bytes, well within HKDF's limit.
+    prk.expand(&labeled, out).expect("valid HKDF length");
+}
+
+/// The KEM shared secret for DH output `dh`, `enc` and the recipient key.
+fn extract_and_expand(dh: &[u8], enc: &[u8], recipient: &PublicKey) -> Key {
+    let suite_id = kem_suite_id();
+    let (_, prk) = labeled_extract(&suite_id, b"", b"eae_prk", dh);
+    let mut context = enc.to_vec();
+    context.extend_from_slice(recipient.as_bytes());
+    let mut shared_secret = Key::default();
+    labeled_expand(
+        &suite_id,
+        &prk,
+        b"shared_secret",
+        &context,
+        &mut shared_secret[..],
+    );
+    shared_secret
+}
+
+/// The base-mode key schedule: the AEAD key and base nonce.
+fn key_schedule(shared_secret: &[u8], info: &[u8]) -> (Key, [u8; NONCE_LENGTH]) {
+    let suite_id = hpke_suite_id();
+    let (psk_id_hash, _) = labeled_extract(&suite_id, b"", b"psk_id_hash", b"");
+    let (info_hash, _) = labeled_extract(&suite_id, b"", b"info_hash", info);
+    let mut context = vec![MODE_BASE];
+    context.extend_from_slice(&psk_id_hash[..]);
+    context.extend_from_slice(&info_hash[..]);
+
+    let (_, secret) = labeled_extract(&suite_id, shared_secret, b"secret", b"");
+    let mut key = Key::default();
+    labeled_expand(&suite_id, &secret, b"key", &context, &mut key[..]);
+    let mut base_nonce = [0u8; NONCE_LENGTH];
+    labeled_expand(&suite_id, &secret, b"base_nonce", &context, &mut base_nonce);
+    (key, base_nonce)
+}
+
+/// The output of [`hpke_seal`].
+#[wasm_bindgen]
+#[derive(Clone, Debug)]
+pub struct HpkeSealed {
+    enc: Vec<u8>,
+    ciphertext: Vec<u8>,
+}
+
+#[wasm_bindgen]
+impl HpkeSealed {
+    /// The 32-byte encapsulated key, sent alongside the ciphertext.
+    #[wasm_bindgen(getter)]
+    pub fn enc(&self) -> Vec<u8> {
+        self.enc.clone()
+    }
+
+    /// The ciphertext with the Poly1305 tag appended.
+    #[wasm_bindgen(getter)]
+    pub fn ciphertext(&self) -> Vec<u8> {
+        self.ciphertext.clone()
+    }
+}
+
+/// HPKE `SealBase`: encrypts `plaintext` to an X25519 public key, binding
+/// the optional `info` (context) and `aad`.
+///
+/// # Errors
+///
+/// - If the key is not 32 bytes or is a low-order point, an error is
+///   returned.
+/// - If the random number generator is unavailable, an error is returned.
+#[wasm_bindgen]
+pub fn hpke_seal(
+    recipient_public_key: &[u8],
+    info: Option<Vec<u8>>,
+    aad: Option<Vec<u8>>,
+    plaintext: &[u8],
+) -> Result<HpkeSealed, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    let recipient = public_key(recipient_public_key)?;
+    let ephemeral_bytes = Zeroizing::new(random_bytes(KEY_LENGTH)?);
+    let ephemeral = secret_key(&ephemeral_bytes)?;
+    let enc = PublicKey::from(&ephemeral).to_bytes().to_vec();
+
+    let dh = diffie_hellman(&ephemeral, &recipient)?;
+    let shared_secret = extract_and_expand(&dh[..], &enc, &recipient);
+    let (key, nonce) = key_schedule(&shared_secret[..], &info.unwrap_or_default());
+    let ciphertext = Cipher::ChaCha20Poly1305.encrypt_with_aad(
+        &key[..],
+        &nonce,
+        plaintext,
+        &aad.unwrap_or_default(),
+    )?;
+    Ok(HpkeSealed { enc, ciphertext })
+}
+
+/// HPKE `OpenBase`: decrypts a ciphertext from any RFC 9180 implementation
+/// using this suite, given its `enc` and the same `info` and `aad`.
+///
+/// # Errors
+///
+/// - If a key or `enc` is not 32 bytes, or `enc` is a low-order point, an
+///   error is returned.
+/// - If the key, `info` or `aad` is wrong, or the ciphertext was tampered
+///   with, an [`crate::ErrorCode::AuthFailed`] error is returned.
+#[wasm_bindgen]
+pub fn hpke_open(
+    enc: &[u8],
+    ciphertext: &[u8],
+    recipient_secret_key: &[u8],
+    info: Option<Vec<u8>>,
+    aad: Option<Vec<u8>>,
+) -> Result<Vec<u8>, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    let recipient = secret_key(recipient_secret_key)?;
+    let ephemeral = public_key(enc)?;
+
+    let dh = diffie_hellman(&recipient, &ephemeral)?;
+    let shared_secret = extract_and_expand(&dh[..], enc, &PublicKey::from(&recipient));
+    let (key, nonce) = key_schedule(&shared_secret[..], &info.unwrap_or_default());
+    Cipher::ChaCha20Poly1305.decrypt_with_aad(
+        &key[..],
+        &nonce,
+        ciphertext,
+        &aad.unwrap_or_default(),
+    )
+}
@@ -0,0 +1,164 @@
+//! Checking a vault blob for corruption or tampering without unlocking it
+//! or producing any plaintext, for sync code that wants to reject a bad
+//! download before prompting for the password.
+//!
+//! Vault entries are authenticated by their AEAD tags, which only the
+//! master key can check, so there are two levels:
+//!
+//! - [`verify_vault_integrity`] needs no key. It parses the whole
+//!   container (header, KDF, salt, entry table, trailing bytes) and checks
+//!   every entry's nonce and body lengths. This catches truncation and most accidental
+//!   damage, but anyone able to rewrite the blob can pass it; vaults
+//!   signed with [`crate::sign_vault`] can be checked against tampering
+//!   without a key by [`crate::verify_vault`].
+//! - [`Vault::verify_blob`] uses the key of a vault that is already
+//!   unlocked (such as the local copy of the one being synced) to check
+//!   every entry's tag as well, which detects any modification. Decrypted
+//!   bytes are wiped at once and never returned.
+
+use std::collections::BTreeMap;
+
+use wasm_bindgen::prelude::*;
+use zeroize::Zeroizing;
+
+use crate::vault::Locked;
+use crate::{BVaultError, Cipher, ErrorCode, Vault};
+
+/// The result of [`verify_vault_integrity`] or [`Vault::verify_blob`].
+#[wasm_bindgen]
+#[derive(Clone, Debug)]
+pub struct VaultIntegrityReport {
+    authenticated: bool,
+    /// The failure of each entry that failed; `None` for those that passed.
+    entries: BTreeMap<String, Option<String>>,
+}
+
+#[wasm_bindgen]
+impl VaultIntegrityReport {
+    /// Whether every entry passed.
+    #[wasm_bindgen(getter)]
+    pub fn ok(&self) -> bool {
+        self.entries.values().all(Option::is_none)
+    }
+
+    /// Whether the entries' tags were checked, so a passing entry is known
+    /// to be unmodified; `false` for [`verify_vault_integrity`].
+    #[wasm_bindgen(getter)]
+    pub fn authenticated(&self) -> bool {
+        self.authenticated
+    }
+
+    /// IDs of the entries that passed, in order.
+    #[wasm_bindgen(getter)]
+    pub fn passed(&self) -> Vec<String> {
+        self.ids(false)
+    }
+
+    /// IDs of the entries that failed, in order.
+    #[wasm_bindgen(getter)]
+    pub fn failed(&self) -> Vec<String> {
+        self.ids(true)
+    }
+
+    /// Why the entry `id` failed, or `undefined` if it passed or does not
+    /// exist.
+    pub fn failure(&self, id: &str) -> Option<String> {
+        self.entries.get(id).cloned().flatten()
+    }
+}
+
+impl VaultIntegrityReport {
+    fn ids(&self, failed: bool) -> Vec<String> {
+        self.entries
+            .iter()
+            .filter(|(_, failure)| failure.is_some() == failed)
+            .map(|(id, _)| id.clone())
+            .collect()
+    }
+}
+
+fn check_length(cipher: Cipher, body: &[u8]) -> Option<String> {
+    if body.len() < cipher.tag_length() {
+        Some("entry is shorter than its authentication tag".to_string())
+    } else {
+        None
+    }
+}
+
+/// Checks the structure of the vault blob `blob` (binary or CBOR) and of
+/// each of its entries, without a key; see the module documentation for
+/// what this can and cannot detect.
+///
+/// # Errors
+///
+/// - If the container itself is malformed, truncated or uses an unknown
+///   version, cipher or KDF, an error is returned instead of a report.
+#[wasm_bindgen]
+pub fn verify_vault_integrity(blob: &[u8]) -> Result<VaultIntegrityReport, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    let locked = Locked::parse(blob)?;
+    Ok(VaultIntegrityReport {
+        authenticated: false,
+        entries: locked
+            .entries
+            .iter()
+            .map(|(id, entry)| {
+                let failure = check_length(locked.cipher, &entry.body).or_else(|| {
+                    entry
+                        .metadata
+                        .as_ref()
+                        .and_then(|metadata| check_length(locked.cipher, &metadata.body))
+                });
+                (id.clone(), failure)
+            })
+            .collect(),
+    })
+}
+
+#[wasm_bindgen]
+impl Vault {
+    /// Checks every entry of the vault blob `blob` against its tag, using
+    /// this vault's key, without returning any plaintext. `blob` must be a
+    /// copy of this vault (same master key), possibly with other entries,
+    /// as when a synced copy is downloaded.
+    ///
+    /// # Errors
+    ///
+    /// - If the container itself is malformed, an error is returned; see
+    ///   [`verify_vault_integrity`].
+    /// - If `blob` is not under this vault's cipher, salt and key, an
+    ///   [`ErrorCode::SessionMismatch`] error is returned.
+    pub fn verify_blob(&self, blob: &[u8]) -> Result<VaultIntegrityReport, BVaultError> {
+        let _operation = crate::utils::set_panic_hook();
+        let theirs = Locked::parse(blob)?;
+        let ours = self.locked()?;
+        if theirs.cipher != ours.cipher
+            || theirs.salt != ours.salt
+            || !crate::constant_time_eq(&theirs.kcv, &ours.kcv)
+        {
+            return Err(BVaultError::new(
+                ErrorCode::SessionMismatch,
+                "the blob is not a copy of this vault",
+            ));
+        }
+        let entries = theirs
+            .entries
+            .iter()
+            .map(|(id, entry)| {
+                let failure = check_length(theirs.cipher, &entry.body).or_else(|| {
+                    self.open_entry(id, entry)
+                        .map(Zeroizing::new)
+                        .and_then(|_| self.open_entry_metadata(id, entry))
+                        .map(|metadata| metadata.map(Zeroizing::new))
+                        .err()
+                        .map(|err| err.message())
+                });
+                (id.clone(), failure)
+            })
+            .collect();
+        Ok(VaultIntegrityReport {
+            authenticated: true,
+            entries,
+        })
+    }
+}
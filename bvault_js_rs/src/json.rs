@@ -0,0 +1,314 @@
+//! JSON import/export of vault containers, for debugging and portability.
+//!
+//! The JSON form carries exactly what the binary form does, with byte
+//! strings as standard base64:
+//!
+//! ```text
+//! {
+//!   "format": "bvault-vault",
+//!   "version": 3,
+//!   "cipher": "aes-256-gcm" | "chacha20-poly1305" | "xchacha20-poly1305"
+//!           | "aes-256-gcm-siv" | "aes-256-gcm+xchacha20-poly1305",
+//!   "kdf": { "algorithm": "pbkdf2-sha256", "iterations": 100000 }
+//!        | { "algorithm": "pbkdf2-sha512", "iterations": … }
+//!        | { "algorithm": "argon2id", "memory_kib": …, "iterations": …, "parallelism": … }
+//!        | { "algorithm": "scrypt", "log_n": …, "r": …, "p": … },
+//!   "salt": "<base64>",
+//!   "kcv": "<base64, 8 bytes>",
+//!   "entries": [ { "id": "…", "modified": 1700000000000,
+//!                  "nonce": "<base64>", "body": "<base64>",
+//!                  "metadata_nonce": "<base64>", "metadata": "<base64>" } ]
+//! }
+//! ```
+//!
+//! JSON versions follow vault versions: version 1 exports come from vaults
+//! without entry timestamps and have no `modified` fields, which versions 2
+//! and 3 require. Only version 3 has the optional `metadata_nonce` and
+//! `metadata` fields, which go together.
+//!
+//! Import is strict: unknown fields, wrong types, missing fields and
+//! out-of-range values are all rejected with an [`ErrorCode::InvalidJson`]
+//! error whose message starts with the path of the offending field.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+
+use crate::envelope::{check_nonce_length, check_salt_length};
+use crate::kcv::KCV_LENGTH;
+use crate::kdf::{Kdf, Pbkdf2Hash};
+use crate::vault::{Locked, SealedEntry, SealedMetadata, VERSION, VERSION_UNTIMED};
+use crate::{b64_to_bytes, bytes_to_b64, BVaultError, Cipher, ErrorCode, KdfParams, Vault};
+
+const FORMAT: &str = "bvault-vault";
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct VaultJson {
+    format: String,
+    version: u8,
+    cipher: CipherJson,
+    kdf: KdfJson,
+    salt: String,
+    kcv: String,
+    entries: Vec<EntryJson>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) enum CipherJson {
+    #[serde(rename = "aes-256-gcm")]
+    Aes256Gcm,
+    #[serde(rename = "chacha20-poly1305")]
+    ChaCha20Poly1305,
+    #[serde(rename = "xchacha20-poly1305")]
+    XChaCha20Poly1305,
+    #[serde(rename = "aes-256-gcm-siv")]
+    Aes256GcmSiv,
+    #[serde(rename = "aes-256-gcm+xchacha20-poly1305")]
+    Cascade,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "algorithm", deny_unknown_fields)]
+pub(crate) enum KdfJson {
+    #[serde(rename = "pbkdf2-sha256")]
+    Pbkdf2 { iterations: u32 },
+    #[serde(rename = "pbkdf2-sha512")]
+    Pbkdf2Sha512 { iterations: u32 },
+    #[serde(rename = "argon2id")]
+    Argon2id {
+        memory_kib: u32,
+        iterations: u32,
+        parallelism: u32,
+    },
+    #[serde(rename = "scrypt")]
+    Scrypt { log_n: u8, r: u32, p: u32 },
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct EntryJson {
+    id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    modified: Option<u64>,
+    nonce: String,
+    body: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    metadata_nonce: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    metadata: Option<String>,
+}
+
+impl From<Cipher> for CipherJson {
+    fn from(cipher: Cipher) -> CipherJson {
+        match cipher {
+            Cipher::Aes256Gcm => CipherJson::Aes256Gcm,
+            Cipher::ChaCha20Poly1305 => CipherJson::ChaCha20Poly1305,
+            Cipher::XChaCha20Poly1305 => CipherJson::XChaCha20Poly1305,
+            Cipher::Aes256GcmSiv => CipherJson::Aes256GcmSiv,
+            Cipher::Cascade => CipherJson::Cascade,
+        }
+    }
+}
+
+impl From<CipherJson> for Cipher {
+    fn from(cipher: CipherJson) -> Cipher {
+        match cipher {
+            CipherJson::Aes256Gcm => Cipher::Aes256Gcm,
+            CipherJson::ChaCha20Poly1305 => Cipher::ChaCha20Poly1305,
+            CipherJson::XChaCha20Poly1305 => Cipher::XChaCha20Poly1305,
+            CipherJson::Aes256GcmSiv => Cipher::Aes256GcmSiv,
+            CipherJson::Cascade => Cipher::Cascade,
+        }
+    }
+}
+
+impl From<&KdfParams> for KdfJson {
+    fn from(kdf: &KdfParams) -> KdfJson {
+        match kdf.kdf() {
+            Kdf::Pbkdf2 {
+                iterations,
+                hash: Pbkdf2Hash::Sha256,
+            } => KdfJson::Pbkdf2 { iterations },
+            Kdf::Pbkdf2 {
+                iterations,
+                hash: Pbkdf2Hash::Sha512,
+            } => KdfJson::Pbkdf2Sha512 { iterations },
+            Kdf::Argon2id {
+                memory_kib,
+                iterations,
+                parallelism,
+            } => KdfJson::Argon2id {
+                memory_kib,
+                iterations,
+                parallelism,
+            },
+            Kdf::Scrypt { log_n, r, p } => KdfJson::Scrypt { log_n, r, p },
+        }
+    }
+}
+
+impl From<KdfJson> for KdfParams {
+    fn from(kdf: KdfJson) -> KdfParams {
+        match kdf {
+            KdfJson::Pbkdf2 { iterations } => KdfParams::pbkdf2(iterations),
+            KdfJson::Pbkdf2Sha512 { iterations } => KdfParams::pbkdf2_sha512(iterations),
+            KdfJson::Argon2id {
+                memory_kib,
+                iterations,
+                parallelism,
+            } => KdfParams::argon2id(Some(memory_kib), Some(iterations), Some(parallelism)),
+            KdfJson::Scrypt { log_n, r, p } => KdfParams::scrypt(Some(log_n), Some(r), Some(p)),
+        }
+    }
+}
+
+/// Exports a vault, entries still encrypted, as JSON.
+///
+/// # Errors
+///
+/// - If the key check value cannot be computed, an error is returned.
+#[wasm_bindgen]
+pub fn export_json(vault: &Vault) -> Result<String, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    let locked = vault.locked()?;
+    let json = VaultJson {
+        format: FORMAT.to_string(),
+        version: locked.version,
+        cipher: locked.cipher.into(),
+        kdf: (&locked.kdf).into(),
+        salt: bytes_to_b64(&locked.salt),
+        kcv: bytes_to_b64(&locked.kcv),
+        entries: locked
+            .entries
+            .iter()
+            .map(|(id, entry)| EntryJson {
+                id: id.clone(),
+                modified: Some(entry.modified).filter(|_| locked.version != VERSION_UNTIMED),
+                nonce: bytes_to_b64(&entry.nonce),
+                body: bytes_to_b64(&entry.body),
+                metadata_nonce: entry
+                    .metadata
+                    .as_ref()
+                    .map(|metadata| bytes_to_b64(&metadata.nonce)),
+                metadata: entry
+                    .metadata
+                    .as_ref()
+                    .map(|metadata| bytes_to_b64(&metadata.body)),
+            })
+            .collect(),
+    };
+    serde_json::to_string_pretty(&json)
+        .map_err(|e| BVaultError::new(ErrorCode::InvalidJson, e.to_string()))
+}
+
+/// Validates a JSON export and converts it back into a binary vault blob
+/// for [`Vault::unlock`].
+///
+/// # Errors
+///
+/// - If the JSON does not match the schema, an [`ErrorCode::InvalidJson`]
+///   error naming the offending field is returned.
+/// - If the KDF parameters are below the accepted minimum, an error is
+///   returned.
+#[wasm_bindgen]
+pub fn import_json(json: &str) -> Result<Vec<u8>, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    let deserializer = &mut serde_json::Deserializer::from_str(json);
+    let json: VaultJson = serde_path_to_error::deserialize(deserializer).map_err(|e| {
+        let path = e.path().to_string();
+        invalid_json(&path, e.into_inner().to_string())
+    })?;
+
+    if json.format != FORMAT {
+        return Err(invalid_json("format", format!("expected \"{}\"", FORMAT)));
+    }
+    if json.version == 0 || json.version > VERSION {
+        return Err(BVaultError::new(
+            ErrorCode::UnsupportedVersion,
+            format!("unsupported vault version {}", json.version),
+        ));
+    }
+
+    let cipher = Cipher::from(json.cipher);
+    let kdf = KdfParams::from(json.kdf);
+    kdf.validate()?;
+
+    let salt = decode_field("salt", &json.salt)?;
+    check_salt_length(salt.len()).map_err(|e| invalid_json("salt", e.message()))?;
+    let kcv = decode_field("kcv", &json.kcv)?;
+    if kcv.len() != KCV_LENGTH {
+        return Err(invalid_json("kcv", "must be 8 bytes"));
+    }
+
+    let mut entries = BTreeMap::new();
+    for (i, entry) in json.entries.into_iter().enumerate() {
+        let path = |field: &str| format!("entries[{}].{}", i, field);
+        if entry.id.is_empty() || entry.id.len() > u8::MAX as usize {
+            return Err(invalid_json(&path("id"), "must be 1 to 255 bytes"));
+        }
+        let nonce = decode_field(&path("nonce"), &entry.nonce)?;
+        check_nonce_length(cipher, &nonce)
+            .map_err(|e| invalid_json(&path("nonce"), e.message()))?;
+        let body = decode_field(&path("body"), &entry.body)?;
+        let modified = match (json.version, entry.modified) {
+            (VERSION_UNTIMED, None) => 0,
+            (VERSION_UNTIMED, Some(_)) => {
+                return Err(invalid_json(&path("modified"), "not allowed in version 1"))
+            }
+            (_, Some(modified)) => modified,
+            (_, None) => return Err(invalid_json(&path("modified"), "missing field")),
+        };
+        let metadata = match (json.version, entry.metadata_nonce, entry.metadata) {
+            (_, None, None) => None,
+            (VERSION, Some(metadata_nonce), Some(metadata)) => {
+                let nonce = decode_field(&path("metadata_nonce"), &metadata_nonce)?;
+                check_nonce_length(cipher, &nonce)
+                    .map_err(|e| invalid_json(&path("metadata_nonce"), e.message()))?;
+                let body = decode_field(&path("metadata"), &metadata)?;
+                Some(SealedMetadata { nonce, body })
+            }
+            (VERSION, _, _) => {
+                return Err(invalid_json(
+                    &path("metadata"),
+                    "metadata and metadata_nonce go together",
+                ))
+            }
+            _ => {
+                return Err(invalid_json(
+                    &path("metadata"),
+                    format!("not allowed in version {}", json.version),
+                ))
+            }
+        };
+        let entry_id = entry.id;
+        let entry = SealedEntry {
+            modified,
+            nonce,
+            body,
+            metadata,
+        };
+        if entries.insert(entry_id, entry).is_some() {
+            return Err(invalid_json(&path("id"), "duplicate entry ID"));
+        }
+    }
+
+    Ok(Locked {
+        version: json.version,
+        cipher,
+        kdf,
+        salt,
+        kcv,
+        entries,
+    }
+    .to_bytes())
+}
+
+fn decode_field(path: &str, b64: &str) -> Result<Vec<u8>, BVaultError> {
+    b64_to_bytes(b64).map_err(|e| invalid_json(path, e.message()))
+}
+
+fn invalid_json(path: &str, message: impl std::fmt::Display) -> BVaultError {
+    BVaultError::new(ErrorCode::InvalidJson, format!("{}: {}", path, message))
+}
@@ -0,0 +1,202 @@
+//! JWE compact serialization (RFC 7516) with password-based key wrapping.
+//!
+//! Only the combination the backend uses is supported: `alg`
+//! `PBES2-HS256+A128KW` (RFC 7518 §4.8) and `enc` `A256GCM`. A token is
+//!
+//! ```text
+//! BASE64URL(header).BASE64URL(wrapped CEK).BASE64URL(iv).BASE64URL(ciphertext).BASE64URL(tag)
+//! ```
+//!
+//! The random content key is wrapped with AES-128 Key Wrap under
+//! PBKDF2-SHA256(password, "PBES2-HS256+A128KW" || 0x00 || p2s, p2c), and
+//! the encoded header is the AAD for AES-256-GCM.
+
+use aes_kw::KekAes128;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use wasm_bindgen::prelude::*;
+use zeroize::Zeroizing;
+
+use crate::envelope::invalid;
+use crate::kcv::wrong_password;
+use crate::kdf::KEY_LENGTH;
+use crate::{random_bytes, BVaultError, Cipher, ErrorCode};
+
+const ALG: &str = "PBES2-HS256+A128KW";
+const ENC: &str = "A256GCM";
+
+const KEK_LENGTH: usize = 16;
+const SALT_LENGTH: usize = 16;
+const IV_LENGTH: usize = 12;
+const TAG_LENGTH: usize = 16;
+
+/// Default `p2c`, matching the crate's PBKDF2 default.
+const DEFAULT_ITERATIONS: u32 = 100_000;
+/// Smallest `p2c` RFC 7518 allows implementations to accept.
+const MIN_ITERATIONS: u32 = 1000;
+/// Largest `p2c` accepted, so a hostile token cannot stall the page.
+const MAX_ITERATIONS: u32 = 10_000_000;
+
+/// The protected header. Unknown members are ignored, as JOSE requires,
+/// except for `crit` and `zip`, which change how the token is processed.
+#[derive(Serialize, Deserialize)]
+struct JweHeader {
+    alg: String,
+    enc: String,
+    p2s: String,
+    p2c: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    crit: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    zip: Option<serde_json::Value>,
+}
+
+fn malformed(message: &str) -> BVaultError {
+    invalid(format!("malformed JWE: {}", message))
+}
+
+fn decode_part(part: &str) -> Result<Vec<u8>, BVaultError> {
+    URL_SAFE_NO_PAD
+        .decode(part)
+        .map_err(|_| BVaultError::new(ErrorCode::InvalidBase64, "invalid base64url in JWE"))
+}
+
+fn check_iterations(iterations: u32) -> Result<(), BVaultError> {
+    if !(MIN_ITERATIONS..=MAX_ITERATIONS).contains(&iterations) {
+        return Err(BVaultError::new(
+            ErrorCode::WeakKdfParams,
+            "p2c must be 1000 to 10 000 000",
+        ));
+    }
+    Ok(())
+}
+
+/// The key-encryption key for `password`, `p2s` and `p2c`.
+fn derive_kek(password: &str, p2s: &[u8], p2c: u32) -> KekAes128 {
+    let mut salt = ALG.as_bytes().to_vec();
+    salt.push(0);
+    salt.extend_from_slice(p2s);
+    let mut kek = Zeroizing::new([0u8; KEK_LENGTH]);
+    pbkdf2::pbkdf2_hmac::<Sha256>(password.as_bytes(), &salt, p2c, &mut kek[..]);
+    KekAes128::from(*kek)
+}
+
+/// Encrypts `plaintext` into a compact JWE (`PBES2-HS256+A128KW`,
+/// `A256GCM`) that standard JOSE libraries can decrypt with `password`.
+///
+/// `iterations` is the PBKDF2 count (`p2c`), default 100 000.
+///
+/// # Errors
+///
+/// - If `iterations` is outside 1000..=10 000 000, an error is returned.
+/// - If the random number generator is unavailable, an error is returned.
+#[wasm_bindgen]
+pub fn jwe_encrypt(
+    plaintext: &[u8],
+    password: &str,
+    iterations: Option<u32>,
+) -> Result<String, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    let p2c = iterations.unwrap_or(DEFAULT_ITERATIONS);
+    check_iterations(p2c)?;
+
+    let p2s = random_bytes(SALT_LENGTH)?;
+    let header = JweHeader {
+        alg: ALG.to_string(),
+        enc: ENC.to_string(),
+        p2s: URL_SAFE_NO_PAD.encode(&p2s),
+        p2c,
+        crit: None,
+        zip: None,
+    };
+    let header = URL_SAFE_NO_PAD.encode(
+        serde_json::to_vec(&header)
+            .map_err(|e| BVaultError::new(ErrorCode::InvalidJson, e.to_string()))?,
+    );
+
+    let cek = Zeroizing::new(random_bytes(KEY_LENGTH)?);
+    let wrapped = derive_kek(password, &p2s, p2c)
+        .wrap_vec(&cek)
+        .map_err(|_| BVaultError::new(ErrorCode::EncryptionFailed, "key wrap failed"))?;
+
+    let iv = random_bytes(IV_LENGTH)?;
+    let mut sealed = Cipher::Aes256Gcm.encrypt_with_aad(&cek, &iv, plaintext, header.as_bytes())?;
+    let tag = sealed.split_off(sealed.len() - TAG_LENGTH);
+
+    Ok([
+        header,
+        URL_SAFE_NO_PAD.encode(wrapped),
+        URL_SAFE_NO_PAD.encode(iv),
+        URL_SAFE_NO_PAD.encode(sealed),
+        URL_SAFE_NO_PAD.encode(tag),
+    ]
+    .join("."))
+}
+
+/// Decrypts a compact JWE produced with `PBES2-HS256+A128KW` and `A256GCM`.
+///
+/// # Errors
+///
+/// - If the token is malformed, an error is returned.
+/// - If the header names another algorithm, uses compression or critical
+///   extensions, an error is returned.
+/// - If `p2c` is outside 1000..=10 000 000, an error is returned.
+/// - If the password is wrong, an [`ErrorCode::WrongPassword`] error is
+///   returned.
+/// - If the content fails authentication, an error is returned.
+#[wasm_bindgen]
+pub fn jwe_decrypt(token: &str, password: &str) -> Result<Vec<u8>, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    let parts: Vec<&str> = token.trim().split('.').collect();
+    let (b64_header, wrapped, iv, ciphertext, tag) = match parts.as_slice() {
+        [header, wrapped, iv, ciphertext, tag] => (
+            *header,
+            decode_part(wrapped)?,
+            decode_part(iv)?,
+            decode_part(ciphertext)?,
+            decode_part(tag)?,
+        ),
+        _ => return Err(malformed("expected five dot-separated parts")),
+    };
+
+    let header: JweHeader = serde_json::from_slice(&decode_part(b64_header)?)
+        .map_err(|e| BVaultError::new(ErrorCode::InvalidJson, format!("JWE header: {}", e)))?;
+    if header.alg != ALG || header.enc != ENC {
+        return Err(BVaultError::new(
+            ErrorCode::UnsupportedAlgorithm,
+            format!(
+                "unsupported JWE algorithms {}/{}; expected {}/{}",
+                header.alg, header.enc, ALG, ENC
+            ),
+        ));
+    }
+    if header.crit.is_some() || header.zip.is_some() {
+        return Err(BVaultError::new(
+            ErrorCode::UnsupportedAlgorithm,
+            "JWE compression and critical extensions are not supported",
+        ));
+    }
+    check_iterations(header.p2c)?;
+    let p2s = decode_part(&header.p2s)?;
+    if p2s.len() < 8 {
+        return Err(malformed("p2s must be at least 8 bytes"));
+    }
+    if iv.len() != IV_LENGTH || tag.len() != TAG_LENGTH {
+        return Err(malformed("A256GCM needs a 12-byte IV and 16-byte tag"));
+    }
+
+    let cek = Zeroizing::new(
+        derive_kek(password, &p2s, header.p2c)
+            .unwrap_vec(&wrapped)
+            .map_err(|_| wrong_password())?,
+    );
+    if cek.len() != KEY_LENGTH {
+        return Err(malformed("A256GCM needs a 32-byte content key"));
+    }
+
+    let mut sealed = ciphertext;
+    sealed.extend_from_slice(&tag);
+    Cipher::Aes256Gcm.decrypt_with_aad(&cek, &iv, &sealed, b64_header.as_bytes())
+}
@@ -0,0 +1,128 @@
+//! Key check values: telling a wrong password apart from damaged data.
+//!
+//! A KCV is a short fingerprint of the derived key, stored next to the
+//! ciphertext. Deriving the key and comparing fingerprints answers "is this
+//! the right password?" before any decryption is attempted, so an
+//! authentication failure afterwards can only mean corruption or tampering.
+//!
+//! The KCV is the first 8 bytes of HKDF-SHA256(key, info = `bvault-kcv`).
+//! It reveals nothing about the key, and checking a guess still costs a full
+//! KDF run.
+//!
+//! A key fingerprint is the same idea for people: the first 4 bytes of
+//! HKDF-SHA256(key, info = `bvault-fingerprint`) as uppercase hex in two
+//! groups (`A1B2-C3D4`), for UIs to show which key a device uses and for
+//! sync code to notice two devices derived different keys.
+
+use subtle::ConstantTimeEq;
+use wasm_bindgen::prelude::*;
+
+use crate::kdf::hkdf_sha256;
+use crate::{b64_to_bytes, bytes_to_b64, derive_key, BVaultError, ErrorCode, KdfParams};
+
+/// HKDF label separating the KCV from every other use of the key.
+const KCV_INFO: &[u8] = b"bvault-kcv";
+
+/// Length of a key check value, in bytes.
+pub(crate) const KCV_LENGTH: usize = 8;
+
+/// HKDF label for key fingerprints.
+const FINGERPRINT_INFO: &[u8] = b"bvault-fingerprint";
+
+/// Length of a key fingerprint, in bytes.
+const FINGERPRINT_LENGTH: usize = 4;
+
+/// Computes the key check value of `key`.
+pub(crate) fn compute(key: &[u8]) -> Result<[u8; KCV_LENGTH], BVaultError> {
+    let mut kcv = [0u8; KCV_LENGTH];
+    hkdf_sha256(key, None, KCV_INFO, &mut kcv)?;
+    Ok(kcv)
+}
+
+/// Compares the KCV of `key` with `kcv` in constant time.
+pub(crate) fn matches(key: &[u8], kcv: &[u8]) -> Result<bool, BVaultError> {
+    Ok(compute(key)?.ct_eq(kcv).into())
+}
+
+/// Computes the displayable fingerprint of `key`.
+pub(crate) fn fingerprint(key: &[u8]) -> Result<String, BVaultError> {
+    let mut fingerprint = [0u8; FINGERPRINT_LENGTH];
+    hkdf_sha256(key, None, FINGERPRINT_INFO, &mut fingerprint)?;
+    let hex = crate::utils::to_hex(&fingerprint).to_uppercase();
+    Ok(format!("{}-{}", &hex[..4], &hex[4..]))
+}
+
+/// The error returned when a KCV does not match.
+pub(crate) fn wrong_password() -> BVaultError {
+    BVaultError::new(ErrorCode::WrongPassword, "wrong password")
+}
+
+/// Derives the key for `password` and returns its base64 key check value,
+/// for callers that store ciphertexts outside an envelope.
+///
+/// # Errors
+///
+/// - If the salt is invalid base64, an error is returned.
+/// - If the KDF parameters are below the accepted minimum, an error is
+///   returned.
+#[wasm_bindgen]
+pub fn key_check_value(
+    password: &str,
+    b64_salt: &str,
+    kdf: Option<KdfParams>,
+) -> Result<String, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    let key = derive_key(password, &b64_to_bytes(b64_salt)?, kdf)?;
+    compute(&key[..]).map(|kcv| bytes_to_b64(&kcv))
+}
+
+/// Checks `password` against a stored base64 key check value without
+/// decrypting anything.
+///
+/// Returns `false` for a wrong password. The comparison runs in constant
+/// time.
+///
+/// # Errors
+///
+/// - If the salt or KCV is invalid base64, an error is returned.
+/// - If the KCV is not 8 bytes, an error is returned.
+/// - If the KDF parameters are below the accepted minimum, an error is
+///   returned.
+#[wasm_bindgen]
+pub fn verify_password(
+    password: &str,
+    b64_salt: &str,
+    kdf: Option<KdfParams>,
+    b64_kcv: &str,
+) -> Result<bool, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+
+    let kcv = b64_to_bytes(b64_kcv)?;
+    if kcv.len() != KCV_LENGTH {
+        return Err(BVaultError::new(
+            ErrorCode::BadLength,
+            "key check value must be 8 bytes",
+        ));
+    }
+
+    let key = derive_key(password, &b64_to_bytes(b64_salt)?, kdf)?;
+    matches(&key[..], &kcv)
+}
+
+/// Derives the key for `password` and returns its fingerprint, such as
+/// `A1B2-C3D4`. The same password, salt and KDF parameters always give the
+/// same fingerprint; it cannot be turned back into the key.
+///
+/// # Errors
+///
+/// See [`key_check_value`].
+#[wasm_bindgen]
+pub fn key_fingerprint(
+    password: &str,
+    b64_salt: &str,
+    kdf: Option<KdfParams>,
+) -> Result<String, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    let key = derive_key(password, &b64_to_bytes(b64_salt)?, kdf)?;
+    fingerprint(&key[..])
+}
@@ -0,0 +1,815 @@
+//! Reading KeePass KDBX 4 databases, so a KeePass vault can be migrated
+//! without the file leaving the browser.
+//!
+//! All integers are little-endian:
+//!
+//! ```text
+//! signature  8      03 d9 a2 9a 67 fb 4b b5
+//! version    4      minor u16, major u16 (4)
+//! header     …      fields of id u8, length u32, data; id 0 ends it
+//! sha256     32     of the header
+//! hmac       32     HMAC-SHA256 of the header, block index 2^64 - 1
+//! blocks     …      hmac 32, length u32, data; an empty block ends them
+//! ```
+//!
+//! The composite key is SHA-256(SHA-256(password) || keyfile key), and the
+//! header's KDF (AES-KDF, Argon2d or Argon2id) turns it into the transformed
+//! key. The payload key is SHA-256(master seed || transformed key) and every
+//! HMAC key is SHA-512(index || SHA-512(master seed || transformed key ||
+//! 01)), so a wrong key is caught by the header HMAC before any decryption.
+//! The payload is AES-256-CBC or ChaCha20, optionally gzipped, and starts
+//! with an inner header holding the ChaCha20 or Salsa20 key that hides
+//! protected values (passwords, mostly) in the XML that follows.
+//!
+//! Entries in the recycle bin and past versions of entries are not
+//! returned; attachments and icons are ignored.
+
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryInto;
+
+use aes::cipher::{generic_array::GenericArray, BlockEncrypt, KeyInit};
+use aes::Aes256;
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20::cipher::{KeyIvInit, StreamCipher};
+use chacha20::ChaCha20;
+use hmac::{Hmac, Mac};
+use salsa20::Salsa20;
+use sha2::{Digest, Sha256, Sha512};
+use subtle::ConstantTimeEq;
+use wasm_bindgen::prelude::*;
+use zeroize::{Zeroize, Zeroizing};
+
+use crate::encoding::decode_base64;
+use crate::envelope::invalid;
+use crate::kcv::wrong_password;
+use crate::legacy::cbc_decrypt_with_key;
+use crate::utils::{from_hex, to_hex};
+use crate::{BVaultError, ErrorCode};
+
+const SIGNATURE_1: u32 = 0x9AA2_D903;
+const SIGNATURE_2: u32 = 0xB54B_FB67;
+const MAJOR_VERSION: u16 = 4;
+
+const HEADER_END: u8 = 0;
+const HEADER_CIPHER: u8 = 2;
+const HEADER_COMPRESSION: u8 = 3;
+const HEADER_MASTER_SEED: u8 = 4;
+const HEADER_IV: u8 = 7;
+const HEADER_KDF: u8 = 11;
+
+const INNER_END: u8 = 0;
+const INNER_STREAM_ID: u8 = 1;
+const INNER_STREAM_KEY: u8 = 2;
+
+const CIPHER_AES256: [u8; 16] = uuid(0x31c1f2e6_bf71_4350_be58_05216afc5aff);
+const CIPHER_CHACHA20: [u8; 16] = uuid(0xd6038a2b_8b6f_4cb5_a524_339a31dbb59a);
+const KDF_AES: [u8; 16] = uuid(0xc9d9f39a_628a_4460_bf74_0d08c18a4fea);
+const KDF_ARGON2D: [u8; 16] = uuid(0xef636ddf_8c29_444b_91f7_a9a403e30a0c);
+const KDF_ARGON2ID: [u8; 16] = uuid(0x9e298b19_56db_4773_b23d_fc3ec6f0a1e6);
+
+const STREAM_NONE: u32 = 0;
+const STREAM_SALSA20: u32 = 2;
+const STREAM_CHACHA20: u32 = 3;
+const SALSA20_NONCE: [u8; 8] = [0xe8, 0x30, 0x09, 0x4b, 0x97, 0x20, 0x5d, 0x2a];
+
+/// Highest KDF costs accepted, so a hostile file cannot stall the page.
+const MAX_AES_KDF_ROUNDS: u64 = 100_000_000;
+const MAX_ARGON2_MEMORY: u64 = 1 << 30;
+const MAX_ARGON2_ITERATIONS: u64 = 100;
+/// Highest decompressed payload accepted.
+const MAX_PAYLOAD_LENGTH: usize = 256 * 1024 * 1024;
+
+const STANDARD_FIELDS: [&str; 5] = ["Title", "UserName", "Password", "URL", "Notes"];
+
+const fn uuid(value: u128) -> [u8; 16] {
+    value.to_be_bytes()
+}
+
+fn malformed(message: &str) -> BVaultError {
+    invalid(format!("malformed KDBX file: {}", message))
+}
+
+/// Little-endian counterpart of the envelope reader.
+struct LeReader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> LeReader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], BVaultError> {
+        if self.data.len() < len {
+            return Err(malformed("truncated"));
+        }
+        let (head, tail) = self.data.split_at(len);
+        self.data = tail;
+        Ok(head)
+    }
+
+    fn u8(&mut self) -> Result<u8, BVaultError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, BVaultError> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32, BVaultError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    /// A type-length-value field of a header.
+    fn field(&mut self) -> Result<(u8, &'a [u8]), BVaultError> {
+        let id = self.u8()?;
+        let len = self.u32()? as usize;
+        Ok((id, self.take(len)?))
+    }
+}
+
+/// A KeePass "variant dictionary", as raw type tags and values.
+struct VariantDictionary(BTreeMap<String, (u8, Vec<u8>)>);
+
+impl VariantDictionary {
+    const UINT32: u8 = 0x04;
+    const UINT64: u8 = 0x05;
+    const BYTES: u8 = 0x42;
+
+    fn parse(data: &[u8]) -> Result<VariantDictionary, BVaultError> {
+        let mut reader = LeReader { data };
+        if reader.u16()? >> 8 != 1 {
+            return Err(malformed("unsupported KDF parameters version"));
+        }
+        let mut entries = BTreeMap::new();
+        loop {
+            let kind = reader.u8()?;
+            if kind == 0 {
+                return Ok(VariantDictionary(entries));
+            }
+            let name_len = reader.u32()? as usize;
+            let name = String::from_utf8(reader.take(name_len)?.to_vec())
+                .map_err(|_| malformed("KDF parameter name is not valid utf-8"))?;
+            let value_len = reader.u32()? as usize;
+            entries.insert(name, (kind, reader.take(value_len)?.to_vec()));
+        }
+    }
+
+    fn get(&self, name: &str, kind: u8) -> Result<Option<&[u8]>, BVaultError> {
+        match self.0.get(name) {
+            None => Ok(None),
+            Some((found, value)) if *found == kind => Ok(Some(value)),
+            Some(_) => Err(malformed(&format!(
+                "KDF parameter {} has the wrong type",
+                name
+            ))),
+        }
+    }
+
+    fn bytes(&self, name: &str) -> Result<&[u8], BVaultError> {
+        self.get(name, Self::BYTES)?
+            .ok_or_else(|| malformed(&format!("KDF parameter {} is missing", name)))
+    }
+
+    fn u64(&self, name: &str) -> Result<u64, BVaultError> {
+        let value = self
+            .get(name, Self::UINT64)?
+            .ok_or_else(|| malformed(&format!("KDF parameter {} is missing", name)))?;
+        Ok(u64::from_le_bytes(value.try_into().map_err(|_| {
+            malformed(&format!("KDF parameter {} is not 8 bytes", name))
+        })?))
+    }
+
+    fn u32(&self, name: &str) -> Result<Option<u32>, BVaultError> {
+        self.get(name, Self::UINT32)?
+            .map(|value| {
+                value
+                    .try_into()
+                    .map(u32::from_le_bytes)
+                    .map_err(|_| malformed(&format!("KDF parameter {} is not 4 bytes", name)))
+            })
+            .transpose()
+    }
+}
+
+/// The outer header fields needed to open the payload.
+struct Header {
+    cipher: [u8; 16],
+    compressed: bool,
+    master_seed: Vec<u8>,
+    iv: Vec<u8>,
+    kdf: VariantDictionary,
+}
+
+impl Header {
+    fn parse(reader: &mut LeReader) -> Result<Header, BVaultError> {
+        let (mut cipher, mut compressed, mut master_seed, mut iv, mut kdf) =
+            (None, false, None, None, None);
+        loop {
+            let (id, data) = reader.field()?;
+            match id {
+                HEADER_END => break,
+                HEADER_CIPHER => {
+                    cipher = Some(
+                        data.try_into()
+                            .map_err(|_| malformed("the cipher id is not 16 bytes"))?,
+                    )
+                }
+                HEADER_COMPRESSION => {
+                    compressed = match data {
+                        [0, 0, 0, 0] => false,
+                        [1, 0, 0, 0] => true,
+                        _ => return Err(malformed("unknown compression")),
+                    }
+                }
+                HEADER_MASTER_SEED => master_seed = Some(data.to_vec()),
+                HEADER_IV => iv = Some(data.to_vec()),
+                HEADER_KDF => kdf = Some(VariantDictionary::parse(data)?),
+                _ => {}
+            }
+        }
+        let missing = |field: &str| malformed(&format!("the header has no {}", field));
+        let master_seed = master_seed.ok_or_else(|| missing("master seed"))?;
+        if master_seed.len() != 32 {
+            return Err(malformed("the master seed is not 32 bytes"));
+        }
+        Ok(Header {
+            cipher: cipher.ok_or_else(|| missing("cipher"))?,
+            compressed,
+            master_seed,
+            iv: iv.ok_or_else(|| missing("IV"))?,
+            kdf: kdf.ok_or_else(|| missing("KDF parameters"))?,
+        })
+    }
+
+    /// Runs the KDF over the composite key.
+    fn transform(&self, composite: &[u8; 32]) -> Result<Zeroizing<[u8; 32]>, BVaultError> {
+        let id = self.kdf.bytes("$UUID")?;
+        if id == KDF_AES {
+            let rounds = self.kdf.u64("R")?;
+            if rounds > MAX_AES_KDF_ROUNDS {
+                return Err(BVaultError::new(
+                    ErrorCode::WeakKdfParams,
+                    "AES-KDF rounds are above 100 000 000",
+                ));
+            }
+            let seed: &[u8; 32] = self
+                .kdf
+                .bytes("S")?
+                .try_into()
+                .map_err(|_| malformed("the AES-KDF seed is not 32 bytes"))?;
+            let aes = Aes256::new(seed.into());
+            let mut key = Zeroizing::new(*composite);
+            for _ in 0..rounds {
+                for block in key.chunks_exact_mut(16) {
+                    aes.encrypt_block(GenericArray::from_mut_slice(block));
+                }
+            }
+            Ok(Zeroizing::new(Sha256::digest(&key[..]).into()))
+        } else if id == KDF_ARGON2D || id == KDF_ARGON2ID {
+            self.argon2(id == KDF_ARGON2ID, composite)
+        } else {
+            Err(BVaultError::new(
+                ErrorCode::UnsupportedAlgorithm,
+                format!("unsupported KDBX KDF {}", to_hex(id)),
+            ))
+        }
+    }
+
+    fn argon2(
+        &self,
+        argon2id: bool,
+        composite: &[u8; 32],
+    ) -> Result<Zeroizing<[u8; 32]>, BVaultError> {
+        let memory = self.kdf.u64("M")?;
+        let iterations = self.kdf.u64("I")?;
+        let parallelism = self.kdf.u32("P")?.unwrap_or(1);
+        if memory > MAX_ARGON2_MEMORY || iterations > MAX_ARGON2_ITERATIONS {
+            return Err(BVaultError::new(
+                ErrorCode::WeakKdfParams,
+                "Argon2 parameters are above 1 GiB or 100 passes",
+            ));
+        }
+        if self.kdf.0.contains_key("K") || self.kdf.0.contains_key("A") {
+            return Err(BVaultError::new(
+                ErrorCode::UnsupportedAlgorithm,
+                "Argon2 secret keys and associated data are not supported",
+            ));
+        }
+        let version = match self.kdf.u32("V")?.unwrap_or(0x13) {
+            0x10 => Version::V0x10,
+            0x13 => Version::V0x13,
+            _ => return Err(malformed("unknown Argon2 version")),
+        };
+        let algorithm = if argon2id {
+            Algorithm::Argon2id
+        } else {
+            Algorithm::Argon2d
+        };
+        let params = Params::new(
+            (memory / 1024) as u32,
+            iterations as u32,
+            parallelism,
+            Some(32),
+        )
+        .map_err(|_| BVaultError::new(ErrorCode::WeakKdfParams, "invalid Argon2 parameters"))?;
+        let mut key = Zeroizing::new([0u8; 32]);
+        Argon2::new(algorithm, version, params)
+            .hash_password_into(composite, self.kdf.bytes("S")?, &mut key[..])
+            .map_err(|_| BVaultError::new(ErrorCode::KdfFailed, "Argon2 key derivation failed"))?;
+        Ok(key)
+    }
+}
+
+/// The key a KeePass keyfile contributes: the key of an XML keyfile
+/// (version 1.0 or 2.0), 32 raw bytes, 64 hex digits, or else the SHA-256
+/// of the whole file.
+fn keyfile_key(keyfile: &[u8]) -> Result<Zeroizing<Vec<u8>>, BVaultError> {
+    if let Some(key) = std::str::from_utf8(keyfile)
+        .ok()
+        .and_then(|text| roxmltree::Document::parse(text).ok())
+        .map(|doc| xml_keyfile_key(&doc))
+    {
+        return key;
+    }
+    if keyfile.len() == 32 {
+        return Ok(Zeroizing::new(keyfile.to_vec()));
+    }
+    if keyfile.len() == 64 {
+        if let Some(key) = std::str::from_utf8(keyfile).ok().and_then(from_hex) {
+            return Ok(Zeroizing::new(key));
+        }
+    }
+    Ok(Zeroizing::new(Sha256::digest(keyfile).to_vec()))
+}
+
+fn xml_keyfile_key(doc: &roxmltree::Document) -> Result<Zeroizing<Vec<u8>>, BVaultError> {
+    let bad = || BVaultError::new(ErrorCode::InvalidKey, "malformed KeePass keyfile");
+    let root = doc.root_element();
+    let version = child(root, "Meta")
+        .and_then(|meta| child(meta, "Version"))
+        .and_then(|version| version.text())
+        .ok_or_else(bad)?;
+    let data = child(root, "Key")
+        .and_then(|key| child(key, "Data"))
+        .ok_or_else(bad)?;
+    let text: String = data
+        .text()
+        .unwrap_or("")
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect();
+    if version.starts_with("2.") {
+        let key = Zeroizing::new(from_hex(&text).ok_or_else(bad)?);
+        if let Some(hash) = data.attribute("Hash") {
+            if !to_hex(&Sha256::digest(&key[..])[..4]).eq_ignore_ascii_case(hash) {
+                return Err(bad());
+            }
+        }
+        Ok(key)
+    } else {
+        Ok(Zeroizing::new(decode_base64(&text).map_err(|_| bad())?))
+    }
+}
+
+/// The keystream hiding protected values in the XML.
+enum InnerStream {
+    None,
+    Salsa20(Salsa20),
+    ChaCha20(ChaCha20),
+}
+
+impl InnerStream {
+    fn new(id: u32, key: &[u8]) -> Result<InnerStream, BVaultError> {
+        match id {
+            STREAM_NONE => Ok(InnerStream::None),
+            STREAM_SALSA20 => {
+                let key = Zeroizing::new(<[u8; 32]>::from(Sha256::digest(key)));
+                Ok(InnerStream::Salsa20(Salsa20::new(
+                    GenericArray::from_slice(&key[..]),
+                    &SALSA20_NONCE.into(),
+                )))
+            }
+            STREAM_CHACHA20 => {
+                let hash = Zeroizing::new(<[u8; 64]>::from(Sha512::digest(key)));
+                Ok(InnerStream::ChaCha20(ChaCha20::new(
+                    GenericArray::from_slice(&hash[..32]),
+                    GenericArray::from_slice(&hash[32..44]),
+                )))
+            }
+            _ => Err(BVaultError::new(
+                ErrorCode::UnsupportedAlgorithm,
+                format!("unsupported KDBX inner stream {}", id),
+            )),
+        }
+    }
+
+    fn apply(&mut self, buf: &mut [u8]) {
+        match self {
+            InnerStream::None => {}
+            InnerStream::Salsa20(cipher) => cipher.apply_keystream(buf),
+            InnerStream::ChaCha20(cipher) => cipher.apply_keystream(buf),
+        }
+    }
+}
+
+/// SHA-512(index || `base`), the HMAC key of block `index`.
+fn block_key(index: u64, base: &[u8]) -> Zeroizing<Vec<u8>> {
+    let mut hash = Sha512::new();
+    hash.update(index.to_le_bytes());
+    hash.update(base);
+    Zeroizing::new(hash.finalize().to_vec())
+}
+
+fn block_hmac(key: &[u8]) -> Hmac<Sha256> {
+    <Hmac<Sha256> as Mac>::new_from_slice(key).expect("HMAC takes any key length")
+}
+
+/// Strips a gzip wrapper and inflates the DEFLATE stream inside. The CRC is
+/// not checked, since the block HMACs already authenticate the data.
+fn gunzip(data: &[u8]) -> Result<Zeroizing<Vec<u8>>, BVaultError> {
+    const FEXTRA: u8 = 4;
+    const FNAME: u8 = 8;
+    const FCOMMENT: u8 = 16;
+    const FHCRC: u8 = 2;
+
+    let mut reader = LeReader { data };
+    let header = reader.take(10)?;
+    if header[..3] != [0x1f, 0x8b, 8] {
+        return Err(malformed("the payload is not gzip"));
+    }
+    let flags = header[3];
+    if flags & FEXTRA != 0 {
+        let len = reader.u16()? as usize;
+        reader.take(len)?;
+    }
+    for flag in [FNAME, FCOMMENT] {
+        if flags & flag != 0 {
+            let end = reader
+                .data
+                .iter()
+                .position(|&b| b == 0)
+                .ok_or_else(|| malformed("truncated"))?;
+            reader.take(end + 1)?;
+        }
+    }
+    if flags & FHCRC != 0 {
+        reader.take(2)?;
+    }
+    miniz_oxide::inflate::decompress_to_vec_with_limit(reader.data, MAX_PAYLOAD_LENGTH)
+        .map(Zeroizing::new)
+        .map_err(|mut err| {
+            err.output.zeroize();
+            malformed("the payload is corrupt or too large")
+        })
+}
+
+/// An entry of a KDBX database.
+#[wasm_bindgen]
+#[derive(Clone)]
+pub struct KdbxEntry {
+    uuid: String,
+    group: String,
+    tags: Vec<String>,
+    fields: BTreeMap<String, Zeroizing<String>>,
+}
+
+impl KdbxEntry {
+    fn standard(&self, name: &str) -> String {
+        self.fields
+            .get(name)
+            .map(|value| value.to_string())
+            .unwrap_or_default()
+    }
+}
+
+#[wasm_bindgen]
+impl KdbxEntry {
+    /// The entry's UUID, as 32 hex digits.
+    #[wasm_bindgen(getter)]
+    pub fn uuid(&self) -> String {
+        self.uuid.clone()
+    }
+
+    /// The path of the entry's group, such as "Passwords/Email".
+    #[wasm_bindgen(getter)]
+    pub fn group(&self) -> String {
+        self.group.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn title(&self) -> String {
+        self.standard("Title")
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn username(&self) -> String {
+        self.standard("UserName")
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn password(&self) -> String {
+        self.standard("Password")
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn url(&self) -> String {
+        self.standard("URL")
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn notes(&self) -> String {
+        self.standard("Notes")
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn tags(&self) -> Vec<String> {
+        self.tags.clone()
+    }
+
+    /// The names of the custom fields, in order.
+    pub fn field_names(&self) -> Vec<String> {
+        self.fields
+            .keys()
+            .filter(|name| !STANDARD_FIELDS.contains(&name.as_str()))
+            .cloned()
+            .collect()
+    }
+
+    /// The value of the field `name`, standard or custom.
+    pub fn field(&self, name: &str) -> Option<String> {
+        self.fields.get(name).map(|value| value.to_string())
+    }
+}
+
+/// An opened KDBX database.
+#[wasm_bindgen]
+pub struct KdbxDatabase {
+    name: String,
+    entries: Vec<KdbxEntry>,
+}
+
+#[wasm_bindgen]
+impl KdbxDatabase {
+    /// The database name set in KeePass.
+    #[wasm_bindgen(getter)]
+    pub fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    /// Every entry outside the recycle bin, group by group.
+    #[wasm_bindgen(getter)]
+    pub fn entries(&self) -> Vec<KdbxEntry> {
+        self.entries.clone()
+    }
+}
+
+/// Unmasks every protected value in document order, which is the order
+/// they consumed the inner stream.
+fn unprotect(
+    doc: &roxmltree::Document,
+    stream: &mut InnerStream,
+) -> Result<HashMap<roxmltree::NodeId, Zeroizing<String>>, BVaultError> {
+    let mut values = HashMap::new();
+    for node in doc.descendants() {
+        if !node
+            .attribute("Protected")
+            .is_some_and(|value| value.eq_ignore_ascii_case("true"))
+        {
+            continue;
+        }
+        let mut value = Zeroizing::new(decode_base64(node.text().unwrap_or(""))?);
+        stream.apply(&mut value);
+        let text = String::from_utf8(value.to_vec())
+            .map_err(|_| malformed("a protected value is not valid utf-8"))?;
+        values.insert(node.id(), Zeroizing::new(text));
+    }
+    Ok(values)
+}
+
+struct Walk<'a> {
+    protected: HashMap<roxmltree::NodeId, Zeroizing<String>>,
+    recycle_bin: Option<&'a str>,
+    entries: Vec<KdbxEntry>,
+}
+
+fn child<'a, 'input>(
+    node: roxmltree::Node<'a, 'input>,
+    name: &str,
+) -> Option<roxmltree::Node<'a, 'input>> {
+    node.children().find(|child| child.has_tag_name(name))
+}
+
+fn child_text<'a>(node: roxmltree::Node<'a, '_>, name: &str) -> &'a str {
+    child(node, name)
+        .and_then(|child| child.text())
+        .unwrap_or("")
+}
+
+fn uuid_hex(text: &str) -> String {
+    decode_base64(text)
+        .map(|uuid| to_hex(&uuid))
+        .unwrap_or_default()
+}
+
+impl<'a> Walk<'a> {
+    fn group(&mut self, group: roxmltree::Node, parent: Option<&str>) {
+        if self.recycle_bin == Some(child_text(group, "UUID")) {
+            return;
+        }
+        let name = child_text(group, "Name");
+        let path = match parent {
+            Some(parent) => format!("{}/{}", parent, name),
+            None => name.to_string(),
+        };
+        for node in group.children() {
+            if node.has_tag_name("Group") {
+                self.group(node, Some(&path));
+            } else if node.has_tag_name("Entry") {
+                self.entry(node, &path);
+            }
+        }
+    }
+
+    fn entry(&mut self, entry: roxmltree::Node, group: &str) {
+        let mut fields = BTreeMap::new();
+        for string in entry.children().filter(|node| node.has_tag_name("String")) {
+            let value = child(string, "Value").map_or_else(Zeroizing::default, |value| {
+                self.protected
+                    .get(&value.id())
+                    .cloned()
+                    .unwrap_or_else(|| Zeroizing::new(value.text().unwrap_or("").to_string()))
+            });
+            fields.insert(child_text(string, "Key").to_string(), value);
+        }
+        let tags = child_text(entry, "Tags")
+            .split([';', ','])
+            .map(str::trim)
+            .filter(|tag| !tag.is_empty())
+            .map(str::to_string)
+            .collect();
+        self.entries.push(KdbxEntry {
+            uuid: uuid_hex(child_text(entry, "UUID")),
+            group: group.to_string(),
+            tags,
+            fields,
+        });
+    }
+}
+
+/// Opens a KDBX 4 database with its password, keyfile or both, and returns
+/// its entries.
+///
+/// # Errors
+///
+/// - If neither a password nor a keyfile is given, an
+///   [`ErrorCode::InvalidArgument`] error is returned.
+/// - If the file is not KDBX 4 (KDBX 3.1 files must be saved again by a
+///   current KeePass first), an [`ErrorCode::UnsupportedVersion`] error is
+///   returned.
+/// - If it uses Twofish or another unknown cipher or KDF, an
+///   [`ErrorCode::UnsupportedAlgorithm`] error is returned.
+/// - If its KDF costs are absurdly high, an [`ErrorCode::WeakKdfParams`]
+///   error is returned.
+/// - If the password or keyfile is wrong, an [`ErrorCode::WrongPassword`]
+///   error is returned.
+/// - If the file is corrupt or was tampered with, an error is returned.
+#[wasm_bindgen]
+pub fn kdbx_open(
+    database: &[u8],
+    password: Option<String>,
+    keyfile: Option<Vec<u8>>,
+) -> Result<KdbxDatabase, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    if password.is_none() && keyfile.is_none() {
+        return Err(BVaultError::new(
+            ErrorCode::InvalidArgument,
+            "a KDBX database needs a password, a keyfile or both",
+        ));
+    }
+    let password = password.map(Zeroizing::new);
+
+    let mut reader = LeReader { data: database };
+    if reader.u32()? != SIGNATURE_1 || reader.u32()? != SIGNATURE_2 {
+        return Err(invalid("not a KeePass database"));
+    }
+    let _minor = reader.u16()?;
+    let major = reader.u16()?;
+    if major != MAJOR_VERSION {
+        return Err(BVaultError::new(
+            ErrorCode::UnsupportedVersion,
+            format!(
+                "unsupported KDBX version {}; only KDBX 4 can be read",
+                major
+            ),
+        ));
+    }
+    let header = Header::parse(&mut reader)?;
+    let header_bytes = &database[..database.len() - reader.data.len()];
+    if !bool::from(Sha256::digest(header_bytes).ct_eq(reader.take(32)?)) {
+        return Err(malformed("the header is corrupt"));
+    }
+    let header_tag = reader.take(32)?;
+
+    let mut composite = Sha256::new();
+    if let Some(password) = &password {
+        composite.update(Sha256::digest(password.as_bytes()));
+    }
+    if let Some(keyfile) = &keyfile {
+        composite.update(&keyfile_key(keyfile)?[..]);
+    }
+    let composite = Zeroizing::new(<[u8; 32]>::from(composite.finalize()));
+    let transformed = header.transform(&composite)?;
+
+    let mut hash = Sha512::new();
+    hash.update(&header.master_seed);
+    hash.update(&transformed[..]);
+    hash.update([1]);
+    let hmac_base = Zeroizing::new(hash.finalize().to_vec());
+    let mut mac = block_hmac(&block_key(u64::MAX, &hmac_base));
+    mac.update(header_bytes);
+    mac.verify_slice(header_tag).map_err(|_| wrong_password())?;
+
+    let mut payload = Vec::new();
+    for index in 0u64.. {
+        let tag = reader.take(32)?;
+        let len_bytes = reader.take(4)?;
+        let len = u32::from_le_bytes(len_bytes.try_into().unwrap()) as usize;
+        let block = reader.take(len)?;
+        let mut mac = block_hmac(&block_key(index, &hmac_base));
+        mac.update(&index.to_le_bytes());
+        mac.update(len_bytes);
+        mac.update(block);
+        mac.verify_slice(tag)
+            .map_err(|_| BVaultError::new(ErrorCode::AuthFailed, "a KDBX block was modified"))?;
+        if len == 0 {
+            break;
+        }
+        payload.extend_from_slice(block);
+    }
+
+    let mut hash = Sha256::new();
+    hash.update(&header.master_seed);
+    hash.update(&transformed[..]);
+    let key = Zeroizing::new(<[u8; 32]>::from(hash.finalize()));
+    let mut payload = Zeroizing::new(if header.cipher == CIPHER_AES256 {
+        if header.iv.len() != 16 {
+            return Err(malformed("the AES IV is not 16 bytes"));
+        }
+        cbc_decrypt_with_key(&key[..], &header.iv, payload, None)?
+    } else if header.cipher == CIPHER_CHACHA20 {
+        if header.iv.len() != 12 {
+            return Err(malformed("the ChaCha20 nonce is not 12 bytes"));
+        }
+        ChaCha20::new(
+            GenericArray::from_slice(&key[..]),
+            GenericArray::from_slice(&header.iv),
+        )
+        .apply_keystream(&mut payload);
+        payload
+    } else {
+        return Err(BVaultError::new(
+            ErrorCode::UnsupportedAlgorithm,
+            format!("unsupported KDBX cipher {}", to_hex(&header.cipher)),
+        ));
+    });
+    if header.compressed {
+        payload = Zeroizing::new(gunzip(&payload)?.to_vec());
+    }
+
+    let mut reader = LeReader { data: &payload };
+    let (mut stream_id, mut stream_key) = (STREAM_NONE, Zeroizing::new(Vec::new()));
+    loop {
+        let (id, data) = reader.field()?;
+        match id {
+            INNER_END => break,
+            INNER_STREAM_ID => {
+                stream_id = u32::from_le_bytes(
+                    data.try_into()
+                        .map_err(|_| malformed("the inner stream id is not 4 bytes"))?,
+                )
+            }
+            INNER_STREAM_KEY => stream_key = Zeroizing::new(data.to_vec()),
+            _ => {}
+        }
+    }
+    let xml = std::str::from_utf8(reader.data).map_err(|_| malformed("the XML is not utf-8"))?;
+    let doc = roxmltree::Document::parse(xml).map_err(|e| malformed(&e.to_string()))?;
+    let mut stream = InnerStream::new(stream_id, &stream_key)?;
+
+    let meta = child(doc.root_element(), "Meta");
+    let recycle_bin = meta
+        .filter(|meta| child_text(*meta, "RecycleBinEnabled").eq_ignore_ascii_case("true"))
+        .map(|meta| child_text(meta, "RecycleBinUUID"));
+    let mut walk = Walk {
+        protected: unprotect(&doc, &mut stream)?,
+        recycle_bin,
+        entries: Vec::new(),
+    };
+    let root = child(doc.root_element(), "Root")
+        .and_then(|root| child(root, "Group"))
+        .ok_or_else(|| malformed("the XML has no root group"))?;
+    walk.group(root, None);
+    Ok(KdbxDatabase {
+        name: meta
+            .map_or("", |meta| child_text(meta, "DatabaseName"))
+            .to_string(),
+        entries: walk.entries,
+    })
+}
@@ -0,0 +1,896 @@
+//! Password-based key derivation.
+//!
+//! Every encrypt/decrypt function derives its 256-bit key through a
+//! [`KdfParams`] descriptor. PBKDF2-HMAC-SHA256 with 100 000 iterations is
+//! the default so data produced before the descriptor existed still opens.
+//! PBKDF2-HMAC-SHA512 is available for data derived by other tools.
+//!
+//! [`hkdf_derive`] covers the other direction: splitting one high-entropy
+//! master secret into independent sub-keys.
+
+#[cfg(feature = "argon2")]
+use argon2::{Algorithm, Argon2, Params, Version};
+use hkdf::Hkdf;
+use hmac::{Hmac, Mac};
+use pbkdf2::pbkdf2_hmac;
+use scrypt::Params as ScryptParams;
+use sha2::{Digest, Sha256, Sha512};
+use wasm_bindgen::prelude::*;
+use zeroize::{Zeroize, Zeroizing};
+
+#[cfg(feature = "cbc-legacy")]
+use crate::cancel::{self, AbortSignal};
+use crate::{keycache, BVaultError, ErrorCode};
+
+/// Default number of PBKDF2-HMAC-SHA256 iterations used for key derivation.
+pub(crate) const PBKDF2_ITERATIONS: u32 = 100_000;
+
+/// Lowest PBKDF2 iteration count accepted by any function.
+const MIN_PBKDF2_ITERATIONS: u32 = 10_000;
+
+/// Highest PBKDF2 iteration count accepted, so a hostile header cannot
+/// stall the page.
+const MAX_PBKDF2_ITERATIONS: u32 = 10_000_000;
+
+/// Default Argon2id memory cost, in KiB (19 MiB, the OWASP baseline).
+const ARGON2_MEMORY_KIB: u32 = 19 * 1024;
+
+/// Default Argon2id number of passes.
+const ARGON2_ITERATIONS: u32 = 2;
+
+/// Default Argon2id degree of parallelism.
+const ARGON2_PARALLELISM: u32 = 1;
+
+/// Lowest Argon2id memory cost accepted, in KiB.
+const MIN_ARGON2_MEMORY_KIB: u32 = 8 * 1024;
+
+/// Highest Argon2id memory cost accepted, in KiB (1 GiB), so a hostile
+/// header cannot exhaust or permanently grow WASM memory.
+const MAX_ARGON2_MEMORY_KIB: u32 = 1024 * 1024;
+
+/// Highest Argon2id number of passes accepted.
+const MAX_ARGON2_ITERATIONS: u32 = 64;
+
+/// Highest Argon2id degree of parallelism accepted.
+const MAX_ARGON2_PARALLELISM: u32 = 64;
+
+/// Default scrypt cost, as log2(N) (N = 2^15).
+const SCRYPT_LOG_N: u8 = 15;
+
+/// Default scrypt block size (r).
+const SCRYPT_R: u32 = 8;
+
+/// Default scrypt parallelism (p).
+const SCRYPT_P: u32 = 1;
+
+/// Lowest scrypt cost accepted, as log2(N).
+const MIN_SCRYPT_LOG_N: u8 = 14;
+
+/// Highest scrypt cost accepted, as log2(N).
+const MAX_SCRYPT_LOG_N: u8 = 20;
+
+/// Highest scrypt block size (r) accepted.
+const MAX_SCRYPT_R: u32 = 32;
+
+/// Highest scrypt parallelism (p) accepted.
+const MAX_SCRYPT_P: u32 = 16;
+
+/// Most memory scrypt may need (128 * r * N bytes) to be accepted: 1 GiB.
+const MAX_SCRYPT_MEMORY: u64 = 1 << 30;
+
+/// Length of every derived key, in bytes.
+pub(crate) const KEY_LENGTH: usize = 32;
+
+/// A derived key, wiped from WASM memory when dropped.
+pub(crate) type Key = Zeroizing<[u8; KEY_LENGTH]>;
+
+/// HKDF label for mixing a keyfile into the password-derived key.
+const KEYFILE_INFO: &[u8] = b"bvault-keyfile";
+
+/// Longest PBKDF2 PRF output, in bytes (HMAC-SHA512).
+const PRF_MAX_LENGTH: usize = 64;
+
+/// PBKDF2 iterations run between two yields to the event loop.
+const PBKDF2_BATCH: u32 = 5_000;
+
+/// Shortest stretch of PBKDF2 work timed by [`calibrate_kdf`], in ms.
+const CALIBRATION_SAMPLE_MS: f64 = 50.0;
+
+/// Highest Argon2id memory cost [`recommend_argon2_params`] recommends, in
+/// KiB (1 GiB).
+#[cfg(feature = "argon2")]
+const MAX_RECOMMENDED_ARGON2_MEMORY_KIB: u32 = 1024 * 1024;
+
+/// Memory the probe in [`recommend_argon2_params`] asks for on top of the
+/// Argon2 blocks, for the rest of the page's allocations, in bytes.
+#[cfg(feature = "argon2")]
+const ARGON2_PROBE_HEADROOM: usize = 4 * 1024 * 1024;
+
+/// Longest output HKDF-SHA256 can produce (255 hash blocks), in bytes.
+const MAX_HKDF_LENGTH: usize = 255 * 32;
+
+/// The hash PBKDF2 runs HMAC over.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Pbkdf2Hash {
+    Sha256,
+    Sha512,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Kdf {
+    Pbkdf2 {
+        iterations: u32,
+        hash: Pbkdf2Hash,
+    },
+    Argon2id {
+        memory_kib: u32,
+        iterations: u32,
+        parallelism: u32,
+    },
+    Scrypt {
+        log_n: u8,
+        r: u32,
+        p: u32,
+    },
+}
+
+/// Describes how a key is derived from a password.
+///
+/// Construct one with [`KdfParams::pbkdf2`], [`KdfParams::pbkdf2_sha512`],
+/// [`KdfParams::argon2id`] or [`KdfParams::scrypt`] and pass it to the
+/// `encrypt_*`/`decrypt_*` functions; the same parameters must be supplied
+/// when decrypting.
+///
+/// [`KdfParams::with_keyfile`] adds a keyfile as a second factor. Only its
+/// SHA-256 hash is kept, and it is never written into envelopes: it must be
+/// supplied again to decrypt.
+#[wasm_bindgen]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KdfParams {
+    kdf: Kdf,
+    keyfile: Option<Key>,
+}
+
+impl Default for KdfParams {
+    fn default() -> Self {
+        KdfParams::pbkdf2(PBKDF2_ITERATIONS)
+    }
+}
+
+#[wasm_bindgen]
+impl KdfParams {
+    /// PBKDF2-HMAC-SHA256 with the given iteration count (minimum 10 000).
+    pub fn pbkdf2(iterations: u32) -> KdfParams {
+        KdfParams::new(Kdf::Pbkdf2 {
+            iterations,
+            hash: Pbkdf2Hash::Sha256,
+        })
+    }
+
+    /// PBKDF2-HMAC-SHA512 with the given iteration count (minimum 10 000),
+    /// for opening data derived that way elsewhere.
+    pub fn pbkdf2_sha512(iterations: u32) -> KdfParams {
+        KdfParams::new(Kdf::Pbkdf2 {
+            iterations,
+            hash: Pbkdf2Hash::Sha512,
+        })
+    }
+
+    /// Argon2id with the given memory cost (KiB), passes and parallelism.
+    ///
+    /// Omitted values fall back to 19 MiB, 2 passes and 1 lane.
+    pub fn argon2id(
+        memory_kib: Option<u32>,
+        iterations: Option<u32>,
+        parallelism: Option<u32>,
+    ) -> KdfParams {
+        KdfParams::new(Kdf::Argon2id {
+            memory_kib: memory_kib.unwrap_or(ARGON2_MEMORY_KIB),
+            iterations: iterations.unwrap_or(ARGON2_ITERATIONS),
+            parallelism: parallelism.unwrap_or(ARGON2_PARALLELISM),
+        })
+    }
+
+    /// scrypt with cost N = 2^`log_n`, block size `r` and parallelism `p`.
+    ///
+    /// Omitted values fall back to N = 2^15, r = 8 and p = 1.
+    pub fn scrypt(log_n: Option<u8>, r: Option<u32>, p: Option<u32>) -> KdfParams {
+        KdfParams::new(Kdf::Scrypt {
+            log_n: log_n.unwrap_or(SCRYPT_LOG_N),
+            r: r.unwrap_or(SCRYPT_R),
+            p: p.unwrap_or(SCRYPT_P),
+        })
+    }
+
+    /// Name of the algorithm: `"pbkdf2-sha256"`, `"pbkdf2-sha512"`,
+    /// `"argon2id"` or `"scrypt"`.
+    #[wasm_bindgen(getter)]
+    pub fn algorithm(&self) -> String {
+        match self.kdf {
+            Kdf::Pbkdf2 {
+                hash: Pbkdf2Hash::Sha256,
+                ..
+            } => "pbkdf2-sha256",
+            Kdf::Pbkdf2 {
+                hash: Pbkdf2Hash::Sha512,
+                ..
+            } => "pbkdf2-sha512",
+            Kdf::Argon2id { .. } => "argon2id",
+            Kdf::Scrypt { .. } => "scrypt",
+        }
+        .to_string()
+    }
+
+    /// PBKDF2 iteration count or number of Argon2 passes; `undefined` for
+    /// scrypt.
+    #[wasm_bindgen(getter)]
+    pub fn iterations(&self) -> Option<u32> {
+        match self.kdf {
+            Kdf::Pbkdf2 { iterations, .. } | Kdf::Argon2id { iterations, .. } => Some(iterations),
+            Kdf::Scrypt { .. } => None,
+        }
+    }
+
+    /// Argon2 memory cost in KiB; `undefined` for the other algorithms.
+    #[wasm_bindgen(getter)]
+    pub fn memory_kib(&self) -> Option<u32> {
+        match self.kdf {
+            Kdf::Argon2id { memory_kib, .. } => Some(memory_kib),
+            Kdf::Pbkdf2 { .. } | Kdf::Scrypt { .. } => None,
+        }
+    }
+
+    /// Argon2 lanes or scrypt `p`; `undefined` for PBKDF2.
+    #[wasm_bindgen(getter)]
+    pub fn parallelism(&self) -> Option<u32> {
+        match self.kdf {
+            Kdf::Argon2id { parallelism, .. } => Some(parallelism),
+            Kdf::Scrypt { p, .. } => Some(p),
+            Kdf::Pbkdf2 { .. } => None,
+        }
+    }
+
+    /// scrypt cost as log2(N); `undefined` for the other algorithms.
+    #[wasm_bindgen(getter)]
+    pub fn log_n(&self) -> Option<u8> {
+        match self.kdf {
+            Kdf::Scrypt { log_n, .. } => Some(log_n),
+            Kdf::Pbkdf2 { .. } | Kdf::Argon2id { .. } => None,
+        }
+    }
+
+    /// scrypt block size `r`; `undefined` for the other algorithms.
+    #[wasm_bindgen(getter)]
+    pub fn block_size(&self) -> Option<u32> {
+        match self.kdf {
+            Kdf::Scrypt { r, .. } => Some(r),
+            Kdf::Pbkdf2 { .. } | Kdf::Argon2id { .. } => None,
+        }
+    }
+
+    /// Returns a copy of these parameters that also mixes `keyfile` into
+    /// the key, KeePass-style: the password-derived key and the SHA-256 of
+    /// the keyfile are combined with HKDF-SHA256.
+    pub fn with_keyfile(&self, keyfile: &[u8]) -> KdfParams {
+        KdfParams {
+            kdf: self.kdf,
+            keyfile: Some(Zeroizing::new(Sha256::digest(keyfile).into())),
+        }
+    }
+
+    /// Whether a keyfile is mixed into the key.
+    #[wasm_bindgen(getter)]
+    pub fn has_keyfile(&self) -> bool {
+        self.keyfile.is_some()
+    }
+}
+
+impl KdfParams {
+    fn new(kdf: Kdf) -> KdfParams {
+        KdfParams { kdf, keyfile: None }
+    }
+
+    pub(crate) fn kdf(&self) -> Kdf {
+        self.kdf
+    }
+
+    /// Combines the password-derived key with the keyfile hash, if any.
+    fn mix_keyfile(&self, key: Key) -> Result<Key, BVaultError> {
+        let keyfile = match &self.keyfile {
+            Some(keyfile) => keyfile,
+            None => return Ok(key),
+        };
+
+        let mut ikm = Zeroizing::new([0u8; 2 * KEY_LENGTH]);
+        ikm[..KEY_LENGTH].copy_from_slice(&key[..]);
+        ikm[KEY_LENGTH..].copy_from_slice(&keyfile[..]);
+
+        let mut mixed = Key::default();
+        hkdf_sha256(&ikm[..], None, KEYFILE_INFO, &mut mixed[..])?;
+        Ok(mixed)
+    }
+
+    /// Whether these parameters cost an attacker at least as much as
+    /// `floor`: the same KDF with no cheaper parameter, or a memory-hard
+    /// KDF (Argon2id, scrypt) against a PBKDF2 floor. PBKDF2 iterations
+    /// compare across hashes; parallelism is ignored.
+    pub(crate) fn at_least(&self, floor: &KdfParams) -> bool {
+        match (self.kdf, floor.kdf) {
+            (
+                Kdf::Pbkdf2 { iterations, .. },
+                Kdf::Pbkdf2 {
+                    iterations: min, ..
+                },
+            ) => iterations >= min,
+            (
+                Kdf::Argon2id {
+                    memory_kib,
+                    iterations,
+                    ..
+                },
+                Kdf::Argon2id {
+                    memory_kib: min_memory_kib,
+                    iterations: min_iterations,
+                    ..
+                },
+            ) => memory_kib >= min_memory_kib && iterations >= min_iterations,
+            (
+                Kdf::Scrypt { log_n, r, .. },
+                Kdf::Scrypt {
+                    log_n: min_log_n,
+                    r: min_r,
+                    ..
+                },
+            ) => log_n >= min_log_n && r >= min_r,
+            (Kdf::Argon2id { .. } | Kdf::Scrypt { .. }, Kdf::Pbkdf2 { .. }) => true,
+            _ => false,
+        }
+    }
+
+    /// Rejects parameters too weak to be accepted by any function, or too
+    /// costly to run (see [`KdfParams::check_cost`]).
+    pub(crate) fn validate(&self) -> Result<(), BVaultError> {
+        match self.kdf {
+            Kdf::Pbkdf2 { iterations, .. } => {
+                if iterations < MIN_PBKDF2_ITERATIONS {
+                    return Err(BVaultError::new(
+                        ErrorCode::WeakKdfParams,
+                        "PBKDF2 iterations must be at least 10 000",
+                    ));
+                }
+            }
+            Kdf::Argon2id {
+                memory_kib,
+                iterations,
+                parallelism,
+            } => {
+                if memory_kib < MIN_ARGON2_MEMORY_KIB {
+                    return Err(BVaultError::new(
+                        ErrorCode::WeakKdfParams,
+                        "Argon2 memory must be at least 8 MiB",
+                    ));
+                }
+                if iterations == 0 || parallelism == 0 {
+                    return Err(BVaultError::new(
+                        ErrorCode::WeakKdfParams,
+                        "Argon2 passes and parallelism must be at least 1",
+                    ));
+                }
+            }
+            Kdf::Scrypt { log_n, r, p } => {
+                if log_n < MIN_SCRYPT_LOG_N {
+                    return Err(BVaultError::new(
+                        ErrorCode::WeakKdfParams,
+                        "scrypt N must be at least 2^14",
+                    ));
+                }
+                if r == 0 || p == 0 {
+                    return Err(BVaultError::new(
+                        ErrorCode::WeakKdfParams,
+                        "scrypt r and p must be at least 1",
+                    ));
+                }
+            }
+        }
+        self.check_cost()
+    }
+
+    /// Rejects parameters above the maximum costs, which no legitimate
+    /// envelope needs and which would stall the page or exhaust WASM
+    /// memory.
+    pub(crate) fn check_cost(&self) -> Result<(), BVaultError> {
+        let too_costly = |message: &str| Err(BVaultError::new(ErrorCode::WeakKdfParams, message));
+        match self.kdf {
+            Kdf::Pbkdf2 { iterations, .. } => {
+                if iterations > MAX_PBKDF2_ITERATIONS {
+                    return too_costly("PBKDF2 iterations must be at most 10 000 000");
+                }
+            }
+            Kdf::Argon2id {
+                memory_kib,
+                iterations,
+                parallelism,
+            } => {
+                if memory_kib > MAX_ARGON2_MEMORY_KIB {
+                    return too_costly("Argon2 memory must be at most 1 GiB");
+                }
+                if iterations > MAX_ARGON2_ITERATIONS || parallelism > MAX_ARGON2_PARALLELISM {
+                    return too_costly("Argon2 passes and parallelism must be at most 64");
+                }
+            }
+            Kdf::Scrypt { log_n, r, p } => {
+                if log_n > MAX_SCRYPT_LOG_N || r > MAX_SCRYPT_R || p > MAX_SCRYPT_P {
+                    return too_costly("scrypt N, r and p must be at most 2^20, 32 and 16");
+                }
+                if scrypt_memory(log_n, r) > MAX_SCRYPT_MEMORY {
+                    return too_costly("scrypt N and r must need at most 1 GiB");
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Derives a 256-bit key from `password` and `salt`, and the keyfile if
+    /// one is set. The key cache, if on, is consulted first; see
+    /// [`crate::enable_key_cache`].
+    pub(crate) fn derive(&self, password: &[u8], salt: &[u8]) -> Result<Key, BVaultError> {
+        self.validate()?;
+        let key = match keycache::lookup(self, password, salt) {
+            Some(key) => key,
+            None => {
+                let key = self.stretch(password, salt)?;
+                keycache::store(self, password, salt, &key);
+                key
+            }
+        };
+        self.mix_keyfile(key)
+    }
+
+    /// Runs the password KDF alone.
+    fn stretch(&self, password: &[u8], salt: &[u8]) -> Result<Key, BVaultError> {
+        match self.kdf {
+            Kdf::Pbkdf2 { iterations, hash } => {
+                let mut key = Key::default();
+                match hash {
+                    Pbkdf2Hash::Sha256 => {
+                        pbkdf2_hmac::<Sha256>(password, salt, iterations, &mut key[..])
+                    }
+                    Pbkdf2Hash::Sha512 => {
+                        pbkdf2_hmac::<Sha512>(password, salt, iterations, &mut key[..])
+                    }
+                }
+                Ok(key)
+            }
+            #[cfg(feature = "argon2")]
+            Kdf::Argon2id {
+                memory_kib,
+                iterations,
+                parallelism,
+            } => {
+                let params = Params::new(memory_kib, iterations, parallelism, Some(KEY_LENGTH))
+                    .map_err(|_| {
+                        BVaultError::new(ErrorCode::WeakKdfParams, "invalid Argon2 parameters")
+                    })?;
+                let mut key = Key::default();
+                Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+                    .hash_password_into(password, salt, &mut key[..])
+                    .map_err(|_| {
+                        BVaultError::new(ErrorCode::KdfFailed, "Argon2 key derivation failed")
+                    })?;
+                Ok(key)
+            }
+            #[cfg(not(feature = "argon2"))]
+            Kdf::Argon2id { .. } => Err(BVaultError::new(
+                ErrorCode::UnsupportedAlgorithm,
+                "Argon2id is not enabled in this build",
+            )),
+            Kdf::Scrypt { log_n, r, p } => {
+                let params = ScryptParams::new(log_n, r, p, KEY_LENGTH).map_err(|_| {
+                    BVaultError::new(ErrorCode::WeakKdfParams, "invalid scrypt parameters")
+                })?;
+                let mut key = Key::default();
+                scrypt::scrypt(password, salt, &params, &mut key[..]).map_err(|_| {
+                    BVaultError::new(ErrorCode::KdfFailed, "scrypt key derivation failed")
+                })?;
+                Ok(key)
+            }
+        }
+    }
+
+    /// Like [`KdfParams::derive`], but yields to the JS event loop while it
+    /// works so the page stays responsive.
+    ///
+    /// PBKDF2 yields every few thousand iterations. Argon2 and scrypt cannot
+    /// be split, so they yield once and then run to completion.
+    ///
+    /// `on_progress`, if given, is called with the completed percentage
+    /// (0 to 100) at the start, after every PBKDF2 batch, and at the end.
+    /// Exceptions thrown by the callback are ignored.
+    ///
+    /// `signal`, if given, is checked after every yield; see
+    /// [`crate::cancel`].
+    #[cfg(feature = "cbc-legacy")]
+    pub(crate) async fn derive_async(
+        &self,
+        password: &[u8],
+        salt: &[u8],
+        on_progress: Option<&js_sys::Function>,
+        signal: Option<&AbortSignal>,
+    ) -> Result<Key, BVaultError> {
+        self.validate()?;
+        cancel::check(signal)?;
+        report_progress(on_progress, 0.0);
+
+        let key = match (keycache::lookup(self, password, salt), self.kdf) {
+            (Some(key), _) => key,
+            (None, Kdf::Pbkdf2 { iterations, hash }) => {
+                let mut steps = Pbkdf2Steps::new(hash, password, salt, iterations);
+                while !steps.run(PBKDF2_BATCH) {
+                    report_progress(on_progress, steps.percent());
+                    crate::utils::yield_now().await;
+                    // Dropping `steps` zeroizes the partial key.
+                    cancel::check(signal)?;
+                }
+                steps.key()
+            }
+            (None, Kdf::Argon2id { .. } | Kdf::Scrypt { .. }) => {
+                crate::utils::yield_now().await;
+                cancel::check(signal)?;
+                self.stretch(password, salt)?
+            }
+        };
+        cancel::check(signal)?;
+        keycache::store(self, password, salt, &key);
+        let key = self.mix_keyfile(key)?;
+
+        report_progress(on_progress, 100.0);
+        Ok(key)
+    }
+}
+
+#[cfg(feature = "cbc-legacy")]
+fn report_progress(on_progress: Option<&js_sys::Function>, percent: f64) {
+    if let Some(callback) = on_progress {
+        let _ = callback.call1(&JsValue::NULL, &JsValue::from_f64(percent));
+    }
+}
+
+/// HMAC keyed with the password, for either PBKDF2 hash.
+#[derive(Clone)]
+enum Prf {
+    Sha256(Hmac<Sha256>),
+    Sha512(Hmac<Sha512>),
+}
+
+impl Prf {
+    fn new(hash: Pbkdf2Hash, password: &[u8]) -> Prf {
+        const ANY_LENGTH: &str = "HMAC takes keys of any length";
+        match hash {
+            Pbkdf2Hash::Sha256 => Prf::Sha256(Hmac::new_from_slice(password).expect(ANY_LENGTH)),
+            Pbkdf2Hash::Sha512 => Prf::Sha512(Hmac::new_from_slice(password).expect(ANY_LENGTH)),
+        }
+    }
+
+    /// Output length of the hash, in bytes.
+    fn len(&self) -> usize {
+        match self {
+            Prf::Sha256(_) => 32,
+            Prf::Sha512(_) => 64,
+        }
+    }
+
+    /// Writes PRF(password, parts...) to the start of `out`.
+    fn compute(&self, parts: &[&[u8]], out: &mut [u8; PRF_MAX_LENGTH]) {
+        match self {
+            Prf::Sha256(prf) => {
+                let mut mac = prf.clone();
+                parts.iter().for_each(|part| mac.update(part));
+                out[..32].copy_from_slice(&mac.finalize().into_bytes());
+            }
+            Prf::Sha512(prf) => {
+                let mut mac = prf.clone();
+                parts.iter().for_each(|part| mac.update(part));
+                out.copy_from_slice(&mac.finalize().into_bytes());
+            }
+        }
+    }
+}
+
+/// PBKDF2 for a single 32-byte block, computed in resumable steps so the
+/// work can be spread over several event loop turns.
+#[cfg_attr(not(feature = "cbc-legacy"), allow(dead_code))]
+pub(crate) struct Pbkdf2Steps {
+    prf: Prf,
+    u: [u8; PRF_MAX_LENGTH],
+    key: [u8; KEY_LENGTH],
+    iterations: u32,
+    remaining: u32,
+}
+
+impl Pbkdf2Steps {
+    pub(crate) fn new(
+        hash: Pbkdf2Hash,
+        password: &[u8],
+        salt: &[u8],
+        iterations: u32,
+    ) -> Pbkdf2Steps {
+        let prf = Prf::new(hash, password);
+
+        // U1 = PRF(password, salt || INT(1))
+        let mut u = [0u8; PRF_MAX_LENGTH];
+        prf.compute(&[salt, &1u32.to_be_bytes()], &mut u);
+        let mut key = [0u8; KEY_LENGTH];
+        key.copy_from_slice(&u[..KEY_LENGTH]);
+
+        Pbkdf2Steps {
+            prf,
+            u,
+            key,
+            iterations,
+            remaining: iterations.saturating_sub(1),
+        }
+    }
+
+    /// Runs up to `max` further iterations; returns `true` once finished.
+    pub(crate) fn run(&mut self, max: u32) -> bool {
+        let count = max.min(self.remaining);
+        let len = self.prf.len();
+        let mut next = [0u8; PRF_MAX_LENGTH];
+        for _ in 0..count {
+            self.prf.compute(&[&self.u[..len]], &mut next);
+            self.u = next;
+            // The key is the first block of the output, so longer hashes
+            // are truncated to it.
+            for (k, u) in self.key.iter_mut().zip(self.u.iter()) {
+                *k ^= u;
+            }
+        }
+        next.zeroize();
+        self.remaining -= count;
+        self.remaining == 0
+    }
+
+    /// Share of the iterations completed so far, in percent.
+    #[cfg(feature = "cbc-legacy")]
+    pub(crate) fn percent(&self) -> f64 {
+        let done = self.iterations - self.remaining;
+        f64::from(done) * 100.0 / f64::from(self.iterations.max(1))
+    }
+
+    #[cfg(feature = "cbc-legacy")]
+    pub(crate) fn key(&self) -> Key {
+        Zeroizing::new(self.key)
+    }
+}
+
+impl Drop for Pbkdf2Steps {
+    fn drop(&mut self) {
+        self.u.zeroize();
+        self.key.zeroize();
+    }
+}
+
+/// Fills `okm` with HKDF-SHA256 output for `ikm`, `salt` and `info`.
+pub(crate) fn hkdf_sha256(
+    ikm: &[u8],
+    salt: Option<&[u8]>,
+    info: &[u8],
+    okm: &mut [u8],
+) -> Result<(), BVaultError> {
+    Hkdf::<Sha256>::new(salt, ikm)
+        .expand(info, okm)
+        .map_err(|_| {
+            BVaultError::new(
+                ErrorCode::BadLength,
+                "HKDF output must be at most 8160 bytes",
+            )
+        })
+}
+
+/// Derives `length` bytes from a master secret with HKDF-SHA256 (RFC 5869).
+///
+/// Use a distinct `info` label per purpose (e.g. `"encryption"`, `"mac"`,
+/// `"search-index"`) to get independent keys from the same `master`. The
+/// master must already be uniformly random: use a [`KdfParams`] KDF for
+/// passwords.
+///
+/// # Errors
+///
+/// - If `length` is 0 or greater than 8160, an error is returned.
+#[wasm_bindgen]
+pub fn hkdf_derive(
+    master: &[u8],
+    salt: Option<Vec<u8>>,
+    info: &[u8],
+    length: usize,
+) -> Result<Vec<u8>, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+
+    if length == 0 || length > MAX_HKDF_LENGTH {
+        return Err(BVaultError::new(
+            ErrorCode::BadLength,
+            "HKDF length must be between 1 and 8160 bytes",
+        ));
+    }
+
+    let mut okm = vec![0u8; length];
+    hkdf_sha256(master, salt.as_deref(), info, &mut okm)?;
+    Ok(okm)
+}
+
+/// Benchmarks `kdf` on this device and returns parameters of the same
+/// algorithm that take roughly `target_ms` to derive a key.
+///
+/// - PBKDF2: the iteration count is scaled to the budget.
+/// - Argon2id: the number of passes is scaled; if a single pass at the given
+///   memory cost is already too slow, memory is halved down to 8 MiB.
+/// - scrypt: N is scaled by powers of two, up to 2^20 (less with r above 8).
+///
+/// Results stay within the minimums and maximums every function enforces,
+/// so on a very slow device the recommendation may exceed the budget, and
+/// on a very fast one fall short of it. `kdf`
+/// defaults to PBKDF2 and only its algorithm and fixed costs (Argon2
+/// memory and lanes, scrypt r and p) are used as the starting point.
+///
+/// # Errors
+///
+/// - If `target_ms` is 0, an error is returned.
+/// - If the starting parameters are below the accepted minimum, an error is
+///   returned.
+#[wasm_bindgen]
+pub fn calibrate_kdf(target_ms: u32, kdf: Option<KdfParams>) -> Result<KdfParams, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+
+    if target_ms == 0 {
+        return Err(BVaultError::new(
+            ErrorCode::InvalidArgument,
+            "target time must be at least 1 ms",
+        ));
+    }
+    let target_ms = f64::from(target_ms);
+    let kdf = kdf.unwrap_or_default();
+    kdf.validate()?;
+
+    let password = b"bvault-calibration";
+    let salt = [0u8; 16];
+
+    let kdf = match kdf.kdf {
+        Kdf::Pbkdf2 { hash, .. } => {
+            // Time batches until the sample is long enough to be measurable.
+            let mut steps = Pbkdf2Steps::new(hash, password, &salt, u32::MAX);
+            let start = crate::utils::now_ms();
+            let mut done = 0u32;
+            let mut elapsed = 0.0;
+            while elapsed < CALIBRATION_SAMPLE_MS.min(target_ms) {
+                steps.run(PBKDF2_BATCH);
+                done += PBKDF2_BATCH;
+                elapsed = crate::utils::now_ms() - start;
+            }
+
+            let per_ms = f64::from(done) / elapsed.max(1.0);
+            let iterations = (per_ms * target_ms / 1_000.0).floor() * 1_000.0;
+            Kdf::Pbkdf2 {
+                iterations: (iterations.min(f64::from(MAX_PBKDF2_ITERATIONS)) as u32)
+                    .max(MIN_PBKDF2_ITERATIONS),
+                hash,
+            }
+        }
+        Kdf::Argon2id {
+            mut memory_kib,
+            parallelism,
+            ..
+        } => loop {
+            let one_pass = KdfParams::new(Kdf::Argon2id {
+                memory_kib,
+                iterations: 1,
+                parallelism,
+            });
+            let elapsed = time_derive(&one_pass, password, &salt)?;
+
+            if elapsed > target_ms && memory_kib / 2 >= MIN_ARGON2_MEMORY_KIB {
+                memory_kib /= 2;
+                continue;
+            }
+
+            let passes = (target_ms / elapsed.max(1.0)).floor();
+            break Kdf::Argon2id {
+                memory_kib,
+                iterations: (passes.min(f64::from(MAX_ARGON2_ITERATIONS)) as u32).max(1),
+                parallelism,
+            };
+        },
+        Kdf::Scrypt { log_n, r, p } => {
+            let elapsed = time_derive(&kdf, password, &salt)?;
+
+            // Each step of log_n doubles the work.
+            let steps = (target_ms / elapsed.max(1.0)).log2().floor();
+            let log_n = (f64::from(log_n) + steps)
+                .max(f64::from(MIN_SCRYPT_LOG_N))
+                .min(f64::from(max_scrypt_log_n(r)));
+            Kdf::Scrypt {
+                log_n: log_n as u8,
+                r,
+                p,
+            }
+        }
+    };
+
+    Ok(KdfParams::new(kdf))
+}
+
+/// Recommends Argon2id parameters for this device: the largest memory cost
+/// up to `max_memory_mb` (and 1 GiB) that the WASM heap can actually grow
+/// to, with the number of passes benchmarked to take roughly `target_ms`, as
+/// [`calibrate_kdf`] does.
+///
+/// Argon2 aborts the module when its memory cannot be allocated, which is
+/// how low-end mobile browsers fail on costs a desktop handles. The probe
+/// reserves the memory first and halves the cost until that succeeds, down
+/// to 8 MiB. WASM memory never shrinks, so a successful probe leaves the
+/// heap at that size; later derivations at the recommended cost reuse it.
+///
+/// # Errors
+///
+/// - If `max_memory_mb` is below 8 or `target_ms` is 0, an
+///   [`ErrorCode::InvalidArgument`] error is returned.
+/// - If not even 8 MiB can be allocated, an [`ErrorCode::KdfFailed`] error
+///   is returned.
+#[cfg(feature = "argon2")]
+#[wasm_bindgen]
+pub fn recommend_argon2_params(
+    max_memory_mb: u32,
+    target_ms: u32,
+) -> Result<KdfParams, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+
+    if max_memory_mb < MIN_ARGON2_MEMORY_KIB / 1024 {
+        return Err(BVaultError::new(
+            ErrorCode::InvalidArgument,
+            "Argon2 needs at least 8 MiB",
+        ));
+    }
+    let mut memory_kib = max_memory_mb
+        .saturating_mul(1024)
+        .min(MAX_RECOMMENDED_ARGON2_MEMORY_KIB);
+    while !can_allocate(memory_kib as usize * 1024 + ARGON2_PROBE_HEADROOM) {
+        if memory_kib / 2 < MIN_ARGON2_MEMORY_KIB {
+            return Err(BVaultError::new(
+                ErrorCode::KdfFailed,
+                "not enough memory for Argon2, even at 8 MiB",
+            ));
+        }
+        memory_kib /= 2;
+    }
+    calibrate_kdf(
+        target_ms,
+        Some(KdfParams::argon2id(Some(memory_kib), Some(1), None)),
+    )
+}
+
+/// Whether `bytes` can be allocated now, growing WASM memory if needed. The
+/// allocation is freed again at once.
+#[cfg(feature = "argon2")]
+fn can_allocate(bytes: usize) -> bool {
+    Vec::<u8>::new().try_reserve_exact(bytes).is_ok()
+}
+
+/// Bytes of memory scrypt needs with N = 2^`log_n` and block size `r`.
+fn scrypt_memory(log_n: u8, r: u32) -> u64 {
+    (128 * u64::from(r)) << log_n
+}
+
+/// The highest scrypt cost accepted with block size `r`, as log2(N).
+fn max_scrypt_log_n(r: u32) -> u8 {
+    (MIN_SCRYPT_LOG_N..=MAX_SCRYPT_LOG_N)
+        .rev()
+        .find(|&log_n| scrypt_memory(log_n, r) <= MAX_SCRYPT_MEMORY)
+        .unwrap_or(MIN_SCRYPT_LOG_N)
+}
+
+/// Runs one derivation with `kdf` and returns how long it took, in ms. The
+/// key cache is bypassed, so a cached key cannot cut the timing short.
+fn time_derive(kdf: &KdfParams, password: &[u8], salt: &[u8]) -> Result<f64, BVaultError> {
+    let start = crate::utils::now_ms();
+    kdf.stretch(password, salt)?;
+    Ok(crate::utils::now_ms() - start)
+}
@@ -0,0 +1,172 @@
+//! An optional cache of password-derived keys.
+//!
+//! A [`crate::VaultSession`] keeps its key between calls, but code that
+//! cannot hold one, such as a stateless service worker, pays the full KDF
+//! on every call even for the same password and salt. With
+//! [`enable_key_cache`], the keys derived by every password-based function
+//! are kept for a short time and reused for the same password, salt and
+//! KDF parameters, so repeating a derivation is free.
+//!
+//! The cache holds at most `max_entries` keys and drops the least recently
+//! used one beyond that; each key also expires `ttl_ms` after it was
+//! derived. Keys are wiped when they are dropped, by [`clear_key_cache`]
+//! (call it on logout) and by [`disable_key_cache`]. Entries are found by
+//! an HMAC of the password and salt under a random secret made when the
+//! cache is enabled, so the passwords themselves are not kept.
+//!
+//! A cached key is exactly as sensitive as the data it opens, and a
+//! derivation that returns at once shows that the password was used
+//! recently. Only enable the cache where that is acceptable.
+
+use std::sync::{Mutex, PoisonError};
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use wasm_bindgen::prelude::*;
+use zeroize::Zeroizing;
+
+use crate::kdf::{Kdf, KdfParams, Key};
+use crate::{BVaultError, ErrorCode};
+
+type Fingerprint = [u8; 32];
+
+static CACHE: Mutex<Option<KeyCache>> = Mutex::new(None);
+
+struct KeyCache {
+    max_entries: usize,
+    ttl_ms: f64,
+    secret: Zeroizing<Vec<u8>>,
+    entries: Vec<Entry>,
+}
+
+struct Entry {
+    fingerprint: Fingerprint,
+    kdf: Kdf,
+    /// The output of the password KDF, before any keyfile is mixed in.
+    key: Key,
+    expires_ms: f64,
+    last_used_ms: f64,
+}
+
+impl KeyCache {
+    fn fingerprint(&self, password: &[u8], salt: &[u8]) -> Fingerprint {
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(&self.secret).expect("HMAC takes keys of any length");
+        mac.update(&(password.len() as u64).to_be_bytes());
+        mac.update(password);
+        mac.update(salt);
+        mac.finalize().into_bytes().into()
+    }
+
+    fn prune(&mut self, now: f64) {
+        self.entries.retain(|entry| entry.expires_ms > now);
+    }
+}
+
+/// The cached key for `password` and `salt` under `kdf`, if the cache is
+/// on and holds one.
+pub(crate) fn lookup(kdf: &KdfParams, password: &[u8], salt: &[u8]) -> Option<Key> {
+    let mut cache = CACHE.lock().unwrap_or_else(PoisonError::into_inner);
+    let cache = cache.as_mut()?;
+    let now = crate::utils::now_ms();
+    cache.prune(now);
+    let fingerprint = cache.fingerprint(password, salt);
+    let entry = cache.entries.iter_mut().find(|entry| {
+        entry.kdf == kdf.kdf() && crate::constant_time_eq(&entry.fingerprint, &fingerprint)
+    })?;
+    entry.last_used_ms = now;
+    Some(entry.key.clone())
+}
+
+/// Caches `key` as derived from `password` and `salt` under `kdf`, if the
+/// cache is on.
+pub(crate) fn store(kdf: &KdfParams, password: &[u8], salt: &[u8], key: &Key) {
+    let mut cache = CACHE.lock().unwrap_or_else(PoisonError::into_inner);
+    let cache = match cache.as_mut() {
+        Some(cache) => cache,
+        None => return,
+    };
+    let now = crate::utils::now_ms();
+    cache.prune(now);
+    let fingerprint = cache.fingerprint(password, salt);
+    cache.entries.retain(|entry| {
+        !(entry.kdf == kdf.kdf() && crate::constant_time_eq(&entry.fingerprint, &fingerprint))
+    });
+    if cache.entries.len() >= cache.max_entries {
+        let oldest = cache
+            .entries
+            .iter()
+            .enumerate()
+            .min_by(|a, b| a.1.last_used_ms.total_cmp(&b.1.last_used_ms))
+            .map(|(index, _)| index);
+        if let Some(oldest) = oldest {
+            cache.entries.remove(oldest);
+        }
+    }
+    let expires_ms = now + cache.ttl_ms;
+    cache.entries.push(Entry {
+        fingerprint,
+        kdf: kdf.kdf(),
+        key: key.clone(),
+        expires_ms,
+        last_used_ms: now,
+    });
+}
+
+/// Turns on the key cache, holding at most `max_entries` keys for
+/// `ttl_ms` milliseconds each. Any keys cached before are wiped.
+///
+/// # Errors
+///
+/// - If `max_entries` is 0 or `ttl_ms` is not a positive number, an
+///   [`ErrorCode::InvalidArgument`] error is returned.
+/// - If no secure random source is available, an error is returned.
+#[wasm_bindgen]
+pub fn enable_key_cache(max_entries: u32, ttl_ms: f64) -> Result<(), BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    if !(max_entries > 0 && ttl_ms > 0.0) {
+        return Err(BVaultError::new(
+            ErrorCode::InvalidArgument,
+            "max_entries must be at least 1 and ttl_ms a positive number",
+        ));
+    }
+    let secret = Zeroizing::new(crate::random_bytes(32)?);
+    *CACHE.lock().unwrap_or_else(PoisonError::into_inner) = Some(KeyCache {
+        max_entries: max_entries as usize,
+        ttl_ms,
+        secret,
+        entries: Vec::new(),
+    });
+    Ok(())
+}
+
+/// Turns the key cache off, wiping every cached key.
+#[wasm_bindgen]
+pub fn disable_key_cache() {
+    let _operation = crate::utils::set_panic_hook();
+    *CACHE.lock().unwrap_or_else(PoisonError::into_inner) = None;
+}
+
+/// Wipes every cached key; the cache stays on if it was.
+#[wasm_bindgen]
+pub fn clear_key_cache() {
+    let _operation = crate::utils::set_panic_hook();
+    if let Some(cache) = CACHE
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .as_mut()
+    {
+        cache.entries.clear();
+    }
+}
+
+/// How many keys are cached and not yet expired; 0 if the cache is off.
+#[wasm_bindgen]
+pub fn key_cache_size() -> u32 {
+    let _operation = crate::utils::set_panic_hook();
+    let mut cache = CACHE.lock().unwrap_or_else(PoisonError::into_inner);
+    cache.as_mut().map_or(0, |cache| {
+        cache.prune(crate::utils::now_ms());
+        cache.entries.len() as u32
+    })
+}
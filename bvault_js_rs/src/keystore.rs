@@ -0,0 +1,336 @@
+//! Ethereum keystore v3 files (the "Web3 Secret Storage" format used by
+//! geth, MetaMask and ethers).
+//!
+//! ```text
+//! {
+//!   "crypto": {
+//!     "cipher": "aes-128-ctr",
+//!     "cipherparams": { "iv": "<hex, 16 bytes>" },
+//!     "ciphertext": "<hex>",
+//!     "kdf": "scrypt" | "pbkdf2",
+//!     "kdfparams": { "dklen": 32, "n": …, "r": …, "p": …, "salt": "<hex>" }
+//!                | { "dklen": 32, "c": …, "prf": "hmac-sha256" | "hmac-sha512", "salt": "<hex>" },
+//!     "mac": "<hex>"
+//!   },
+//!   "id": "<uuid>",
+//!   "version": 3
+//! }
+//! ```
+//!
+//! The 32-byte derived key is split in two: the first half is the AES-128-CTR
+//! key, and `mac` is Keccak-256(second half || ciphertext). Key derivation
+//! goes through [`KdfParams`], so keystores below the crate's KDF minimums
+//! (scrypt N < 2^14, PBKDF2 c < 10 000) are rejected.
+
+use aes::Aes128;
+use ctr::cipher::{KeyIvInit, StreamCipher};
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Keccak256};
+use subtle::ConstantTimeEq;
+use wasm_bindgen::prelude::*;
+use zeroize::Zeroizing;
+
+use crate::kcv::wrong_password;
+use crate::kdf::{Kdf, Pbkdf2Hash, KEY_LENGTH};
+use crate::utils::{from_hex, to_hex};
+use crate::{random_bytes, BVaultError, ErrorCode, KdfParams};
+
+type Aes128Ctr = ctr::Ctr128BE<Aes128>;
+
+const VERSION: u8 = 3;
+const CIPHER: &str = "aes-128-ctr";
+const PRF_SHA256: &str = "hmac-sha256";
+const PRF_SHA512: &str = "hmac-sha512";
+
+const IV_LENGTH: usize = 16;
+const SALT_LENGTH: usize = 32;
+const PRIVATE_KEY_LENGTH: usize = 32;
+
+/// scrypt cost geth uses for new keystores, as log2(N).
+const DEFAULT_SCRYPT_LOG_N: u8 = 18;
+/// Highest scrypt cost accepted, as log2(N) (1 GiB with r = 8).
+const MAX_SCRYPT_LOG_N: u8 = 20;
+/// Highest PBKDF2 count accepted, so a hostile file cannot stall the page.
+const MAX_PBKDF2_ITERATIONS: u32 = 10_000_000;
+
+/// A keystore file. Unknown members (`address`, `x-ethers`, …) are ignored
+/// on import.
+#[derive(Serialize, Deserialize)]
+struct KeystoreJson {
+    #[serde(alias = "Crypto")]
+    crypto: CryptoJson,
+    #[serde(default)]
+    id: String,
+    version: u8,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CryptoJson {
+    cipher: String,
+    cipherparams: CipherParamsJson,
+    ciphertext: String,
+    #[serde(flatten)]
+    kdf: KdfJson,
+    mac: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CipherParamsJson {
+    iv: String,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "kdf", content = "kdfparams", rename_all = "lowercase")]
+enum KdfJson {
+    Scrypt {
+        dklen: usize,
+        n: u64,
+        r: u32,
+        p: u32,
+        salt: String,
+    },
+    Pbkdf2 {
+        c: u32,
+        dklen: usize,
+        prf: String,
+        salt: String,
+    },
+}
+
+impl KdfJson {
+    /// The crate's descriptor for these parameters, and the salt.
+    fn params(&self) -> Result<(KdfParams, Vec<u8>), BVaultError> {
+        let (dklen, salt, kdf) = match self {
+            KdfJson::Scrypt {
+                dklen,
+                n,
+                r,
+                p,
+                salt,
+            } => {
+                if *n < 2 || !n.is_power_of_two() {
+                    return Err(invalid_json("crypto.kdfparams.n", "must be a power of two"));
+                }
+                let log_n = n.trailing_zeros() as u8;
+                if log_n > MAX_SCRYPT_LOG_N {
+                    return Err(BVaultError::new(
+                        ErrorCode::WeakKdfParams,
+                        "scrypt N is above 2^20",
+                    ));
+                }
+                (
+                    dklen,
+                    salt,
+                    KdfParams::scrypt(Some(log_n), Some(*r), Some(*p)),
+                )
+            }
+            KdfJson::Pbkdf2 {
+                c,
+                dklen,
+                prf,
+                salt,
+            } => {
+                let pbkdf2 = match prf.as_str() {
+                    PRF_SHA256 => KdfParams::pbkdf2,
+                    PRF_SHA512 => KdfParams::pbkdf2_sha512,
+                    _ => {
+                        return Err(BVaultError::new(
+                            ErrorCode::UnsupportedAlgorithm,
+                            format!(
+                                "unsupported keystore PRF {}; expected {} or {}",
+                                prf, PRF_SHA256, PRF_SHA512
+                            ),
+                        ))
+                    }
+                };
+                if *c > MAX_PBKDF2_ITERATIONS {
+                    return Err(BVaultError::new(
+                        ErrorCode::WeakKdfParams,
+                        "PBKDF2 c is above 10 000 000",
+                    ));
+                }
+                (dklen, salt, pbkdf2(*c))
+            }
+        };
+        if *dklen != KEY_LENGTH {
+            return Err(invalid_json("crypto.kdfparams.dklen", "must be 32"));
+        }
+        Ok((kdf, decode_hex("crypto.kdfparams.salt", salt)?))
+    }
+}
+
+/// Keccak-256(mac key || ciphertext), the keystore's integrity check.
+fn keystore_mac(mac_key: &[u8], ciphertext: &[u8]) -> [u8; 32] {
+    let mut keccak = Keccak256::new();
+    keccak.update(mac_key);
+    keccak.update(ciphertext);
+    keccak.finalize().into()
+}
+
+/// Applies AES-128-CTR under the first half of `derived`.
+fn apply_ctr(derived: &[u8], iv: &[u8], buf: &mut [u8]) {
+    Aes128Ctr::new(derived[..16].into(), iv.into()).apply_keystream(buf);
+}
+
+/// A random (version 4) UUID for the `id` member.
+fn uuid_v4() -> Result<String, BVaultError> {
+    let mut bytes = random_bytes(16)?;
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex = to_hex(&bytes);
+    Ok(format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    ))
+}
+
+/// Decrypts an Ethereum keystore v3 file, returning the raw private key.
+///
+/// # Errors
+///
+/// - If the JSON does not match the keystore schema, an
+///   [`ErrorCode::InvalidJson`] error naming the offending field is
+///   returned.
+/// - If the version is not 3, or the cipher or PRF is not the standard
+///   one, an error is returned.
+/// - If the KDF parameters are below the crate's minimums, or absurdly
+///   high, an error is returned.
+/// - If the password is wrong or the file was tampered with, an
+///   [`ErrorCode::WrongPassword`] error is returned.
+#[wasm_bindgen]
+pub fn keystore_decrypt(json: &str, password: &str) -> Result<Vec<u8>, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    let deserializer = &mut serde_json::Deserializer::from_str(json);
+    let keystore: KeystoreJson = serde_path_to_error::deserialize(deserializer).map_err(|e| {
+        let path = e.path().to_string();
+        invalid_json(&path, e.into_inner().to_string())
+    })?;
+    if keystore.version != VERSION {
+        return Err(BVaultError::new(
+            ErrorCode::UnsupportedVersion,
+            format!("unsupported keystore version {}", keystore.version),
+        ));
+    }
+    let crypto = keystore.crypto;
+    if crypto.cipher != CIPHER {
+        return Err(BVaultError::new(
+            ErrorCode::UnsupportedAlgorithm,
+            format!(
+                "unsupported keystore cipher {}; expected {}",
+                crypto.cipher, CIPHER
+            ),
+        ));
+    }
+    let iv = decode_hex("crypto.cipherparams.iv", &crypto.cipherparams.iv)?;
+    if iv.len() != IV_LENGTH {
+        return Err(invalid_json("crypto.cipherparams.iv", "must be 16 bytes"));
+    }
+    let ciphertext = decode_hex("crypto.ciphertext", &crypto.ciphertext)?;
+    let mac = decode_hex("crypto.mac", &crypto.mac)?;
+
+    let (kdf, salt) = crypto.kdf.params()?;
+    let derived = kdf.derive(password.as_bytes(), &salt)?;
+    let expected = keystore_mac(&derived[16..], &ciphertext);
+    if !bool::from(expected.ct_eq(&mac)) {
+        return Err(wrong_password());
+    }
+
+    let mut private_key = ciphertext;
+    apply_ctr(&derived[..], &iv, &mut private_key);
+    Ok(private_key)
+}
+
+/// Encrypts a 32-byte private key into a keystore v3 file that geth,
+/// MetaMask and ethers can import.
+///
+/// `kdf` must be scrypt or PBKDF2; the default is scrypt with N = 2^18,
+/// r = 8 and p = 1, as geth uses. The optional `address` member is not
+/// written, since computing it needs secp256k1.
+///
+/// # Errors
+///
+/// - If `private_key` is not 32 bytes, an error is returned.
+/// - If `kdf` is Argon2id or mixes in a keyfile, an error is returned.
+/// - If the KDF parameters are below the accepted minimum, an error is
+///   returned.
+/// - If the random number generator is unavailable, an error is returned.
+#[wasm_bindgen]
+pub fn keystore_encrypt(
+    private_key: &[u8],
+    password: &str,
+    kdf: Option<KdfParams>,
+) -> Result<String, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    if private_key.len() != PRIVATE_KEY_LENGTH {
+        return Err(BVaultError::new(
+            ErrorCode::BadKeyLength,
+            "private keys must be 32 bytes",
+        ));
+    }
+    let kdf = kdf.unwrap_or_else(|| KdfParams::scrypt(Some(DEFAULT_SCRYPT_LOG_N), None, None));
+    if kdf.has_keyfile() {
+        return Err(BVaultError::new(
+            ErrorCode::UnsupportedAlgorithm,
+            "keystore files cannot use a keyfile",
+        ));
+    }
+
+    let salt = random_bytes(SALT_LENGTH)?;
+    let kdf_json = match kdf.kdf() {
+        Kdf::Scrypt { log_n, r, p } => KdfJson::Scrypt {
+            dklen: KEY_LENGTH,
+            n: 1 << log_n,
+            r,
+            p,
+            salt: to_hex(&salt),
+        },
+        Kdf::Pbkdf2 { iterations, hash } => KdfJson::Pbkdf2 {
+            c: iterations,
+            dklen: KEY_LENGTH,
+            prf: match hash {
+                Pbkdf2Hash::Sha256 => PRF_SHA256,
+                Pbkdf2Hash::Sha512 => PRF_SHA512,
+            }
+            .to_string(),
+            salt: to_hex(&salt),
+        },
+        Kdf::Argon2id { .. } => {
+            return Err(BVaultError::new(
+                ErrorCode::UnsupportedAlgorithm,
+                "keystore files only support scrypt and PBKDF2",
+            ))
+        }
+    };
+    let derived = kdf.derive(password.as_bytes(), &salt)?;
+
+    let iv = random_bytes(IV_LENGTH)?;
+    let mut ciphertext = Zeroizing::new(private_key.to_vec());
+    apply_ctr(&derived[..], &iv, &mut ciphertext);
+    let mac = keystore_mac(&derived[16..], &ciphertext);
+
+    let keystore = KeystoreJson {
+        crypto: CryptoJson {
+            cipher: CIPHER.to_string(),
+            cipherparams: CipherParamsJson { iv: to_hex(&iv) },
+            ciphertext: to_hex(&ciphertext),
+            kdf: kdf_json,
+            mac: to_hex(&mac),
+        },
+        id: uuid_v4()?,
+        version: VERSION,
+    };
+    serde_json::to_string(&keystore)
+        .map_err(|e| BVaultError::new(ErrorCode::InvalidJson, e.to_string()))
+}
+
+fn decode_hex(path: &str, hex: &str) -> Result<Vec<u8>, BVaultError> {
+    from_hex(hex).ok_or_else(|| invalid_json(path, "invalid hex"))
+}
+
+fn invalid_json(path: &str, message: impl std::fmt::Display) -> BVaultError {
+    BVaultError::new(ErrorCode::InvalidJson, format!("{}: {}", path, message))
+}
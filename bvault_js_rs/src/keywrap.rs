@@ -0,0 +1,150 @@
+//! AES Key Wrap (RFC 3394) and AES Key Wrap with Padding (RFC 5649), for
+//! data-encryption keys wrapped under a key-encryption key by the backend.
+//!
+//! Key-encryption keys may be 16, 24 or 32 bytes, selecting AES-128, -192
+//! or -256. Plain key wrap takes keys that are a multiple of 8 bytes and at
+//! least 16 bytes long; the padded variant takes any non-empty key. Both
+//! add 8 bytes (the padded variant after rounding up to a multiple of 8).
+
+use aes_kw::{Error, KekAes128, KekAes192, KekAes256};
+use wasm_bindgen::prelude::*;
+
+use crate::{BVaultError, ErrorCode};
+
+/// Length of the integrity check value prepended by the wrap.
+const SEMIBLOCK: usize = 8;
+
+enum Kek {
+    Aes128(KekAes128),
+    Aes192(KekAes192),
+    Aes256(KekAes256),
+}
+
+impl Kek {
+    fn new(kek: &[u8]) -> Result<Kek, BVaultError> {
+        Ok(match kek.len() {
+            16 => Kek::Aes128(KekAes128::new(kek.into())),
+            24 => Kek::Aes192(KekAes192::new(kek.into())),
+            32 => Kek::Aes256(KekAes256::new(kek.into())),
+            _ => {
+                return Err(BVaultError::new(
+                    ErrorCode::BadKeyLength,
+                    "key-encryption keys must be 16, 24 or 32 bytes",
+                ))
+            }
+        })
+    }
+}
+
+fn wrap_error(error: Error) -> BVaultError {
+    match error {
+        Error::IntegrityCheckFailed => BVaultError::decryption_failed(
+            ErrorCode::AuthFailed,
+            "key does not unwrap with this key-encryption key",
+        ),
+        _ => BVaultError::new(ErrorCode::BadLength, "invalid wrapped key length"),
+    }
+}
+
+/// Wraps `key` under `kek` with RFC 3394 AES Key Wrap.
+///
+/// # Errors
+///
+/// - If `kek` is not 16, 24 or 32 bytes, an error is returned.
+/// - If `key` is shorter than 16 bytes or not a multiple of 8 bytes, an
+///   error is returned.
+#[wasm_bindgen]
+pub fn aes_kw_wrap(kek: &[u8], key: &[u8]) -> Result<Vec<u8>, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    let kek = Kek::new(kek)?;
+    if key.len() < 2 * SEMIBLOCK || !key.len().is_multiple_of(SEMIBLOCK) {
+        return Err(BVaultError::new(
+            ErrorCode::BadLength,
+            "AES-KW keys must be a multiple of 8 bytes and at least 16 bytes",
+        ));
+    }
+    match kek {
+        Kek::Aes128(kek) => kek.wrap_vec(key),
+        Kek::Aes192(kek) => kek.wrap_vec(key),
+        Kek::Aes256(kek) => kek.wrap_vec(key),
+    }
+    .map_err(wrap_error)
+}
+
+/// Unwraps a key wrapped with [`aes_kw_wrap`] or any RFC 3394
+/// implementation.
+///
+/// # Errors
+///
+/// - If `kek` is not 16, 24 or 32 bytes, an error is returned.
+/// - If `wrapped` has an impossible length, an error is returned.
+/// - If the integrity check fails (wrong KEK or tampered data), an
+///   [`ErrorCode::AuthFailed`] error is returned.
+#[wasm_bindgen]
+pub fn aes_kw_unwrap(kek: &[u8], wrapped: &[u8]) -> Result<Vec<u8>, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    let kek = Kek::new(kek)?;
+    if wrapped.len() < 3 * SEMIBLOCK || !wrapped.len().is_multiple_of(SEMIBLOCK) {
+        return Err(BVaultError::new(
+            ErrorCode::BadLength,
+            "invalid wrapped key length",
+        ));
+    }
+    match kek {
+        Kek::Aes128(kek) => kek.unwrap_vec(wrapped),
+        Kek::Aes192(kek) => kek.unwrap_vec(wrapped),
+        Kek::Aes256(kek) => kek.unwrap_vec(wrapped),
+    }
+    .map_err(wrap_error)
+}
+
+/// Wraps `key` under `kek` with RFC 5649 AES Key Wrap with Padding.
+///
+/// # Errors
+///
+/// - If `kek` is not 16, 24 or 32 bytes, an error is returned.
+/// - If `key` is empty, an error is returned.
+#[wasm_bindgen]
+pub fn aes_kwp_wrap(kek: &[u8], key: &[u8]) -> Result<Vec<u8>, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    let kek = Kek::new(kek)?;
+    if key.is_empty() {
+        return Err(BVaultError::new(
+            ErrorCode::BadLength,
+            "AES-KWP keys must not be empty",
+        ));
+    }
+    match kek {
+        Kek::Aes128(kek) => kek.wrap_with_padding_vec(key),
+        Kek::Aes192(kek) => kek.wrap_with_padding_vec(key),
+        Kek::Aes256(kek) => kek.wrap_with_padding_vec(key),
+    }
+    .map_err(wrap_error)
+}
+
+/// Unwraps a key wrapped with [`aes_kwp_wrap`] or any RFC 5649
+/// implementation.
+///
+/// # Errors
+///
+/// - If `kek` is not 16, 24 or 32 bytes, an error is returned.
+/// - If `wrapped` has an impossible length, an error is returned.
+/// - If the integrity check fails (wrong KEK or tampered data), an
+///   [`ErrorCode::AuthFailed`] error is returned.
+#[wasm_bindgen]
+pub fn aes_kwp_unwrap(kek: &[u8], wrapped: &[u8]) -> Result<Vec<u8>, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    let kek = Kek::new(kek)?;
+    if wrapped.len() < 2 * SEMIBLOCK || !wrapped.len().is_multiple_of(SEMIBLOCK) {
+        return Err(BVaultError::new(
+            ErrorCode::BadLength,
+            "invalid wrapped key length",
+        ));
+    }
+    match kek {
+        Kek::Aes128(kek) => kek.unwrap_with_padding_vec(wrapped),
+        Kek::Aes192(kek) => kek.unwrap_with_padding_vec(wrapped),
+        Kek::Aes256(kek) => kek.unwrap_with_padding_vec(wrapped),
+    }
+    .map_err(wrap_error)
+}
@@ -0,0 +1,352 @@
+//! The original password API: AES-256-CBC with PKCS#7 padding and an
+//! HMAC-SHA256 tag (encrypt-then-MAC), with base64 or raw-bytes inputs and
+//! outputs. New data should use an AEAD suite or an envelope (see
+//! `envelope`); this module stays so existing ciphertexts keep decrypting.
+
+use aes::cipher::block_padding::NoPadding;
+use aes::cipher::{BlockDecryptMut, BlockEncryptMut, KeyIvInit};
+use subtle::{ConstantTimeEq, ConstantTimeGreater};
+use wasm_bindgen::prelude::*;
+use zeroize::Zeroize;
+
+use crate::{
+    b64_to_bytes, derive_key, mac, new_salted_key, random_bytes, utils, BVaultError,
+    EncryptOptions, EncryptedBytes, EncryptedData, ErrorCode, KdfParams, TextDecoding, IV_LENGTH,
+};
+
+pub(crate) type Aes256CbcEnc = cbc::Encryptor<aes::Aes256>;
+pub(crate) type Aes256CbcDec = cbc::Decryptor<aes::Aes256>;
+
+/// AES block size, in bytes.
+const BLOCK_SIZE: usize = 16;
+
+/// The suite named in errors of this module.
+const SUITE: &str = "aes-256-cbc";
+
+pub(crate) fn padding_error() -> BVaultError {
+    BVaultError::decryption_failed(ErrorCode::PaddingError, "decryption / padding error")
+}
+
+/// Length of the data in PKCS#7-padded `buf`, or `None` if the padding is
+/// invalid. The whole last block is checked with the same operations
+/// whatever its contents, so the timing reveals nothing about the padding.
+pub(crate) fn pkcs7_data_len(buf: &[u8]) -> Option<usize> {
+    if buf.is_empty() || !buf.len().is_multiple_of(BLOCK_SIZE) {
+        return None;
+    }
+    let last = &buf[buf.len() - BLOCK_SIZE..];
+    let pad = last[BLOCK_SIZE - 1];
+    let mut valid = !pad.ct_eq(&0) & !pad.ct_gt(&(BLOCK_SIZE as u8));
+    for (i, byte) in last.iter().enumerate() {
+        let from_end = (BLOCK_SIZE - i) as u8;
+        let in_padding = !from_end.ct_gt(&pad);
+        valid &= !in_padding | byte.ct_eq(&pad);
+    }
+    if bool::from(valid) {
+        Some(buf.len() - usize::from(pad))
+    } else {
+        None
+    }
+}
+
+pub(crate) fn cbc_encrypt_with_key(
+    key: &[u8],
+    iv: &[u8],
+    plaintext: &[u8],
+) -> Result<Vec<u8>, BVaultError> {
+    let enc = Aes256CbcEnc::new_from_slices(key, iv)
+        .map_err(|_| BVaultError::new(ErrorCode::BadKeyLength, "invalid key/iv length"))?;
+    Ok(enc.encrypt_padded_vec_mut::<cbc::cipher::block_padding::Pkcs7>(plaintext))
+}
+
+/// Decrypts a CBC ciphertext, first checking its tag if `tag` is given.
+pub(crate) fn cbc_decrypt_with_key(
+    key: &[u8],
+    iv: &[u8],
+    ciphertext: Vec<u8>,
+    tag: Option<&[u8]>,
+) -> Result<Vec<u8>, BVaultError> {
+    if let Some(tag) = tag {
+        mac::verify_tag(key, iv, &ciphertext, tag)?;
+    }
+
+    let mut buf = ciphertext;
+    let dec = Aes256CbcDec::new_from_slices(key, iv)
+        .map_err(|_| BVaultError::new(ErrorCode::BadKeyLength, "invalid key/iv length"))?;
+
+    let len = match dec
+        .decrypt_padded_mut::<NoPadding>(&mut buf)
+        .ok()
+        .and_then(pkcs7_data_len)
+    {
+        Some(len) => len,
+        None => {
+            buf.zeroize();
+            return Err(padding_error());
+        }
+    };
+    // Wipe the padding bytes before they are cut off.
+    buf[len..].zeroize();
+    buf.truncate(len);
+
+    Ok(buf)
+}
+
+/// Decoded inputs of a CBC decryption.
+pub(crate) struct CbcInputs {
+    pub(crate) ciphertext: Vec<u8>,
+    pub(crate) iv: Vec<u8>,
+    pub(crate) salt: Vec<u8>,
+}
+
+pub(crate) fn decode_cbc_inputs(
+    b64_ciphertext: &str,
+    b64_iv: &str,
+    b64_salt: &str,
+) -> Result<CbcInputs, BVaultError> {
+    let ciphertext = b64_to_bytes(b64_ciphertext)?;
+    let iv = b64_to_bytes(b64_iv)?;
+    let salt = b64_to_bytes(b64_salt)?;
+    check_iv(&iv)?;
+
+    Ok(CbcInputs {
+        ciphertext,
+        iv,
+        salt,
+    })
+}
+
+fn check_iv(iv: &[u8]) -> Result<(), BVaultError> {
+    if iv.len() != IV_LENGTH {
+        return Err(BVaultError::new(
+            ErrorCode::BadIvLength,
+            "IV must be 16 bytes",
+        ));
+    }
+    Ok(())
+}
+
+fn encrypt_cbc(
+    plaintext: &[u8],
+    password: &str,
+    options: &EncryptOptions,
+) -> Result<EncryptedBytes, BVaultError> {
+    options.reject_aad()?;
+
+    // --- key derivation -----------------------------------------------------
+    let (salt, key) = new_salted_key(password, options.salt_length, &options.kdf, None)?;
+    let iv = random_bytes(IV_LENGTH)?;
+
+    // --- encryption ----------------------------------------------------------
+    let ciphertext =
+        cbc_encrypt_with_key(&key[..], &iv, plaintext).map_err(|err| err.with_suite(SUITE))?;
+    let tag = mac::tag(&key[..], &iv, &ciphertext)?;
+
+    Ok(EncryptedBytes {
+        ciphertext,
+        iv,
+        salt,
+        mac: Some(tag),
+    })
+}
+
+pub(crate) fn decrypt_cbc(
+    b64_ciphertext: &str,
+    password: &str,
+    b64_iv: &str,
+    b64_salt: &str,
+    kdf: Option<KdfParams>,
+    b64_mac: Option<&str>,
+) -> Result<Vec<u8>, BVaultError> {
+    // --- inputs --------------------------------------------------------------
+    crate::policy::check_input(|| {
+        crate::validation::diagnose_legacy(b64_ciphertext, b64_iv, b64_salt, kdf.as_ref(), b64_mac)
+    })?;
+    let inputs = decode_cbc_inputs(b64_ciphertext, b64_iv, b64_salt)?;
+    let tag = b64_mac.map(b64_to_bytes).transpose()?;
+
+    decrypt_cbc_bytes(
+        inputs.ciphertext,
+        password,
+        &inputs.iv,
+        &inputs.salt,
+        kdf,
+        tag.as_deref(),
+    )
+}
+
+fn decrypt_cbc_bytes(
+    ciphertext: Vec<u8>,
+    password: &str,
+    iv: &[u8],
+    salt: &[u8],
+    kdf: Option<KdfParams>,
+    tag: Option<&[u8]>,
+) -> Result<Vec<u8>, BVaultError> {
+    check_iv(iv)?;
+
+    // --- key derivation -----------------------------------------------------
+    let key = derive_key(password, salt, kdf)?;
+
+    // --- authentication + decryption ------------------------------------------
+    cbc_decrypt_with_key(&key[..], iv, ciphertext, tag).map_err(|err| err.with_suite(SUITE))
+}
+
+/// Synchronously encrypts a plaintext string using a password.
+///
+/// A random salt and IV are generated for every call, the key is derived
+/// with PBKDF2-HMAC-SHA256 (100 000 iters unless `options` selects another
+/// KDF) and the plaintext is encrypted with AES-256-CBC (PKCS#7 padding).
+/// The result carries an HMAC-SHA256 tag (encrypt-then-MAC) in `mac`.
+///
+/// # Errors
+///
+/// - If the requested salt length is shorter than 8 bytes, an error is returned.
+/// - If the parameters or the password fall short of the security policy
+///   (see [`crate::set_policy`]), an error is returned.
+/// - If the KDF parameters are below the accepted minimum, an error is returned.
+/// - If the random number generator is unavailable, an error is returned.
+#[wasm_bindgen]
+pub fn encrypt_sync(
+    plaintext: &str,
+    password: &str,
+    options: Option<EncryptOptions>,
+) -> Result<EncryptedData, BVaultError> {
+    let _operation = utils::set_panic_hook();
+    let options = options.unwrap_or_default();
+    Ok(encrypt_cbc(plaintext.as_bytes(), password, &options)?.encode(options.encoding))
+}
+
+/// Synchronously encrypts arbitrary bytes using a password.
+///
+/// Identical to [`encrypt_sync`], but takes a `Uint8Array` so binary
+/// payloads (images, PDFs, ...) can be stored without a text encoding.
+///
+/// # Errors
+///
+/// See [`encrypt_sync`].
+#[wasm_bindgen]
+pub fn encrypt_bytes_sync(
+    plaintext: &[u8],
+    password: &str,
+    options: Option<EncryptOptions>,
+) -> Result<EncryptedData, BVaultError> {
+    let _operation = utils::set_panic_hook();
+    let options = options.unwrap_or_default();
+    Ok(encrypt_cbc(plaintext, password, &options)?.encode(options.encoding))
+}
+
+/// Synchronously decrypts a base64-encoded ciphertext using a password,
+/// a base64-encoded IV, and a base64-encoded salt.
+///
+/// `kdf` must match the parameters used at encryption time and defaults to
+/// PBKDF2 with 100 000 iterations when omitted.
+///
+/// `b64_mac` is the `mac` from [`EncryptedData`]. When given, the ciphertext
+/// is authenticated before it is decrypted, so tampering is reported as
+/// [`ErrorCode::AuthFailed`] without ever reaching the padding check. Omit it
+/// only for data encrypted before tags were introduced.
+///
+/// `decoding` says how the plaintext is decoded and defaults to strict
+/// utf-8; see [`TextDecoding`].
+///
+/// # Errors
+///
+/// - If the inputs are invalid base64, an error is returned.
+/// - If the IV is not 16 bytes, an error is returned.
+/// - If the KDF parameters are below the accepted minimum, an error is returned.
+/// - If [`crate::SecurityPolicy::strict_validation`] is on and any input
+///   is malformed, an [`ErrorCode::ValidationFailed`] error listing every
+///   problem is returned first.
+/// - If `b64_mac` is given and does not match, an error is returned.
+/// - If the key derivation, decryption or padding fails, an error is returned.
+/// - If the decrypted bytes are not valid in `decoding`, an error is returned.
+#[wasm_bindgen]
+pub fn decrypt_sync(
+    b64_ciphertext: &str,
+    password: &str,
+    b64_iv: &str,
+    b64_salt: &str,
+    kdf: Option<KdfParams>,
+    b64_mac: Option<String>,
+    decoding: Option<TextDecoding>,
+) -> Result<String, BVaultError> {
+    let _operation = utils::set_panic_hook();
+    let buf = decrypt_cbc(
+        b64_ciphertext,
+        password,
+        b64_iv,
+        b64_salt,
+        kdf,
+        b64_mac.as_deref(),
+    )?;
+
+    // --- text ----------------------------------------------------------------
+    crate::text::decode_plaintext(buf, decoding)
+}
+
+/// Synchronously decrypts a base64-encoded ciphertext into raw bytes.
+///
+/// Identical to [`decrypt_sync`], but returns a `Uint8Array` and performs no
+/// utf-8 validation, so it works for binary payloads.
+///
+/// # Errors
+///
+/// See [`decrypt_sync`], minus the utf-8 check.
+#[wasm_bindgen]
+pub fn decrypt_bytes_sync(
+    b64_ciphertext: &str,
+    password: &str,
+    b64_iv: &str,
+    b64_salt: &str,
+    kdf: Option<KdfParams>,
+    b64_mac: Option<String>,
+) -> Result<Vec<u8>, BVaultError> {
+    let _operation = utils::set_panic_hook();
+    decrypt_cbc(
+        b64_ciphertext,
+        password,
+        b64_iv,
+        b64_salt,
+        kdf,
+        b64_mac.as_deref(),
+    )
+}
+
+/// Synchronously encrypts bytes with AES-256-CBC like [`encrypt_bytes_sync`],
+/// but returns the ciphertext, IV, salt and tag as raw bytes.
+///
+/// For large payloads this skips the base64 encoding (a third more memory
+/// and a pass over the data) entirely.
+///
+/// # Errors
+///
+/// See [`encrypt_sync`].
+#[wasm_bindgen]
+pub fn encrypt_binary(
+    plaintext: &[u8],
+    password: &str,
+    options: Option<EncryptOptions>,
+) -> Result<EncryptedBytes, BVaultError> {
+    let _operation = utils::set_panic_hook();
+    encrypt_cbc(plaintext, password, &options.unwrap_or_default())
+}
+
+/// Synchronously decrypts the raw output of [`encrypt_binary`]; the
+/// counterpart of [`decrypt_bytes_sync`] for callers that hold bytes.
+///
+/// # Errors
+///
+/// See [`decrypt_sync`], minus the base64 and utf-8 checks.
+#[wasm_bindgen]
+pub fn decrypt_binary(
+    ciphertext: &[u8],
+    password: &str,
+    iv: &[u8],
+    salt: &[u8],
+    kdf: Option<KdfParams>,
+    mac: Option<Vec<u8>>,
+) -> Result<Vec<u8>, BVaultError> {
+    let _operation = utils::set_panic_hook();
+    decrypt_cbc_bytes(ciphertext.to_vec(), password, iv, salt, kdf, mac.as_deref())
+}
@@ -1,54 +1,462 @@
+#[cfg(any(feature = "gcm", feature = "chacha"))]
+mod aead;
+mod aes_ctr;
+#[cfg(feature = "formats")]
+mod age;
+#[cfg(feature = "cbc-legacy")]
+mod asynchronous;
+mod backup;
+#[cfg(feature = "formats")]
+mod bcrypt_hash;
+#[cfg(all(feature = "formats", feature = "cbc-legacy"))]
+mod bitwarden;
+mod buffer;
+#[cfg(any(feature = "cbc-legacy", feature = "streams"))]
+mod cancel;
+mod cbor;
+#[cfg(feature = "chacha")]
+mod chacha;
+mod chunked;
+mod cipher;
+mod compression;
+mod datakey;
+mod detailed;
+mod deterministic;
+mod diff;
+mod digest;
+mod document;
+mod encoding;
+mod envelope;
+mod ephemeral;
+mod error;
+#[cfg(feature = "gcm")]
+mod gcm;
+mod generate;
+mod hd;
+mod hidden;
+mod hpke;
+mod integrity;
+mod json;
+#[cfg(feature = "formats")]
+mod jwe;
+mod kcv;
+#[cfg(feature = "keepass")]
+mod kdbx;
+mod kdf;
+mod keycache;
+mod keys;
+#[cfg(feature = "formats")]
+mod keystore;
+mod keywrap;
+#[cfg(feature = "cbc-legacy")]
+mod legacy;
+#[cfg(feature = "cbc-legacy")]
+mod mac;
+mod merge;
+mod metadata;
+mod migration;
+mod mnemonic;
+mod multi;
+mod oaep;
+#[cfg(feature = "opaque")]
+mod opaque;
+#[cfg(feature = "formats")]
+mod openssl;
+mod packed;
+mod padding;
+#[cfg(feature = "formats")]
+mod paseto;
+#[cfg(feature = "argon2")]
+mod phc;
+mod policy;
+mod random;
+mod recovery;
+mod rekey;
+mod rotation;
+mod runtime;
+mod sealedbox;
+mod secret;
+mod secretbox;
+mod selftest;
+mod session;
+#[cfg(feature = "sharing")]
+mod shamir;
+mod signing;
+mod simd;
+mod srp;
+#[cfg(feature = "cbc-legacy")]
+mod stream;
+mod strength;
+mod text;
+mod threads;
+mod throttle;
+mod totp;
 mod utils;
+mod validation;
+mod vault;
+mod vectors;
+mod webcrypto;
+#[cfg(feature = "streams")]
+mod webstream;
+mod worker;
+mod x25519;
+mod xts;
 
-use aes::cipher::{BlockDecryptMut, KeyIvInit};
-use pbkdf2::pbkdf2_hmac_array;
-use sha2::Sha256;
 use wasm_bindgen::prelude::*;
 
-type Aes256CbcDec = cbc::Decryptor<aes::Aes256>;
+// Locked rather than `AssumeSingleThreaded`, so it is sound with `threads`.
+#[cfg(feature = "small-alloc")]
+#[global_allocator]
+static ALLOC: lol_alloc::LockedAllocator<lol_alloc::FreeListAllocator> =
+    lol_alloc::LockedAllocator::new(lol_alloc::FreeListAllocator::new());
 
-/// Synchronously decrypts a base64-encoded ciphertext using a password,
-/// a base64-encoded IV, and a base64-encoded salt.
-///
-/// # Errors
-///
-/// - If the inputs are invalid base64, an error is returned.
-/// - If the IV is not 16 bytes, an error is returned.
-/// - If the key derivation, decryption or padding fails, an error is returned.
-/// - If the decrypted bytes are not valid utf-8, an error is returned.
-#[wasm_bindgen]
-pub fn decrypt_sync(
-    b64_ciphertext: &str,
+pub use aes_ctr::{aes_ctr_decrypt, aes_ctr_encrypt};
+#[cfg(feature = "formats")]
+pub use age::{
+    age_decrypt, age_decrypt_passphrase, age_encrypt, age_encrypt_passphrase,
+    age_generate_identity, age_identity_to_recipient,
+};
+#[cfg(feature = "cbc-legacy")]
+pub use asynchronous::{decrypt_async, encrypt_async};
+pub use backup::{export_backup, import_backup, BackupAttachments, RestoredBackup};
+#[cfg(feature = "formats")]
+pub use bcrypt_hash::{bcrypt_hash, bcrypt_verify};
+#[cfg(all(feature = "formats", feature = "cbc-legacy"))]
+pub use bitwarden::bitwarden_import;
+pub use buffer::{alloc_buffer, decrypt_in_place, WasmBuffer};
+#[cfg(any(feature = "cbc-legacy", feature = "streams"))]
+pub use cancel::AbortSignal;
+#[cfg(feature = "chacha")]
+pub use chacha::{
+    decrypt_chacha, decrypt_chacha_binary, decrypt_xchacha, decrypt_xchacha_binary, encrypt_chacha,
+    encrypt_chacha_binary, encrypt_xchacha, encrypt_xchacha_binary,
+};
+pub use chunked::{ChunkedDecryptor, ChunkedEncryptor};
+pub use cipher::Cipher;
+pub use compression::Compression;
+pub use datakey::{
+    decrypt_with_data_key, encrypt_with_data_key, generate_data_key, unwrap_data_key, DataKey,
+};
+pub use detailed::{open_detailed, seal_detailed, OpenResult, SealResult};
+pub use deterministic::{deterministic_decrypt, deterministic_encrypt};
+pub use diff::{diff_vaults, VaultDiff};
+pub use digest::{
+    blake3_derive_key, blake3_keyed_hash, blind_index, blind_index_batch, constant_time_eq, hash,
+    hmac_sha256, hmac_sha512, hmac_verify, DigestOutput, HashAlgorithm, Hasher,
+};
+pub use document::{decrypt_json, encrypt_object};
+pub use encoding::{decode, encode, transcode, Base64Decoder, Encoding};
+pub use envelope::{
+    envelope_from_cbor, envelope_to_cbor, open, open_string, open_with_raw_key, reencrypt, seal,
+    seal_string, seal_with_raw_key, OpenOptions, SealOptions,
+};
+pub use ephemeral::EphemeralSecret;
+pub use error::{set_debug_errors, BVaultError, ErrorCode};
+#[cfg(feature = "gcm")]
+pub use gcm::{decrypt_gcm, decrypt_gcm_binary, encrypt_gcm, encrypt_gcm_binary};
+pub use generate::{generate_passphrase, generate_password, PasswordOptions};
+pub use hd::{derive_path, ExtendedKey, HdCurve};
+pub use hidden::{open_hidden, reseal_hidden, seal_hidden};
+pub use hpke::{hpke_open, hpke_seal, HpkeSealed};
+pub use integrity::{verify_vault_integrity, VaultIntegrityReport};
+pub use json::{export_json, import_json};
+#[cfg(feature = "formats")]
+pub use jwe::{jwe_decrypt, jwe_encrypt};
+pub use kcv::{key_check_value, key_fingerprint, verify_password};
+#[cfg(feature = "keepass")]
+pub use kdbx::{kdbx_open, KdbxDatabase, KdbxEntry};
+#[cfg(feature = "argon2")]
+pub use kdf::recommend_argon2_params;
+pub use kdf::{calibrate_kdf, hkdf_derive, KdfParams};
+pub use keycache::{clear_key_cache, disable_key_cache, enable_key_cache, key_cache_size};
+pub use keys::{
+    export_private_key, export_public_key, import_private_key, import_public_key, ImportedKey,
+    KeyAlgorithm, KeyDocument,
+};
+#[cfg(feature = "formats")]
+pub use keystore::{keystore_decrypt, keystore_encrypt};
+pub use keywrap::{aes_kw_unwrap, aes_kw_wrap, aes_kwp_unwrap, aes_kwp_wrap};
+#[cfg(feature = "cbc-legacy")]
+pub use legacy::{
+    decrypt_binary, decrypt_bytes_sync, decrypt_sync, encrypt_binary, encrypt_bytes_sync,
+    encrypt_sync,
+};
+pub use merge::{merge_vaults, MergeResult};
+pub use metadata::VaultMetadata;
+#[cfg(feature = "cbc-legacy")]
+pub use migration::{migrate_envelope, MigratedEnvelope};
+pub use migration::{open_any, try_decrypt_with_passwords, OpenedItem, PasswordMatch};
+pub use mnemonic::{
+    entropy_to_mnemonic, generate_mnemonic, mnemonic_to_entropy, mnemonic_to_seed,
+    validate_mnemonic,
+};
+pub use multi::{multi_open, multi_seal, Recipients};
+pub use oaep::{rsa_open, rsa_seal};
+#[cfg(feature = "opaque")]
+pub use opaque::{OpaqueLogin, OpaqueLoginResult, OpaqueRegistration, OpaqueRegistrationResult};
+#[cfg(feature = "formats")]
+pub use openssl::{
+    decrypt_cryptojs, decrypt_openssl, encrypt_openssl, OpensslDigest, OpensslOptions,
+};
+pub use padding::Padding;
+#[cfg(feature = "formats")]
+pub use paseto::{paseto_decrypt, paseto_encrypt};
+#[cfg(feature = "argon2")]
+pub use phc::{hash_password, verify_password_hash};
+pub use policy::{get_policy, set_policy, SecurityPolicy};
+pub use random::{generate_iv, generate_nonce, generate_salt, random_bytes};
+pub use recovery::{
+    generate_recovery_codes, recovery_codes_remaining, verify_recovery_code, RecoveryCodes,
+};
+pub use rekey::RekeyJob;
+pub use rotation::{needs_rotation, rotate, RotationPolicy};
+pub use runtime::{detect_runtime, runtime_info, Runtime, RuntimeInfo};
+pub use sealedbox::{sealed_box_open, sealed_box_seal};
+pub use secret::{open_secret, Secret};
+pub use secretbox::{secretbox_open, secretbox_seal};
+pub use selftest::{self_test, SelfTestReport, SelfTestResult};
+pub use session::{decrypt_many, DecryptResult, VaultSession};
+#[cfg(feature = "sharing")]
+pub use shamir::{combine_shares, split_secret};
+pub use signing::{
+    ed25519_public_key, ed25519_sign, ed25519_verify, generate_ed25519_keypair, sign_vault,
+    verify_vault, Ed25519KeyPair,
+};
+pub use simd::{simd_enabled, simd_supported};
+pub use srp::{srp_verifier, SrpClient, SrpGroup, SrpHash};
+#[cfg(feature = "cbc-legacy")]
+pub use stream::{StreamDecryptor, StreamEncryptor};
+pub use strength::{estimate_password_strength, PasswordStrength};
+pub use text::{decode_text, TextDecoding};
+#[cfg(feature = "threads")]
+pub use threads::init_threads;
+pub use threads::threads_enabled;
+pub use throttle::{disable_throttling, enable_throttling, throttle_delay};
+pub use totp::{totp_generate, totp_verify, TotpAlgorithm};
+pub use utils::BVaultInternalError;
+#[cfg(feature = "cbc-legacy")]
+pub use validation::validate_legacy;
+pub use validation::{validate_envelope, validate_envelope_string, ValidationReport};
+pub use vault::Vault;
+pub use vectors::generate_test_vectors;
+pub use webcrypto::{derive_key_jwk, derive_key_raw};
+#[cfg(feature = "streams")]
+pub use webstream::{decrypt_stream, decrypt_stream_with_raw_key, encrypt_stream};
+pub use worker::{
+    open_buffer, open_buffer_with_raw_key, process_message, seal_buffer, seal_buffer_with_raw_key,
+};
+pub use x25519::{
+    generate_x25519_keypair, open_from_sender, seal_for_recipient, x25519_public_key, X25519KeyPair,
+};
+pub use xts::{aes_xts_decrypt, aes_xts_encrypt};
+
+/// Length of the AES-CBC initialization vector, in bytes.
+const IV_LENGTH: usize = 16;
+
+/// Default length of the randomly generated salt, in bytes.
+const SALT_LENGTH: usize = 16;
+
+/// Shortest salt [`encrypt_sync`] accepts, in bytes.
+const MIN_SALT_LENGTH: usize = 8;
+
+// --- helpers -----------------------------------------------------------------
+
+fn b64_to_bytes(string: &str) -> Result<Vec<u8>, BVaultError> {
+    encoding::decode_base64(string)
+}
+
+fn bytes_to_b64(bytes: &[u8]) -> String {
+    base64::Engine::encode(&base64::engine::general_purpose::STANDARD, bytes)
+}
+
+/// Converts decrypted bytes into a string, wiping them if they are not
+/// valid utf-8.
+#[cfg_attr(not(feature = "formats"), allow(dead_code))]
+fn into_string(plaintext: Vec<u8>) -> Result<String, BVaultError> {
+    String::from_utf8(plaintext).map_err(|err| {
+        zeroize::Zeroize::zeroize(&mut err.into_bytes());
+        BVaultError::decryption_failed(ErrorCode::Utf8Error, "invalid utf-8")
+    })
+}
+
+/// Derives the key for a decryption, defaulting to PBKDF2 with 100 000
+/// iterations when the caller did not say otherwise.
+fn derive_key(
+    password: &str,
+    salt: &[u8],
+    kdf: Option<KdfParams>,
+) -> Result<kdf::Key, BVaultError> {
+    let kdf = kdf.unwrap_or_default();
+    policy::check_decrypt(password, salt.len(), &kdf);
+    kdf.derive(password.as_bytes(), salt)
+}
+
+/// Validates the parameters against the minimums and the security policy,
+/// and generates a new random salt. `cipher` is `None` for legacy
+/// AES-256-CBC.
+fn new_salt(
     password: &str,
-    b64_iv: &str,
-    b64_salt: &str,
-) -> Result<String, JsValue> {
-    // --- helpers -------------------------------------------------------------
-    fn b64_to_bytes(string: &str) -> Result<Vec<u8>, JsValue> {
-        base64::Engine::decode(&base64::engine::general_purpose::STANDARD, string)
-            .map_err(|_| JsValue::from_str("invalid base64"))
+    salt_length: usize,
+    kdf: &KdfParams,
+    cipher: Option<Cipher>,
+) -> Result<Vec<u8>, BVaultError> {
+    if salt_length < MIN_SALT_LENGTH {
+        return Err(BVaultError::new(
+            ErrorCode::BadSaltLength,
+            "salt must be at least 8 bytes",
+        ));
     }
 
-    // --- inputs --------------------------------------------------------------
-    let ciphertext = b64_to_bytes(b64_ciphertext)?;
-    let iv = b64_to_bytes(b64_iv)?;
-    let salt = b64_to_bytes(b64_salt)?;
+    kdf.validate()?;
+    policy::check_encrypt(password, salt_length, kdf, cipher)?;
+    random_bytes(salt_length)
+}
 
-    if iv.len() != 16 {
-        return Err(JsValue::from_str("IV must be 16 bytes"));
+/// Validates the parameters and derives a fresh key under a new random salt.
+fn new_salted_key(
+    password: &str,
+    salt_length: usize,
+    kdf: &KdfParams,
+    cipher: Option<Cipher>,
+) -> Result<(Vec<u8>, kdf::Key), BVaultError> {
+    let salt = new_salt(password, salt_length, kdf, cipher)?;
+    let key = kdf.derive(password.as_bytes(), &salt)?;
+    Ok((salt, key))
+}
+
+/// Options accepted by [`encrypt_sync`] and the other `encrypt_*` functions.
+#[wasm_bindgen]
+#[derive(Clone, Debug)]
+pub struct EncryptOptions {
+    salt_length: usize,
+    kdf: KdfParams,
+    encoding: Encoding,
+    aad: Vec<u8>,
+}
+
+impl Default for EncryptOptions {
+    fn default() -> Self {
+        EncryptOptions {
+            salt_length: SALT_LENGTH,
+            kdf: KdfParams::default(),
+            encoding: Encoding::default(),
+            aad: Vec::new(),
+        }
     }
+}
 
-    // --- key derivation (PBKDF2-HMAC-SHA256, 100 000 iters) ------------------
-    let key = pbkdf2_hmac_array::<Sha256, 32>(password.as_bytes(), &salt, 100_000);
+#[wasm_bindgen]
+impl EncryptOptions {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> EncryptOptions {
+        EncryptOptions::default()
+    }
+
+    /// Length of the random salt to generate, in bytes (default 16).
+    #[wasm_bindgen(getter)]
+    pub fn salt_length(&self) -> usize {
+        self.salt_length
+    }
 
-    // --- decryption ----------------------------------------------------------
-    let mut buf = ciphertext;
-    let dec = Aes256CbcDec::new_from_slices(&key, &iv)
-        .map_err(|_| JsValue::from_str("invalid key/iv length"))?;
+    #[wasm_bindgen(setter)]
+    pub fn set_salt_length(&mut self, salt_length: usize) {
+        self.salt_length = salt_length;
+    }
 
-    dec.decrypt_padded_mut::<cbc::cipher::block_padding::Pkcs7>(&mut buf)
-        .map_err(|_| JsValue::from_str("decryption / padding error"))?;
+    /// Key derivation parameters (default PBKDF2, 100 000 iterations).
+    #[wasm_bindgen(getter)]
+    pub fn kdf(&self) -> KdfParams {
+        self.kdf.clone()
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_kdf(&mut self, kdf: &KdfParams) {
+        self.kdf = kdf.clone();
+    }
+
+    /// Encoding of the strings in the returned [`EncryptedData`] (default
+    /// standard base64). Base64url output can be passed back to the
+    /// `decrypt_*` functions as is; convert hex back with [`transcode`]
+    /// first.
+    #[wasm_bindgen(getter)]
+    pub fn encoding(&self) -> Encoding {
+        self.encoding
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_encoding(&mut self, encoding: Encoding) {
+        self.encoding = encoding;
+    }
+
+    /// Associated data the AEAD functions bind the ciphertext to, such as
+    /// an entry ID (default none). It is authenticated but not encrypted
+    /// or returned; pass the same bytes as `aad` to decrypt. The CBC
+    /// functions reject it.
+    #[wasm_bindgen(getter)]
+    pub fn aad(&self) -> Vec<u8> {
+        self.aad.clone()
+    }
 
-    // --- utf-8 ---------------------------------------------------------------
-    String::from_utf8(buf).map_err(|_| JsValue::from_str("invalid utf-8"))
+    #[wasm_bindgen(setter)]
+    pub fn set_aad(&mut self, aad: &[u8]) {
+        self.aad = aad.to_vec();
+    }
+}
+
+impl EncryptOptions {
+    /// Fails if associated data is set, for modes that cannot bind it.
+    #[cfg(feature = "cbc-legacy")]
+    pub(crate) fn reject_aad(&self) -> Result<(), BVaultError> {
+        if !self.aad.is_empty() {
+            return Err(BVaultError::new(
+                ErrorCode::InvalidArgument,
+                "AES-256-CBC cannot bind associated data; use an AEAD cipher",
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// The output of [`encrypt_sync`]: the encoded ciphertext, IV and salt, in
+/// the shape [`decrypt_sync`] expects them. The encoding is base64 unless
+/// [`EncryptOptions::encoding`] says otherwise.
+///
+/// For the AEAD modes `iv` holds the nonce and `ciphertext` has the
+/// authentication tag appended.
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Clone, Debug)]
+pub struct EncryptedData {
+    pub ciphertext: String,
+    pub iv: String,
+    pub salt: String,
+    /// HMAC-SHA256 tag over the IV and ciphertext (AES-CBC only;
+    /// `undefined` for the AEAD modes). Pass it back to [`decrypt_sync`]
+    /// to have the ciphertext authenticated.
+    pub mac: Option<String>,
+}
+
+/// The output of [`encrypt_binary`] and the other `*_binary` encryptions:
+/// the fields of [`EncryptedData`] as raw bytes, skipping the text encoding.
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Clone, Debug)]
+pub struct EncryptedBytes {
+    pub ciphertext: Vec<u8>,
+    pub iv: Vec<u8>,
+    pub salt: Vec<u8>,
+    /// HMAC-SHA256 tag over the IV and ciphertext (AES-CBC only;
+    /// `undefined` for the AEAD modes). Pass it back to [`decrypt_binary`]
+    /// to have the ciphertext authenticated.
+    pub mac: Option<Vec<u8>>,
+}
+
+impl EncryptedBytes {
+    #[cfg(any(feature = "cbc-legacy", feature = "gcm", feature = "chacha"))]
+    pub(crate) fn encode(&self, encoding: Encoding) -> EncryptedData {
+        EncryptedData {
+            ciphertext: encoding.encode(&self.ciphertext),
+            iv: encoding.encode(&self.iv),
+            salt: encoding.encode(&self.salt),
+            mac: self.mac.as_deref().map(|mac| encoding.encode(mac)),
+        }
+    }
 }
@@ -0,0 +1,70 @@
+//! Encrypt-then-MAC for the AES-CBC format.
+//!
+//! CBC on its own is malleable and, when padding errors are observable, a
+//! padding oracle. New CBC encryptions therefore carry an HMAC-SHA256 tag
+//! over `iv || ciphertext`, keyed with a MAC key derived from the encryption
+//! key by HKDF. Decryption checks the tag before touching the padding.
+//!
+//! The encryption key itself is unchanged, so data written before the tag
+//! existed still decrypts when no tag is supplied.
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use zeroize::Zeroizing;
+
+use crate::kdf::{hkdf_sha256, KEY_LENGTH};
+use crate::{b64_to_bytes, BVaultError, ErrorCode};
+
+/// HKDF label separating the MAC key from the encryption key.
+const MAC_KEY_INFO: &[u8] = b"bvault-cbc-mac";
+
+/// Length of a CBC tag, in bytes.
+pub(crate) const TAG_LENGTH: usize = 32;
+
+/// Returns an HMAC keyed for `key`, ready to absorb `iv || ciphertext`.
+pub(crate) fn cbc_mac(key: &[u8]) -> Result<Hmac<Sha256>, BVaultError> {
+    let mut mac_key = Zeroizing::new([0u8; KEY_LENGTH]);
+    hkdf_sha256(key, None, MAC_KEY_INFO, &mut mac_key[..])?;
+    Ok(Hmac::<Sha256>::new_from_slice(&mac_key[..]).expect("HMAC takes keys of any length"))
+}
+
+/// Computes the tag of a complete CBC ciphertext.
+pub(crate) fn tag(key: &[u8], iv: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>, BVaultError> {
+    let mut mac = cbc_mac(key)?;
+    mac.update(iv);
+    mac.update(ciphertext);
+    Ok(mac.finalize().into_bytes().to_vec())
+}
+
+/// Checks a finished HMAC against a base64 tag in constant time.
+pub(crate) fn verify(mac: Hmac<Sha256>, b64_tag: &str) -> Result<(), BVaultError> {
+    verify_bytes(mac, &b64_to_bytes(b64_tag)?)
+}
+
+/// Checks a finished HMAC against a raw tag in constant time.
+pub(crate) fn verify_bytes(mac: Hmac<Sha256>, tag: &[u8]) -> Result<(), BVaultError> {
+    if tag.len() != TAG_LENGTH {
+        return Err(auth_failed());
+    }
+    mac.verify_slice(tag).map_err(|_| auth_failed())
+}
+
+/// Checks the raw tag of a complete CBC ciphertext.
+pub(crate) fn verify_tag(
+    key: &[u8],
+    iv: &[u8],
+    ciphertext: &[u8],
+    tag: &[u8],
+) -> Result<(), BVaultError> {
+    let mut mac = cbc_mac(key)?;
+    mac.update(iv);
+    mac.update(ciphertext);
+    verify_bytes(mac, tag)
+}
+
+fn auth_failed() -> BVaultError {
+    BVaultError::decryption_failed(
+        ErrorCode::AuthFailed,
+        "MAC verification failed: wrong password or tampered ciphertext",
+    )
+}
@@ -0,0 +1,154 @@
+//! Three-way merging of vaults, for syncing one vault across devices.
+//!
+//! [`merge_vaults`] unlocks the local copy, the remote copy and their
+//! common ancestor, and compares them entry by entry, by ID and plaintext:
+//!
+//! - an entry changed (added, updated or deleted) on one side only takes
+//!   that side's state;
+//! - an entry changed the same way on both sides is kept as is;
+//! - an entry changed differently on both sides is a conflict: the side
+//!   modified later wins (local on a tie), an edit beats a deletion, and
+//!   the ID is reported in [`MergeResult::conflicts`].
+//!
+//! Plaintexts are only ever decrypted inside WASM. The merged vault keeps
+//! the local vault's key, salt, KDF and cipher, and the local blob's form
+//! (binary or CBOR); entries taken from the remote are re-encrypted under
+//! the local key, keeping their timestamps.
+
+use std::collections::BTreeSet;
+
+use wasm_bindgen::prelude::*;
+use zeroize::Zeroizing;
+
+use crate::envelope::is_cbor;
+use crate::{BVaultError, OpenOptions, Vault};
+
+/// An entry's decrypted metadata, if it has any.
+type Metadata = Option<Zeroizing<Vec<u8>>>;
+
+/// An entry's state in one vault: its timestamp, plaintext and metadata, or
+/// `None` if the vault has no such entry.
+type EntryState = Option<(u64, Zeroizing<Vec<u8>>, Metadata)>;
+
+fn state(vault: &Vault, id: &str) -> Result<EntryState, BVaultError> {
+    match vault.entries().get(id) {
+        Some(entry) => Ok(Some((
+            entry.modified,
+            Zeroizing::new(vault.open_entry(id, entry)?),
+            vault.open_entry_metadata(id, entry)?.map(Zeroizing::new),
+        ))),
+        None => Ok(None),
+    }
+}
+
+fn same(a: &EntryState, b: &EntryState) -> bool {
+    match (a, b) {
+        (Some((_, a, a_metadata)), Some((_, b, b_metadata))) => {
+            a[..] == b[..] && a_metadata == b_metadata
+        }
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+/// The merged vault and the IDs of the entries that conflicted.
+#[wasm_bindgen]
+pub struct MergeResult {
+    vault: Vec<u8>,
+    conflicts: Vec<String>,
+}
+
+#[wasm_bindgen]
+impl MergeResult {
+    /// The merged vault blob, to store on both sides.
+    #[wasm_bindgen(getter)]
+    pub fn vault(&self) -> Vec<u8> {
+        self.vault.clone()
+    }
+
+    /// The IDs of entries changed differently on both sides, in sorted
+    /// order. The merged vault holds the winning side of each.
+    #[wasm_bindgen(getter)]
+    pub fn conflicts(&self) -> Vec<String> {
+        self.conflicts.clone()
+    }
+}
+
+/// Merges the `local` and `remote` copies of a vault, given the `base` they
+/// were last in sync at. Without a base, entries found on only one side
+/// are treated as added there, so nothing is deleted.
+///
+/// All three vaults must open with `password` (and the keyfile in
+/// `options`, if any), though their salts may differ.
+///
+/// # Errors
+///
+/// - If a vault is malformed or the password is wrong for any of them, an
+///   error is returned.
+/// - If an entry fails authentication, an error is returned.
+/// - If the random number generator is unavailable, an error is returned.
+#[wasm_bindgen]
+pub fn merge_vaults(
+    local: &[u8],
+    remote: &[u8],
+    base: Option<Vec<u8>>,
+    password: &str,
+    options: Option<OpenOptions>,
+) -> Result<MergeResult, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    let mut merged = Vault::unlock(local, password, options.clone())?;
+    let remote_vault = Vault::unlock(remote, password, options.clone())?;
+    let base = match base {
+        Some(base) => Some(Vault::unlock(&base, password, options)?),
+        None => None,
+    };
+
+    let ids: BTreeSet<String> = merged
+        .entries()
+        .keys()
+        .chain(remote_vault.entries().keys())
+        .chain(base.iter().flat_map(|base| base.entries().keys()))
+        .cloned()
+        .collect();
+
+    let mut conflicts = Vec::new();
+    for id in ids {
+        let ours = state(&merged, &id)?;
+        let theirs = state(&remote_vault, &id)?;
+        let ancestor = match &base {
+            Some(base) => state(base, &id)?,
+            None => None,
+        };
+
+        let take_theirs = if same(&ours, &theirs) || same(&theirs, &ancestor) {
+            false
+        } else if same(&ours, &ancestor) {
+            true
+        } else {
+            conflicts.push(id.clone());
+            match (&ours, &theirs) {
+                (Some((ours, ..)), Some((theirs, ..))) => theirs > ours,
+                (None, Some(_)) => true,
+                _ => false,
+            }
+        };
+        if take_theirs {
+            match theirs {
+                Some((modified, plaintext, metadata)) => merged.put_entry(
+                    &id,
+                    &plaintext,
+                    metadata.as_deref().map(Vec::as_slice),
+                    modified,
+                )?,
+                None => merged.remove_entry(&id),
+            }
+        }
+    }
+
+    let vault = if is_cbor(local) {
+        merged.to_cbor()?
+    } else {
+        merged.to_bytes()?
+    };
+    Ok(MergeResult { vault, conflicts })
+}
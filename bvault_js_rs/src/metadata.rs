@@ -0,0 +1,103 @@
+//! A "partially locked" view of a vault: entry metadata can be read, entry
+//! secrets cannot.
+//!
+//! [`VaultMetadata`] holds only the metadata key (see `vault`), so an app
+//! can list titles and URLs, for example after locking its [`Vault`] but
+//! keeping [`Vault::metadata_key`], without any entry's plaintext being
+//! reachable. Opening it with the password derives the master key, keeps
+//! the metadata key and wipes the rest.
+
+use std::collections::BTreeMap;
+
+use wasm_bindgen::prelude::*;
+
+use crate::kdf::{Key, KEY_LENGTH};
+use crate::vault::{not_found, open_metadata, Locked, SealedEntry};
+use crate::{BVaultError, Cipher, ErrorCode, OpenOptions, Vault};
+
+/// The metadata of a vault's entries; see the module documentation.
+#[wasm_bindgen]
+pub struct VaultMetadata {
+    key: Key,
+    cipher: Cipher,
+    entries: BTreeMap<String, SealedEntry>,
+}
+
+#[wasm_bindgen]
+impl VaultMetadata {
+    /// Opens the metadata of a vault blob with its password.
+    ///
+    /// # Errors
+    ///
+    /// See [`Vault::unlock`].
+    pub fn open(
+        blob: &[u8],
+        password: &str,
+        options: Option<OpenOptions>,
+    ) -> Result<VaultMetadata, BVaultError> {
+        let _operation = crate::utils::set_panic_hook();
+        let vault = Vault::unlock(blob, password, options)?;
+        Ok(VaultMetadata {
+            key: vault.metadata_subkey()?,
+            cipher: vault.cipher(),
+            entries: vault.entries().clone(),
+        })
+    }
+
+    /// Opens the metadata of a vault blob with the key from
+    /// [`Vault::metadata_key`]. No key derivation runs, so this is cheap;
+    /// a wrong key is only noticed when metadata is read.
+    ///
+    /// # Errors
+    ///
+    /// - If the blob is malformed, an error is returned; see
+    ///   [`Vault::unlock`].
+    /// - If `key` is not 32 bytes, an [`ErrorCode::BadKeyLength`] error is
+    ///   returned.
+    pub fn open_with_key(blob: &[u8], key: &[u8]) -> Result<VaultMetadata, BVaultError> {
+        let _operation = crate::utils::set_panic_hook();
+        if key.len() != KEY_LENGTH {
+            return Err(BVaultError::new(
+                ErrorCode::BadKeyLength,
+                "metadata key must be 32 bytes",
+            ));
+        }
+        let locked = Locked::parse(blob)?;
+        let mut metadata_key = Key::default();
+        metadata_key.copy_from_slice(key);
+        Ok(VaultMetadata {
+            key: metadata_key,
+            cipher: locked.cipher,
+            entries: locked.entries,
+        })
+    }
+
+    /// The IDs of all entries, in sorted order.
+    pub fn list_ids(&self) -> Vec<String> {
+        self.entries.keys().cloned().collect()
+    }
+
+    /// When the entry with the given ID was last modified; see
+    /// [`Vault::entry_modified`].
+    ///
+    /// # Errors
+    ///
+    /// - If there is no such entry, an error is returned.
+    pub fn entry_modified(&self, id: &str) -> Result<f64, BVaultError> {
+        let entry = self.entries.get(id).ok_or_else(|| not_found(id))?;
+        Ok(entry.modified as f64)
+    }
+
+    /// Decrypts the metadata of the entry with the given ID; `undefined` if
+    /// it has none.
+    ///
+    /// # Errors
+    ///
+    /// - If there is no such entry, an error is returned.
+    /// - If the key is wrong or the metadata fails authentication, an error
+    ///   is returned.
+    pub fn get_metadata(&self, id: &str) -> Result<Option<Vec<u8>>, BVaultError> {
+        let entry = self.entries.get(id).ok_or_else(|| not_found(id))?;
+        open_metadata(&self.key[..], self.cipher, id, entry)
+    }
+}
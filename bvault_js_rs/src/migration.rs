@@ -0,0 +1,365 @@
+//! Opening items in either the envelope or the legacy CBC format, for apps
+//! migrating stored data.
+//!
+//! Legacy items are the three strings returned by [`crate::encrypt_sync`]
+//! (plus the optional `mac`), stored as a JSON object:
+//!
+//! ```text
+//! { "ciphertext": "…", "iv": "…", "salt": "…", "mac": "…" }
+//! ```
+//!
+//! `encryptedData` is accepted in place of `ciphertext`, as written by the
+//! TypeScript package. Everything else is parsed as an envelope.
+//!
+//! [`migrate_envelope`] converts one legacy item into an envelope in a
+//! single call, for bulk migrations.
+
+use serde::Deserialize;
+use wasm_bindgen::prelude::*;
+use zeroize::Zeroizing;
+
+use crate::{open, BVaultError, ErrorCode, OpenOptions};
+#[cfg(feature = "cbc-legacy")]
+use crate::{seal, Cipher, KdfParams, SealOptions};
+
+/// The result of [`open_any`].
+#[wasm_bindgen]
+#[derive(Clone)]
+pub struct OpenedItem {
+    plaintext: Zeroizing<Vec<u8>>,
+    legacy: bool,
+}
+
+#[wasm_bindgen]
+impl OpenedItem {
+    /// The decrypted bytes.
+    #[wasm_bindgen(getter)]
+    pub fn plaintext(&self) -> Vec<u8> {
+        self.plaintext.to_vec()
+    }
+
+    /// Whether the item was in the legacy CBC format. Re-encrypt such items
+    /// with [`crate::seal`] and store the envelope in their place.
+    #[wasm_bindgen(getter)]
+    pub fn legacy(&self) -> bool {
+        self.legacy
+    }
+}
+
+#[derive(Deserialize)]
+#[cfg_attr(not(feature = "cbc-legacy"), allow(dead_code))]
+struct LegacyItem {
+    #[serde(alias = "encryptedData")]
+    ciphertext: String,
+    iv: String,
+    salt: String,
+    #[serde(default)]
+    mac: Option<String>,
+}
+
+fn is_legacy(data: &[u8]) -> bool {
+    data.iter()
+        .find(|b| !b.is_ascii_whitespace())
+        .is_some_and(|&b| b == b'{')
+}
+
+#[cfg(feature = "cbc-legacy")]
+fn open_legacy(
+    item: LegacyItem,
+    password: &str,
+    options: &OpenOptions,
+) -> Result<Vec<u8>, BVaultError> {
+    crate::legacy::decrypt_cbc(
+        &item.ciphertext,
+        password,
+        &item.iv,
+        &item.salt,
+        Some(options.legacy_kdf()),
+        item.mac.as_deref(),
+    )
+}
+
+#[cfg(not(feature = "cbc-legacy"))]
+fn open_legacy(_: LegacyItem, _: &str, _: &OpenOptions) -> Result<Vec<u8>, BVaultError> {
+    Err(BVaultError::new(
+        ErrorCode::UnsupportedAlgorithm,
+        "legacy CBC items need the cbc-legacy feature",
+    ))
+}
+
+/// Decrypts `data`, which may be an envelope (binary or CBOR) or a legacy
+/// CBC item as UTF-8 JSON, and reports which it was.
+///
+/// Legacy items are decrypted with the KDF set by
+/// [`OpenOptions::with_legacy_kdf`] (default PBKDF2, 100 000 iterations)
+/// and the keyfile, if any. [`open`] itself stays strict and only accepts
+/// envelopes.
+///
+/// # Errors
+///
+/// - If `data` looks like JSON but is not a legacy item, an
+///   [`ErrorCode::InvalidJson`] error is returned.
+/// - Otherwise see [`open`] and [`crate::decrypt_bytes_sync`].
+#[wasm_bindgen]
+pub fn open_any(
+    data: &[u8],
+    password: &str,
+    options: Option<OpenOptions>,
+) -> Result<OpenedItem, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    if !is_legacy(data) {
+        return Ok(OpenedItem {
+            plaintext: Zeroizing::new(open(data, password, options)?),
+            legacy: false,
+        });
+    }
+
+    let item: LegacyItem = serde_json::from_slice(data)
+        .map_err(|e| BVaultError::new(ErrorCode::InvalidJson, e.to_string()))?;
+    let plaintext = open_legacy(item, password, &options.unwrap_or_default())?;
+    Ok(OpenedItem {
+        plaintext: Zeroizing::new(plaintext),
+        legacy: true,
+    })
+}
+
+/// The result of [`try_decrypt_with_passwords`].
+#[wasm_bindgen]
+#[derive(Clone)]
+pub struct PasswordMatch {
+    index: u32,
+    item: OpenedItem,
+}
+
+#[wasm_bindgen]
+impl PasswordMatch {
+    /// Position in the candidate list of the password that worked.
+    #[wasm_bindgen(getter)]
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    /// The decrypted bytes.
+    #[wasm_bindgen(getter)]
+    pub fn plaintext(&self) -> Vec<u8> {
+        self.item.plaintext()
+    }
+
+    /// Whether the item was in the legacy CBC format; see
+    /// [`OpenedItem::legacy`].
+    #[wasm_bindgen(getter)]
+    pub fn legacy(&self) -> bool {
+        self.item.legacy
+    }
+}
+
+/// Decrypts `data` as [`open_any`] does, trying each of `passwords` in
+/// turn, for users who may have any of several past master passwords.
+/// Re-seal the item under the current password once it opens.
+///
+/// Each distinct candidate costs one key derivation; repeats are skipped.
+/// With throttling on, every wrong candidate counts as a failed attempt.
+///
+/// # Errors
+///
+/// - If `passwords` is empty, an [`ErrorCode::InvalidArgument`] error is
+///   returned.
+/// - If no candidate opens the item, an [`ErrorCode::WrongPassword`] error
+///   is returned.
+/// - Any other error, such as a malformed item or [`ErrorCode::Throttled`],
+///   is returned as soon as it occurs; see [`open_any`].
+#[wasm_bindgen]
+pub fn try_decrypt_with_passwords(
+    data: &[u8],
+    passwords: Vec<String>,
+    options: Option<OpenOptions>,
+) -> Result<PasswordMatch, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    let passwords: Vec<Zeroizing<String>> = passwords.into_iter().map(Zeroizing::new).collect();
+    if passwords.is_empty() {
+        return Err(BVaultError::new(
+            ErrorCode::InvalidArgument,
+            "at least one password is required",
+        ));
+    }
+    for (index, password) in passwords.iter().enumerate() {
+        if passwords[..index].contains(password) {
+            continue;
+        }
+        match open_any(data, password, options.clone()) {
+            Ok(item) => {
+                return Ok(PasswordMatch {
+                    index: index as u32,
+                    item,
+                })
+            }
+            Err(err) if matches!(err.code(), ErrorCode::AuthFailed | ErrorCode::WrongPassword) => {}
+            Err(err) => return Err(err),
+        }
+    }
+    Err(BVaultError::new(
+        ErrorCode::WrongPassword,
+        "none of the passwords opens the item",
+    ))
+}
+
+/// The result of [`migrate_envelope`].
+#[cfg(feature = "cbc-legacy")]
+#[wasm_bindgen]
+#[derive(Clone)]
+pub struct MigratedEnvelope {
+    envelope: Vec<u8>,
+    cipher: Cipher,
+    kdf: KdfParams,
+    previous_kdf: KdfParams,
+    authenticated: bool,
+    changes: Vec<String>,
+}
+
+#[cfg(feature = "cbc-legacy")]
+#[wasm_bindgen]
+impl MigratedEnvelope {
+    /// The new envelope, to store in place of the legacy item.
+    #[wasm_bindgen(getter)]
+    pub fn envelope(&self) -> Vec<u8> {
+        self.envelope.clone()
+    }
+
+    /// The cipher suite of the new envelope.
+    #[wasm_bindgen(getter)]
+    pub fn cipher(&self) -> Cipher {
+        self.cipher
+    }
+
+    /// The key derivation parameters of the new envelope.
+    #[wasm_bindgen(getter)]
+    pub fn kdf(&self) -> KdfParams {
+        self.kdf.clone()
+    }
+
+    /// The key derivation parameters the legacy item was decrypted with.
+    #[wasm_bindgen(getter)]
+    pub fn previous_kdf(&self) -> KdfParams {
+        self.previous_kdf.clone()
+    }
+
+    /// Whether the legacy item carried an HMAC tag, which was verified.
+    /// Items without one were only checked by their padding, so damage to
+    /// them may have gone unnoticed.
+    #[wasm_bindgen(getter)]
+    pub fn authenticated(&self) -> bool {
+        self.authenticated
+    }
+
+    /// What changed, one human-readable line per property, such as
+    /// `"cipher: aes-256-cbc -> aes-256-gcm"`.
+    #[wasm_bindgen(getter)]
+    pub fn changes(&self) -> Vec<String> {
+        self.changes.clone()
+    }
+}
+
+#[cfg(feature = "cbc-legacy")]
+fn describe_kdf(kdf: &KdfParams) -> String {
+    let algorithm = kdf.algorithm();
+    let params = match (kdf.memory_kib(), kdf.log_n()) {
+        (Some(memory_kib), _) => format!(
+            "{} KiB, {} passes, {} lanes",
+            memory_kib,
+            kdf.iterations().unwrap_or_default(),
+            kdf.parallelism().unwrap_or_default()
+        ),
+        (None, Some(log_n)) => format!(
+            "N = 2^{}, r = {}, p = {}",
+            log_n,
+            kdf.block_size().unwrap_or_default(),
+            kdf.parallelism().unwrap_or_default()
+        ),
+        (None, None) => format!("{} iterations", kdf.iterations().unwrap_or_default()),
+    };
+    let keyfile = if kdf.has_keyfile() { ", keyfile" } else { "" };
+    format!("{} ({}{})", algorithm, params, keyfile)
+}
+
+/// Re-encrypts a legacy CBC item (the three base64 strings of
+/// [`crate::encrypt_sync`], plus its `mac` if it has one) as an envelope
+/// under the same password, and reports what changed.
+///
+/// The item is decrypted with PBKDF2 at 100 000 iterations, as the old
+/// format always used, and sealed with `target_suite` (default
+/// [`SealOptions::new`]), which costs a second key derivation. A keyfile
+/// set on the target KDF is mixed into the new envelope only.
+///
+/// # Errors
+///
+/// - If the inputs are not base64, the IV is not 16 bytes or the
+///   ciphertext is not a whole number of AES blocks, the item is not in the
+///   legacy format and an [`ErrorCode::InvalidArgument`] error is returned.
+/// - If `mac` is given and does not match, an [`ErrorCode::AuthFailed`]
+///   error is returned.
+/// - Otherwise see [`crate::decrypt_bytes_sync`] and [`seal`].
+#[cfg(feature = "cbc-legacy")]
+#[wasm_bindgen]
+pub fn migrate_envelope(
+    legacy_ciphertext: &str,
+    iv: &str,
+    salt: &str,
+    password: &str,
+    target_suite: Option<SealOptions>,
+    mac: Option<String>,
+) -> Result<MigratedEnvelope, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    let not_legacy = |why: &str| {
+        BVaultError::new(
+            ErrorCode::InvalidArgument,
+            format!("not a legacy CBC item: {}", why),
+        )
+    };
+    let inputs = crate::legacy::decode_cbc_inputs(legacy_ciphertext, iv, salt)
+        .map_err(|err| not_legacy(&err.message()).caused_by(err))?;
+    if inputs.ciphertext.is_empty() || !inputs.ciphertext.len().is_multiple_of(16) {
+        return Err(not_legacy("the ciphertext is not a whole number of blocks"));
+    }
+
+    let previous_kdf = KdfParams::default();
+    let plaintext = Zeroizing::new(crate::legacy::decrypt_cbc(
+        legacy_ciphertext,
+        password,
+        iv,
+        salt,
+        Some(previous_kdf.clone()),
+        mac.as_deref(),
+    )?);
+    let options = target_suite.unwrap_or_default();
+    let envelope = seal(&plaintext, password, Some(options.clone()))?;
+
+    let cipher = options.cipher();
+    let kdf = options.kdf();
+    let integrity = if mac.is_some() { "hmac-sha256" } else { "none" };
+    let mut changes = vec![
+        format!("cipher: aes-256-cbc -> {}", cipher.name()),
+        format!("integrity: {} -> aead", integrity),
+    ];
+    let (before, after) = (describe_kdf(&previous_kdf), describe_kdf(&kdf));
+    if before != after {
+        changes.push(format!("kdf: {} -> {}", before, after));
+    }
+    if inputs.salt.len() != options.salt_length() {
+        changes.push(format!(
+            "salt: {} -> {} bytes",
+            inputs.salt.len(),
+            options.salt_length()
+        ));
+    }
+    if options.key_check() {
+        changes.push("key check value: none -> added".to_string());
+    }
+    Ok(MigratedEnvelope {
+        envelope,
+        cipher,
+        kdf,
+        previous_kdf,
+        authenticated: mac.is_some(),
+        changes,
+    })
+}
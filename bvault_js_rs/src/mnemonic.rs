@@ -0,0 +1,185 @@
+//! BIP39 mnemonics, for writing a vault master key down as a recovery
+//! phrase.
+//!
+//! A mnemonic encodes 128 to 256 bits of entropy followed by a checksum of
+//! entropy/32 bits (the leading bits of its SHA-256), 11 bits per word, so
+//! 12 to 24 words. [`mnemonic_to_seed`] stretches a phrase and optional
+//! passphrase into a 64-byte seed with PBKDF2-HMAC-SHA512, as wallets do.
+//!
+//! Only the English wordlist is supported:
+//! <https://github.com/bitcoin/bips/blob/master/bip-0039/english.txt>.
+
+use sha2::{Digest, Sha256, Sha512};
+use unicode_normalization::UnicodeNormalization;
+use wasm_bindgen::prelude::*;
+use zeroize::Zeroizing;
+
+use crate::{random_bytes, BVaultError, ErrorCode};
+
+/// The BIP39 English wordlist, one word per line, in sorted order.
+const WORDLIST: &str = include_str!("bip39_english.txt");
+
+const BITS_PER_WORD: usize = 11;
+
+/// Default entropy, in bits (12 words).
+const DEFAULT_STRENGTH: u32 = 128;
+
+const SEED_ITERATIONS: u32 = 2048;
+const SEED_LENGTH: usize = 64;
+
+fn words() -> Vec<&'static str> {
+    WORDLIST.lines().collect()
+}
+
+fn invalid_mnemonic(message: impl Into<String>) -> BVaultError {
+    BVaultError::new(ErrorCode::InvalidMnemonic, message)
+}
+
+/// Encodes `entropy` (16 to 32 bytes, a multiple of 4) as words.
+fn encode(entropy: &[u8]) -> Result<String, BVaultError> {
+    if !(16..=32).contains(&entropy.len()) || !entropy.len().is_multiple_of(4) {
+        return Err(BVaultError::new(
+            ErrorCode::InvalidArgument,
+            "mnemonic entropy must be 16, 20, 24, 28 or 32 bytes",
+        ));
+    }
+    let checksum = Sha256::digest(entropy);
+    let mut bits = Zeroizing::new(entropy.to_vec());
+    bits.push(checksum[0]);
+    let bit = |i: usize| (bits[i / 8] >> (7 - i % 8)) & 1;
+
+    let wordlist = words();
+    let word_count = entropy.len() * 8 * 33 / 32 / BITS_PER_WORD;
+    let phrase: Vec<&str> = (0..word_count)
+        .map(|w| {
+            let index = (0..BITS_PER_WORD).fold(0usize, |acc, b| {
+                acc << 1 | bit(w * BITS_PER_WORD + b) as usize
+            });
+            wordlist[index]
+        })
+        .collect();
+    Ok(phrase.join(" "))
+}
+
+/// Decodes a mnemonic back into its entropy, checking the checksum.
+/// Words may be separated by any whitespace.
+fn decode(mnemonic: &str) -> Result<Zeroizing<Vec<u8>>, BVaultError> {
+    let normalized: String = mnemonic.nfkd().collect();
+    let phrase: Vec<&str> = normalized.split_whitespace().collect();
+    if !matches!(phrase.len(), 12 | 15 | 18 | 21 | 24) {
+        return Err(invalid_mnemonic(
+            "a mnemonic must have 12, 15, 18, 21 or 24 words",
+        ));
+    }
+
+    let wordlist = words();
+    let mut bits = Zeroizing::new(vec![0u8; (phrase.len() * BITS_PER_WORD).div_ceil(8)]);
+    for (w, word) in phrase.iter().enumerate() {
+        let index = wordlist
+            .binary_search(word)
+            .map_err(|_| invalid_mnemonic(format!("word {} is not in the wordlist", w + 1)))?;
+        for b in 0..BITS_PER_WORD {
+            if index >> (BITS_PER_WORD - 1 - b) & 1 == 1 {
+                let i = w * BITS_PER_WORD + b;
+                bits[i / 8] |= 0x80 >> (i % 8);
+            }
+        }
+    }
+
+    let entropy_len = phrase.len() * BITS_PER_WORD * 32 / 33 / 8;
+    let checksum_bits = entropy_len / 4;
+    let entropy = Zeroizing::new(bits[..entropy_len].to_vec());
+    let expected = Sha256::digest(&entropy[..])[0] >> (8 - checksum_bits);
+    let actual = bits[entropy_len] >> (8 - checksum_bits);
+    if expected != actual {
+        return Err(invalid_mnemonic("mnemonic checksum does not match"));
+    }
+    Ok(entropy)
+}
+
+/// Generates a random mnemonic with `strength` bits of entropy: 128 (the
+/// default, 12 words), 160, 192, 224 or 256 (24 words).
+///
+/// # Errors
+///
+/// - If `strength` is not one of the above, an error is returned.
+/// - If the random number generator is unavailable, an error is returned.
+#[wasm_bindgen]
+pub fn generate_mnemonic(strength: Option<u32>) -> Result<String, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    let strength = strength.unwrap_or(DEFAULT_STRENGTH);
+    if !(128..=256).contains(&strength) || !strength.is_multiple_of(32) {
+        return Err(BVaultError::new(
+            ErrorCode::InvalidArgument,
+            "strength must be 128, 160, 192, 224 or 256 bits",
+        ));
+    }
+    let entropy = Zeroizing::new(random_bytes(strength as usize / 8)?);
+    encode(&entropy)
+}
+
+/// Whether `mnemonic` is a well-formed English BIP39 phrase: known words,
+/// a valid length and a matching checksum.
+#[wasm_bindgen]
+pub fn validate_mnemonic(mnemonic: &str) -> bool {
+    let _operation = crate::utils::set_panic_hook();
+    decode(mnemonic).is_ok()
+}
+
+/// Encodes existing key material (16 to 32 bytes, a multiple of 4) as a
+/// mnemonic, e.g. to write a 32-byte master key down as 24 words.
+///
+/// # Errors
+///
+/// - If `entropy` has an unsupported length, an error is returned.
+#[wasm_bindgen]
+pub fn entropy_to_mnemonic(entropy: &[u8]) -> Result<String, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    encode(entropy)
+}
+
+/// Recovers the key material encoded by [`entropy_to_mnemonic`] or
+/// [`generate_mnemonic`].
+///
+/// # Errors
+///
+/// - If the mnemonic is invalid, an [`ErrorCode::InvalidMnemonic`] error is
+///   returned.
+#[wasm_bindgen]
+pub fn mnemonic_to_entropy(mnemonic: &str) -> Result<Vec<u8>, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    Ok(decode(mnemonic)?.to_vec())
+}
+
+/// Derives the 64-byte BIP39 seed from a mnemonic and optional passphrase,
+/// compatible with hardware and software wallets.
+///
+/// Both are NFKD-normalized; words may be separated by any whitespace.
+///
+/// # Errors
+///
+/// - If the mnemonic is invalid, an [`ErrorCode::InvalidMnemonic`] error is
+///   returned.
+#[wasm_bindgen]
+pub fn mnemonic_to_seed(
+    mnemonic: &str,
+    passphrase: Option<String>,
+) -> Result<Vec<u8>, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    decode(mnemonic)?;
+    let normalized: String = mnemonic.nfkd().collect();
+    let phrase = Zeroizing::new(normalized.split_whitespace().collect::<Vec<_>>().join(" "));
+    let salt = Zeroizing::new(format!(
+        "mnemonic{}",
+        passphrase.unwrap_or_default().nfkd().collect::<String>()
+    ));
+
+    let mut seed = vec![0u8; SEED_LENGTH];
+    pbkdf2::pbkdf2_hmac::<Sha512>(
+        phrase.as_bytes(),
+        salt.as_bytes(),
+        SEED_ITERATIONS,
+        &mut seed,
+    );
+    Ok(seed)
+}
@@ -0,0 +1,204 @@
+//! One ciphertext for several recipients, for items shared in team vaults.
+//!
+//! The payload is encrypted once with AES-256-GCM under a random content
+//! key, and the content key is wrapped to each recipient in a stanza:
+//!
+//! ```text
+//! magic     4      "BVMR"
+//! version   1      currently 1
+//! count     2      number of stanzas, big-endian, then per stanza:
+//!   type    1      1: X25519 sealed box, 2: RSA-OAEP-256
+//!   length  2      big-endian
+//!   wrapped n      the content key wrapped to the recipient's public key
+//! nonce     12
+//! body      rest   ciphertext with the GCM tag appended
+//! ```
+//!
+//! Everything before the body is the GCM associated data, so stanzas
+//! cannot be added, dropped or swapped without the payload failing
+//! authentication. Stanzas carry no key IDs: [`multi_open`] tries each one
+//! of its key's type, and recipients cannot tell who else can read it
+//! beyond the count.
+
+use std::convert::TryFrom;
+
+use rand_core::OsRng;
+use rsa::{Oaep, RsaPublicKey};
+use sha2::Sha256;
+use wasm_bindgen::prelude::*;
+use zeroize::Zeroizing;
+
+use crate::envelope::{check_version, invalid, Reader};
+use crate::kdf::KEY_LENGTH;
+use crate::oaep::{parse_private_key, parse_public_key};
+use crate::x25519::public_key;
+use crate::{random_bytes, sealed_box_open, sealed_box_seal, BVaultError, Cipher, ErrorCode};
+
+const MAGIC: &[u8; 4] = b"BVMR";
+const VERSION: u8 = 1;
+
+const STANZA_X25519: u8 = 1;
+const STANZA_RSA: u8 = 2;
+
+/// Length of an X25519 secret key, which tells it apart from RSA keys.
+const X25519_KEY_LENGTH: usize = 32;
+
+enum Recipient {
+    X25519(Vec<u8>),
+    Rsa(RsaPublicKey),
+}
+
+/// The public keys a [`multi_seal`] ciphertext is encrypted to.
+#[wasm_bindgen]
+#[derive(Default)]
+pub struct Recipients {
+    keys: Vec<Recipient>,
+}
+
+#[wasm_bindgen]
+impl Recipients {
+    /// An empty list.
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Recipients {
+        let _operation = crate::utils::set_panic_hook();
+        Recipients::default()
+    }
+
+    /// Adds an X25519 public key (32 bytes).
+    ///
+    /// # Errors
+    ///
+    /// - If the key is not 32 bytes or is a low-order point, an error is
+    ///   returned.
+    pub fn add_x25519(&mut self, public_key_bytes: &[u8]) -> Result<(), BVaultError> {
+        public_key(public_key_bytes)?;
+        self.keys.push(Recipient::X25519(public_key_bytes.to_vec()));
+        Ok(())
+    }
+
+    /// Adds an RSA public key (SPKI, DER or PEM).
+    ///
+    /// # Errors
+    ///
+    /// - If the key is not an RSA SPKI key of 2048 to 8192 bits, an error is
+    ///   returned.
+    pub fn add_rsa(&mut self, public_key: &[u8]) -> Result<(), BVaultError> {
+        self.keys
+            .push(Recipient::Rsa(parse_public_key(public_key)?));
+        Ok(())
+    }
+
+    /// Number of keys added.
+    #[wasm_bindgen(getter)]
+    pub fn length(&self) -> u32 {
+        self.keys.len() as u32
+    }
+}
+
+/// Encrypts `plaintext` once so that any one of `recipients` can decrypt
+/// it with [`multi_open`].
+///
+/// # Errors
+///
+/// - If there are no recipients, or more than 65535, an
+///   [`ErrorCode::InvalidArgument`] error is returned.
+/// - If the random number generator is unavailable, an error is returned.
+#[wasm_bindgen]
+pub fn multi_seal(plaintext: &[u8], recipients: &Recipients) -> Result<Vec<u8>, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    let count = u16::try_from(recipients.keys.len())
+        .ok()
+        .filter(|&count| count > 0)
+        .ok_or_else(|| {
+            BVaultError::new(
+                ErrorCode::InvalidArgument,
+                "between 1 and 65535 recipients are required",
+            )
+        })?;
+    let cek = Zeroizing::new(random_bytes(KEY_LENGTH)?);
+
+    let mut out = Vec::new();
+    out.extend_from_slice(MAGIC);
+    out.push(VERSION);
+    out.extend_from_slice(&count.to_be_bytes());
+    for recipient in &recipients.keys {
+        let (stanza_type, wrapped) = match recipient {
+            Recipient::X25519(key) => (STANZA_X25519, sealed_box_seal(&cek, key)?),
+            Recipient::Rsa(key) => (
+                STANZA_RSA,
+                key.encrypt(&mut OsRng, Oaep::new::<Sha256>(), &cek)
+                    .map_err(|_| {
+                        BVaultError::new(ErrorCode::EncryptionFailed, "RSA-OAEP failed")
+                    })?,
+            ),
+        };
+        out.push(stanza_type);
+        out.extend_from_slice(&(wrapped.len() as u16).to_be_bytes());
+        out.extend_from_slice(&wrapped);
+    }
+
+    let cipher = Cipher::Aes256Gcm;
+    let nonce = random_bytes(cipher.nonce_length())?;
+    out.extend_from_slice(&nonce);
+    let body = cipher.encrypt_with_aad(&cek, &nonce, plaintext, &out)?;
+    out.extend_from_slice(&body);
+    Ok(out)
+}
+
+/// Decrypts the output of [`multi_seal`] with the private key of any one
+/// recipient: a 32-byte X25519 secret key, or an RSA private key (PKCS#8,
+/// DER or PEM).
+///
+/// # Errors
+///
+/// - If the key is neither, an error is returned.
+/// - If the ciphertext is malformed or uses an unknown version, an error is
+///   returned.
+/// - If no stanza is for this key, an [`ErrorCode::WrongPassword`] error is
+///   returned.
+/// - If the payload fails authentication, an [`ErrorCode::AuthFailed`]
+///   error is returned.
+#[wasm_bindgen]
+pub fn multi_open(sealed: &[u8], private_key: &[u8]) -> Result<Vec<u8>, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    let rsa_key = match private_key.len() {
+        X25519_KEY_LENGTH => None,
+        _ => Some(parse_private_key(private_key)?),
+    };
+
+    let mut reader = Reader::new(sealed);
+    if reader.take(MAGIC.len())? != MAGIC {
+        return Err(invalid("not a multi-recipient message"));
+    }
+    check_version("multi-recipient message", reader.u8()?, VERSION)?;
+    let count = reader.u16()?;
+    let mut cek = None;
+    for _ in 0..count {
+        let stanza_type = reader.u8()?;
+        let length = reader.u16()? as usize;
+        let wrapped = reader.take(length)?;
+        if cek.is_some() {
+            continue;
+        }
+        cek = match (stanza_type, &rsa_key) {
+            (STANZA_X25519, None) => sealed_box_open(wrapped, private_key).ok(),
+            (STANZA_RSA, Some(key)) => key.decrypt(Oaep::new::<Sha256>(), wrapped).ok(),
+            (STANZA_X25519, _) | (STANZA_RSA, _) => None,
+            _ => return Err(invalid("unknown stanza type")),
+        }
+        .filter(|cek| cek.len() == KEY_LENGTH)
+        .map(Zeroizing::new);
+    }
+    let cek = cek.ok_or_else(|| {
+        BVaultError::new(
+            ErrorCode::WrongPassword,
+            "message was not sealed for this key",
+        )
+    })?;
+
+    let cipher = Cipher::Aes256Gcm;
+    let nonce = reader.take(cipher.nonce_length())?;
+    let header_length = sealed.len() - reader.rest().len();
+    let (header, body) = sealed.split_at(header_length);
+    cipher.decrypt_with_aad(&cek, nonce, body, header)
+}
@@ -0,0 +1,159 @@
+//! Hybrid RSA encryption, for recipients that only publish RSA keys.
+//!
+//! A random AES-256 content key encrypts the payload with AES-256-GCM and is
+//! itself wrapped with RSA-OAEP (SHA-256, MGF1-SHA256, empty label), the
+//! combination WebCrypto and OpenSSL call `RSA-OAEP-256`. The output is
+//!
+//! ```text
+//! magic    4  "BVRS"
+//! version  1  currently 1
+//! length   2  wrapped key length (the modulus size), big-endian
+//! wrapped  n  RSA-OAEP-wrapped content key
+//! nonce    12
+//! body     rest ciphertext with the GCM tag appended
+//! ```
+//!
+//! and everything before the body is the GCM associated data. Keys are SPKI
+//! (public) or PKCS#8 (private), as DER or PEM.
+
+use rand_core::OsRng;
+use rsa::pkcs8::{DecodePrivateKey, DecodePublicKey};
+use rsa::traits::PublicKeyParts;
+use rsa::{Oaep, RsaPrivateKey, RsaPublicKey};
+use sha2::Sha256;
+use wasm_bindgen::prelude::*;
+use zeroize::Zeroizing;
+
+use crate::envelope::{check_version, invalid, Reader};
+use crate::kdf::KEY_LENGTH;
+use crate::{random_bytes, BVaultError, Cipher, ErrorCode};
+
+const MAGIC: &[u8; 4] = b"BVRS";
+const VERSION: u8 = 1;
+
+/// Smallest modulus accepted, in bits.
+const MIN_MODULUS_BITS: usize = 2048;
+/// Largest modulus accepted, in bits, so a hostile key cannot stall the page.
+const MAX_MODULUS_BITS: usize = 8192;
+
+fn invalid_key(message: &str) -> BVaultError {
+    BVaultError::new(ErrorCode::InvalidKey, message)
+}
+
+/// The key as PEM text, if it is PEM rather than DER.
+fn as_pem(key: &[u8]) -> Option<&str> {
+    std::str::from_utf8(key)
+        .ok()
+        .map(str::trim)
+        .filter(|text| text.starts_with("-----BEGIN"))
+}
+
+fn check_modulus(bits: usize) -> Result<(), BVaultError> {
+    if !(MIN_MODULUS_BITS..=MAX_MODULUS_BITS).contains(&bits) {
+        return Err(BVaultError::new(
+            ErrorCode::BadKeyLength,
+            "RSA keys must be 2048 to 8192 bits",
+        ));
+    }
+    Ok(())
+}
+
+pub(crate) fn parse_public_key(key: &[u8]) -> Result<RsaPublicKey, BVaultError> {
+    let key = match as_pem(key) {
+        Some(pem) => RsaPublicKey::from_public_key_pem(pem),
+        None => RsaPublicKey::from_public_key_der(key),
+    }
+    .map_err(|_| invalid_key("not an RSA SPKI public key"))?;
+    check_modulus(key.n().bits())?;
+    Ok(key)
+}
+
+pub(crate) fn parse_private_key(key: &[u8]) -> Result<RsaPrivateKey, BVaultError> {
+    let key = match as_pem(key) {
+        Some(pem) => RsaPrivateKey::from_pkcs8_pem(pem),
+        None => RsaPrivateKey::from_pkcs8_der(key),
+    }
+    .map_err(|_| invalid_key("not an RSA PKCS#8 private key"))?;
+    check_modulus(key.n().bits())?;
+    Ok(key)
+}
+
+/// Encrypts `plaintext` for the holder of an RSA public key (SPKI, DER or
+/// PEM).
+///
+/// # Errors
+///
+/// - If the key is not an RSA SPKI key of 2048 to 8192 bits, an error is
+///   returned.
+/// - If the random number generator is unavailable, an error is returned.
+#[wasm_bindgen]
+pub fn rsa_seal(plaintext: &[u8], public_key: &[u8]) -> Result<Vec<u8>, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    let public_key = parse_public_key(public_key)?;
+    let cek = Zeroizing::new(random_bytes(KEY_LENGTH)?);
+    let wrapped = public_key
+        .encrypt(&mut OsRng, Oaep::new::<Sha256>(), &cek)
+        .map_err(|_| BVaultError::new(ErrorCode::EncryptionFailed, "RSA-OAEP failed"))?;
+
+    let cipher = Cipher::Aes256Gcm;
+    let nonce = random_bytes(cipher.nonce_length())?;
+    let mut out = Vec::with_capacity(7 + wrapped.len() + nonce.len() + plaintext.len() + 16);
+    out.extend_from_slice(MAGIC);
+    out.push(VERSION);
+    out.extend_from_slice(&(wrapped.len() as u16).to_be_bytes());
+    out.extend_from_slice(&wrapped);
+    out.extend_from_slice(&nonce);
+    let body = cipher.encrypt_with_aad(&cek, &nonce, plaintext, &out)?;
+    out.extend_from_slice(&body);
+    Ok(out)
+}
+
+/// Decrypts the output of [`rsa_seal`] with the matching RSA private key
+/// (PKCS#8, DER or PEM).
+///
+/// # Errors
+///
+/// - If the key is not an RSA PKCS#8 key of 2048 to 8192 bits, an error is
+///   returned.
+/// - If the message is malformed, an error is returned.
+/// - If the content key does not unwrap with this key, an
+///   [`ErrorCode::WrongPassword`] error is returned.
+/// - If the payload fails authentication, an error is returned.
+#[wasm_bindgen]
+pub fn rsa_open(sealed: &[u8], private_key: &[u8]) -> Result<Vec<u8>, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    let private_key = parse_private_key(private_key)?;
+
+    let mut reader = Reader::new(sealed);
+    if reader.take(MAGIC.len())? != MAGIC {
+        return Err(invalid("not an RSA sealed message"));
+    }
+    check_version("RSA sealed message", reader.u8()?, VERSION)?;
+    let wrapped_length = reader.u16()? as usize;
+    let wrapped = reader.take(wrapped_length)?;
+    if wrapped.len() != private_key.size() {
+        return Err(BVaultError::new(
+            ErrorCode::WrongPassword,
+            "message was sealed for a different RSA key",
+        ));
+    }
+    let cipher = Cipher::Aes256Gcm;
+    let nonce = reader.take(cipher.nonce_length())?;
+    let body = reader.rest();
+    let header = &sealed[..sealed.len() - body.len()];
+
+    let cek = Zeroizing::new(
+        private_key
+            .decrypt_blinded(&mut OsRng, Oaep::new::<Sha256>(), wrapped)
+            .map_err(|_| {
+                BVaultError::new(
+                    ErrorCode::WrongPassword,
+                    "content key does not unwrap with this RSA key",
+                )
+            })?,
+    );
+    if cek.len() != KEY_LENGTH {
+        return Err(invalid("wrapped content key must be 32 bytes"));
+    }
+    cipher.decrypt_with_aad(&cek, nonce, body, header)
+}
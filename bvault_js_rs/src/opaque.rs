@@ -0,0 +1,319 @@
+//! The client side of OPAQUE (RFC 9807), so the server authenticates the
+//! user without ever seeing the master password, not even at login.
+//!
+//! Both flows are two round trips started and finished by the client:
+//!
+//! ```text
+//! registration: new OpaqueRegistration(pw).request  → server
+//!               server response → finish() → upload  → server stores it
+//! login:        new OpaqueLogin(pw).request          → server
+//!               server response → finish() → finalization → server
+//! ```
+//!
+//! Each `finish` also returns the export key, a secret derived from the
+//! password and the server's OPRF key that the server cannot compute. Its
+//! [`OpaqueLoginResult::vault_key`] is meant as the vault KEK, for
+//! [`crate::seal_with_raw_key`] and friends, so one password unlocks both
+//! the account and the vault while the server learns neither.
+//!
+//! The suite is fixed, and the server must use the same: OPRF and key
+//! exchange over ristretto255, 3DH with SHA-512, and Argon2id with the
+//! `argon2` crate defaults (19 MiB, 2 passes, 1 lane) as the key stretching
+//! function. Messages are the RFC's byte encodings.
+
+use hkdf::Hkdf;
+use opaque_ke::errors::ProtocolError;
+use opaque_ke::key_exchange::tripledh::TripleDh;
+use opaque_ke::{
+    CipherSuite, ClientLogin, ClientLoginFinishParameters, ClientRegistration,
+    ClientRegistrationFinishParameters, CredentialResponse, Identifiers, RegistrationResponse,
+    Ristretto255,
+};
+use rand_core::OsRng;
+use sha2::Sha256;
+use wasm_bindgen::prelude::*;
+use zeroize::Zeroizing;
+
+use crate::kdf::Key;
+use crate::{BVaultError, ErrorCode};
+
+const VAULT_KEY_INFO: &[u8] = b"bvault-opaque-vault-key";
+
+struct Suite;
+
+impl CipherSuite for Suite {
+    type OprfCs = Ristretto255;
+    type KeGroup = Ristretto255;
+    type KeyExchange = TripleDh;
+    type Ksf = argon2::Argon2<'static>;
+}
+
+fn protocol_error(err: ProtocolError) -> BVaultError {
+    match err {
+        ProtocolError::InvalidLoginError => {
+            BVaultError::new(ErrorCode::WrongPassword, "OPAQUE login failed")
+        }
+        ProtocolError::SerializationError | ProtocolError::IdentityGroupElementError => {
+            BVaultError::new(ErrorCode::InvalidArgument, "malformed OPAQUE message")
+        }
+        ProtocolError::ReflectedValueError => BVaultError::new(
+            ErrorCode::InvalidArgument,
+            "the server reflected the OPAQUE request",
+        ),
+        ProtocolError::LibraryError(err) => {
+            BVaultError::new(ErrorCode::KdfFailed, format!("OPAQUE failed: {:?}", err))
+        }
+    }
+}
+
+fn finished() -> BVaultError {
+    BVaultError::new(
+        ErrorCode::InvalidArgument,
+        "this OPAQUE flow was already finished",
+    )
+}
+
+fn identifiers<'a>(client: &'a Option<Vec<u8>>, server: &'a Option<Vec<u8>>) -> Identifiers<'a> {
+    Identifiers {
+        client: client.as_deref(),
+        server: server.as_deref(),
+    }
+}
+
+/// The 32-byte vault key for `export_key`.
+fn vault_key(export_key: &[u8]) -> Key {
+    let mut key = Key::default();
+    Hkdf::<Sha256>::new(None, export_key)
+        .expand(VAULT_KEY_INFO, &mut key[..])
+        .expect("valid HKDF length");
+    key
+}
+
+/// A registration in progress: send [`OpaqueRegistration::request`] to the
+/// server, then pass its response to [`OpaqueRegistration::finish`].
+#[wasm_bindgen]
+pub struct OpaqueRegistration {
+    state: Option<ClientRegistration<Suite>>,
+    password: Zeroizing<Vec<u8>>,
+    request: Vec<u8>,
+}
+
+#[wasm_bindgen]
+impl OpaqueRegistration {
+    /// Starts registering `password`.
+    ///
+    /// # Errors
+    ///
+    /// - If the password cannot be mapped to the group, an error is
+    ///   returned.
+    #[wasm_bindgen(constructor)]
+    pub fn new(password: &str) -> Result<OpaqueRegistration, BVaultError> {
+        let _operation = crate::utils::set_panic_hook();
+        let start = ClientRegistration::<Suite>::start(&mut OsRng, password.as_bytes())
+            .map_err(protocol_error)?;
+        Ok(OpaqueRegistration {
+            state: Some(start.state),
+            password: Zeroizing::new(password.as_bytes().to_vec()),
+            request: start.message.serialize().to_vec(),
+        })
+    }
+
+    /// The registration request to send to the server.
+    #[wasm_bindgen(getter)]
+    pub fn request(&self) -> Vec<u8> {
+        self.request.clone()
+    }
+
+    /// Completes registration with the server's `response`. The identifiers
+    /// default to the public keys, as in the RFC; if given, the same must
+    /// be passed at every login.
+    ///
+    /// # Errors
+    ///
+    /// - If `response` is malformed, an [`ErrorCode::InvalidArgument`]
+    ///   error is returned.
+    /// - If called twice, an [`ErrorCode::InvalidArgument`] error is
+    ///   returned.
+    pub fn finish(
+        &mut self,
+        response: &[u8],
+        client_identifier: Option<Vec<u8>>,
+        server_identifier: Option<Vec<u8>>,
+    ) -> Result<OpaqueRegistrationResult, BVaultError> {
+        let _operation = crate::utils::set_panic_hook();
+        let response =
+            RegistrationResponse::<Suite>::deserialize(response).map_err(protocol_error)?;
+        let state = self.state.take().ok_or_else(finished)?;
+        let params = ClientRegistrationFinishParameters::new(
+            identifiers(&client_identifier, &server_identifier),
+            None,
+        );
+        let result = state
+            .finish(&mut OsRng, &self.password, response, params)
+            .map_err(protocol_error)?;
+        Ok(OpaqueRegistrationResult {
+            upload: result.message.serialize().to_vec(),
+            export_key: Zeroizing::new(result.export_key.to_vec()),
+            server_public_key: result.server_s_pk.serialize().to_vec(),
+        })
+    }
+}
+
+/// The result of [`OpaqueRegistration::finish`].
+#[wasm_bindgen]
+#[derive(Clone)]
+pub struct OpaqueRegistrationResult {
+    upload: Vec<u8>,
+    export_key: Zeroizing<Vec<u8>>,
+    server_public_key: Vec<u8>,
+}
+
+#[wasm_bindgen]
+impl OpaqueRegistrationResult {
+    /// The registration record to send to the server for storage.
+    #[wasm_bindgen(getter)]
+    pub fn upload(&self) -> Vec<u8> {
+        self.upload.clone()
+    }
+
+    /// The 64-byte export key; the same at every login with this password.
+    #[wasm_bindgen(getter)]
+    pub fn export_key(&self) -> Vec<u8> {
+        self.export_key.to_vec()
+    }
+
+    /// 32 bytes derived from the export key, for use as the vault KEK.
+    #[wasm_bindgen(getter)]
+    pub fn vault_key(&self) -> Vec<u8> {
+        vault_key(&self.export_key).to_vec()
+    }
+
+    /// The server's static public key. Pin it to detect a different server
+    /// at login.
+    #[wasm_bindgen(getter)]
+    pub fn server_public_key(&self) -> Vec<u8> {
+        self.server_public_key.clone()
+    }
+}
+
+/// A login in progress: send [`OpaqueLogin::request`] to the server, then
+/// pass its response to [`OpaqueLogin::finish`].
+#[wasm_bindgen]
+pub struct OpaqueLogin {
+    state: Option<ClientLogin<Suite>>,
+    password: Zeroizing<Vec<u8>>,
+    request: Vec<u8>,
+}
+
+#[wasm_bindgen]
+impl OpaqueLogin {
+    /// Starts logging in with `password`.
+    ///
+    /// # Errors
+    ///
+    /// - If the password cannot be mapped to the group, an error is
+    ///   returned.
+    #[wasm_bindgen(constructor)]
+    pub fn new(password: &str) -> Result<OpaqueLogin, BVaultError> {
+        let _operation = crate::utils::set_panic_hook();
+        let start =
+            ClientLogin::<Suite>::start(&mut OsRng, password.as_bytes()).map_err(protocol_error)?;
+        Ok(OpaqueLogin {
+            state: Some(start.state),
+            password: Zeroizing::new(password.as_bytes().to_vec()),
+            request: start.message.serialize().to_vec(),
+        })
+    }
+
+    /// The credential request to send to the server.
+    #[wasm_bindgen(getter)]
+    pub fn request(&self) -> Vec<u8> {
+        self.request.clone()
+    }
+
+    /// Completes the login with the server's `response`, authenticating the
+    /// server. Pass the identifiers given at registration, and a `context`
+    /// the server also binds, if any.
+    ///
+    /// # Errors
+    ///
+    /// - If the password is wrong, or the server does not hold this
+    ///   account's record, an [`ErrorCode::WrongPassword`] error is
+    ///   returned.
+    /// - If `response` is malformed, an [`ErrorCode::InvalidArgument`]
+    ///   error is returned.
+    /// - If called twice, an [`ErrorCode::InvalidArgument`] error is
+    ///   returned.
+    pub fn finish(
+        &mut self,
+        response: &[u8],
+        client_identifier: Option<Vec<u8>>,
+        server_identifier: Option<Vec<u8>>,
+        context: Option<Vec<u8>>,
+    ) -> Result<OpaqueLoginResult, BVaultError> {
+        let _operation = crate::utils::set_panic_hook();
+        let response =
+            CredentialResponse::<Suite>::deserialize(response).map_err(protocol_error)?;
+        let state = self.state.take().ok_or_else(finished)?;
+        let params = ClientLoginFinishParameters::new(
+            context.as_deref(),
+            identifiers(&client_identifier, &server_identifier),
+            None,
+        );
+        let result = state
+            .finish(&self.password, response, params)
+            .map_err(protocol_error)?;
+        Ok(OpaqueLoginResult {
+            finalization: result.message.serialize().to_vec(),
+            session_key: Zeroizing::new(result.session_key.to_vec()),
+            export_key: Zeroizing::new(result.export_key.to_vec()),
+            server_public_key: result.server_s_pk.serialize().to_vec(),
+        })
+    }
+}
+
+/// The result of [`OpaqueLogin::finish`].
+#[wasm_bindgen]
+#[derive(Clone)]
+pub struct OpaqueLoginResult {
+    finalization: Vec<u8>,
+    session_key: Zeroizing<Vec<u8>>,
+    export_key: Zeroizing<Vec<u8>>,
+    server_public_key: Vec<u8>,
+}
+
+#[wasm_bindgen]
+impl OpaqueLoginResult {
+    /// The credential finalization to send to the server, which
+    /// authenticates the client.
+    #[wasm_bindgen(getter)]
+    pub fn finalization(&self) -> Vec<u8> {
+        self.finalization.clone()
+    }
+
+    /// The 64-byte session key shared with the server for this login.
+    #[wasm_bindgen(getter)]
+    pub fn session_key(&self) -> Vec<u8> {
+        self.session_key.to_vec()
+    }
+
+    /// The 64-byte export key, as at registration.
+    #[wasm_bindgen(getter)]
+    pub fn export_key(&self) -> Vec<u8> {
+        self.export_key.to_vec()
+    }
+
+    /// 32 bytes derived from the export key, for use as the vault KEK; the
+    /// same as [`OpaqueRegistrationResult::vault_key`].
+    #[wasm_bindgen(getter)]
+    pub fn vault_key(&self) -> Vec<u8> {
+        vault_key(&self.export_key).to_vec()
+    }
+
+    /// The server's static public key; compare it with the one pinned at
+    /// registration.
+    #[wasm_bindgen(getter)]
+    pub fn server_public_key(&self) -> Vec<u8> {
+        self.server_public_key.clone()
+    }
+}
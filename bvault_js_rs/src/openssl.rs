@@ -0,0 +1,265 @@
+//! Compatibility with OpenSSL's salted `enc` format.
+//!
+//! `openssl enc -aes-256-cbc` writes
+//!
+//! ```text
+//! "Salted__"  8
+//! salt        8
+//! ciphertext  rest   AES-256-CBC, PKCS#7 padding
+//! ```
+//!
+//! optionally base64-armored (`-a`). With `-pbkdf2` the key and IV come from
+//! PBKDF2 (SHA-256 and 10 000 iterations unless `-md`/`-iter` say
+//! otherwise); see [`encrypt_openssl`] and [`decrypt_openssl`].
+//!
+//! Without `-pbkdf2`, OpenSSL uses `EVP_BytesToKey` with MD5 and a single
+//! iteration, which is also what CryptoJS's default passphrase mode
+//! (`CryptoJS.AES.encrypt(msg, "pass")`) produces. That derivation is weak;
+//! [`decrypt_cryptojs`] only exists to read old data.
+
+use md5::{Digest, Md5};
+use sha2::{Sha256, Sha512};
+use wasm_bindgen::prelude::*;
+use zeroize::Zeroizing;
+
+use crate::envelope::invalid;
+use crate::kdf::KEY_LENGTH;
+use crate::legacy::{cbc_decrypt_with_key, cbc_encrypt_with_key};
+use crate::{b64_to_bytes, bytes_to_b64, random_bytes, BVaultError, ErrorCode, IV_LENGTH};
+
+const MAGIC: &[u8; 8] = b"Salted__";
+const SALT_LENGTH: usize = 8;
+
+/// `openssl enc -pbkdf2`'s default iteration count.
+const DEFAULT_ITERATIONS: u32 = 10_000;
+
+/// Line width of `openssl enc -a` output.
+const ARMOR_LINE: usize = 64;
+
+/// Digest used by OpenSSL's PBKDF2 (the `-md` option).
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum OpensslDigest {
+    /// `-md sha256`, OpenSSL's default.
+    #[default]
+    Sha256 = 1,
+    /// `-md sha512`.
+    Sha512 = 2,
+}
+
+/// Options accepted by [`encrypt_openssl`] and [`decrypt_openssl`],
+/// mirroring the `openssl enc` flags.
+#[wasm_bindgen]
+#[derive(Clone, Debug)]
+pub struct OpensslOptions {
+    iterations: u32,
+    digest: OpensslDigest,
+    armor: bool,
+}
+
+impl Default for OpensslOptions {
+    fn default() -> Self {
+        OpensslOptions {
+            iterations: DEFAULT_ITERATIONS,
+            digest: OpensslDigest::default(),
+            armor: false,
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl OpensslOptions {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> OpensslOptions {
+        OpensslOptions::default()
+    }
+
+    /// PBKDF2 iteration count (`-iter`, default 10 000).
+    #[wasm_bindgen(getter)]
+    pub fn iterations(&self) -> u32 {
+        self.iterations
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_iterations(&mut self, iterations: u32) {
+        self.iterations = iterations;
+    }
+
+    /// PBKDF2 digest (`-md`, default SHA-256).
+    #[wasm_bindgen(getter)]
+    pub fn digest(&self) -> OpensslDigest {
+        self.digest
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_digest(&mut self, digest: OpensslDigest) {
+        self.digest = digest;
+    }
+
+    /// Write base64 with 64-column lines, like `-a` (default `false`).
+    /// Decryption detects armor on its own.
+    #[wasm_bindgen(getter)]
+    pub fn armor(&self) -> bool {
+        self.armor
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_armor(&mut self, armor: bool) {
+        self.armor = armor;
+    }
+}
+
+impl OpensslOptions {
+    /// Derives the AES-256 key followed by the IV, as `-pbkdf2` does.
+    fn derive(
+        &self,
+        password: &[u8],
+        salt: &[u8],
+    ) -> Result<Zeroizing<[u8; KEY_LENGTH + IV_LENGTH]>, BVaultError> {
+        if self.iterations == 0 {
+            return Err(BVaultError::new(
+                ErrorCode::WeakKdfParams,
+                "iterations must be at least 1",
+            ));
+        }
+        let mut out = Zeroizing::new([0u8; KEY_LENGTH + IV_LENGTH]);
+        match self.digest {
+            OpensslDigest::Sha256 => {
+                pbkdf2::pbkdf2_hmac::<Sha256>(password, salt, self.iterations, &mut out[..])
+            }
+            OpensslDigest::Sha512 => {
+                pbkdf2::pbkdf2_hmac::<Sha512>(password, salt, self.iterations, &mut out[..])
+            }
+        }
+        Ok(out)
+    }
+}
+
+/// Splits an OpenSSL salted blob into its salt and ciphertext.
+fn split_salted(bytes: &[u8]) -> Result<(&[u8], &[u8]), BVaultError> {
+    if bytes.len() < MAGIC.len() + SALT_LENGTH || &bytes[..MAGIC.len()] != MAGIC {
+        return Err(invalid("missing \"Salted__\" header"));
+    }
+    let rest = &bytes[MAGIC.len()..];
+    Ok(rest.split_at(SALT_LENGTH))
+}
+
+/// Accepts raw bytes or their base64 armor, which may span several lines.
+fn dearmor(data: &[u8]) -> Result<Vec<u8>, BVaultError> {
+    if data.starts_with(MAGIC) {
+        return Ok(data.to_vec());
+    }
+    let b64: String = String::from_utf8_lossy(data)
+        .chars()
+        .filter(|c| !c.is_ascii_whitespace())
+        .collect();
+    b64_to_bytes(&b64)
+}
+
+fn armor(bytes: &[u8]) -> Vec<u8> {
+    let b64 = bytes_to_b64(bytes);
+    let mut out = Vec::with_capacity(b64.len() + b64.len() / ARMOR_LINE + 1);
+    for line in b64.as_bytes().chunks(ARMOR_LINE) {
+        out.extend_from_slice(line);
+        out.push(b'\n');
+    }
+    out
+}
+
+/// Encrypts `plaintext` like `openssl enc -aes-256-cbc -pbkdf2 -salt`, so
+/// the result opens with
+/// `openssl enc -d -aes-256-cbc -pbkdf2 [-iter N] [-md sha512] [-a]`.
+///
+/// # Errors
+///
+/// - If the iteration count is 0, an error is returned.
+/// - If the random number generator is unavailable, an error is returned.
+#[wasm_bindgen]
+pub fn encrypt_openssl(
+    plaintext: &[u8],
+    password: &str,
+    options: Option<OpensslOptions>,
+) -> Result<Vec<u8>, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    let options = options.unwrap_or_default();
+    let salt = random_bytes(SALT_LENGTH)?;
+
+    let key_iv = options.derive(password.as_bytes(), &salt)?;
+    let (key, iv) = key_iv.split_at(KEY_LENGTH);
+    let ciphertext = cbc_encrypt_with_key(key, iv, plaintext)?;
+
+    let mut out = Vec::with_capacity(MAGIC.len() + SALT_LENGTH + ciphertext.len());
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&salt);
+    out.extend_from_slice(&ciphertext);
+    Ok(if options.armor { armor(&out) } else { out })
+}
+
+/// Decrypts the output of `openssl enc -aes-256-cbc -pbkdf2`, raw or
+/// base64-armored. `options` must match the `-iter` and `-md` flags used
+/// to encrypt.
+///
+/// # Errors
+///
+/// - If the data is neither a salted OpenSSL file nor its base64, an error
+///   is returned.
+/// - If the iteration count is 0, an error is returned.
+/// - If the padding is invalid (wrong password, options or corrupted data),
+///   an error is returned.
+#[wasm_bindgen]
+pub fn decrypt_openssl(
+    data: &[u8],
+    password: &str,
+    options: Option<OpensslOptions>,
+) -> Result<Vec<u8>, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    let options = options.unwrap_or_default();
+    let bytes = dearmor(data)?;
+    let (salt, ciphertext) = split_salted(&bytes)?;
+
+    let key_iv = options.derive(password.as_bytes(), salt)?;
+    let (key, iv) = key_iv.split_at(KEY_LENGTH);
+    cbc_decrypt_with_key(key, iv, ciphertext.to_vec(), None)
+}
+
+/// OpenSSL's `EVP_BytesToKey` with MD5 and one iteration, returning the
+/// AES-256 key followed by the IV.
+fn evp_bytes_to_key(password: &[u8], salt: &[u8]) -> Zeroizing<[u8; KEY_LENGTH + IV_LENGTH]> {
+    let mut out = Zeroizing::new([0u8; KEY_LENGTH + IV_LENGTH]);
+    let mut previous = Zeroizing::new(Vec::new());
+    let mut filled = 0;
+    while filled < out.len() {
+        let mut md5 = Md5::new();
+        md5.update(&previous[..]);
+        md5.update(password);
+        md5.update(salt);
+        *previous = md5.finalize().to_vec();
+
+        let n = previous.len().min(out.len() - filled);
+        out[filled..filled + n].copy_from_slice(&previous[..n]);
+        filled += n;
+    }
+    out
+}
+
+/// Decrypts a CryptoJS passphrase-mode ciphertext (the base64 string from
+/// `CryptoJS.AES.encrypt(msg, password).toString()`).
+///
+/// # Errors
+///
+/// - If the input is not base64 or lacks the `Salted__` header, an error is
+///   returned.
+/// - If the padding is invalid (wrong password or corrupted data), an error
+///   is returned.
+/// - If the plaintext is not valid utf-8, an error is returned.
+#[wasm_bindgen]
+pub fn decrypt_cryptojs(b64_ciphertext: &str, password: &str) -> Result<String, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    let bytes = b64_to_bytes(b64_ciphertext)?;
+    let (salt, ciphertext) = split_salted(&bytes)?;
+
+    let key_iv = evp_bytes_to_key(password.as_bytes(), salt);
+    let (key, iv) = key_iv.split_at(KEY_LENGTH);
+    let plaintext = cbc_decrypt_with_key(key, iv, ciphertext.to_vec(), None)?;
+    crate::into_string(plaintext)
+}
@@ -0,0 +1,70 @@
+//! The packed batch format of [`crate::VaultSession::decrypt_packed`] and
+//! [`crate::VaultSession::encrypt_packed`].
+//!
+//! Passing thousands of small `Uint8Array`s costs a boundary crossing (and
+//! a JS object) per item; a packed batch crosses once each way. Lengths
+//! are big-endian u32:
+//!
+//! ```text
+//! input   per item:   length 4, bytes
+//! output  per item:   status 1, length 4, bytes
+//! ```
+//!
+//! Results are in input order. Status 0 means success and the bytes are
+//! the item's result; any other status is the [`crate::ErrorCode`] of that
+//! item's failure and the bytes are its utf-8 message. An empty input
+//! gives an empty output.
+
+use std::convert::TryInto;
+
+use zeroize::Zeroize;
+
+use crate::envelope::invalid;
+use crate::BVaultError;
+
+const OK: u8 = 0;
+
+/// Splits a packed input into its items.
+pub(crate) fn unpack(mut packed: &[u8]) -> Result<Vec<&[u8]>, BVaultError> {
+    let mut items = Vec::new();
+    while !packed.is_empty() {
+        if packed.len() < 4 {
+            return Err(invalid("packed batch is truncated"));
+        }
+        let (len, rest) = packed.split_at(4);
+        let len = u32::from_be_bytes(len.try_into().unwrap()) as usize;
+        if rest.len() < len {
+            return Err(invalid("packed batch is truncated"));
+        }
+        let (item, rest) = rest.split_at(len);
+        items.push(item);
+        packed = rest;
+    }
+    Ok(items)
+}
+
+/// Packs per-item results, wiping the successful ones as they are copied.
+pub(crate) fn pack(results: Vec<Result<Vec<u8>, BVaultError>>) -> Vec<u8> {
+    let capacity = results
+        .iter()
+        .map(|result| 5 + result.as_ref().map_or(64, Vec::len))
+        .sum();
+    let mut out = Vec::with_capacity(capacity);
+    for result in results {
+        match result {
+            Ok(mut bytes) => {
+                push(&mut out, OK, &bytes);
+                bytes.zeroize();
+            }
+            Err(err) => push(&mut out, err.code() as u8, err.message().as_bytes()),
+        }
+    }
+    out
+}
+
+fn push(out: &mut Vec<u8>, status: u8, bytes: &[u8]) {
+    out.push(status);
+    // Items come from a single WASM allocation, so they are under 4 GiB.
+    out.extend_from_slice(&(bytes.len() as u32).to_be_bytes());
+    out.extend_from_slice(bytes);
+}
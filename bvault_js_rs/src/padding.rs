@@ -0,0 +1,123 @@
+//! Length-hiding padding of envelope plaintexts.
+//!
+//! An AEAD ciphertext is exactly as long as its plaintext plus the tag, so
+//! the size of a stored entry can reveal what it is (a short PIN, a
+//! particular site's password, a passport scan).
+//! [`SealOptions`](crate::SealOptions) can pad the plaintext before
+//! encryption; the choice is recorded in the envelope and [`crate::open`]
+//! strips it transparently.
+//!
+//! Padding appends a `0x80` byte and then zeros (ISO/IEC 7816-4) up to the
+//! target length, which is never below 32 bytes:
+//!
+//! - [`Padding::Padme`] rounds up to the Padmé lengths of Nikitin et al.,
+//!   which leak O(log log n) bits of the length, at up to 12% overhead.
+//! - [`Padding::PowerOfTwo`] rounds up to the next power of two, which
+//!   leaves far fewer distinct sizes, at up to 100% overhead.
+//!
+//! When combined with compression, the compressed plaintext is padded.
+
+use wasm_bindgen::prelude::*;
+use zeroize::{Zeroize, Zeroizing};
+
+use crate::{BVaultError, ErrorCode};
+
+/// Shortest padded plaintext, in bytes, so short secrets all look alike.
+const MIN_PADDED_LENGTH: usize = 32;
+
+/// Marks where the padding starts.
+const MARKER: u8 = 0x80;
+
+/// A padding scheme applied to the plaintext before encryption.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Padding {
+    /// No padding. The default.
+    #[default]
+    None = 0,
+    /// Padmé: at most 12% overhead.
+    Padme = 1,
+    /// The next power of two: at most 100% overhead.
+    PowerOfTwo = 2,
+}
+
+impl Padding {
+    pub(crate) fn id(self) -> u8 {
+        self as u8
+    }
+
+    pub(crate) fn from_id(id: u8) -> Option<Padding> {
+        match id {
+            0 => Some(Padding::None),
+            1 => Some(Padding::Padme),
+            2 => Some(Padding::PowerOfTwo),
+            _ => None,
+        }
+    }
+
+    /// The padded length for `length` bytes of data plus the marker.
+    fn target(self, length: usize) -> Option<usize> {
+        let length = length.checked_add(1)?;
+        let target = match self {
+            Padding::None => return Some(length - 1),
+            Padding::Padme => padme(length)?,
+            Padding::PowerOfTwo => length.checked_next_power_of_two()?,
+        };
+        Some(target.max(MIN_PADDED_LENGTH))
+    }
+
+    /// Pads `data`, wiping the unpadded copy.
+    pub(crate) fn pad(
+        self,
+        mut data: Zeroizing<Vec<u8>>,
+    ) -> Result<Zeroizing<Vec<u8>>, BVaultError> {
+        if self == Padding::None {
+            return Ok(data);
+        }
+        let target = self.target(data.len()).ok_or_else(|| {
+            BVaultError::new(ErrorCode::BadLength, "plaintext is too long to pad")
+        })?;
+        // Copy into an allocation of the final size so growing never leaves
+        // an unwiped copy behind.
+        let mut padded = Zeroizing::new(Vec::with_capacity(target));
+        padded.extend_from_slice(&data);
+        padded.push(MARKER);
+        padded.resize(target, 0);
+        data.zeroize();
+        Ok(padded)
+    }
+
+    /// Strips the padding added by [`Padding::pad`], wiping `data` if it is
+    /// malformed.
+    pub(crate) fn unpad(self, mut data: Vec<u8>) -> Result<Vec<u8>, BVaultError> {
+        if self == Padding::None {
+            return Ok(data);
+        }
+        match data.iter().rposition(|&b| b != 0) {
+            Some(end) if data[end] == MARKER => {
+                data[end..].zeroize();
+                data.truncate(end);
+                Ok(data)
+            }
+            _ => {
+                data.zeroize();
+                Err(BVaultError::new(
+                    ErrorCode::InvalidEnvelope,
+                    "malformed plaintext padding",
+                ))
+            }
+        }
+    }
+}
+
+/// Rounds `length` up to the nearest Padmé length: keeps the top
+/// ⌊log2 ⌊log2 L⌋⌋ + 1 significant bits and clears the rest.
+fn padme(length: usize) -> Option<usize> {
+    if length < 2 {
+        return Some(length);
+    }
+    let e = usize::BITS - 1 - length.leading_zeros();
+    let s = u32::BITS - e.leading_zeros();
+    let mask = (1usize << (e - s)) - 1;
+    Some(length.checked_add(mask)? & !mask)
+}
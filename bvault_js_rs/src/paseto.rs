@@ -0,0 +1,351 @@
+//! PASETO v4.local tokens (<https://paseto.io>), for tamper-proof, expiring
+//! share links.
+//!
+//! A token is `v4.local.` followed by base64url(nonce || ciphertext || tag)
+//! and, optionally, `.` and the base64url footer. Per the spec, a random
+//! 32-byte nonce and the key give, via keyed BLAKE2b, an XChaCha20 key and
+//! nonce and a separate MAC key; the tag is BLAKE2b-MAC over the
+//! pre-authentication encoding (PAE) of the header, nonce, ciphertext,
+//! footer and implicit assertion. The footer is sent in the clear but
+//! authenticated; the implicit assertion is authenticated yet never sent.
+//!
+//! The payload is a JSON object of claims. Decryption checks the
+//! registered `exp` and `nbf` claims (RFC 3339 timestamps) against the
+//! current time.
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use blake2::digest::consts::{U32, U56};
+use blake2::digest::{FixedOutput, KeyInit, Mac};
+use blake2::Blake2bMac;
+use chacha20::cipher::{KeyIvInit, StreamCipher};
+use chacha20::XChaCha20;
+use subtle::ConstantTimeEq;
+use wasm_bindgen::prelude::*;
+use zeroize::Zeroizing;
+
+use crate::envelope::invalid;
+use crate::kdf::KEY_LENGTH;
+use crate::{random_bytes, BVaultError, ErrorCode};
+
+const HEADER: &str = "v4.local.";
+const NONCE_LENGTH: usize = 32;
+const TAG_LENGTH: usize = 32;
+
+const ENCRYPTION_KEY_LABEL: &[u8] = b"paseto-encryption-key";
+const AUTH_KEY_LABEL: &[u8] = b"paseto-auth-key-for-aead";
+
+fn check_key(key: &[u8]) -> Result<(), BVaultError> {
+    if key.len() != KEY_LENGTH {
+        return Err(BVaultError::new(
+            ErrorCode::BadKeyLength,
+            "PASETO v4.local keys must be 32 bytes",
+        ));
+    }
+    Ok(())
+}
+
+/// Pre-authentication encoding: the piece count, then each piece prefixed
+/// with its length, all as little-endian u64 with the top bit cleared.
+fn pae(pieces: &[&[u8]]) -> Vec<u8> {
+    let le64 = |n: usize| ((n as u64) & (u64::MAX >> 1)).to_le_bytes();
+    let mut out = le64(pieces.len()).to_vec();
+    for piece in pieces {
+        out.extend_from_slice(&le64(piece.len()));
+        out.extend_from_slice(piece);
+    }
+    out
+}
+
+/// The per-token keys derived from the key and nonce.
+struct TokenKeys {
+    encryption_key: Zeroizing<[u8; 32]>,
+    stream_nonce: [u8; 24],
+    auth_key: Zeroizing<[u8; 32]>,
+}
+
+impl TokenKeys {
+    fn derive(key: &[u8], nonce: &[u8]) -> TokenKeys {
+        // The key length was checked, and BLAKE2b accepts keys up to 64 bytes.
+        let mut mac = <Blake2bMac<U56> as KeyInit>::new_from_slice(key).expect("valid key");
+        Mac::update(&mut mac, ENCRYPTION_KEY_LABEL);
+        Mac::update(&mut mac, nonce);
+        let tmp = Zeroizing::new(mac.finalize_fixed());
+
+        let mut encryption_key = Zeroizing::new([0u8; 32]);
+        encryption_key.copy_from_slice(&tmp[..32]);
+        let mut stream_nonce = [0u8; 24];
+        stream_nonce.copy_from_slice(&tmp[32..]);
+
+        let mut auth_key = Zeroizing::new([0u8; 32]);
+        auth_key.copy_from_slice(&blake2b_256(key, &[AUTH_KEY_LABEL, nonce]));
+
+        TokenKeys {
+            encryption_key,
+            stream_nonce,
+            auth_key,
+        }
+    }
+
+    fn apply_keystream(&self, buf: &mut [u8]) {
+        XChaCha20::new((&*self.encryption_key).into(), (&self.stream_nonce).into())
+            .apply_keystream(buf);
+    }
+
+    fn tag(&self, nonce: &[u8], ciphertext: &[u8], footer: &[u8], implicit: &[u8]) -> [u8; 32] {
+        let pre_auth = pae(&[HEADER.as_bytes(), nonce, ciphertext, footer, implicit]);
+        blake2b_256(&self.auth_key[..], &[&pre_auth])
+    }
+}
+
+fn blake2b_256(key: &[u8], parts: &[&[u8]]) -> [u8; 32] {
+    let mut mac = <Blake2bMac<U32> as KeyInit>::new_from_slice(key).expect("valid key");
+    for part in parts {
+        Mac::update(&mut mac, part);
+    }
+    mac.finalize_fixed().into()
+}
+
+/// Creates a v4.local token carrying `claims`, a JSON object.
+///
+/// An expiring share link sets `"exp"` to an RFC 3339 timestamp, e.g.
+/// `{"item":"…","exp":"2030-01-01T00:00:00Z"}`. The `footer` (e.g. a key
+/// ID) is readable by anyone holding the token; the `implicit` assertion is
+/// not included in the token and must be supplied again to decrypt.
+///
+/// # Errors
+///
+/// - If `key` is not 32 bytes, an error is returned.
+/// - If `claims` is not a JSON object, an error is returned.
+/// - If the random number generator is unavailable, an error is returned.
+#[wasm_bindgen]
+pub fn paseto_encrypt(
+    key: &[u8],
+    claims: &str,
+    footer: Option<String>,
+    implicit: Option<String>,
+) -> Result<String, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    check_key(key)?;
+    parse_claims(claims.as_bytes())?;
+    let footer = footer.unwrap_or_default();
+    let implicit = implicit.unwrap_or_default();
+
+    let nonce = random_bytes(NONCE_LENGTH)?;
+    let keys = TokenKeys::derive(key, &nonce);
+    let mut ciphertext = claims.as_bytes().to_vec();
+    keys.apply_keystream(&mut ciphertext);
+    let tag = keys.tag(&nonce, &ciphertext, footer.as_bytes(), implicit.as_bytes());
+
+    let mut body = nonce;
+    body.extend_from_slice(&ciphertext);
+    body.extend_from_slice(&tag);
+    let mut token = format!("{}{}", HEADER, URL_SAFE_NO_PAD.encode(body));
+    if !footer.is_empty() {
+        token.push('.');
+        token.push_str(&URL_SAFE_NO_PAD.encode(footer));
+    }
+    Ok(token)
+}
+
+/// Verifies and decrypts a v4.local token, returning its claims as JSON.
+///
+/// If `footer` is given the token's footer must match it exactly; the
+/// `implicit` assertion must be the one the token was created with.
+///
+/// # Errors
+///
+/// - If `key` is not 32 bytes or the token is malformed, an error is
+///   returned.
+/// - If the key, footer or implicit assertion is wrong, or the token was
+///   tampered with, an [`ErrorCode::AuthFailed`] error is returned.
+/// - If the `exp` claim is in the past, an [`ErrorCode::TokenExpired`]
+///   error is returned; if `nbf` is in the future, an
+///   [`ErrorCode::TokenNotYetValid`] error is returned.
+#[wasm_bindgen]
+pub fn paseto_decrypt(
+    token: &str,
+    key: &[u8],
+    footer: Option<String>,
+    implicit: Option<String>,
+) -> Result<String, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    check_key(key)?;
+    let rest = token
+        .strip_prefix(HEADER)
+        .ok_or_else(|| invalid("not a PASETO v4.local token"))?;
+    let (b64_body, token_footer) = match rest.split_once('.') {
+        Some((body, b64_footer)) if !b64_footer.is_empty() => (body, decode(b64_footer)?),
+        Some(_) => return Err(invalid("PASETO footer is empty")),
+        None => (rest, Vec::new()),
+    };
+    if let Some(expected) = footer {
+        if !bool::from(expected.as_bytes().ct_eq(&token_footer)) {
+            return Err(BVaultError::new(
+                ErrorCode::AuthFailed,
+                "PASETO footer does not match",
+            ));
+        }
+    }
+
+    let body = decode(b64_body)?;
+    if body.len() < NONCE_LENGTH + TAG_LENGTH {
+        return Err(invalid("PASETO token is truncated"));
+    }
+    let (nonce, rest) = body.split_at(NONCE_LENGTH);
+    let (ciphertext, tag) = rest.split_at(rest.len() - TAG_LENGTH);
+
+    let keys = TokenKeys::derive(key, nonce);
+    let implicit = implicit.unwrap_or_default();
+    let expected = keys.tag(nonce, ciphertext, &token_footer, implicit.as_bytes());
+    if !bool::from(expected.ct_eq(tag)) {
+        return Err(BVaultError::decryption_failed(
+            ErrorCode::AuthFailed,
+            "PASETO authentication failed",
+        ));
+    }
+
+    let mut plaintext = Zeroizing::new(ciphertext.to_vec());
+    keys.apply_keystream(&mut plaintext);
+    let claims = parse_claims(&plaintext)?;
+    validate_claims(&claims, crate::utils::now_ms() / 1000.0)?;
+    crate::into_string(plaintext.to_vec())
+}
+
+fn decode(b64: &str) -> Result<Vec<u8>, BVaultError> {
+    URL_SAFE_NO_PAD
+        .decode(b64)
+        .map_err(|_| BVaultError::new(ErrorCode::InvalidBase64, "invalid base64url in token"))
+}
+
+fn parse_claims(bytes: &[u8]) -> Result<serde_json::Map<String, serde_json::Value>, BVaultError> {
+    match serde_json::from_slice(bytes) {
+        Ok(serde_json::Value::Object(claims)) => Ok(claims),
+        _ => Err(BVaultError::new(
+            ErrorCode::InvalidJson,
+            "PASETO claims must be a JSON object",
+        )),
+    }
+}
+
+/// Checks `exp` and `nbf` against `now`, in seconds since the Unix epoch.
+fn validate_claims(
+    claims: &serde_json::Map<String, serde_json::Value>,
+    now: f64,
+) -> Result<(), BVaultError> {
+    let time = |name: &str| -> Result<Option<f64>, BVaultError> {
+        match claims.get(name) {
+            None => Ok(None),
+            Some(value) => value
+                .as_str()
+                .and_then(parse_rfc3339)
+                .map(Some)
+                .ok_or_else(|| {
+                    BVaultError::new(
+                        ErrorCode::InvalidJson,
+                        format!("claim {} must be an RFC 3339 timestamp", name),
+                    )
+                }),
+        }
+    };
+
+    if let Some(exp) = time("exp")? {
+        if now >= exp {
+            return Err(BVaultError::new(
+                ErrorCode::TokenExpired,
+                "token has expired",
+            ));
+        }
+    }
+    if let Some(nbf) = time("nbf")? {
+        if now < nbf {
+            return Err(BVaultError::new(
+                ErrorCode::TokenNotYetValid,
+                "token is not valid yet",
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Parses an RFC 3339 date-time (`2030-01-01T00:00:00Z`,
+/// `2030-01-01T02:00:00.5+02:00`) into seconds since the Unix epoch.
+fn parse_rfc3339(s: &str) -> Option<f64> {
+    let b = s.as_bytes();
+    let digits = |range: std::ops::Range<usize>| -> Option<i64> {
+        let part = b.get(range)?;
+        if !part.iter().all(u8::is_ascii_digit) {
+            return None;
+        }
+        std::str::from_utf8(part).ok()?.parse().ok()
+    };
+    if b.len() < 20 || b[4] != b'-' || b[7] != b'-' || b[13] != b':' || b[16] != b':' {
+        return None;
+    }
+    if !matches!(b[10], b'T' | b't' | b' ') {
+        return None;
+    }
+    let (year, month, day) = (digits(0..4)?, digits(5..7)?, digits(8..10)?);
+    let (hour, minute, second) = (digits(11..13)?, digits(14..16)?, digits(17..19)?);
+    if !(1..=12).contains(&month) || day < 1 || day > days_in_month(year, month) {
+        return None;
+    }
+    if hour > 23 || minute > 59 || second > 60 {
+        return None;
+    }
+
+    let mut pos = 19;
+    let mut fraction = 0.0;
+    if b[pos] == b'.' {
+        let start = pos + 1;
+        pos = start;
+        while pos < b.len() && b[pos].is_ascii_digit() {
+            pos += 1;
+        }
+        if pos == start {
+            return None;
+        }
+        fraction = format!("0.{}", &s[start..pos]).parse().ok()?;
+    }
+
+    let offset = match b.get(pos)? {
+        b'Z' | b'z' if pos + 1 == b.len() => 0,
+        sign @ (b'+' | b'-') if pos + 6 == b.len() && b[pos + 3] == b':' => {
+            let (h, m) = (digits(pos + 1..pos + 3)?, digits(pos + 4..pos + 6)?);
+            if h > 23 || m > 59 {
+                return None;
+            }
+            let offset = h * 3600 + m * 60;
+            if *sign == b'+' {
+                offset
+            } else {
+                -offset
+            }
+        }
+        _ => return None,
+    };
+
+    let days = days_from_civil(year, month, day);
+    let seconds = days * 86_400 + hour * 3600 + minute * 60 + second - offset;
+    Some(seconds as f64 + fraction)
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Days since 1970-01-01 for a proleptic Gregorian date.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month_index = (month + 9) % 12;
+    let day_of_year = (153 * month_index + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
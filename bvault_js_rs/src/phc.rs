@@ -0,0 +1,121 @@
+//! Argon2id password hashes for login checks, in the PHC string format
+//! that servers and other Argon2 libraries read:
+//!
+//! ```text
+//! $argon2id$v=19$m=19456,t=2,p=1$<salt, base64>$<hash, base64>
+//! ```
+//!
+//! Unlike the KDFs behind encryption, these hashes are stored and compared
+//! rather than used as keys. [`verify_password_hash`] takes its parameters
+//! from the string, so hashes made with older or stronger settings keep
+//! verifying after the defaults change.
+
+use std::convert::TryFrom;
+
+use argon2::password_hash::{self, PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::{Algorithm, Argon2, Params, Version};
+use wasm_bindgen::prelude::*;
+
+use crate::kdf::Kdf;
+use crate::{random_bytes, BVaultError, ErrorCode, KdfParams, SALT_LENGTH};
+
+/// Highest Argon2 memory cost [`verify_password_hash`] accepts, in KiB
+/// (1 GiB), so a hostile string cannot exhaust WASM memory.
+const MAX_MEMORY_KIB: u32 = 1024 * 1024;
+
+/// Highest Argon2 pass count [`verify_password_hash`] accepts, so a
+/// hostile string cannot stall the page.
+const MAX_ITERATIONS: u32 = 64;
+
+fn malformed(err: password_hash::Error) -> BVaultError {
+    BVaultError::new(
+        ErrorCode::InvalidArgument,
+        format!("malformed PHC string: {}", err),
+    )
+}
+
+/// Hashes `password` with Argon2id under a fresh 16-byte salt and returns
+/// the PHC string to store.
+///
+/// `params` must be [`KdfParams::argon2id`] parameters, without a keyfile;
+/// they default to 19 MiB, 2 passes and 1 lane.
+///
+/// # Errors
+///
+/// - If `params` are not Argon2id, an [`ErrorCode::UnsupportedAlgorithm`]
+///   error is returned; if they have a keyfile, an
+///   [`ErrorCode::InvalidArgument`] error.
+/// - If the parameters are below the accepted minimum, an error is returned.
+/// - If the random number generator is unavailable, an error is returned.
+#[wasm_bindgen]
+pub fn hash_password(password: &str, params: Option<KdfParams>) -> Result<String, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    let params = params.unwrap_or_else(|| KdfParams::argon2id(None, None, None));
+    let (memory_kib, iterations, parallelism) = match params.kdf() {
+        Kdf::Argon2id {
+            memory_kib,
+            iterations,
+            parallelism,
+        } => (memory_kib, iterations, parallelism),
+        Kdf::Pbkdf2 { .. } | Kdf::Scrypt { .. } => {
+            return Err(BVaultError::new(
+                ErrorCode::UnsupportedAlgorithm,
+                "password hashes use Argon2id",
+            ))
+        }
+    };
+    if params.has_keyfile() {
+        return Err(BVaultError::new(
+            ErrorCode::InvalidArgument,
+            "password hashes cannot use a keyfile",
+        ));
+    }
+    params.validate()?;
+
+    let argon2_params = Params::new(memory_kib, iterations, parallelism, None)
+        .map_err(|_| BVaultError::new(ErrorCode::WeakKdfParams, "invalid Argon2 parameters"))?;
+    let salt = SaltString::encode_b64(&random_bytes(SALT_LENGTH)?).map_err(malformed)?;
+    let hash = Argon2::new(Algorithm::Argon2id, Version::V0x13, argon2_params)
+        .hash_password(password.as_bytes(), &salt)
+        .map_err(|_| BVaultError::new(ErrorCode::KdfFailed, "Argon2 hashing failed"))?;
+    Ok(hash.to_string())
+}
+
+/// Whether `password` matches the PHC string `phc`, compared in constant
+/// time. The Argon2 variant, version and parameters are read from `phc`.
+///
+/// # Errors
+///
+/// - If `phc` is not a valid PHC string, an [`ErrorCode::InvalidArgument`]
+///   error is returned.
+/// - If it is not an Argon2 hash, an [`ErrorCode::UnsupportedAlgorithm`]
+///   error is returned.
+/// - If its memory cost is above 1 GiB or it runs more than 64 passes, an
+///   [`ErrorCode::WeakKdfParams`] error is returned.
+#[wasm_bindgen]
+pub fn verify_password_hash(password: &str, phc: &str) -> Result<bool, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    let hash = PasswordHash::new(phc).map_err(malformed)?;
+    if hash.salt.is_none() || hash.hash.is_none() {
+        return Err(malformed(password_hash::Error::PhcStringField));
+    }
+    if Algorithm::try_from(hash.algorithm).is_err() {
+        return Err(BVaultError::new(
+            ErrorCode::UnsupportedAlgorithm,
+            format!("{} password hashes are not supported", hash.algorithm),
+        ));
+    }
+    let params = Params::try_from(&hash).map_err(malformed)?;
+    if params.m_cost() > MAX_MEMORY_KIB || params.t_cost() > MAX_ITERATIONS {
+        return Err(BVaultError::new(
+            ErrorCode::WeakKdfParams,
+            "Argon2 parameters are above 1 GiB or 64 passes",
+        ));
+    }
+
+    match Argon2::default().verify_password(password.as_bytes(), &hash) {
+        Ok(()) => Ok(true),
+        Err(password_hash::Error::Password) => Ok(false),
+        Err(err) => Err(malformed(err)),
+    }
+}
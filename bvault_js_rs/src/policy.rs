@@ -0,0 +1,255 @@
+//! A process-wide security policy, so integrators cannot accidentally
+//! create weak vaults.
+//!
+//! [`set_policy`] installs a [`SecurityPolicy`] that every password-based
+//! encryption checks before deriving its key: the PBKDF2 iteration count,
+//! the salt length, the password length and the cipher. A violation fails
+//! with [`ErrorCode::PolicyViolation`].
+//!
+//! Decryption never fails on policy grounds, since existing data must stay
+//! readable. With [`SecurityPolicy::warn_on_decrypt`] set, decrypting under
+//! a KDF, salt or password below the policy logs a `console.warn` instead.
+//! [`SecurityPolicy::strict_validation`] is not a floor but a debugging
+//! aid: malformed input then fails up front with every problem listed.
+//!
+//! The default policy only enforces the minimums every function already
+//! applies.
+
+use std::sync::{PoisonError, RwLock};
+
+use wasm_bindgen::prelude::*;
+
+use crate::kdf::Kdf;
+use crate::validation::ValidationReport;
+use crate::{BVaultError, Cipher, ErrorCode, KdfParams};
+
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(js_namespace = console)]
+    fn warn(message: &str);
+}
+
+static POLICY: RwLock<SecurityPolicy> = RwLock::new(SecurityPolicy::DEFAULT);
+
+/// Minimums and allowed algorithms enforced on every encryption.
+#[wasm_bindgen]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SecurityPolicy {
+    min_pbkdf2_iterations: u32,
+    min_salt_length: usize,
+    min_password_length: usize,
+    denied_ciphers: Vec<Cipher>,
+    allow_legacy_cbc: bool,
+    warn_on_decrypt: bool,
+    strict_validation: bool,
+}
+
+impl SecurityPolicy {
+    const DEFAULT: SecurityPolicy = SecurityPolicy {
+        min_pbkdf2_iterations: 10_000,
+        min_salt_length: crate::MIN_SALT_LENGTH,
+        min_password_length: 0,
+        denied_ciphers: Vec::new(),
+        allow_legacy_cbc: true,
+        warn_on_decrypt: false,
+        strict_validation: false,
+    };
+}
+
+impl Default for SecurityPolicy {
+    fn default() -> Self {
+        SecurityPolicy::DEFAULT
+    }
+}
+
+#[wasm_bindgen]
+impl SecurityPolicy {
+    /// The default policy: every cipher allowed, no minimums beyond the
+    /// built-in ones.
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> SecurityPolicy {
+        SecurityPolicy::default()
+    }
+
+    /// Lowest PBKDF2 iteration count accepted (default 10 000). Argon2id
+    /// and scrypt are not affected.
+    #[wasm_bindgen(getter)]
+    pub fn min_pbkdf2_iterations(&self) -> u32 {
+        self.min_pbkdf2_iterations
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_min_pbkdf2_iterations(&mut self, iterations: u32) {
+        self.min_pbkdf2_iterations = iterations;
+    }
+
+    /// Shortest salt accepted, in bytes (default 8).
+    #[wasm_bindgen(getter)]
+    pub fn min_salt_length(&self) -> usize {
+        self.min_salt_length
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_min_salt_length(&mut self, length: usize) {
+        self.min_salt_length = length;
+    }
+
+    /// Shortest password accepted, in characters (default 0).
+    #[wasm_bindgen(getter)]
+    pub fn min_password_length(&self) -> usize {
+        self.min_password_length
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_min_password_length(&mut self, length: usize) {
+        self.min_password_length = length;
+    }
+
+    /// Whether the legacy AES-256-CBC functions may encrypt (default
+    /// `true`).
+    #[wasm_bindgen(getter)]
+    pub fn allow_legacy_cbc(&self) -> bool {
+        self.allow_legacy_cbc
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_allow_legacy_cbc(&mut self, allowed: bool) {
+        self.allow_legacy_cbc = allowed;
+    }
+
+    /// Whether decrypting below the policy logs a warning (default
+    /// `false`).
+    #[wasm_bindgen(getter)]
+    pub fn warn_on_decrypt(&self) -> bool {
+        self.warn_on_decrypt
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_warn_on_decrypt(&mut self, warn: bool) {
+        self.warn_on_decrypt = warn;
+    }
+
+    /// Whether [`crate::open`], [`crate::open_string`] and the legacy
+    /// `decrypt_*` functions validate their input before deriving a key,
+    /// failing with an [`ErrorCode::ValidationFailed`] error that lists
+    /// every problem (default `false`); see [`crate::validate_envelope`].
+    #[wasm_bindgen(getter)]
+    pub fn strict_validation(&self) -> bool {
+        self.strict_validation
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_strict_validation(&mut self, strict: bool) {
+        self.strict_validation = strict;
+    }
+
+    /// Allows or forbids encrypting with `cipher`. All ciphers are allowed
+    /// by default.
+    pub fn allow_cipher(&mut self, cipher: Cipher, allowed: bool) {
+        self.denied_ciphers.retain(|&denied| denied != cipher);
+        if !allowed {
+            self.denied_ciphers.push(cipher);
+        }
+    }
+
+    /// Whether encrypting with `cipher` is allowed.
+    pub fn cipher_allowed(&self, cipher: Cipher) -> bool {
+        !self.denied_ciphers.contains(&cipher)
+    }
+}
+
+impl SecurityPolicy {
+    /// The first way the parameters fall short of the policy, if any.
+    fn violation(&self, password: &str, salt_length: usize, kdf: &KdfParams) -> Option<String> {
+        if let Kdf::Pbkdf2 { iterations, .. } = kdf.kdf() {
+            if iterations < self.min_pbkdf2_iterations {
+                return Some(format!(
+                    "PBKDF2 iterations must be at least {}",
+                    self.min_pbkdf2_iterations
+                ));
+            }
+        }
+        if salt_length < self.min_salt_length {
+            return Some(format!(
+                "salt must be at least {} bytes",
+                self.min_salt_length
+            ));
+        }
+        if password.chars().count() < self.min_password_length {
+            return Some(format!(
+                "password must be at least {} characters",
+                self.min_password_length
+            ));
+        }
+        None
+    }
+}
+
+/// Installs `policy` for every later call.
+#[wasm_bindgen]
+pub fn set_policy(policy: &SecurityPolicy) {
+    let _operation = crate::utils::set_panic_hook();
+    *POLICY.write().unwrap_or_else(PoisonError::into_inner) = policy.clone();
+}
+
+/// Returns a copy of the policy in force.
+#[wasm_bindgen]
+pub fn get_policy() -> SecurityPolicy {
+    let _operation = crate::utils::set_panic_hook();
+    POLICY
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone()
+}
+
+/// Checks an encryption against the policy; `cipher` is `None` for legacy
+/// AES-256-CBC.
+pub(crate) fn check_encrypt(
+    password: &str,
+    salt_length: usize,
+    kdf: &KdfParams,
+    cipher: Option<Cipher>,
+) -> Result<(), BVaultError> {
+    let policy = POLICY.read().unwrap_or_else(PoisonError::into_inner);
+    let violation = match cipher {
+        Some(cipher) if !policy.cipher_allowed(cipher) => {
+            Some(format!("{:?} is not allowed by the policy", cipher))
+        }
+        None if !policy.allow_legacy_cbc => {
+            Some("legacy AES-256-CBC is not allowed by the policy".to_string())
+        }
+        _ => policy.violation(password, salt_length, kdf),
+    };
+    match violation {
+        Some(message) => Err(BVaultError::new(ErrorCode::PolicyViolation, message)),
+        None => Ok(()),
+    }
+}
+
+/// Warns if a decryption falls below the policy and the policy asks for it.
+pub(crate) fn check_decrypt(password: &str, salt_length: usize, kdf: &KdfParams) {
+    let policy = POLICY.read().unwrap_or_else(PoisonError::into_inner);
+    if !policy.warn_on_decrypt {
+        return;
+    }
+    if let Some(message) = policy.violation(password, salt_length, kdf) {
+        warn(&format!(
+            "bvault: decrypting below the security policy: {}",
+            message
+        ));
+    }
+}
+
+/// Fails with the problems `diagnose` finds if the policy asks for strict
+/// validation.
+pub(crate) fn check_input(diagnose: impl FnOnce() -> ValidationReport) -> Result<(), BVaultError> {
+    let strict = POLICY
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .strict_validation;
+    if strict {
+        diagnose().into_result()
+    } else {
+        Ok(())
+    }
+}
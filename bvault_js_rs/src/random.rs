@@ -0,0 +1,89 @@
+//! Random salts, IVs and nonces from the platform CSPRNG.
+//!
+//! Everything here reads from `crypto.getRandomValues` through `getrandom`
+//! (or from Node's `crypto` module where that global is missing), so
+//! callers never need to size or generate these values in JS, in any
+//! runtime; see `runtime`.
+
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    bytes_to_b64, BVaultError, Cipher, ErrorCode, IV_LENGTH, MIN_SALT_LENGTH, SALT_LENGTH,
+};
+
+/// Returns `length` cryptographically secure random bytes.
+///
+/// # Errors
+///
+/// - If the random number generator is unavailable, an error is returned.
+#[wasm_bindgen]
+pub fn random_bytes(length: usize) -> Result<Vec<u8>, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    let mut buf = vec![0u8; length];
+    getrandom::getrandom(&mut buf).map_err(|_| {
+        BVaultError::new(
+            ErrorCode::RandomUnavailable,
+            crate::runtime::random_unavailable(),
+        )
+    })?;
+    Ok(buf)
+}
+
+/// Returns a new base64-encoded random salt of `length` bytes (default 16).
+///
+/// # Errors
+///
+/// - If `length` is shorter than 8 bytes, an error is returned.
+/// - If the random number generator is unavailable, an error is returned.
+#[wasm_bindgen]
+pub fn generate_salt(length: Option<usize>) -> Result<String, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    let length = length.unwrap_or(SALT_LENGTH);
+    if length < MIN_SALT_LENGTH {
+        return Err(BVaultError::new(
+            ErrorCode::BadSaltLength,
+            "salt must be at least 8 bytes",
+        ));
+    }
+    random_bytes(length).map(|salt| bytes_to_b64(&salt))
+}
+
+/// Returns a new base64-encoded 16-byte IV for the AES-CBC functions.
+///
+/// # Errors
+///
+/// - If the random number generator is unavailable, an error is returned.
+#[wasm_bindgen]
+pub fn generate_iv() -> Result<String, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    random_bytes(IV_LENGTH).map(|iv| bytes_to_b64(&iv))
+}
+
+/// Returns a new base64-encoded nonce of the right length for `cipher`.
+///
+/// A nonce must never be reused with the same key; generate a new one for
+/// every message.
+///
+/// # Errors
+///
+/// - If the random number generator is unavailable, an error is returned.
+#[wasm_bindgen]
+pub fn generate_nonce(cipher: Cipher) -> Result<String, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    random_bytes(cipher.nonce_length()).map(|nonce| bytes_to_b64(&nonce))
+}
+
+/// Returns a uniformly distributed random integer in `0..bound`.
+///
+/// Uses rejection sampling so no value is more likely than another.
+pub(crate) fn random_below(bound: u32) -> Result<u32, BVaultError> {
+    debug_assert!(bound > 0);
+    let zone = u32::MAX - u32::MAX % bound;
+    loop {
+        let bytes = random_bytes(4)?;
+        let value = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        if value < zone {
+            return Ok(value % bound);
+        }
+    }
+}
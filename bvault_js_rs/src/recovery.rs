@@ -0,0 +1,221 @@
+//! One-time recovery codes for account recovery.
+//!
+//! [`generate_recovery_codes`] returns the codes to show the user once,
+//! together with a record to store, which holds only a salted SHA-256 hash
+//! of each code. [`verify_recovery_code`] checks a code against the record
+//! and returns the record with that code marked as used. The record is
+//! JSON, so every client reads and writes the same format:
+//!
+//! ```text
+//! {
+//!   "format": "bvault-recovery-codes",
+//!   "version": 1,
+//!   "codes": [ { "salt": "<base64>", "hash": "<base64>", "used": false } ]
+//! }
+//! ```
+//!
+//! Codes are 16 characters of Crockford's base32 (80 random bits), shown
+//! in groups of four: `7K2M-Q9XD-3HRT-W0PA`. They are too strong to guess,
+//! so a fast hash suffices. When checking a code, case, spaces and dashes
+//! are ignored and the look-alikes `O`, `I` and `L` read as `0` and `1`.
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use subtle::ConstantTimeEq;
+use wasm_bindgen::prelude::*;
+use zeroize::Zeroizing;
+
+use crate::{b64_to_bytes, bytes_to_b64, random_bytes, BVaultError, ErrorCode};
+
+const FORMAT: &str = "bvault-recovery-codes";
+const VERSION: u8 = 1;
+
+/// Crockford's base32 alphabet, without `I`, `L`, `O` and `U`.
+const ALPHABET: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+
+/// Characters per code, and per displayed group.
+const CODE_LENGTH: usize = 16;
+const GROUP_LENGTH: usize = 4;
+
+/// Salt length per code, in bytes.
+const SALT_LENGTH: usize = 16;
+
+/// Codes generated when the caller does not say.
+const DEFAULT_COUNT: u32 = 10;
+
+/// Most codes generated at once.
+const MAX_COUNT: u32 = 100;
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RecordJson {
+    format: String,
+    version: u8,
+    codes: Vec<CodeJson>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CodeJson {
+    salt: String,
+    hash: String,
+    used: bool,
+}
+
+impl RecordJson {
+    fn parse(stored: &str) -> Result<RecordJson, BVaultError> {
+        let record: RecordJson = serde_json::from_str(stored)
+            .map_err(|e| BVaultError::new(ErrorCode::InvalidJson, e.to_string()))?;
+        if record.format != FORMAT {
+            return Err(BVaultError::new(
+                ErrorCode::InvalidJson,
+                "not a bvault recovery code record",
+            ));
+        }
+        crate::envelope::check_version("recovery code record", record.version, VERSION)?;
+        Ok(record)
+    }
+
+    fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("recovery code records always serialize")
+    }
+}
+
+/// Uppercases `code`, drops separators and maps look-alikes onto the
+/// alphabet.
+fn normalize(code: &str) -> Zeroizing<Vec<u8>> {
+    Zeroizing::new(
+        code.bytes()
+            .filter(|b| !matches!(b, b' ' | b'-'))
+            .map(|b| match b.to_ascii_uppercase() {
+                b'O' => b'0',
+                b'I' | b'L' => b'1',
+                b => b,
+            })
+            .collect(),
+    )
+}
+
+fn hash_code(salt: &[u8], code: &[u8]) -> Vec<u8> {
+    let mut hasher = Sha256::new();
+    hasher.update(salt);
+    hasher.update(code);
+    hasher.finalize().to_vec()
+}
+
+/// Freshly generated recovery codes and the record to store for them.
+#[wasm_bindgen]
+pub struct RecoveryCodes {
+    codes: Vec<String>,
+    stored: String,
+}
+
+#[wasm_bindgen]
+impl RecoveryCodes {
+    /// The codes, to show the user once and then forget.
+    #[wasm_bindgen(getter)]
+    pub fn codes(&self) -> Vec<String> {
+        self.codes.clone()
+    }
+
+    /// The record to store; it holds only salted hashes of the codes.
+    #[wasm_bindgen(getter)]
+    pub fn stored(&self) -> String {
+        self.stored.clone()
+    }
+}
+
+/// Generates `count` recovery codes (default 10, at most 100).
+///
+/// # Errors
+///
+/// - If `count` is 0 or above 100, an [`ErrorCode::InvalidArgument`] error
+///   is returned.
+/// - If the random number generator is unavailable, an error is returned.
+#[wasm_bindgen]
+pub fn generate_recovery_codes(count: Option<u32>) -> Result<RecoveryCodes, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    let count = count.unwrap_or(DEFAULT_COUNT);
+    if count == 0 || count > MAX_COUNT {
+        return Err(BVaultError::new(
+            ErrorCode::InvalidArgument,
+            "count must be between 1 and 100",
+        ));
+    }
+
+    let mut codes = Vec::with_capacity(count as usize);
+    let mut entries = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        let code = Zeroizing::new(
+            random_bytes(CODE_LENGTH)?
+                .iter()
+                .map(|b| ALPHABET[(b & 0x1f) as usize])
+                .collect::<Vec<u8>>(),
+        );
+        let salt = random_bytes(SALT_LENGTH)?;
+        entries.push(CodeJson {
+            hash: bytes_to_b64(&hash_code(&salt, &code)),
+            salt: bytes_to_b64(&salt),
+            used: false,
+        });
+        let groups: Vec<&str> = code
+            .chunks(GROUP_LENGTH)
+            .map(|group| std::str::from_utf8(group).expect("the alphabet is ASCII"))
+            .collect();
+        codes.push(groups.join("-"));
+    }
+
+    let record = RecordJson {
+        format: FORMAT.to_string(),
+        version: VERSION,
+        codes: entries,
+    };
+    Ok(RecoveryCodes {
+        codes,
+        stored: record.to_json(),
+    })
+}
+
+/// Checks `code` against the `stored` record. Returns the record with the
+/// code marked as used, to store in place of the old one, or `undefined`
+/// if the code is wrong or already used. Every entry is compared in
+/// constant time.
+///
+/// # Errors
+///
+/// - If `stored` is not a valid record, an [`ErrorCode::InvalidJson`],
+///   [`ErrorCode::InvalidBase64`] or [`ErrorCode::UnsupportedVersion`]
+///   error is returned.
+#[wasm_bindgen]
+pub fn verify_recovery_code(code: &str, stored: &str) -> Result<Option<String>, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    let mut record = RecordJson::parse(stored)?;
+    let code = normalize(code);
+
+    let mut matched = None;
+    for (index, entry) in record.codes.iter().enumerate() {
+        let salt = b64_to_bytes(&entry.salt)?;
+        let hash = b64_to_bytes(&entry.hash)?;
+        let equal: bool = hash_code(&salt, &code).ct_eq(&hash).into();
+        if equal && !entry.used && matched.is_none() {
+            matched = Some(index);
+        }
+    }
+
+    Ok(matched.map(|index| {
+        record.codes[index].used = true;
+        record.to_json()
+    }))
+}
+
+/// How many codes in the `stored` record are still unused.
+///
+/// # Errors
+///
+/// See [`verify_recovery_code`].
+#[wasm_bindgen]
+pub fn recovery_codes_remaining(stored: &str) -> Result<u32, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    let record = RecordJson::parse(stored)?;
+    Ok(record.codes.iter().filter(|entry| !entry.used).count() as u32)
+}
@@ -0,0 +1,198 @@
+//! Re-keying a vault under a new password in batches, for vaults too large
+//! to re-encrypt within one frame or one page load.
+//!
+//! A [`RekeyJob`] moves entries from the old vault into a new one, a batch
+//! per [`RekeyJob::step`], in ID order. [`RekeyJob::save`] captures the job
+//! between batches as a blob (big-endian):
+//!
+//! ```text
+//! magic    4      "BVRK"
+//! version  1      currently 1
+//! form     1      0 for a binary vault, 1 for CBOR
+//! old      4 + n  length-prefixed binary vault of the entries left to move
+//! new      n      binary vault of the entries moved so far, to the end
+//! ```
+//!
+//! Both vaults stay encrypted in the saved job, each under its own key, and
+//! [`RekeyJob::resume`] needs both passwords. Keep the original vault until
+//! [`RekeyJob::finish`] returns its replacement.
+
+use std::convert::TryFrom;
+
+use wasm_bindgen::prelude::*;
+use zeroize::Zeroizing;
+
+use crate::envelope::{check_version, invalid, is_cbor, Reader};
+use crate::{BVaultError, ErrorCode, OpenOptions, SealOptions, Vault};
+
+const MAGIC: &[u8; 4] = b"BVRK";
+const VERSION: u8 = 1;
+
+const FORM_BINARY: u8 = 0;
+const FORM_CBOR: u8 = 1;
+
+/// A resumable move of a vault's entries under a new password.
+#[wasm_bindgen]
+pub struct RekeyJob {
+    old: Vault,
+    new: Vault,
+    cbor: bool,
+}
+
+#[wasm_bindgen]
+impl RekeyJob {
+    /// Starts re-keying the vault `blob` from `old_password` to
+    /// `new_password`. The new vault uses the salt length, KDF and cipher
+    /// in `new_options`; no entry is moved yet.
+    ///
+    /// # Errors
+    ///
+    /// - If the blob is malformed or `old_password` is wrong, an error is
+    ///   returned.
+    /// - Otherwise see [`Vault::create`].
+    #[wasm_bindgen(constructor)]
+    pub fn new(
+        blob: &[u8],
+        old_password: &str,
+        new_password: &str,
+        old_options: Option<OpenOptions>,
+        new_options: Option<SealOptions>,
+    ) -> Result<RekeyJob, BVaultError> {
+        let _operation = crate::utils::set_panic_hook();
+        Ok(RekeyJob {
+            old: Vault::unlock(blob, old_password, old_options)?,
+            new: Vault::create(new_password, new_options)?,
+            cbor: is_cbor(blob),
+        })
+    }
+
+    /// Resumes a job saved with [`RekeyJob::save`]. `new_options` carries
+    /// the new keyfile, if the new vault has one.
+    ///
+    /// # Errors
+    ///
+    /// - If `state` is malformed or uses an unknown version, an
+    ///   [`ErrorCode::InvalidEnvelope`] or [`ErrorCode::UnsupportedVersion`]
+    ///   error is returned.
+    /// - If either password is wrong, an [`ErrorCode::WrongPassword`] error
+    ///   is returned.
+    pub fn resume(
+        state: &[u8],
+        old_password: &str,
+        new_password: &str,
+        old_options: Option<OpenOptions>,
+        new_options: Option<OpenOptions>,
+    ) -> Result<RekeyJob, BVaultError> {
+        let _operation = crate::utils::set_panic_hook();
+        let mut reader = Reader::new(state);
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err(invalid("not a bvault re-key job"));
+        }
+        check_version("re-key job", reader.u8()?, VERSION)?;
+        let cbor = match reader.u8()? {
+            FORM_BINARY => false,
+            FORM_CBOR => true,
+            _ => return Err(invalid("unknown vault form")),
+        };
+        let old_len = reader.u32()? as usize;
+        let old = reader.take(old_len)?;
+        let new = reader.rest();
+        Ok(RekeyJob {
+            old: Vault::unlock(old, old_password, old_options)?,
+            new: Vault::unlock(new, new_password, new_options)?,
+            cbor,
+        })
+    }
+
+    /// Moves up to `batch_size` more entries into the new vault. Returns
+    /// whether every entry has been moved.
+    ///
+    /// # Errors
+    ///
+    /// - If an entry fails authentication, an error is returned and the job
+    ///   is left as it was before that entry.
+    /// - If the random number generator is unavailable, an error is returned.
+    pub fn step(&mut self, batch_size: u32) -> Result<bool, BVaultError> {
+        let ids: Vec<String> = self
+            .old
+            .entries()
+            .keys()
+            .take(batch_size as usize)
+            .cloned()
+            .collect();
+        for id in ids {
+            let entry = &self.old.entries()[&id];
+            let modified = entry.modified;
+            let plaintext = Zeroizing::new(self.old.open_entry(&id, entry)?);
+            let metadata = self
+                .old
+                .open_entry_metadata(&id, entry)?
+                .map(Zeroizing::new);
+            self.new.put_entry(
+                &id,
+                &plaintext,
+                metadata.as_deref().map(Vec::as_slice),
+                modified,
+            )?;
+            self.old.remove_entry(&id);
+        }
+        Ok(self.finished())
+    }
+
+    /// Entries moved so far.
+    #[wasm_bindgen(getter)]
+    pub fn done(&self) -> u32 {
+        self.new.entries().len() as u32
+    }
+
+    /// Entries in the vault being re-keyed.
+    #[wasm_bindgen(getter)]
+    pub fn total(&self) -> u32 {
+        (self.old.entries().len() + self.new.entries().len()) as u32
+    }
+
+    /// Whether every entry has been moved.
+    #[wasm_bindgen(getter)]
+    pub fn finished(&self) -> bool {
+        self.old.entries().is_empty()
+    }
+
+    /// The job's state, to store and pass to [`RekeyJob::resume`].
+    ///
+    /// # Errors
+    ///
+    /// - If a key check value cannot be computed, an error is returned.
+    pub fn save(&self) -> Result<Vec<u8>, BVaultError> {
+        let old = self.old.to_bytes()?;
+        let old_len = u32::try_from(old.len()).map_err(|_| invalid("vault too large"))?;
+        let mut out = Vec::new();
+        out.extend_from_slice(MAGIC);
+        out.push(VERSION);
+        out.push(if self.cbor { FORM_CBOR } else { FORM_BINARY });
+        out.extend_from_slice(&old_len.to_be_bytes());
+        out.extend_from_slice(&old);
+        out.extend_from_slice(&self.new.to_bytes()?);
+        Ok(out)
+    }
+
+    /// The re-keyed vault, in the same form (binary or CBOR) as the
+    /// original.
+    ///
+    /// # Errors
+    ///
+    /// - If entries are left to move, an [`ErrorCode::InvalidArgument`]
+    ///   error is returned.
+    pub fn finish(&self) -> Result<Vec<u8>, BVaultError> {
+        if !self.finished() {
+            return Err(BVaultError::new(
+                ErrorCode::InvalidArgument,
+                "re-keying is not finished",
+            ));
+        }
+        if self.cbor {
+            self.new.to_cbor()
+        } else {
+            self.new.to_bytes()
+        }
+    }
+}
@@ -0,0 +1,154 @@
+//! Scheduled key rotation for envelopes.
+//!
+//! Envelopes sealed with [`SealOptions::generation`] or
+//! [`SealOptions::rotate_after_ms`] record a key generation number, when
+//! they were created and when they fall due (header extension 4, see
+//! `envelope`). From version 2 the header is authenticated, so the
+//! metadata cannot be rewritten to postpone a rotation without the
+//! envelope failing to open.
+//!
+//! [`needs_rotation`] reads the header alone, without the password, so an
+//! app can sweep its store and pass the due items to [`rotate`] at the next
+//! unlock: each comes back under a fresh salt, nonce and key with the
+//! generation bumped and the same interval until the next deadline.
+
+use wasm_bindgen::prelude::*;
+use zeroize::Zeroizing;
+
+use crate::envelope::Envelope;
+use crate::{open, seal, BVaultError, ErrorCode, OpenOptions, SealOptions};
+
+/// When [`needs_rotation`] considers an envelope due, besides its own
+/// deadline.
+#[wasm_bindgen]
+#[derive(Clone, Debug)]
+pub struct RotationPolicy {
+    max_age_ms: Option<f64>,
+    min_generation: u32,
+    rotate_unmarked: bool,
+}
+
+impl Default for RotationPolicy {
+    fn default() -> Self {
+        RotationPolicy {
+            max_age_ms: None,
+            min_generation: 0,
+            rotate_unmarked: true,
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl RotationPolicy {
+    /// A policy that only applies the envelopes' own deadlines, and counts
+    /// envelopes without rotation metadata as due.
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> RotationPolicy {
+        RotationPolicy::default()
+    }
+
+    /// Returns a copy of this policy under which envelopes older than
+    /// `max_age_ms` are due, whatever their deadline.
+    pub fn with_max_age_ms(&self, max_age_ms: f64) -> RotationPolicy {
+        RotationPolicy {
+            max_age_ms: Some(max_age_ms),
+            ..self.clone()
+        }
+    }
+
+    /// Returns a copy of this policy under which envelopes of a lower key
+    /// generation are due, as after a suspected compromise.
+    pub fn with_min_generation(&self, min_generation: u32) -> RotationPolicy {
+        RotationPolicy {
+            min_generation,
+            ..self.clone()
+        }
+    }
+
+    /// Returns a copy of this policy that decides whether envelopes without
+    /// rotation metadata, whose age is unknown, are due.
+    pub fn with_rotate_unmarked(&self, rotate_unmarked: bool) -> RotationPolicy {
+        RotationPolicy {
+            rotate_unmarked,
+            ..self.clone()
+        }
+    }
+}
+
+/// Whether `envelope` is due for rotation under `policy`: its deadline has
+/// passed, it is older than the policy's maximum age or below its minimum
+/// generation. Only the header is read; no password is needed.
+///
+/// # Errors
+///
+/// - If the envelope is malformed, an error is returned.
+#[wasm_bindgen]
+pub fn needs_rotation(
+    envelope: &[u8],
+    policy: Option<RotationPolicy>,
+) -> Result<bool, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    let policy = policy.unwrap_or_default();
+    let rotation = match Envelope::parse(envelope)?.rotation {
+        Some(rotation) => rotation,
+        None => return Ok(policy.rotate_unmarked),
+    };
+    let now = crate::utils::now_ms() as u64;
+    let age = now.saturating_sub(rotation.created_at) as f64;
+    Ok((rotation.rotate_after != 0 && now >= rotation.rotate_after)
+        || policy.max_age_ms.is_some_and(|max_age| age >= max_age)
+        || rotation.generation < policy.min_generation)
+}
+
+/// Re-encrypts `envelope` under a fresh key from the same password, keeping
+/// its cipher, KDF and framing. The key generation goes up by one (to 1 for
+/// envelopes without rotation metadata) and the next deadline is as far
+/// from now as the old one was from its creation.
+///
+/// Pass the keyfile and associated data the envelope was sealed with in
+/// `options`; they are reused for the new envelope. Use
+/// [`crate::reencrypt`] to change the password or parameters instead.
+///
+/// # Errors
+///
+/// - If the envelope was sealed under a raw key, an
+///   [`ErrorCode::InvalidArgument`] error is returned.
+/// - See [`crate::open`] and [`crate::seal`].
+#[wasm_bindgen]
+pub fn rotate(
+    envelope: &[u8],
+    password: &str,
+    options: Option<OpenOptions>,
+) -> Result<Vec<u8>, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    let parsed = Envelope::parse(envelope)?;
+    let kdf = parsed.kdf.ok_or_else(|| {
+        BVaultError::new(
+            ErrorCode::InvalidArgument,
+            "envelopes sealed under a raw key cannot be rotated with a password",
+        )
+    })?;
+    let options = options.unwrap_or_default();
+    let plaintext = Zeroizing::new(open(envelope, password, Some(options.clone()))?);
+
+    let mut seal_options = SealOptions::for_cipher(parsed.cipher);
+    seal_options.set_kdf(&match options.keyfile() {
+        Some(keyfile) => kdf.with_keyfile(keyfile),
+        None => kdf,
+    });
+    seal_options.set_salt_length(parsed.salt.len());
+    seal_options.set_key_check(parsed.kcv.is_some());
+    seal_options.set_compression(parsed.framing.compression);
+    seal_options.set_padding(parsed.framing.padding);
+    seal_options.set_aad(options.aad());
+    let rotation = parsed.rotation;
+    seal_options.set_generation(Some(
+        rotation.map_or(1, |rotation| rotation.generation.saturating_add(1)),
+    ));
+    seal_options.set_rotate_after_ms(
+        rotation
+            .filter(|rotation| rotation.rotate_after != 0)
+            .map(|rotation| rotation.rotate_after.saturating_sub(rotation.created_at) as f64),
+    );
+    seal(&plaintext, password, Some(seal_options))
+}
@@ -0,0 +1,175 @@
+//! Detection of the JS runtime the module is loaded in.
+//!
+//! The same `.wasm` serves browsers, Node, Deno and Cloudflare Workers;
+//! build it with the matching `wasm-pack build --target` (`web` or
+//! `bundler` for browsers, Deno and Workers, `nodejs` for CommonJS Node).
+//! Nothing here is needed for that: the module only touches globals that
+//! all of them share (`crypto.getRandomValues`, `setTimeout`, `console`,
+//! Web Streams), and randomness comes through `getrandom`, which also
+//! falls back to Node's `crypto` module where there is no global one.
+//!
+//! [`detect_runtime`] and [`runtime_info`] tell apps, and this module's
+//! error messages, which runtime that is and what it offers, so a server
+//! can check its environment at startup rather than on the first call
+//! that needs a missing global.
+
+use js_sys::Reflect;
+use wasm_bindgen::prelude::*;
+
+/// A JS runtime; see [`detect_runtime`].
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Runtime {
+    /// None of the others.
+    Unknown = 0,
+    /// A browser page's main thread.
+    Browser = 1,
+    /// A dedicated, shared or service worker in a browser.
+    WebWorker = 2,
+    /// Node.js, and runtimes that present themselves as it, such as Bun.
+    Node = 3,
+    /// Deno, including Deno Deploy.
+    Deno = 4,
+    /// Cloudflare Workers, recognized by their `navigator.userAgent`.
+    CloudflareWorkers = 5,
+}
+
+impl Runtime {
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Runtime::Unknown => "an unknown runtime",
+            Runtime::Browser => "a browser",
+            Runtime::WebWorker => "a web worker",
+            Runtime::Node => "Node",
+            Runtime::Deno => "Deno",
+            Runtime::CloudflareWorkers => "Cloudflare Workers",
+        }
+    }
+}
+
+/// The member `name` of `value`; `undefined` if there is none.
+fn member(value: &JsValue, name: &str) -> JsValue {
+    if value.is_undefined() || value.is_null() {
+        return JsValue::UNDEFINED;
+    }
+    Reflect::get(value, &JsValue::from_str(name)).unwrap_or(JsValue::UNDEFINED)
+}
+
+/// The global `name`; `undefined` if there is none.
+fn global(name: &str) -> JsValue {
+    member(&js_sys::global().into(), name)
+}
+
+/// Deno and Node expose `process` to npm code, so Deno is tested first.
+pub(crate) fn detect() -> Runtime {
+    if global("Deno").is_object() {
+        return Runtime::Deno;
+    }
+    if member(&member(&global("process"), "versions"), "node").is_string() {
+        return Runtime::Node;
+    }
+    let user_agent = member(&global("navigator"), "userAgent").as_string();
+    if user_agent.as_deref() == Some("Cloudflare-Workers") {
+        return Runtime::CloudflareWorkers;
+    }
+    if global("document").is_object() {
+        return Runtime::Browser;
+    }
+    if global("WorkerGlobalScope").is_function() {
+        return Runtime::WebWorker;
+    }
+    Runtime::Unknown
+}
+
+/// The version of the runtime, where it reports one.
+fn version(runtime: Runtime) -> Option<String> {
+    match runtime {
+        Runtime::Node => member(&member(&global("process"), "versions"), "node").as_string(),
+        Runtime::Deno => member(&member(&global("Deno"), "version"), "deno").as_string(),
+        _ => None,
+    }
+}
+
+/// Whether `crypto.getRandomValues` is a global function.
+fn has_web_crypto() -> bool {
+    member(&global("crypto"), "getRandomValues").is_function()
+}
+
+/// What the runtime is and offers; see [`runtime_info`].
+#[wasm_bindgen]
+#[derive(Clone, Debug)]
+pub struct RuntimeInfo {
+    runtime: Runtime,
+    version: Option<String>,
+    web_crypto: bool,
+    web_streams: bool,
+    workers: bool,
+}
+
+#[wasm_bindgen]
+impl RuntimeInfo {
+    /// The runtime; see [`detect_runtime`].
+    #[wasm_bindgen(getter)]
+    pub fn runtime(&self) -> Runtime {
+        self.runtime
+    }
+
+    /// The runtime's version, for Node and Deno; `undefined` elsewhere.
+    #[wasm_bindgen(getter)]
+    pub fn version(&self) -> Option<String> {
+        self.version.clone()
+    }
+
+    /// Whether `crypto.getRandomValues` is global. Without it, randomness
+    /// needs CommonJS Node, where `getrandom` loads the `crypto` module.
+    #[wasm_bindgen(getter)]
+    pub fn web_crypto(&self) -> bool {
+        self.web_crypto
+    }
+
+    /// Whether `ReadableStream` is global, as the `streams` exports need.
+    #[wasm_bindgen(getter)]
+    pub fn web_streams(&self) -> bool {
+        self.web_streams
+    }
+
+    /// Whether Web Workers can be started, as `init_threads` needs.
+    #[wasm_bindgen(getter)]
+    pub fn workers(&self) -> bool {
+        self.workers
+    }
+}
+
+/// The JS runtime the module is loaded in.
+#[wasm_bindgen]
+pub fn detect_runtime() -> Runtime {
+    let _operation = crate::utils::set_panic_hook();
+    detect()
+}
+
+/// The JS runtime the module is loaded in and the globals it offers.
+#[wasm_bindgen]
+pub fn runtime_info() -> RuntimeInfo {
+    let _operation = crate::utils::set_panic_hook();
+    let runtime = detect();
+    RuntimeInfo {
+        runtime,
+        version: version(runtime),
+        web_crypto: has_web_crypto(),
+        web_streams: global("ReadableStream").is_function(),
+        // Node has `worker_threads`, but not the `Worker` global that
+        // wasm-bindgen-rayon starts.
+        workers: runtime != Runtime::Node && global("Worker").is_function(),
+    }
+}
+
+/// Why no secure random bytes could be had, and what to do about it.
+pub(crate) fn random_unavailable() -> String {
+    let runtime = detect();
+    match runtime {
+        Runtime::Node if !has_web_crypto() => "random generator unavailable: this Node has no \
+             global crypto; use Node 19 or later, or the `nodejs` build target"
+            .to_string(),
+        _ => format!("random generator unavailable in {}", runtime.name()),
+    }
+}
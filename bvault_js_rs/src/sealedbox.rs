@@ -0,0 +1,110 @@
+//! libsodium `crypto_box_seal` compatibility, for anonymous senders.
+//!
+//! A sealed box is encrypted to an X25519 public key with a fresh ephemeral
+//! key pair, so the sender needs no key pair of their own and cannot be
+//! identified (or authenticated) by the recipient. The output matches
+//! libsodium:
+//!
+//! ```text
+//! ephemeral public key  32
+//! crypto_box_easy       16-byte Poly1305 tag, then the ciphertext
+//! ```
+//!
+//! where the box key is `HSalsa20(X25519(ephemeral, recipient), 0)` and the
+//! nonce is `BLAKE2b-192(ephemeral public || recipient public)`.
+
+use blake2::digest::consts::{U10, U16, U24};
+use blake2::digest::generic_array::GenericArray;
+use blake2::{Blake2b, Digest};
+use crypto_secretbox::aead::Aead;
+use wasm_bindgen::prelude::*;
+use x25519_dalek::{PublicKey, StaticSecret};
+use zeroize::Zeroizing;
+
+use crate::envelope::invalid;
+use crate::kdf::KEY_LENGTH;
+use crate::secretbox::{secretbox, NONCE_LENGTH};
+use crate::x25519::{diffie_hellman, public_key, secret_key};
+use crate::{random_bytes, BVaultError, ErrorCode};
+
+/// `crypto_box_SEALBYTES`: the ephemeral key plus the Poly1305 tag.
+const OVERHEAD: usize = 32 + 16;
+
+/// `crypto_box_beforenm`: the box key for one key pair and public key.
+fn box_key(secret: &StaticSecret, public: &PublicKey) -> Result<Zeroizing<[u8; 32]>, BVaultError> {
+    let shared = diffie_hellman(secret, public)?;
+    let key = salsa20::hsalsa::<U10>(
+        GenericArray::from_slice(&shared[..]),
+        &GenericArray::<u8, U16>::default(),
+    );
+    Ok(Zeroizing::new(key.into()))
+}
+
+fn seal_nonce(ephemeral: &PublicKey, recipient: &PublicKey) -> [u8; NONCE_LENGTH] {
+    let mut hasher = Blake2b::<U24>::new();
+    hasher.update(ephemeral.as_bytes());
+    hasher.update(recipient.as_bytes());
+    hasher.finalize().into()
+}
+
+/// Encrypts `message` to an X25519 public key like libsodium's
+/// `crypto_box_seal`. Only the holder of the matching secret key can open
+/// it, and nothing identifies the sender.
+///
+/// # Errors
+///
+/// - If the key is not 32 bytes or is a low-order point, an error is
+///   returned.
+/// - If the random number generator is unavailable, an error is returned.
+#[wasm_bindgen]
+pub fn sealed_box_seal(
+    message: &[u8],
+    recipient_public_key: &[u8],
+) -> Result<Vec<u8>, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    let recipient = public_key(recipient_public_key)?;
+    let ephemeral_bytes = Zeroizing::new(random_bytes(KEY_LENGTH)?);
+    let ephemeral = secret_key(&ephemeral_bytes)?;
+    let ephemeral_public = PublicKey::from(&ephemeral);
+
+    let key = box_key(&ephemeral, &recipient)?;
+    let nonce = seal_nonce(&ephemeral_public, &recipient);
+    let body = secretbox(&key[..], &nonce)?
+        .encrypt(&nonce.into(), message)
+        .map_err(|_| BVaultError::new(ErrorCode::EncryptionFailed, "encryption error"))?;
+    let mut out = Vec::with_capacity(32 + body.len());
+    out.extend_from_slice(ephemeral_public.as_bytes());
+    out.extend_from_slice(&body);
+    Ok(out)
+}
+
+/// Decrypts a box produced by [`sealed_box_seal`] or libsodium's
+/// `crypto_box_seal`.
+///
+/// # Errors
+///
+/// - If the key is not 32 bytes, an error is returned.
+/// - If the box is shorter than 48 bytes, an error is returned.
+/// - If the box was sealed for a different key or was tampered with, an
+///   [`ErrorCode::AuthFailed`] error is returned.
+#[wasm_bindgen]
+pub fn sealed_box_open(sealed: &[u8], recipient_secret_key: &[u8]) -> Result<Vec<u8>, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    let recipient = secret_key(recipient_secret_key)?;
+    if sealed.len() < OVERHEAD {
+        return Err(invalid("sealed box is truncated"));
+    }
+    let (ephemeral, body) = sealed.split_at(32);
+    let ephemeral = public_key(ephemeral)?;
+
+    let key = box_key(&recipient, &ephemeral)?;
+    let nonce = seal_nonce(&ephemeral, &PublicKey::from(&recipient));
+    secretbox(&key[..], &nonce)?
+        .decrypt(&nonce.into(), body)
+        .map_err(|_| {
+            BVaultError::decryption_failed(
+                ErrorCode::AuthFailed,
+                "sealed box authentication failed",
+            )
+        })
+}
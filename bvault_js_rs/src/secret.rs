@@ -0,0 +1,161 @@
+//! Decrypted secrets that stay in WASM memory.
+//!
+//! Every plaintext handed to JS becomes a string or `Uint8Array` that the
+//! garbage collector frees whenever it likes, without wiping it. A
+//! [`Secret`] is a handle instead: its bytes stay in WASM memory, and the
+//! common operations (comparing, generating TOTP codes, sealing again) run
+//! without them crossing over. [`Secret::reveal`] copies them out only when
+//! the app explicitly asks, such as to fill a password field.
+//!
+//! [`Secret::destroy`] wipes the bytes at once; after it every operation
+//! fails with [`ErrorCode::SecretDestroyed`]. Freeing the handle from JS, or
+//! letting it be collected, wipes them too.
+//!
+//! For secrets that should wipe themselves after a read or a timeout, see
+//! [`crate::EphemeralSecret`].
+
+use wasm_bindgen::prelude::*;
+use zeroize::Zeroizing;
+
+use crate::{
+    open, seal, totp_generate, BVaultError, ErrorCode, OpenOptions, SealOptions, TotpAlgorithm,
+};
+
+/// A secret held in WASM memory.
+#[wasm_bindgen]
+pub struct Secret {
+    /// `None` once destroyed.
+    bytes: Option<Zeroizing<Vec<u8>>>,
+}
+
+impl Secret {
+    pub(crate) fn new(bytes: Vec<u8>) -> Secret {
+        Secret {
+            bytes: Some(Zeroizing::new(bytes)),
+        }
+    }
+
+    fn bytes(&self) -> Result<&[u8], BVaultError> {
+        self.bytes
+            .as_deref()
+            .map(Vec::as_slice)
+            .ok_or_else(|| BVaultError::new(ErrorCode::SecretDestroyed, "the secret was destroyed"))
+    }
+
+    fn text(&self) -> Result<&str, BVaultError> {
+        std::str::from_utf8(self.bytes()?)
+            .map_err(|_| BVaultError::new(ErrorCode::Utf8Error, "the secret is not valid utf-8"))
+    }
+}
+
+#[wasm_bindgen]
+impl Secret {
+    /// Wraps `bytes`, such as a freshly typed password, so later operations
+    /// need not keep it in JS.
+    #[wasm_bindgen(constructor)]
+    pub fn from_bytes(bytes: &[u8]) -> Secret {
+        let _operation = crate::utils::set_panic_hook();
+        Secret::new(bytes.to_vec())
+    }
+
+    /// The length in bytes; 0 once destroyed.
+    #[wasm_bindgen(getter)]
+    pub fn length(&self) -> usize {
+        self.bytes.as_ref().map_or(0, |bytes| bytes.len())
+    }
+
+    /// Whether [`Secret::destroy`] has been called.
+    #[wasm_bindgen(getter)]
+    pub fn destroyed(&self) -> bool {
+        self.bytes.is_none()
+    }
+
+    /// Whether `other` holds the same bytes, compared in constant time.
+    ///
+    /// # Errors
+    ///
+    /// - If either secret was destroyed, an [`ErrorCode::SecretDestroyed`]
+    ///   error is returned.
+    pub fn equals(&self, other: &Secret) -> Result<bool, BVaultError> {
+        Ok(crate::constant_time_eq(self.bytes()?, other.bytes()?))
+    }
+
+    /// The TOTP code for this secret, a base32 TOTP key, at `time`; see
+    /// [`crate::totp_generate`] for the parameters.
+    ///
+    /// # Errors
+    ///
+    /// - If the secret was destroyed, an [`ErrorCode::SecretDestroyed`]
+    ///   error is returned.
+    /// - Otherwise see [`crate::totp_generate`].
+    pub fn use_for_totp(
+        &self,
+        period: Option<u32>,
+        digits: Option<u32>,
+        algorithm: Option<TotpAlgorithm>,
+        time: Option<f64>,
+    ) -> Result<String, BVaultError> {
+        totp_generate(self.text()?, period, digits, algorithm, time)
+    }
+
+    /// Encrypts the secret into an envelope, as [`crate::seal`] would.
+    ///
+    /// # Errors
+    ///
+    /// - If the secret was destroyed, an [`ErrorCode::SecretDestroyed`]
+    ///   error is returned.
+    /// - Otherwise see [`crate::seal`].
+    pub fn seal(
+        &self,
+        password: &str,
+        options: Option<SealOptions>,
+    ) -> Result<Vec<u8>, BVaultError> {
+        seal(self.bytes()?, password, options)
+    }
+
+    /// Copies the secret out as a string. It is then in JS memory for as
+    /// long as the garbage collector keeps it; only reveal what must be
+    /// shown or handed to another API.
+    ///
+    /// # Errors
+    ///
+    /// - If the secret was destroyed, an [`ErrorCode::SecretDestroyed`]
+    ///   error is returned.
+    /// - If it is not valid utf-8, an [`ErrorCode::Utf8Error`] error is
+    ///   returned.
+    pub fn reveal(&self) -> Result<String, BVaultError> {
+        Ok(self.text()?.to_string())
+    }
+
+    /// Copies the secret out as bytes; see [`Secret::reveal`].
+    ///
+    /// # Errors
+    ///
+    /// - If the secret was destroyed, an [`ErrorCode::SecretDestroyed`]
+    ///   error is returned.
+    pub fn reveal_bytes(&self) -> Result<Vec<u8>, BVaultError> {
+        Ok(self.bytes()?.to_vec())
+    }
+
+    /// Wipes the secret. The handle stays usable as an object, but every
+    /// operation on it now fails.
+    pub fn destroy(&mut self) {
+        self.bytes = None;
+    }
+}
+
+/// Decrypts an envelope, as [`crate::open`] does, into a [`Secret`]
+/// instead of a `Uint8Array`.
+///
+/// # Errors
+///
+/// See [`crate::open`].
+#[wasm_bindgen]
+pub fn open_secret(
+    envelope: &[u8],
+    password: &str,
+    options: Option<OpenOptions>,
+) -> Result<Secret, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    Ok(Secret::new(open(envelope, password, options)?))
+}
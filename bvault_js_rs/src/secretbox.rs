@@ -0,0 +1,68 @@
+//! libsodium `crypto_secretbox` (XSalsa20-Poly1305) compatibility.
+//!
+//! The output matches `crypto_secretbox_easy`: the 16-byte Poly1305 tag
+//! followed by the ciphertext. Keys are 32 bytes and nonces 24 bytes; the
+//! nonce is not included, so send it alongside as the native apps do.
+
+use crypto_secretbox::aead::{Aead, KeyInit};
+use crypto_secretbox::XSalsa20Poly1305;
+use wasm_bindgen::prelude::*;
+
+use crate::kdf::KEY_LENGTH;
+use crate::{BVaultError, ErrorCode};
+
+/// `crypto_secretbox_NONCEBYTES`.
+pub(crate) const NONCE_LENGTH: usize = 24;
+
+pub(crate) fn secretbox(key: &[u8], nonce: &[u8]) -> Result<XSalsa20Poly1305, BVaultError> {
+    if key.len() != KEY_LENGTH {
+        return Err(BVaultError::new(
+            ErrorCode::BadKeyLength,
+            "secretbox key must be 32 bytes",
+        ));
+    }
+    if nonce.len() != NONCE_LENGTH {
+        return Err(BVaultError::new(
+            ErrorCode::BadIvLength,
+            "secretbox nonce must be 24 bytes",
+        ));
+    }
+    XSalsa20Poly1305::new_from_slice(key)
+        .map_err(|_| BVaultError::new(ErrorCode::BadKeyLength, "invalid key length"))
+}
+
+/// Encrypts `message` like libsodium's `crypto_secretbox_easy`.
+///
+/// Never reuse a nonce with the same key; generate one with
+/// `random_bytes(24)`.
+///
+/// # Errors
+///
+/// - If the key is not 32 bytes or the nonce not 24 bytes, an error is
+///   returned.
+#[wasm_bindgen]
+pub fn secretbox_seal(message: &[u8], nonce: &[u8], key: &[u8]) -> Result<Vec<u8>, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    secretbox(key, nonce)?
+        .encrypt(nonce.into(), message)
+        .map_err(|_| BVaultError::new(ErrorCode::EncryptionFailed, "encryption error"))
+}
+
+/// Decrypts a box produced by [`secretbox_seal`] or libsodium's
+/// `crypto_secretbox_easy`.
+///
+/// # Errors
+///
+/// - If the key is not 32 bytes or the nonce not 24 bytes, an error is
+///   returned.
+/// - If authentication fails (wrong key or nonce, or tampered data), an
+///   error is returned.
+#[wasm_bindgen]
+pub fn secretbox_open(ciphertext: &[u8], nonce: &[u8], key: &[u8]) -> Result<Vec<u8>, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    secretbox(key, nonce)?
+        .decrypt(nonce.into(), ciphertext)
+        .map_err(|_| {
+            BVaultError::decryption_failed(ErrorCode::AuthFailed, "secretbox authentication failed")
+        })
+}
@@ -0,0 +1,366 @@
+//! Known-answer tests of the primitives in this build, for deployments that
+//! must show the cryptography works where it runs (FIPS 140-style power-on
+//! self-tests).
+//!
+//! [`self_test`] runs each algorithm on a published test vector and
+//! compares the output byte for byte. The vectors come from the standards
+//! named beside each check; algorithms left out of the build by a feature
+//! are left out of the report.
+
+use std::convert::TryInto;
+
+use aes::cipher::{KeyIvInit, StreamCipher};
+use ed25519_dalek::{Signer, SigningKey};
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256, Sha512};
+use wasm_bindgen::prelude::*;
+
+use crate::kdf::hkdf_sha256;
+use crate::utils::from_hex;
+use crate::{BVaultError, Cipher};
+
+/// One algorithm's outcome in a [`SelfTestReport`].
+#[wasm_bindgen]
+#[derive(Clone, Debug)]
+pub struct SelfTestResult {
+    algorithm: &'static str,
+    passed: bool,
+}
+
+#[wasm_bindgen]
+impl SelfTestResult {
+    /// The algorithm tested, such as `"aes-256-gcm"` or `"pbkdf2-sha256"`.
+    #[wasm_bindgen(getter)]
+    pub fn algorithm(&self) -> String {
+        self.algorithm.to_string()
+    }
+
+    /// Whether its output matched the known answer.
+    #[wasm_bindgen(getter)]
+    pub fn passed(&self) -> bool {
+        self.passed
+    }
+}
+
+/// The result of [`self_test`].
+#[wasm_bindgen]
+#[derive(Clone, Debug)]
+pub struct SelfTestReport {
+    results: Vec<SelfTestResult>,
+}
+
+#[wasm_bindgen]
+impl SelfTestReport {
+    /// Whether every algorithm passed.
+    #[wasm_bindgen(getter)]
+    pub fn passed(&self) -> bool {
+        self.results.iter().all(|result| result.passed)
+    }
+
+    /// Every algorithm tested, in a fixed order.
+    #[wasm_bindgen(getter)]
+    pub fn results(&self) -> Vec<SelfTestResult> {
+        self.results.clone()
+    }
+
+    /// The algorithms that failed.
+    #[wasm_bindgen(getter)]
+    pub fn failures(&self) -> Vec<String> {
+        self.results
+            .iter()
+            .filter(|result| !result.passed)
+            .map(SelfTestResult::algorithm)
+            .collect()
+    }
+}
+
+fn hex(text: &str) -> Vec<u8> {
+    from_hex(text).expect("test vectors are valid hex")
+}
+
+/// Whether `output` succeeded with the bytes in `expected`.
+fn matches(output: Result<Vec<u8>, BVaultError>, expected: &str) -> bool {
+    output.is_ok_and(|output| output == hex(expected))
+}
+
+// FIPS 180-2 and RFC 7693 / the BLAKE3 reference, on "abc" and "".
+
+fn sha256() -> bool {
+    let expected = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+    Sha256::digest(b"abc").to_vec() == hex(expected)
+}
+
+fn sha512() -> bool {
+    let expected = "ddaf35a193617abacc417349ae20413112e6fa4e89a97ea20a9eeee64b55d39a\
+                    2192992a274fc1a836ba3c23a3feebbd454d4423643ce80e2a9ac94fa54ca49f";
+    Sha512::digest(b"abc").to_vec() == hex(expected)
+}
+
+fn sha1() -> bool {
+    sha1::Sha1::digest(b"abc").to_vec() == hex("a9993e364706816aba3e25717850c26c9cd0d89d")
+}
+
+fn blake2b() -> bool {
+    let expected = "ba80a53f981c4d0d6a2797b69f12f6e94c212f14685ac4b74b12bb6fdbffa2d1\
+                    7d87c5392aab792dc252d5de4533cc9518d38aa8dbf1925ab92386edd4009923";
+    blake2::Blake2b512::digest(b"abc").to_vec() == hex(expected)
+}
+
+fn blake3() -> bool {
+    let expected = "af1349b9f5f9a1a6a0404dea36dcc9499bcb25c9adc112b7cc9a93cae41f3262";
+    blake3::hash(b"").as_bytes().to_vec() == hex(expected)
+}
+
+/// RFC 4231 test case 2.
+fn hmac_sha256() -> bool {
+    let expected = "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843";
+    let mut mac = Hmac::<Sha256>::new_from_slice(b"Jefe").expect("HMAC takes any key");
+    mac.update(b"what do ya want for nothing?");
+    mac.finalize().into_bytes().to_vec() == hex(expected)
+}
+
+/// RFC 5869 test case 1.
+fn hkdf_sha256_kat() -> bool {
+    let expected = "3cb25f25faacd57a90434f64d0362f2a2d2d0a90cf1a5a4c5db02d56ecc4c5bf\
+                    34007208d5b887185865";
+    let mut okm = [0u8; 42];
+    let salt = hex("000102030405060708090a0b0c");
+    let result = hkdf_sha256(
+        &[0x0b; 22],
+        Some(&salt),
+        &hex("f0f1f2f3f4f5f6f7f8f9"),
+        &mut okm,
+    );
+    matches(result.map(|_| okm.to_vec()), expected)
+}
+
+/// RFC 7914 section 11, with c = 4096 for SHA-256.
+fn pbkdf2_sha256() -> bool {
+    let expected = "c5e478d59288c841aa530db6845c4c8d962893a001ce4e11a4963873aa98134a";
+    let mut key = [0u8; 32];
+    pbkdf2::pbkdf2_hmac::<Sha256>(b"password", b"salt", 4096, &mut key);
+    key.to_vec() == hex(expected)
+}
+
+/// RFC 7914 section 12, the first vector.
+fn scrypt() -> bool {
+    let expected = "77d6576238657b203b19ca42c18a0497f16b4844e3074ae8dfdffa3fede21442\
+                    fcd0069ded0948f8326a753a0fc81f17e8d3e0fb2e0d3628cf35e20c38d18906";
+    let params = scrypt::Params::new(4, 1, 1, 64).expect("valid scrypt parameters");
+    let mut key = [0u8; 64];
+    scrypt::scrypt(b"", b"", &params, &mut key).is_ok() && key.to_vec() == hex(expected)
+}
+
+/// RFC 9106 section 5.3.
+#[cfg(feature = "argon2")]
+fn argon2id() -> bool {
+    use argon2::{Algorithm, Argon2, AssociatedData, ParamsBuilder, Version};
+
+    let expected = "0d640df58d78766c08c037a34a8b53c9d01ef0452d75b65eb52520e96b01e659";
+    let params = ParamsBuilder::new()
+        .m_cost(32)
+        .t_cost(3)
+        .p_cost(4)
+        .data(AssociatedData::new(&[0x04; 12]).expect("short associated data"))
+        .output_len(32)
+        .build()
+        .expect("valid Argon2 parameters");
+    let argon2 = Argon2::new_with_secret(&[0x03; 8], Algorithm::Argon2id, Version::V0x13, params)
+        .expect("short secret");
+    let mut key = [0u8; 32];
+    argon2
+        .hash_password_into(&[0x01; 32], &[0x02; 16], &mut key)
+        .is_ok()
+        && key.to_vec() == hex(expected)
+}
+
+/// The Galois/Counter Mode specification, test case 14.
+#[cfg(feature = "gcm")]
+fn aes_256_gcm() -> bool {
+    let expected = "cea7403d4d606b6e074ec5d3baf39d18d0d1c8a799996bf0265b98b5d48ab919";
+    matches(
+        Cipher::Aes256Gcm.encrypt_with_aad(&[0; 32], &[0; 12], &[0; 16], &[]),
+        expected,
+    )
+}
+
+/// RFC 8452 appendix C.2, the first vector.
+fn aes_256_gcm_siv() -> bool {
+    let mut key = [0u8; 32];
+    key[0] = 1;
+    let mut nonce = [0u8; 12];
+    nonce[0] = 3;
+    matches(
+        Cipher::Aes256GcmSiv.encrypt_with_aad(&key, &nonce, &[], &[]),
+        "07f5f4169bbf55a8400cd47ea6fd400f",
+    )
+}
+
+#[cfg(feature = "chacha")]
+const SUNSCREEN: &[u8] = b"Ladies and Gentlemen of the class of '99: If I could offer you \
+                           only one tip for the future, sunscreen would be it.";
+
+/// RFC 8439 section 2.8.2.
+#[cfg(feature = "chacha")]
+fn chacha20_poly1305() -> bool {
+    let expected = "d31a8d34648e60db7b86afbc53ef7ec2a4aded51296e08fea9e2b5a736ee62d6\
+                    3dbea45e8ca9671282fafb69da92728b1a71de0a9e060b2905d6a5b67ecd3b36\
+                    92ddbd7f2d778b8c9803aee328091b58fab324e4fad675945585808b4831d7bc\
+                    3ff4def08e4b7a9de576d26586cec64b6116\
+                    1ae10b594f09e26a7e902ecbd0600691";
+    let key: Vec<u8> = (0x80..=0x9f).collect();
+    matches(
+        Cipher::ChaCha20Poly1305.encrypt_with_aad(
+            &key,
+            &hex("070000004041424344454647"),
+            SUNSCREEN,
+            &hex("50515253c0c1c2c3c4c5c6c7"),
+        ),
+        expected,
+    )
+}
+
+/// draft-irtf-cfrg-xchacha-03 appendix A.3.1.
+#[cfg(feature = "chacha")]
+fn xchacha20_poly1305() -> bool {
+    let expected = "bd6d179d3e83d43b9576579493c0e939572a1700252bfaccbed2902c21396cbb\
+                    731c7f1b0b4aa6440bf3a82f4eda7e39ae64c6708c54c216cb96b72e1213b452\
+                    2f8c9ba40db5d945b11b69b982c1bb9e3f3fac2bc369488f76b2383565d3fff9\
+                    21f9664c97637da9768812f615c68b13b52e\
+                    c0875924c1c7987947deafd8780acf49";
+    let key: Vec<u8> = (0x80..=0x9f).collect();
+    matches(
+        Cipher::XChaCha20Poly1305.encrypt_with_aad(
+            &key,
+            &hex("404142434445464748494a4b4c4d4e4f5051525354555657"),
+            SUNSCREEN,
+            &hex("50515253c0c1c2c3c4c5c6c7"),
+        ),
+        expected,
+    )
+}
+
+/// No published vector exists for this construction, so the known answer
+/// was computed independently from its definition: AES-256-GCM under
+/// `HKDF(key, nonce, "bvault cascade aes-256-gcm")` with the first 12
+/// nonce bytes, then XChaCha20-Poly1305 under `HKDF(key, -, "bvault
+/// cascade xchacha20-poly1305")` with the whole nonce, each tag appended.
+/// Inputs are those of the XChaCha20-Poly1305 check.
+#[cfg(all(feature = "gcm", feature = "chacha"))]
+fn cascade() -> bool {
+    let expected = "35b2480d23c8955eb0c38e755d557aaad45352719b061d93918bf76fcc55743d\
+                    0c46b52c64cb762723dc10295760e3c660d10eb6047d2d0c6aa058475443ee7e\
+                    29d08675dccd79002689a09ec2168fe0544b0e65a286ffc002a3e7a0520b22e1\
+                    013f83b73f57c0813a30068120c4ff2d813df90c679556d56f5e71e8413636bb\
+                    94d3aa9d0dbf30b71eb354e5fc425ff85cd3";
+    let key: Vec<u8> = (0x80..=0x9f).collect();
+    matches(
+        Cipher::Cascade.encrypt_with_aad(
+            &key,
+            &hex("404142434445464748494a4b4c4d4e4f5051525354555657"),
+            SUNSCREEN,
+            &hex("50515253c0c1c2c3c4c5c6c7"),
+        ),
+        expected,
+    )
+}
+
+const SP800_38A_KEY: &str = "603deb1015ca71be2b73aef0857d77811f352c073b6108d72d9810a30914dff4";
+const SP800_38A_BLOCK: &str = "6bc1bee22e409f96e93d7e117393172a";
+
+/// NIST SP 800-38A F.2.5, the first block.
+#[cfg(feature = "cbc-legacy")]
+fn aes_256_cbc() -> bool {
+    let iv: Vec<u8> = (0..16).collect();
+    let output =
+        crate::legacy::cbc_encrypt_with_key(&hex(SP800_38A_KEY), &iv, &hex(SP800_38A_BLOCK));
+    // PKCS#7 appends a second block; the first is the known answer.
+    output.is_ok_and(|output| output[..16] == hex("f58c4c04d6e5f1ba779eabfb5f7bfbd6")[..])
+}
+
+/// NIST SP 800-38A F.5.5, the first block.
+fn aes_256_ctr() -> bool {
+    let counter: Vec<u8> = (0xf0..=0xff).collect();
+    let mut block = hex(SP800_38A_BLOCK);
+    ctr::Ctr128BE::<aes::Aes256>::new_from_slices(&hex(SP800_38A_KEY), &counter)
+        .map(|mut ctr| ctr.apply_keystream(&mut block))
+        .is_ok()
+        && block == hex("601ec313775789a5b7a7f504bbf3d228")
+}
+
+/// RFC 8032 section 7.1, test 1.
+fn ed25519() -> bool {
+    let expected = "e5564300c360ac729086e2cc806e828a84877f1eb8e5d974d873e06522490155\
+                    5fb8821590a33bacc61e39701cf9b46bd25bf5f0595bbe24655141438e7a100b";
+    let secret = hex("9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60");
+    let key = SigningKey::from_bytes(&secret[..].try_into().expect("32-byte key"));
+    let public = "d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a";
+    key.verifying_key().to_bytes().to_vec() == hex(public)
+        && key.sign(b"").to_bytes().to_vec() == hex(expected)
+}
+
+/// RFC 7748 section 5.2, the first vector.
+fn x25519() -> bool {
+    let expected = "c3da55379de9c6908e94ea4df28d084f32eccf03491c71f754b4075577a28552";
+    let scalar = hex("a546e36bf0527c9d3b16154b82465edd62144c0ac1fc5a18506a2244ba449ac4");
+    let point = hex("e6db6867583030db3594c1a424b15f7c726624ec26b3353b10a903a6d0ab1c4c");
+    let shared = x25519_dalek::x25519(
+        scalar[..].try_into().expect("32-byte scalar"),
+        point[..].try_into().expect("32-byte point"),
+    );
+    shared.to_vec() == hex(expected)
+}
+
+/// An algorithm and its known-answer test.
+type Check = (&'static str, fn() -> bool);
+
+/// The algorithms of this build and their checks, in report order.
+fn checks() -> Vec<Check> {
+    let mut checks: Vec<Check> = vec![
+        ("sha-256", sha256),
+        ("sha-512", sha512),
+        ("sha-1", sha1),
+        ("blake2b-512", blake2b),
+        ("blake3", blake3),
+        ("hmac-sha256", hmac_sha256),
+        ("hkdf-sha256", hkdf_sha256_kat),
+        ("pbkdf2-sha256", pbkdf2_sha256),
+        ("scrypt", scrypt),
+    ];
+    #[cfg(feature = "argon2")]
+    checks.push(("argon2id", argon2id));
+    #[cfg(feature = "gcm")]
+    checks.push(("aes-256-gcm", aes_256_gcm));
+    checks.push(("aes-256-gcm-siv", aes_256_gcm_siv));
+    #[cfg(feature = "chacha")]
+    checks.push(("chacha20-poly1305", chacha20_poly1305));
+    #[cfg(feature = "chacha")]
+    checks.push(("xchacha20-poly1305", xchacha20_poly1305));
+    #[cfg(all(feature = "gcm", feature = "chacha"))]
+    checks.push(("aes-256-gcm+xchacha20-poly1305", cascade));
+    #[cfg(feature = "cbc-legacy")]
+    checks.push(("aes-256-cbc", aes_256_cbc));
+    checks.push(("aes-256-ctr", aes_256_ctr));
+    checks.push(("ed25519", ed25519));
+    checks.push(("x25519", x25519));
+    checks
+}
+
+/// Runs a known-answer test for every cipher, KDF, hash and signature
+/// scheme in this build and reports which passed. A failure means the
+/// build or the environment is broken; do not encrypt with it.
+///
+/// Takes a few milliseconds, so it can run at every startup.
+#[wasm_bindgen]
+pub fn self_test() -> SelfTestReport {
+    let _operation = crate::utils::set_panic_hook();
+    SelfTestReport {
+        results: checks()
+            .into_iter()
+            .map(|(algorithm, check)| SelfTestResult {
+                algorithm,
+                passed: check(),
+            })
+            .collect(),
+    }
+}
@@ -0,0 +1,429 @@
+//! Unlocked sessions that derive the key once and reuse it.
+//!
+//! Running the KDF for every entry makes unlocking a large vault take
+//! seconds. A [`VaultSession`] derives the key a single time, keeps it in
+//! WASM memory and seals/opens entries with it. Entries are ordinary
+//! envelopes carrying the session salt and KDF parameters, so
+//! [`crate::open`] can still decrypt them from the password alone.
+//!
+//! [`VaultSession::set_auto_lock`] limits how long the key stays in memory:
+//! after a total lifetime or a stretch without use the key is wiped, and
+//! every later call fails with [`ErrorCode::SessionLocked`].
+
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+use wasm_bindgen::prelude::*;
+
+use crate::envelope::{check_salt_length, seal_with_key, Envelope};
+use crate::kcv;
+use crate::kdf::Key;
+use crate::{
+    b64_to_bytes, bytes_to_b64, new_salted_key, BVaultError, Cipher, ErrorCode, KdfParams,
+    SealOptions, MIN_SALT_LENGTH,
+};
+use crate::{packed, threads};
+
+/// A derived key held in WASM memory for fast entry encryption/decryption.
+///
+/// The key is wiped when the session is dropped, locked with
+/// [`VaultSession::lock`] or expires; call `free()` from JS once the
+/// session is no longer needed rather than waiting for garbage collection.
+#[wasm_bindgen]
+pub struct VaultSession {
+    state: Mutex<KeyState>,
+    salt: Vec<u8>,
+    kdf: KdfParams,
+    cipher: Cipher,
+}
+
+/// The session key and when it expires.
+struct KeyState {
+    /// `None` once locked.
+    key: Option<Key>,
+    ttl_ms: Option<f64>,
+    idle_ms: Option<f64>,
+    /// When the TTL runs out, in ms since the epoch.
+    deadline_ms: f64,
+    last_used_ms: f64,
+}
+
+impl KeyState {
+    fn new(key: Key) -> KeyState {
+        KeyState {
+            key: Some(key),
+            ttl_ms: None,
+            idle_ms: None,
+            deadline_ms: f64::INFINITY,
+            last_used_ms: crate::utils::now_ms(),
+        }
+    }
+
+    /// Wipes the key if the TTL or idle timeout has run out by `now`.
+    fn expire(&mut self, now: f64) {
+        let idle = self
+            .idle_ms
+            .is_some_and(|idle_ms| now - self.last_used_ms >= idle_ms);
+        if now >= self.deadline_ms || idle {
+            self.key = None;
+        }
+    }
+}
+
+fn session_locked() -> BVaultError {
+    BVaultError::new(ErrorCode::SessionLocked, "session is locked")
+}
+
+#[wasm_bindgen]
+impl VaultSession {
+    /// Unlocks a session for an existing vault from its password and the
+    /// base64 salt and KDF parameters the vault was created with.
+    ///
+    /// `cipher` selects the suite used by [`VaultSession::encrypt_entry`]
+    /// (default AES-256-GCM); entries under any suite can be decrypted.
+    ///
+    /// # Errors
+    ///
+    /// - If the salt is invalid base64 or outside 8..=255 bytes, an error is
+    ///   returned.
+    /// - If the KDF parameters are below the accepted minimum, an error is
+    ///   returned.
+    #[wasm_bindgen(constructor)]
+    pub fn new(
+        password: &str,
+        b64_salt: &str,
+        kdf: Option<KdfParams>,
+        cipher: Option<Cipher>,
+    ) -> Result<VaultSession, BVaultError> {
+        let _operation = crate::utils::set_panic_hook();
+
+        let salt = b64_to_bytes(b64_salt)?;
+        if salt.len() < MIN_SALT_LENGTH {
+            return Err(BVaultError::new(
+                ErrorCode::BadSaltLength,
+                "salt must be at least 8 bytes",
+            ));
+        }
+        check_salt_length(salt.len())?;
+
+        let kdf = kdf.unwrap_or_default();
+        let key = kdf.derive(password.as_bytes(), &salt)?;
+
+        Ok(VaultSession {
+            state: Mutex::new(KeyState::new(key)),
+            salt,
+            kdf,
+            cipher: cipher.unwrap_or_default(),
+        })
+    }
+
+    /// Starts a session for a new vault under a freshly generated salt.
+    ///
+    /// # Errors
+    ///
+    /// See [`crate::seal`].
+    pub fn create(
+        password: &str,
+        options: Option<SealOptions>,
+    ) -> Result<VaultSession, BVaultError> {
+        let _operation = crate::utils::set_panic_hook();
+        let options = options.unwrap_or_default();
+
+        check_salt_length(options.salt_length())?;
+        let kdf = options.kdf();
+        let (salt, key) = new_salted_key(
+            password,
+            options.salt_length(),
+            &kdf,
+            Some(options.cipher()),
+        )?;
+
+        Ok(VaultSession {
+            state: Mutex::new(KeyState::new(key)),
+            salt,
+            kdf,
+            cipher: options.cipher(),
+        })
+    }
+
+    /// The base64 salt to persist alongside the vault.
+    #[wasm_bindgen(getter)]
+    pub fn salt(&self) -> String {
+        bytes_to_b64(&self.salt)
+    }
+
+    /// The KDF parameters to persist alongside the vault.
+    #[wasm_bindgen(getter)]
+    pub fn kdf(&self) -> KdfParams {
+        self.kdf.clone()
+    }
+
+    /// Wipes the key after `ttl_ms` milliseconds from now, or after
+    /// `idle_ms` milliseconds without an entry being encrypted or
+    /// decrypted, whichever comes first. `undefined` turns a limit off;
+    /// both are off by default.
+    ///
+    /// # Errors
+    ///
+    /// - If the session is already locked, an [`ErrorCode::SessionLocked`]
+    ///   error is returned.
+    /// - If a limit is negative or not a number, an
+    ///   [`ErrorCode::InvalidArgument`] error is returned.
+    pub fn set_auto_lock(
+        &self,
+        ttl_ms: Option<f64>,
+        idle_ms: Option<f64>,
+    ) -> Result<(), BVaultError> {
+        if ![ttl_ms, idle_ms].iter().flatten().all(|&ms| ms >= 0.0) {
+            return Err(BVaultError::new(
+                ErrorCode::InvalidArgument,
+                "timeouts must be non-negative numbers of milliseconds",
+            ));
+        }
+        let mut state = self.unlocked_state()?;
+        state.ttl_ms = ttl_ms;
+        state.idle_ms = idle_ms;
+        state.deadline_ms = state.last_used_ms + ttl_ms.unwrap_or(f64::INFINITY);
+        Ok(())
+    }
+
+    /// Restarts the TTL and idle timeout from now, e.g. when the user
+    /// confirms they are still there.
+    ///
+    /// # Errors
+    ///
+    /// - If the session has already locked, an [`ErrorCode::SessionLocked`]
+    ///   error is returned; unlock a new one instead.
+    pub fn extend(&self) -> Result<(), BVaultError> {
+        let mut state = self.unlocked_state()?;
+        state.deadline_ms = state.last_used_ms + state.ttl_ms.unwrap_or(f64::INFINITY);
+        Ok(())
+    }
+
+    /// Wipes the key now. Every later call fails with
+    /// [`ErrorCode::SessionLocked`].
+    pub fn lock(&self) {
+        self.state().key = None;
+    }
+
+    /// Whether the key has been wiped, by [`VaultSession::lock`] or by
+    /// expiring.
+    #[wasm_bindgen(getter)]
+    pub fn locked(&self) -> bool {
+        let mut state = self.state();
+        state.expire(crate::utils::now_ms());
+        state.key.is_none()
+    }
+
+    /// The fingerprint of the session key, as [`crate::key_fingerprint`]
+    /// returns it.
+    ///
+    /// # Errors
+    ///
+    /// - If the session is locked, an [`ErrorCode::SessionLocked`] error is
+    ///   returned.
+    pub fn fingerprint(&self) -> Result<String, BVaultError> {
+        kcv::fingerprint(&self.key()?[..])
+    }
+
+    /// Encrypts one entry into an envelope without re-running the KDF.
+    ///
+    /// # Errors
+    ///
+    /// - If the session is locked, an [`ErrorCode::SessionLocked`] error is
+    ///   returned.
+    /// - If the random number generator is unavailable, an error is returned.
+    pub fn encrypt_entry(&self, plaintext: &[u8]) -> Result<Vec<u8>, BVaultError> {
+        let key = self.key()?;
+        seal_with_key(
+            Some(self.kdf.clone()),
+            self.salt.clone(),
+            &key[..],
+            &SealOptions::for_cipher(self.cipher),
+            plaintext,
+        )
+    }
+
+    /// Decrypts one entry envelope without re-running the KDF.
+    ///
+    /// # Errors
+    ///
+    /// - If the session is locked, an [`ErrorCode::SessionLocked`] error is
+    ///   returned.
+    /// - If the envelope is malformed, an error is returned.
+    /// - If the envelope was sealed under a different salt, KDF parameters
+    ///   or password, an error is returned.
+    /// - If authentication fails, an error is returned.
+    pub fn decrypt_entry(&self, envelope: &[u8]) -> Result<Vec<u8>, BVaultError> {
+        self.decrypt_with(&self.key()?, envelope)
+    }
+
+    /// [`VaultSession::decrypt_entry`], returning the plaintext as a
+    /// [`crate::Secret`] that stays in WASM memory.
+    ///
+    /// # Errors
+    ///
+    /// See [`VaultSession::decrypt_entry`].
+    pub fn decrypt_entry_secret(&self, envelope: &[u8]) -> Result<crate::Secret, BVaultError> {
+        Ok(crate::secret::Secret::new(self.decrypt_entry(envelope)?))
+    }
+
+    /// Decrypts many entry envelopes in one call, collecting a result per
+    /// entry instead of stopping at the first failure.
+    ///
+    /// Entries are decrypted in parallel once a thread pool is running (see
+    /// `init_threads`). If the session is locked, every entry fails with
+    /// [`ErrorCode::SessionLocked`].
+    pub fn decrypt_entries(&self, entries: Vec<js_sys::Uint8Array>) -> Vec<DecryptResult> {
+        let key = self.key();
+        let entries: Vec<Vec<u8>> = entries.iter().map(js_sys::Uint8Array::to_vec).collect();
+        threads::map(entries, |entry| {
+            DecryptResult::from(
+                key.as_ref()
+                    .map_err(Clone::clone)
+                    .and_then(|key| self.decrypt_with(key, &entry)),
+            )
+        })
+    }
+
+    /// Decrypts a packed batch of entry envelopes (see `packed` for the
+    /// format) and returns the packed results, crossing the JS boundary
+    /// once instead of once per entry. Like [`Self::decrypt_entries`],
+    /// each entry succeeds or fails on its own, and entries are decrypted
+    /// in parallel once a thread pool is running.
+    ///
+    /// # Errors
+    ///
+    /// - If `packed` is truncated, an [`ErrorCode::InvalidEnvelope`] error
+    ///   is returned; per-entry failures are reported in the results.
+    pub fn decrypt_packed(&self, packed: &[u8]) -> Result<Vec<u8>, BVaultError> {
+        let entries = packed::unpack(packed)?;
+        let key = self.key();
+        Ok(packed::pack(threads::map(entries, |entry| {
+            key.as_ref()
+                .map_err(Clone::clone)
+                .and_then(|key| self.decrypt_with(key, entry))
+        })))
+    }
+
+    /// Encrypts a packed batch of plaintexts into packed entry envelopes,
+    /// the counterpart of [`Self::decrypt_packed`].
+    ///
+    /// # Errors
+    ///
+    /// - If `packed` is truncated, an [`ErrorCode::InvalidEnvelope`] error
+    ///   is returned; per-entry failures are reported in the results.
+    pub fn encrypt_packed(&self, packed: &[u8]) -> Result<Vec<u8>, BVaultError> {
+        let plaintexts = packed::unpack(packed)?;
+        let key = self.key();
+        let options = SealOptions::for_cipher(self.cipher);
+        Ok(packed::pack(threads::map(plaintexts, |plaintext| {
+            key.as_ref().map_err(Clone::clone).and_then(|key| {
+                seal_with_key(
+                    Some(self.kdf.clone()),
+                    self.salt.clone(),
+                    &key[..],
+                    &options,
+                    plaintext,
+                )
+            })
+        })))
+    }
+}
+
+impl VaultSession {
+    fn state(&self) -> MutexGuard<'_, KeyState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// The key state, expired first if its time is up.
+    fn unlocked_state(&self) -> Result<MutexGuard<'_, KeyState>, BVaultError> {
+        let mut state = self.state();
+        let now = crate::utils::now_ms();
+        state.expire(now);
+        if state.key.is_none() {
+            return Err(session_locked());
+        }
+        state.last_used_ms = now;
+        Ok(state)
+    }
+
+    /// A copy of the key for one call, counting as a use.
+    fn key(&self) -> Result<Key, BVaultError> {
+        Ok(self.unlocked_state()?.key.clone().expect("checked above"))
+    }
+
+    fn decrypt_with(&self, key: &Key, envelope: &[u8]) -> Result<Vec<u8>, BVaultError> {
+        let envelope = Envelope::parse(envelope)?;
+
+        let same_key = match &envelope.kcv {
+            Some(kcv) => kcv::matches(&key[..], kcv)?,
+            None => true,
+        };
+        if envelope.salt != self.salt
+            || envelope.kdf.as_ref().map(KdfParams::kdf) != Some(self.kdf.kdf())
+            || !same_key
+        {
+            return Err(BVaultError::new(
+                ErrorCode::SessionMismatch,
+                "entry was not sealed under this session's key",
+            ));
+        }
+
+        envelope.decrypt(&key[..], &[])
+    }
+}
+
+/// The outcome of decrypting one entry in [`decrypt_many`].
+#[wasm_bindgen]
+#[derive(Clone, Debug)]
+pub struct DecryptResult {
+    result: Result<Vec<u8>, BVaultError>,
+}
+
+#[wasm_bindgen]
+impl DecryptResult {
+    /// Whether the entry decrypted successfully.
+    #[wasm_bindgen(getter)]
+    pub fn ok(&self) -> bool {
+        self.result.is_ok()
+    }
+
+    /// The decrypted bytes; `undefined` if decryption failed.
+    #[wasm_bindgen(getter)]
+    pub fn plaintext(&self) -> Option<Vec<u8>> {
+        self.result.as_ref().ok().cloned()
+    }
+
+    /// Why decryption failed; `undefined` on success.
+    #[wasm_bindgen(getter)]
+    pub fn error(&self) -> Option<BVaultError> {
+        self.result.as_ref().err().cloned()
+    }
+}
+
+impl From<Result<Vec<u8>, BVaultError>> for DecryptResult {
+    fn from(result: Result<Vec<u8>, BVaultError>) -> Self {
+        DecryptResult { result }
+    }
+}
+
+/// Decrypts a batch of envelopes sealed under the same salt and KDF
+/// parameters, running the KDF once for all of them.
+///
+/// Returns one [`DecryptResult`] per entry, in order; a damaged or foreign
+/// entry fails on its own without affecting the others.
+///
+/// # Errors
+///
+/// See [`VaultSession::new`]; per-entry failures are reported in the
+/// results instead.
+#[wasm_bindgen]
+pub fn decrypt_many(
+    entries: Vec<js_sys::Uint8Array>,
+    password: &str,
+    b64_salt: &str,
+    kdf: Option<KdfParams>,
+) -> Result<Vec<DecryptResult>, BVaultError> {
+    let session = VaultSession::new(password, b64_salt, kdf, None)?;
+    Ok(session.decrypt_entries(entries))
+}
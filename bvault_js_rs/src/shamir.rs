@@ -0,0 +1,249 @@
+//! Shamir's Secret Sharing over GF(2^8), for splitting a recovery key among
+//! several administrators.
+//!
+//! Each byte of the secret is the constant term of its own random
+//! polynomial of degree `threshold - 1`; share `x` holds the polynomials
+//! evaluated at `x`. Any `threshold` shares recover the secret by Lagrange
+//! interpolation at 0, and fewer reveal nothing about it.
+//!
+//! A share is the base64 of
+//!
+//! ```text
+//! version   1      (1)
+//! set ID    4      random, the same for every share of one split
+//! threshold 1
+//! x         1      1 to 255
+//! y         len    one byte per secret byte
+//! checksum  4      first 4 bytes of SHA-256 over everything above
+//! ```
+//!
+//! The checksum catches typos and truncation; it is not a MAC and does not
+//! stop a malicious shareholder from submitting a forged share.
+
+use std::collections::BTreeSet;
+
+use sha2::{Digest, Sha256};
+use wasm_bindgen::prelude::*;
+use zeroize::Zeroizing;
+
+use crate::{b64_to_bytes, bytes_to_b64, random_bytes, BVaultError, ErrorCode};
+
+const VERSION: u8 = 1;
+const SET_ID_LENGTH: usize = 4;
+const CHECKSUM_LENGTH: usize = 4;
+const HEADER_LENGTH: usize = 1 + SET_ID_LENGTH + 1 + 1;
+
+/// Multiplication in GF(2^8) with the AES polynomial, without
+/// data-dependent branches or table lookups.
+fn gf_mul(mut a: u8, mut b: u8) -> u8 {
+    let mut product = 0u8;
+    for _ in 0..8 {
+        product ^= a & 0u8.wrapping_sub(b & 1);
+        let carry = 0u8.wrapping_sub(a >> 7);
+        a = (a << 1) ^ (0x1b & carry);
+        b >>= 1;
+    }
+    product
+}
+
+/// The multiplicative inverse, as a^254.
+fn gf_inv(a: u8) -> u8 {
+    let mut result = 1u8;
+    let mut power = a;
+    let mut exponent = 254u8;
+    while exponent > 0 {
+        if exponent & 1 == 1 {
+            result = gf_mul(result, power);
+        }
+        power = gf_mul(power, power);
+        exponent >>= 1;
+    }
+    result
+}
+
+fn invalid_share(message: impl Into<String>) -> BVaultError {
+    BVaultError::new(ErrorCode::InvalidShare, message)
+}
+
+fn checksum(bytes: &[u8]) -> [u8; CHECKSUM_LENGTH] {
+    let mut out = [0u8; CHECKSUM_LENGTH];
+    out.copy_from_slice(&Sha256::digest(bytes)[..CHECKSUM_LENGTH]);
+    out
+}
+
+/// A decoded share.
+struct Share {
+    set_id: [u8; SET_ID_LENGTH],
+    threshold: u8,
+    x: u8,
+    y: Zeroizing<Vec<u8>>,
+}
+
+impl Share {
+    fn to_b64(&self) -> String {
+        let mut bytes = Zeroizing::new(Vec::with_capacity(
+            HEADER_LENGTH + self.y.len() + CHECKSUM_LENGTH,
+        ));
+        bytes.push(VERSION);
+        bytes.extend_from_slice(&self.set_id);
+        bytes.push(self.threshold);
+        bytes.push(self.x);
+        bytes.extend_from_slice(&self.y);
+        let sum = checksum(&bytes);
+        bytes.extend_from_slice(&sum);
+        bytes_to_b64(&bytes)
+    }
+
+    fn parse(number: usize, b64: &str) -> Result<Share, BVaultError> {
+        let bytes = Zeroizing::new(
+            b64_to_bytes(b64.trim())
+                .map_err(|_| invalid_share(format!("share {} is not base64", number)))?,
+        );
+        if bytes.len() < HEADER_LENGTH + 1 + CHECKSUM_LENGTH {
+            return Err(invalid_share(format!("share {} is truncated", number)));
+        }
+        let (body, sum) = bytes.split_at(bytes.len() - CHECKSUM_LENGTH);
+        if checksum(body) != sum {
+            return Err(invalid_share(format!(
+                "share {} is corrupted: checksum mismatch",
+                number
+            )));
+        }
+        if body[0] != VERSION {
+            return Err(BVaultError::new(
+                ErrorCode::UnsupportedVersion,
+                format!("unsupported share version {}", body[0]),
+            ));
+        }
+        let mut set_id = [0u8; SET_ID_LENGTH];
+        set_id.copy_from_slice(&body[1..1 + SET_ID_LENGTH]);
+        let threshold = body[1 + SET_ID_LENGTH];
+        let x = body[2 + SET_ID_LENGTH];
+        if threshold < 2 || x == 0 {
+            return Err(invalid_share(format!("share {} is malformed", number)));
+        }
+        Ok(Share {
+            set_id,
+            threshold,
+            x,
+            y: Zeroizing::new(body[HEADER_LENGTH..].to_vec()),
+        })
+    }
+}
+
+/// Splits `secret` into `shares` base64 shares, any `threshold` of which
+/// recover it with [`combine_shares`].
+///
+/// # Errors
+///
+/// - If `secret` is empty, an error is returned.
+/// - If `threshold` is below 2 or above `shares`, or `shares` is above
+///   255, an error is returned.
+/// - If the random number generator is unavailable, an error is returned.
+#[wasm_bindgen]
+pub fn split_secret(secret: &[u8], threshold: u8, shares: u8) -> Result<Vec<String>, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    if secret.is_empty() {
+        return Err(BVaultError::new(
+            ErrorCode::InvalidArgument,
+            "the secret must not be empty",
+        ));
+    }
+    if threshold < 2 || threshold > shares {
+        return Err(BVaultError::new(
+            ErrorCode::InvalidArgument,
+            "threshold must be at least 2 and at most the number of shares",
+        ));
+    }
+
+    let mut set_id = [0u8; SET_ID_LENGTH];
+    set_id.copy_from_slice(&random_bytes(SET_ID_LENGTH)?);
+    // coefficients[i * len + j] is the x^(i + 1) coefficient for byte j.
+    let coefficients = Zeroizing::new(random_bytes((threshold as usize - 1) * secret.len())?);
+
+    Ok((1..=shares)
+        .map(|x| {
+            let y = secret
+                .iter()
+                .enumerate()
+                .map(|(j, &constant)| {
+                    // Horner's rule, highest degree first.
+                    let mut value = 0u8;
+                    for i in (0..threshold as usize - 1).rev() {
+                        value = gf_mul(value, x) ^ coefficients[i * secret.len() + j];
+                    }
+                    gf_mul(value, x) ^ constant
+                })
+                .collect();
+            Share {
+                set_id,
+                threshold,
+                x,
+                y: Zeroizing::new(y),
+            }
+            .to_b64()
+        })
+        .collect())
+}
+
+/// Recovers a secret from at least the threshold number of shares
+/// produced by one [`split_secret`] call. Extra shares are ignored.
+///
+/// # Errors
+///
+/// - If a share is malformed or fails its checksum, an
+///   [`ErrorCode::InvalidShare`] error naming it is returned.
+/// - If the shares come from different splits, repeat a share, or are
+///   fewer than the threshold, an [`ErrorCode::InvalidShare`] error is
+///   returned.
+#[wasm_bindgen]
+pub fn combine_shares(shares: Vec<String>) -> Result<Vec<u8>, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    let shares = shares
+        .iter()
+        .enumerate()
+        .map(|(i, b64)| Share::parse(i + 1, b64))
+        .collect::<Result<Vec<Share>, BVaultError>>()?;
+    let first = shares
+        .first()
+        .ok_or_else(|| invalid_share("no shares given"))?;
+
+    let mut seen = BTreeSet::new();
+    for (i, share) in shares.iter().enumerate() {
+        if share.set_id != first.set_id
+            || share.threshold != first.threshold
+            || share.y.len() != first.y.len()
+        {
+            return Err(invalid_share(format!(
+                "share {} belongs to a different split",
+                i + 1
+            )));
+        }
+        if !seen.insert(share.x) {
+            return Err(invalid_share(format!("share {} is a duplicate", i + 1)));
+        }
+    }
+    if shares.len() < first.threshold as usize {
+        return Err(invalid_share(format!(
+            "{} shares are needed, {} given",
+            first.threshold,
+            shares.len()
+        )));
+    }
+
+    let used = &shares[..first.threshold as usize];
+    let mut secret = vec![0u8; first.y.len()];
+    for (i, share) in used.iter().enumerate() {
+        // Lagrange basis polynomial for this share, evaluated at 0.
+        let mut basis = 1u8;
+        for (j, other) in used.iter().enumerate() {
+            if i != j {
+                basis = gf_mul(basis, gf_mul(other.x, gf_inv(other.x ^ share.x)));
+            }
+        }
+        for (byte, &y) in secret.iter_mut().zip(share.y.iter()) {
+            *byte ^= gf_mul(basis, y);
+        }
+    }
+    Ok(secret)
+}
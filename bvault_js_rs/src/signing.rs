@@ -0,0 +1,166 @@
+//! Ed25519 signatures, for detecting tampering with synced vault blobs.
+//!
+//! [`sign_vault`] signs the canonical CBOR form of a vault container, so a
+//! signature made over the binary form verifies against the CBOR form and
+//! vice versa, and no re-encoding by the server can slip past
+//! [`verify_vault`]. Vault signatures are made over
+//! `"bvault-vault-signature\0" || canonical CBOR` so they can never be
+//! mistaken for a signature over arbitrary bytes from [`ed25519_sign`].
+//!
+//! Verification uses the strict rules (no malleable signatures, no
+//! small-order keys).
+
+use std::convert::TryInto;
+
+use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
+use wasm_bindgen::prelude::*;
+use zeroize::Zeroizing;
+
+use crate::vault::Locked;
+use crate::{random_bytes, BVaultError, ErrorCode, Vault};
+
+const VAULT_CONTEXT: &[u8] = b"bvault-vault-signature\0";
+
+/// An Ed25519 key pair.
+#[wasm_bindgen]
+#[derive(Clone)]
+pub struct Ed25519KeyPair {
+    secret_key: Zeroizing<[u8; 32]>,
+    public_key: [u8; 32],
+}
+
+#[wasm_bindgen]
+impl Ed25519KeyPair {
+    /// The 32-byte secret key (the RFC 8032 seed). Store it like a
+    /// password.
+    #[wasm_bindgen(getter)]
+    pub fn secret_key(&self) -> Vec<u8> {
+        self.secret_key.to_vec()
+    }
+
+    /// The 32-byte public key, safe to publish.
+    #[wasm_bindgen(getter)]
+    pub fn public_key(&self) -> Vec<u8> {
+        self.public_key.to_vec()
+    }
+}
+
+pub(crate) fn signing_key(secret_key: &[u8]) -> Result<SigningKey, BVaultError> {
+    let seed: Zeroizing<[u8; 32]> = Zeroizing::new(secret_key.try_into().map_err(|_| {
+        BVaultError::new(
+            ErrorCode::BadKeyLength,
+            "Ed25519 secret keys must be 32 bytes",
+        )
+    })?);
+    Ok(SigningKey::from_bytes(&seed))
+}
+
+pub(crate) fn verifying_key(public_key: &[u8]) -> Result<VerifyingKey, BVaultError> {
+    let bytes: [u8; 32] = public_key.try_into().map_err(|_| {
+        BVaultError::new(
+            ErrorCode::BadKeyLength,
+            "Ed25519 public keys must be 32 bytes",
+        )
+    })?;
+    VerifyingKey::from_bytes(&bytes)
+        .map_err(|_| BVaultError::new(ErrorCode::InvalidKey, "not an Ed25519 public key"))
+}
+
+/// Strict verification; a signature of the wrong length never verifies.
+fn verify(public_key: &[u8], signature: &[u8], message: &[u8]) -> Result<bool, BVaultError> {
+    let key = verifying_key(public_key)?;
+    let signature = match Signature::from_slice(signature) {
+        Ok(signature) => signature,
+        Err(_) => return Ok(false),
+    };
+    Ok(key.verify_strict(message, &signature).is_ok())
+}
+
+fn vault_message(locked: &Locked) -> Vec<u8> {
+    let mut message = VAULT_CONTEXT.to_vec();
+    message.extend_from_slice(&locked.to_cbor());
+    message
+}
+
+/// Generates a random Ed25519 key pair.
+///
+/// # Errors
+///
+/// - If the random number generator is unavailable, an error is returned.
+#[wasm_bindgen]
+pub fn generate_ed25519_keypair() -> Result<Ed25519KeyPair, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    let seed = Zeroizing::new(random_bytes(32)?);
+    let key = signing_key(&seed)?;
+    Ok(Ed25519KeyPair {
+        secret_key: Zeroizing::new(key.to_bytes()),
+        public_key: key.verifying_key().to_bytes(),
+    })
+}
+
+/// The public key belonging to a 32-byte Ed25519 secret key.
+///
+/// # Errors
+///
+/// - If `secret_key` is not 32 bytes, an error is returned.
+#[wasm_bindgen]
+pub fn ed25519_public_key(secret_key: &[u8]) -> Result<Vec<u8>, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    Ok(signing_key(secret_key)?.verifying_key().to_bytes().to_vec())
+}
+
+/// Signs `message`, returning the 64-byte signature.
+///
+/// # Errors
+///
+/// - If `secret_key` is not 32 bytes, an error is returned.
+#[wasm_bindgen]
+pub fn ed25519_sign(secret_key: &[u8], message: &[u8]) -> Result<Vec<u8>, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    Ok(signing_key(secret_key)?.sign(message).to_bytes().to_vec())
+}
+
+/// Whether `signature` is a valid signature of `message` by `public_key`.
+///
+/// # Errors
+///
+/// - If `public_key` is not a 32-byte Ed25519 public key, an error is
+///   returned.
+#[wasm_bindgen]
+pub fn ed25519_verify(
+    public_key: &[u8],
+    signature: &[u8],
+    message: &[u8],
+) -> Result<bool, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    verify(public_key, signature, message)
+}
+
+/// Signs the canonical serialization of `vault`, entries still encrypted.
+///
+/// # Errors
+///
+/// - If `secret_key` is not 32 bytes, an error is returned.
+/// - If the key check value cannot be computed, an error is returned.
+#[wasm_bindgen]
+pub fn sign_vault(vault: &Vault, secret_key: &[u8]) -> Result<Vec<u8>, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    let key = signing_key(secret_key)?;
+    let message = vault_message(&vault.locked()?);
+    Ok(key.sign(&message).to_bytes().to_vec())
+}
+
+/// Whether `signature` by `public_key` covers the vault container `blob`
+/// (binary or CBOR), without unlocking it.
+///
+/// # Errors
+///
+/// - If `public_key` is not a 32-byte Ed25519 public key, an error is
+///   returned.
+/// - If `blob` is not a well-formed vault container, an error is returned.
+#[wasm_bindgen]
+pub fn verify_vault(blob: &[u8], public_key: &[u8], signature: &[u8]) -> Result<bool, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    let message = vault_message(&Locked::parse(blob)?);
+    verify(public_key, signature, &message)
+}
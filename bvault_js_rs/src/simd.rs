@@ -0,0 +1,31 @@
+//! Detection of WebAssembly SIMD support, so a loader can choose between a
+//! `simd` build (see the `simd` cargo feature) and the baseline one.
+//!
+//! This function must be called from the baseline build: a `simd` build
+//! fails to compile on engines without SIMD, before any export can run.
+
+use wasm_bindgen::prelude::*;
+
+#[cfg(all(feature = "simd", not(target_feature = "simd128")))]
+compile_error!("the `simd` feature needs RUSTFLAGS=\"-C target-feature=+simd128\"");
+
+/// The smallest module using a `v128` instruction: one function running
+/// `i32.const 0; i8x16.splat; i8x16.popcnt`.
+const PROBE: [u8; 31] = [
+    0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x05, 0x01, 0x60, 0x00, 0x01, 0x7b, 0x03,
+    0x02, 0x01, 0x00, 0x0a, 0x0a, 0x01, 0x08, 0x00, 0x41, 0x00, 0xfd, 0x0f, 0xfd, 0x62, 0x0b,
+];
+
+/// Whether this JS engine runs WebAssembly SIMD (`simd128`) modules.
+#[wasm_bindgen]
+pub fn simd_supported() -> bool {
+    let _operation = crate::utils::set_panic_hook();
+    let probe = js_sys::Uint8Array::from(&PROBE[..]);
+    js_sys::WebAssembly::validate(&probe).unwrap_or(false)
+}
+
+/// Whether this build was compiled with the `simd` feature.
+#[wasm_bindgen]
+pub fn simd_enabled() -> bool {
+    cfg!(feature = "simd")
+}
@@ -0,0 +1,353 @@
+//! The client side of SRP-6a (RFC 2945, RFC 5054), for backends that
+//! authenticate with it.
+//!
+//! ```text
+//! registration: srp_verifier(I, P, s) → server stores (I, s, v)
+//! login:        new SrpClient(I, P).public_ephemeral (A) → server
+//!               server (s, B) → process_challenge() → M1 → server
+//!               server M2 → verify_server() → session_key (K)
+//! ```
+//!
+//! The computations are the common ones, which most server libraries
+//! follow:
+//!
+//! ```text
+//! k  = H(N | PAD(g))
+//! x  = H(s | H(I | ":" | P))
+//! u  = H(PAD(A) | PAD(B))
+//! S  = (B - k·g^x)^(a + u·x) mod N
+//! K  = H(S)
+//! M1 = H(H(N) xor H(g) | H(I) | s | A | B | K)
+//! M2 = H(A | M1 | K)
+//! ```
+//!
+//! Numbers are unsigned big-endian bytes without leading zeros; `PAD`
+//! left-pads them to the length of N. The groups are those of RFC 5054
+//! appendix A. Plain SRP hashes the password once; to stretch it, pass the
+//! output of a KDF (hex or base64 encoded) as the password on both sides.
+
+use rsa::BigUint;
+use sha1::Sha1;
+use sha2::{Digest, Sha256, Sha512};
+use wasm_bindgen::prelude::*;
+use zeroize::Zeroizing;
+
+use crate::utils::from_hex;
+use crate::{random_bytes, BVaultError, ErrorCode};
+
+/// Bytes of the private ephemeral `a`; RFC 5054 asks for at least 256 bits.
+const EPHEMERAL_LENGTH: usize = 32;
+
+const N_2048: &str = "\
+    AC6BDB41324A9A9BF166DE5E1389582FAF72B6651987EE07FC3192943DB56050\
+    A37329CBB4A099ED8193E0757767A13DD52312AB4B03310DCD7F48A9DA04FD50\
+    E8083969EDB767B0CF6095179A163AB3661A05FBD5FAAAE82918A9962F0B93B8\
+    55F97993EC975EEAA80D740ADBF4FF747359D041D5C33EA71D281E446B14773B\
+    CA97B43A23FB801676BD207A436C6481F1D2B9078717461A5B9D32E688F87748\
+    544523B524B0D57D5EA77A2775D2ECFA032CFBDBF52FB3786160279004E57AE6\
+    AF874E7303CE53299CCC041C7BC308D82A5698F3A8D0C38271AE35F8E9DBFBB6\
+    94B5C803D89F7AE435DE236D525F54759B65E372FCD68EF20FA7111F9E4AFF73";
+
+/// The first 3072 bits of the 4096-bit prime, which differ only after.
+const N_3072_4096_PREFIX: &str = "\
+    FFFFFFFFFFFFFFFFC90FDAA22168C234C4C6628B80DC1CD129024E088A67CC74\
+    020BBEA63B139B22514A08798E3404DDEF9519B3CD3A431B302B0A6DF25F1437\
+    4FE1356D6D51C245E485B576625E7EC6F44C42E9A637ED6B0BFF5CB6F406B7ED\
+    EE386BFB5A899FA5AE9F24117C4B1FE649286651ECE45B3DC2007CB8A163BF05\
+    98DA48361C55D39A69163FA8FD24CF5F83655D23DCA3AD961C62F356208552BB\
+    9ED529077096966D670C354E4ABC9804F1746C08CA18217C32905E462E36CE3B\
+    E39E772C180E86039B2783A2EC07A28FB5C55DF06F4C52C9DE2BCBF695581718\
+    3995497CEA956AE515D2261898FA051015728E5A8AAAC42DAD33170D04507A33\
+    A85521ABDF1CBA64ECFB850458DBEF0A8AEA71575D060C7DB3970F85A6E1E4C7\
+    ABF5AE8CDB0933D71E8C94E04A25619DCEE3D2261AD2EE6BF12FFA06D98A0864\
+    D87602733EC86A64521F2B18177B200CBBE117577A615D6C770988C0BAD946E2\
+    08E24FA074E5AB3143DB5BFCE0FD108E4B82D120A9";
+
+const N_3072_SUFFIX: &str = "3AD2CAFFFFFFFFFFFFFFFF";
+
+const N_4096_SUFFIX: &str = "\
+    2108011A723C12A787E6D7\
+    88719A10BDBA5B2699C327186AF4E23C1A946834B6150BDA2583E9CA2AD44CE8\
+    DBBBC2DB04DE8EF92E8EFC141FBECAA6287C59474E6BC05D99B2964FA090C3A2\
+    233BA186515BE7ED1F612970CEE2D7AFB81BDD762170481CD0069127D5B05AA9\
+    93B4EA988D8FDDC186FFB7DC90A6C08F4DF435C934063199FFFFFFFFFFFFFFFF";
+
+/// The RFC 5054 groups SRP computations can use.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum SrpGroup {
+    /// The 2048-bit group, g = 2. The default.
+    #[default]
+    G2048 = 2048,
+    /// The 3072-bit group, g = 5.
+    G3072 = 3072,
+    /// The 4096-bit group, g = 5.
+    G4096 = 4096,
+}
+
+impl SrpGroup {
+    /// N and g.
+    fn params(self) -> (BigUint, BigUint) {
+        let n = match self {
+            SrpGroup::G2048 => N_2048.to_string(),
+            SrpGroup::G3072 => format!("{}{}", N_3072_4096_PREFIX, N_3072_SUFFIX),
+            SrpGroup::G4096 => format!("{}{}", N_3072_4096_PREFIX, N_4096_SUFFIX),
+        };
+        let g = match self {
+            SrpGroup::G2048 => 2u8,
+            SrpGroup::G3072 | SrpGroup::G4096 => 5,
+        };
+        let n = from_hex(&n.to_lowercase()).expect("group primes are valid hex");
+        (BigUint::from_bytes_be(&n), BigUint::from(g))
+    }
+}
+
+/// Hash functions SRP computations can use.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum SrpHash {
+    /// SHA-1, as in RFC 5054.
+    Sha1 = 1,
+    /// SHA-256, what most current servers use. The default.
+    #[default]
+    Sha256 = 2,
+    /// SHA-512.
+    Sha512 = 3,
+}
+
+impl SrpHash {
+    /// The hash of `parts`, concatenated.
+    fn hash(self, parts: &[&[u8]]) -> Vec<u8> {
+        fn run<D: Digest>(parts: &[&[u8]]) -> Vec<u8> {
+            let mut digest = D::new();
+            for part in parts {
+                digest.update(part);
+            }
+            digest.finalize().to_vec()
+        }
+        match self {
+            SrpHash::Sha1 => run::<Sha1>(parts),
+            SrpHash::Sha256 => run::<Sha256>(parts),
+            SrpHash::Sha512 => run::<Sha512>(parts),
+        }
+    }
+
+    fn number(self, parts: &[&[u8]]) -> BigUint {
+        BigUint::from_bytes_be(&self.hash(parts))
+    }
+}
+
+/// `value` left-padded with zeros to the length of `n`.
+fn pad(value: &BigUint, n: &BigUint) -> Vec<u8> {
+    let bytes = value.to_bytes_be();
+    let mut out = vec![0; n.bits().div_ceil(8) - bytes.len()];
+    out.extend_from_slice(&bytes);
+    out
+}
+
+/// The private key `x`.
+fn private_key(hash: SrpHash, username: &[u8], password: &[u8], salt: &[u8]) -> BigUint {
+    let identity = Zeroizing::new(hash.hash(&[username, b":", password]));
+    hash.number(&[salt, &identity])
+}
+
+/// Computes the password verifier `v = g^x mod N` that the server stores
+/// at registration, with a fresh random `salt` of 16 bytes or more.
+///
+/// # Errors
+///
+/// - If `salt` is empty, an [`ErrorCode::BadSaltLength`] error is
+///   returned.
+#[wasm_bindgen]
+pub fn srp_verifier(
+    username: &str,
+    password: &str,
+    salt: &[u8],
+    group: Option<SrpGroup>,
+    hash: Option<SrpHash>,
+) -> Result<Vec<u8>, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    if salt.is_empty() {
+        return Err(BVaultError::new(
+            ErrorCode::BadSaltLength,
+            "SRP salt must not be empty",
+        ));
+    }
+    let (n, g) = group.unwrap_or_default().params();
+    let x = private_key(
+        hash.unwrap_or_default(),
+        username.as_bytes(),
+        password.as_bytes(),
+        salt,
+    );
+    Ok(g.modpow(&x, &n).to_bytes_be())
+}
+
+/// An SRP-6a login from the client side. The steps must be taken in order:
+/// send [`SrpClient::public_ephemeral`], pass the server's salt and `B` to
+/// [`SrpClient::process_challenge`], send the proof it returns, and check
+/// the server's reply with [`SrpClient::verify_server`].
+#[wasm_bindgen]
+pub struct SrpClient {
+    group: SrpGroup,
+    hash: SrpHash,
+    username: Vec<u8>,
+    password: Zeroizing<Vec<u8>>,
+    a: Zeroizing<Vec<u8>>,
+    a_pub: BigUint,
+    /// `M2` and `K`, once the challenge is processed.
+    expected: Option<(Vec<u8>, Zeroizing<Vec<u8>>)>,
+    verified: bool,
+}
+
+#[wasm_bindgen]
+impl SrpClient {
+    /// Starts a login as `username` with a fresh private ephemeral. `group`
+    /// and `hash` must match the server's; they default to 2048 bits and
+    /// SHA-256.
+    ///
+    /// # Errors
+    ///
+    /// - If no secure random source is available, an error is returned.
+    #[wasm_bindgen(constructor)]
+    pub fn new(
+        username: &str,
+        password: &str,
+        group: Option<SrpGroup>,
+        hash: Option<SrpHash>,
+    ) -> Result<SrpClient, BVaultError> {
+        let _operation = crate::utils::set_panic_hook();
+        let group = group.unwrap_or_default();
+        let (n, g) = group.params();
+        let a = Zeroizing::new(random_bytes(EPHEMERAL_LENGTH)?);
+        let a_pub = g.modpow(&BigUint::from_bytes_be(&a), &n);
+        Ok(SrpClient {
+            group,
+            hash: hash.unwrap_or_default(),
+            username: username.as_bytes().to_vec(),
+            password: Zeroizing::new(password.as_bytes().to_vec()),
+            a,
+            a_pub,
+            expected: None,
+            verified: false,
+        })
+    }
+
+    /// `A`, to send to the server with the username.
+    #[wasm_bindgen(getter)]
+    pub fn public_ephemeral(&self) -> Vec<u8> {
+        self.a_pub.to_bytes_be()
+    }
+
+    /// Takes the server's `salt` and public ephemeral `B` and returns the
+    /// client proof `M1` to send back.
+    ///
+    /// # Errors
+    ///
+    /// - If `B` is zero modulo N, as a malicious server would send to learn
+    ///   the key, or gives a zero `u`, an [`ErrorCode::InvalidArgument`]
+    ///   error is returned.
+    /// - If called twice, an [`ErrorCode::InvalidArgument`] error is
+    ///   returned.
+    pub fn process_challenge(
+        &mut self,
+        salt: &[u8],
+        server_public: &[u8],
+    ) -> Result<Vec<u8>, BVaultError> {
+        let _operation = crate::utils::set_panic_hook();
+        if self.expected.is_some() {
+            return Err(BVaultError::new(
+                ErrorCode::InvalidArgument,
+                "the SRP challenge was already processed",
+            ));
+        }
+        let hash = self.hash;
+        let (n, g) = self.group.params();
+        let zero = BigUint::from(0u8);
+        let b_pub = BigUint::from_bytes_be(server_public);
+        if &b_pub % &n == zero {
+            return Err(BVaultError::new(
+                ErrorCode::InvalidArgument,
+                "illegal SRP server public ephemeral",
+            ));
+        }
+        let u = hash.number(&[&pad(&self.a_pub, &n), &pad(&b_pub, &n)]);
+        if u == zero {
+            return Err(BVaultError::new(
+                ErrorCode::InvalidArgument,
+                "illegal SRP server public ephemeral",
+            ));
+        }
+
+        let k = hash.number(&[&n.to_bytes_be(), &pad(&g, &n)]);
+        let x = private_key(hash, &self.username, &self.password, salt);
+        let kv = (k * g.modpow(&x, &n)) % &n;
+        let base = (&b_pub % &n + &n - kv) % &n;
+        let exponent = BigUint::from_bytes_be(&self.a) + u * x;
+        let premaster = Zeroizing::new(base.modpow(&exponent, &n).to_bytes_be());
+        let key = Zeroizing::new(hash.hash(&[&premaster]));
+
+        let h_g = hash.hash(&[&g.to_bytes_be()]);
+        let h_ng: Vec<u8> = hash
+            .hash(&[&n.to_bytes_be()])
+            .iter()
+            .zip(&h_g)
+            .map(|(a, b)| a ^ b)
+            .collect();
+        let a_pub = self.a_pub.to_bytes_be();
+        let m1 = hash.hash(&[
+            &h_ng,
+            &hash.hash(&[&self.username]),
+            salt,
+            &a_pub,
+            &b_pub.to_bytes_be(),
+            &key,
+        ]);
+        let m2 = hash.hash(&[&a_pub, &m1, &key]);
+        self.expected = Some((m2, key));
+        Ok(m1)
+    }
+
+    /// Checks the server's proof `M2`, authenticating the server.
+    ///
+    /// # Errors
+    ///
+    /// - If the proof does not match, an [`ErrorCode::AuthFailed`] error is
+    ///   returned: the server does not hold this password's verifier.
+    /// - If the challenge was not processed yet, an
+    ///   [`ErrorCode::InvalidArgument`] error is returned.
+    pub fn verify_server(&mut self, server_proof: &[u8]) -> Result<(), BVaultError> {
+        let _operation = crate::utils::set_panic_hook();
+        let (m2, _) = self.expected.as_ref().ok_or_else(|| {
+            BVaultError::new(
+                ErrorCode::InvalidArgument,
+                "the SRP challenge has not been processed yet",
+            )
+        })?;
+        if !crate::constant_time_eq(m2, server_proof) {
+            return Err(BVaultError::new(
+                ErrorCode::AuthFailed,
+                "SRP server proof mismatch",
+            ));
+        }
+        self.verified = true;
+        Ok(())
+    }
+
+    /// The shared session key `K`, once the server is verified.
+    ///
+    /// # Errors
+    ///
+    /// - If [`SrpClient::verify_server`] has not succeeded, an
+    ///   [`ErrorCode::InvalidArgument`] error is returned.
+    #[wasm_bindgen(getter)]
+    pub fn session_key(&self) -> Result<Vec<u8>, BVaultError> {
+        match &self.expected {
+            Some((_, key)) if self.verified => Ok(key.to_vec()),
+            _ => Err(BVaultError::new(
+                ErrorCode::InvalidArgument,
+                "the SRP server has not been verified yet",
+            )),
+        }
+    }
+}
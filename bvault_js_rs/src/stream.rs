@@ -0,0 +1,294 @@
+//! Incremental AES-256-CBC encryption and decryption for payloads too large
+//! to buffer.
+//!
+//! [`StreamEncryptor`] and [`StreamDecryptor`] speak the same format as
+//! [`crate::encrypt_bytes_sync`] and [`crate::decrypt_bytes_sync`], but on
+//! raw bytes fed in chunks of any size. At most one block is held back, so
+//! memory stays bounded by the chunk size.
+//!
+//! The encrypt-then-MAC tag is accumulated as the ciphertext streams past.
+//! A decryptor can only check it in [`StreamDecryptor::finalize`], so the
+//! plaintext returned by earlier `update` calls is unauthenticated until
+//! `finalize` succeeds and must be discarded if it fails.
+
+use aes::cipher::block_padding::{Padding, Pkcs7};
+use aes::cipher::generic_array::GenericArray;
+use aes::cipher::{BlockDecryptMut, BlockEncryptMut, KeyIvInit};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use wasm_bindgen::prelude::*;
+use zeroize::Zeroize;
+
+use crate::legacy::{padding_error, pkcs7_data_len, Aes256CbcDec, Aes256CbcEnc};
+use crate::mac::{cbc_mac, verify};
+use crate::{
+    b64_to_bytes, bytes_to_b64, derive_key, new_salted_key, random_bytes, BVaultError,
+    Base64Decoder, EncryptOptions, ErrorCode, KdfParams, IV_LENGTH,
+};
+
+/// AES block size, in bytes.
+const BLOCK_SIZE: usize = 16;
+
+fn finished() -> BVaultError {
+    BVaultError::new(ErrorCode::StreamFinished, "stream was already finalized")
+}
+
+/// Encrypts a plaintext chunk by chunk with AES-256-CBC.
+///
+/// The random IV and salt are generated up front; persist them (the
+/// stream's header) next to the ciphertext to decrypt it later.
+#[wasm_bindgen]
+pub struct StreamEncryptor {
+    enc: Option<Aes256CbcEnc>,
+    hmac: Hmac<Sha256>,
+    pending: Vec<u8>,
+    iv: Vec<u8>,
+    salt: Vec<u8>,
+    tag: Option<Vec<u8>>,
+}
+
+#[wasm_bindgen]
+impl StreamEncryptor {
+    /// Derives a key under a fresh salt and prepares to encrypt; the options
+    /// are the same as for [`crate::encrypt_bytes_sync`].
+    ///
+    /// # Errors
+    ///
+    /// See [`crate::encrypt_sync`].
+    #[wasm_bindgen(constructor)]
+    pub fn new(
+        password: &str,
+        options: Option<EncryptOptions>,
+    ) -> Result<StreamEncryptor, BVaultError> {
+        let _operation = crate::utils::set_panic_hook();
+        let options = options.unwrap_or_default();
+        options.reject_aad()?;
+
+        let (salt, key) = new_salted_key(password, options.salt_length, &options.kdf, None)?;
+        let iv = random_bytes(IV_LENGTH)?;
+        let enc = Aes256CbcEnc::new_from_slices(&key[..], &iv)
+            .map_err(|_| BVaultError::new(ErrorCode::BadKeyLength, "invalid key/iv length"))?;
+        let mut hmac = cbc_mac(&key[..])?;
+        hmac.update(&iv);
+
+        Ok(StreamEncryptor {
+            enc: Some(enc),
+            hmac,
+            pending: Vec::new(),
+            iv,
+            salt,
+            tag: None,
+        })
+    }
+
+    /// The base64 IV of this stream.
+    #[wasm_bindgen(getter)]
+    pub fn iv(&self) -> String {
+        bytes_to_b64(&self.iv)
+    }
+
+    /// The base64 salt of this stream.
+    #[wasm_bindgen(getter)]
+    pub fn salt(&self) -> String {
+        bytes_to_b64(&self.salt)
+    }
+
+    /// The base64 HMAC-SHA256 tag of the whole ciphertext; `undefined`
+    /// until the stream is finalized.
+    #[wasm_bindgen(getter)]
+    pub fn mac(&self) -> Option<String> {
+        self.tag.as_deref().map(bytes_to_b64)
+    }
+
+    /// Feeds the next plaintext chunk and returns the ciphertext for every
+    /// complete block so far.
+    ///
+    /// # Errors
+    ///
+    /// - If the stream was already finalized, an error is returned.
+    pub fn update(&mut self, chunk: &[u8]) -> Result<Vec<u8>, BVaultError> {
+        let enc = self.enc.as_mut().ok_or_else(finished)?;
+        self.pending.extend_from_slice(chunk);
+
+        let ready = self.pending.len() / BLOCK_SIZE * BLOCK_SIZE;
+        let mut out: Vec<u8> = self.pending.drain(..ready).collect();
+        for block in out.chunks_exact_mut(BLOCK_SIZE) {
+            enc.encrypt_block_mut(GenericArray::from_mut_slice(block));
+        }
+        self.hmac.update(&out);
+        Ok(out)
+    }
+
+    /// Pads and encrypts the remaining bytes and ends the stream.
+    ///
+    /// # Errors
+    ///
+    /// - If the stream was already finalized, an error is returned.
+    pub fn finalize(&mut self) -> Result<Vec<u8>, BVaultError> {
+        let mut enc = self.enc.take().ok_or_else(finished)?;
+
+        let pos = self.pending.len();
+        let mut block = GenericArray::default();
+        block[..pos].copy_from_slice(&self.pending);
+        Pkcs7::pad(&mut block, pos);
+        enc.encrypt_block_mut(&mut block);
+        self.hmac.update(&block);
+        self.tag = Some(self.hmac.clone().finalize().into_bytes().to_vec());
+
+        self.pending.zeroize();
+        Ok(block.to_vec())
+    }
+}
+
+impl Drop for StreamEncryptor {
+    fn drop(&mut self) {
+        self.pending.zeroize();
+    }
+}
+
+/// Decrypts an AES-256-CBC ciphertext chunk by chunk.
+#[wasm_bindgen]
+pub struct StreamDecryptor {
+    dec: Option<Aes256CbcDec>,
+    /// The running HMAC and the expected tag, if one was supplied.
+    mac: Option<(Hmac<Sha256>, String)>,
+    pending: Vec<u8>,
+    /// Carries base64 characters between [`Self::update_base64`] calls.
+    base64: Base64Decoder,
+}
+
+#[wasm_bindgen]
+impl StreamDecryptor {
+    /// Derives the key and prepares to decrypt; the parameters are the same
+    /// as for [`crate::decrypt_bytes_sync`]. A `b64_mac` is checked by
+    /// [`Self::finalize`].
+    ///
+    /// # Errors
+    ///
+    /// - If the IV or salt is invalid base64, an error is returned.
+    /// - If the IV is not 16 bytes, an error is returned.
+    /// - If the KDF parameters are below the accepted minimum, an error is
+    ///   returned.
+    #[wasm_bindgen(constructor)]
+    pub fn new(
+        password: &str,
+        b64_iv: &str,
+        b64_salt: &str,
+        kdf: Option<KdfParams>,
+        b64_mac: Option<String>,
+    ) -> Result<StreamDecryptor, BVaultError> {
+        let _operation = crate::utils::set_panic_hook();
+
+        let iv = b64_to_bytes(b64_iv)?;
+        let salt = b64_to_bytes(b64_salt)?;
+        if iv.len() != IV_LENGTH {
+            return Err(BVaultError::new(
+                ErrorCode::BadIvLength,
+                "IV must be 16 bytes",
+            ));
+        }
+
+        let key = derive_key(password, &salt, kdf)?;
+        let dec = Aes256CbcDec::new_from_slices(&key[..], &iv)
+            .map_err(|_| BVaultError::new(ErrorCode::BadKeyLength, "invalid key/iv length"))?;
+        let mac = match b64_mac {
+            Some(b64_mac) => {
+                let mut hmac = cbc_mac(&key[..])?;
+                hmac.update(&iv);
+                Some((hmac, b64_mac))
+            }
+            None => None,
+        };
+
+        Ok(StreamDecryptor {
+            dec: Some(dec),
+            mac,
+            pending: Vec::new(),
+            base64: Base64Decoder::new(),
+        })
+    }
+
+    /// Feeds the next ciphertext chunk and returns the plaintext decrypted
+    /// so far. The last block is always held back until [`Self::finalize`].
+    ///
+    /// # Errors
+    ///
+    /// - If the stream was already finalized, an error is returned.
+    pub fn update(&mut self, chunk: &[u8]) -> Result<Vec<u8>, BVaultError> {
+        let dec = self.dec.as_mut().ok_or_else(finished)?;
+        self.pending.extend_from_slice(chunk);
+
+        // Keep at least one block (possibly the padded final one) pending.
+        let ready = match self.pending.len() {
+            0 => 0,
+            len => (len - 1) / BLOCK_SIZE * BLOCK_SIZE,
+        };
+
+        let mut out: Vec<u8> = self.pending.drain(..ready).collect();
+        if let Some((hmac, _)) = self.mac.as_mut() {
+            hmac.update(&out);
+        }
+        for block in out.chunks_exact_mut(BLOCK_SIZE) {
+            dec.decrypt_block_mut(GenericArray::from_mut_slice(block));
+        }
+        Ok(out)
+    }
+
+    /// Like [`Self::update`], for a ciphertext stored as base64 (such as
+    /// the output of [`crate::encrypt_sync`]) fed in slices of the text.
+    /// The text is decoded as it arrives, so the whole ciphertext never
+    /// sits in memory in either form.
+    ///
+    /// # Errors
+    ///
+    /// - If the stream was already finalized, an error is returned.
+    /// - If the text is not valid base64, an error is returned.
+    pub fn update_base64(&mut self, chunk: &str) -> Result<Vec<u8>, BVaultError> {
+        self.dec.as_ref().ok_or_else(finished)?;
+        let ciphertext = self.base64.update(chunk)?;
+        self.update(&ciphertext)
+    }
+
+    /// Decrypts the final block, strips the padding and ends the stream.
+    ///
+    /// # Errors
+    ///
+    /// - If the stream was already finalized, an error is returned.
+    /// - If base64 fed to [`Self::update_base64`] ends in a partial
+    ///   quantum, an error is returned.
+    /// - If a MAC was supplied and does not match, an error is returned.
+    /// - If the total ciphertext length is not a non-zero multiple of 16, or
+    ///   the padding is invalid (wrong password or corrupted data), an error
+    ///   is returned.
+    pub fn finalize(&mut self) -> Result<Vec<u8>, BVaultError> {
+        self.dec.as_ref().ok_or_else(finished)?;
+        // The decoded tail is at most two bytes, so for a well-formed
+        // ciphertext this releases nothing and only completes the last block.
+        let tail = self.base64.finalize()?;
+        let mut out = self.update(&tail)?;
+        let mut dec = self.dec.take().ok_or_else(finished)?;
+
+        if let Some((mut hmac, b64_mac)) = self.mac.take() {
+            hmac.update(&self.pending);
+            verify(hmac, &b64_mac)?;
+        }
+
+        if self.pending.len() != BLOCK_SIZE {
+            return Err(padding_error());
+        }
+
+        dec.decrypt_block_mut(GenericArray::from_mut_slice(&mut self.pending));
+        let len = pkcs7_data_len(&self.pending);
+        if let Some(len) = len {
+            out.extend_from_slice(&self.pending[..len]);
+        }
+        self.pending.zeroize();
+        len.map(|_| out).ok_or_else(padding_error)
+    }
+}
+
+impl Drop for StreamDecryptor {
+    fn drop(&mut self) {
+        self.pending.zeroize();
+    }
+}
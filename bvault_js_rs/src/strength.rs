@@ -0,0 +1,280 @@
+//! Password strength estimation.
+//!
+//! The estimate is deliberately conservative: it starts from the size of the
+//! character pool, then discounts repeats, runs like `abc`/`321`, keyboard
+//! rows, common passwords (including l33t spellings with a numeric suffix)
+//! and EFF wordlist passphrases, which are scored per word rather than per
+//! character.
+
+use wasm_bindgen::prelude::*;
+
+use crate::generate::WORDLIST;
+
+/// Guesses per second assumed for the crack time: a GPU rig attacking a
+/// fast hash. Vaults stretch keys with a KDF, so real attacks are slower.
+const GUESSES_PER_SECOND: f64 = 1e10;
+
+/// Bits credited to a character that repeats or continues a run.
+const PATTERN_CHAR_BITS: f64 = 1.0;
+
+/// Entropy of one word drawn from the EFF large wordlist (log2 7776).
+const EFF_WORD_BITS: f64 = 12.925;
+
+const KEYBOARD_ROWS: &[&str] = &["qwertyuiop", "asdfghjkl", "zxcvbnm", "1234567890"];
+
+/// Some of the most common leaked passwords, lowercase.
+const COMMON_PASSWORDS: &[&str] = &[
+    "password",
+    "123456",
+    "12345678",
+    "qwerty",
+    "abc123",
+    "monkey",
+    "letmein",
+    "dragon",
+    "111111",
+    "baseball",
+    "iloveyou",
+    "trustno1",
+    "sunshine",
+    "master",
+    "welcome",
+    "shadow",
+    "ashley",
+    "football",
+    "jesus",
+    "michael",
+    "ninja",
+    "mustang",
+    "password1",
+    "admin",
+    "login",
+    "princess",
+    "qwertyuiop",
+    "solo",
+    "starwars",
+    "passw0rd",
+    "hello",
+    "freedom",
+    "whatever",
+    "charlie",
+    "donald",
+    "superman",
+    "batman",
+    "zaq1zaq1",
+    "secret",
+    "access",
+    "flower",
+    "hottie",
+    "loveme",
+    "qazwsx",
+    "666666",
+    "654321",
+    "000000",
+    "1q2w3e4r",
+    "123123",
+    "google",
+    "computer",
+    "internet",
+    "summer",
+    "winter",
+    "spring",
+    "autumn",
+];
+
+/// The result of [`estimate_password_strength`].
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Clone, Debug)]
+pub struct PasswordStrength {
+    /// 0 (very weak) to 4 (very strong).
+    pub score: u8,
+    /// Estimated entropy in bits.
+    pub entropy_bits: f64,
+    /// Expected time to guess the password offline at 10^10 guesses per
+    /// second, in seconds.
+    pub crack_time_seconds: f64,
+    /// `crack_time_seconds` in words, e.g. `"3 hours"` or `"centuries"`.
+    pub crack_time_display: String,
+}
+
+/// Estimates how hard `password` is to guess.
+///
+/// Scores map to entropy as follows: 0 below 28 bits, 1 below 36, 2 below
+/// 60, 3 below 80 and 4 from 80 bits up.
+#[wasm_bindgen]
+pub fn estimate_password_strength(password: &str) -> PasswordStrength {
+    let _operation = crate::utils::set_panic_hook();
+    let entropy_bits = entropy(password);
+
+    // On average the attacker searches half the space.
+    let crack_time_seconds = 2f64.powf(entropy_bits - 1.0).max(0.0) / GUESSES_PER_SECOND;
+
+    let score = match entropy_bits {
+        e if e < 28.0 => 0,
+        e if e < 36.0 => 1,
+        e if e < 60.0 => 2,
+        e if e < 80.0 => 3,
+        _ => 4,
+    };
+
+    PasswordStrength {
+        score,
+        entropy_bits,
+        crack_time_seconds,
+        crack_time_display: display_duration(crack_time_seconds),
+    }
+}
+
+fn entropy(password: &str) -> f64 {
+    if password.is_empty() {
+        return 0.0;
+    }
+
+    let estimates = [
+        charset_entropy(password),
+        common_password_entropy(password),
+        passphrase_entropy(password),
+    ];
+    estimates
+        .iter()
+        .copied()
+        .flatten()
+        .fold(f64::INFINITY, f64::min)
+}
+
+/// Pool-size estimate with repeats and runs discounted.
+fn charset_entropy(password: &str) -> Option<f64> {
+    let chars: Vec<char> = password.chars().collect();
+    let has = |f: fn(&char) -> bool| chars.iter().any(f);
+
+    let mut pool = 0u32;
+    if has(char::is_ascii_lowercase) {
+        pool += 26;
+    }
+    if has(char::is_ascii_uppercase) {
+        pool += 26;
+    }
+    if has(char::is_ascii_digit) {
+        pool += 10;
+    }
+    if has(char::is_ascii_punctuation) || has(|c| *c == ' ') {
+        pool += 33;
+    }
+    if has(|c| !c.is_ascii()) {
+        pool += 100;
+    }
+    let char_bits = f64::from(pool).log2();
+
+    let lower: Vec<char> = password.to_lowercase().chars().collect();
+    let mut bits = char_bits;
+    for i in 1..lower.len() {
+        bits += if continues_pattern(&lower, i) {
+            PATTERN_CHAR_BITS
+        } else {
+            char_bits
+        };
+    }
+    Some(bits)
+}
+
+/// Whether `chars[i]` repeats, or continues a run or keyboard row started
+/// by, the characters before it.
+fn continues_pattern(chars: &[char], i: usize) -> bool {
+    let (prev, cur) = (chars[i - 1], chars[i]);
+    if prev == cur {
+        return true;
+    }
+
+    let step = cur as i64 - prev as i64;
+    if step.abs() == 1 && prev.is_ascii_alphanumeric() && cur.is_ascii_alphanumeric() {
+        return true;
+    }
+
+    KEYBOARD_ROWS.iter().any(|row| {
+        let row: Vec<char> = row.chars().collect();
+        row.windows(2)
+            .any(|pair| (pair[0] == prev && pair[1] == cur) || (pair[1] == prev && pair[0] == cur))
+    })
+}
+
+/// Entropy of a common password, possibly l33t-spelled and followed by a
+/// short digit/symbol suffix; `None` if the password is not one.
+fn common_password_entropy(password: &str) -> Option<f64> {
+    let chars: Vec<char> = password.chars().map(|c| c.to_ascii_lowercase()).collect();
+    let unleet: Vec<char> = chars
+        .iter()
+        .map(|c| match c {
+            '@' | '4' => 'a',
+            '3' => 'e',
+            '1' | '!' => 'i',
+            '0' => 'o',
+            '$' | '5' => 's',
+            '7' => 't',
+            c => *c,
+        })
+        .collect();
+
+    let rank_bits = (COMMON_PASSWORDS.len() as f64).log2();
+    let mut best: Option<f64> = None;
+    for common in COMMON_PASSWORDS {
+        let common: Vec<char> = common.chars().collect();
+        let len = common.len();
+        if chars.len() < len {
+            continue;
+        }
+
+        let exact = chars[..len] == common[..];
+        if !exact && unleet[..len] != common[..] {
+            continue;
+        }
+        let suffix = &chars[len..];
+        if suffix.len() > 4 || suffix.iter().any(|c| c.is_alphabetic()) {
+            continue;
+        }
+
+        // One bit for the l33t variant, ~3.5 per suffix character.
+        let l33t_bits = if exact { 0.0 } else { 1.0 };
+        let bits = rank_bits + l33t_bits + suffix.len() as f64 * 3.5;
+        best = Some(best.map_or(bits, |b| b.min(bits)));
+    }
+    best
+}
+
+/// Entropy of a passphrase made only of EFF wordlist words separated by
+/// non-letters; `None` if the password is not one.
+fn passphrase_entropy(password: &str) -> Option<f64> {
+    let lowered = password.to_lowercase();
+    let words: Vec<&str> = lowered
+        .split(|c: char| !c.is_ascii_lowercase())
+        .filter(|w| !w.is_empty())
+        .collect();
+    if words.len() < 2 || !words.iter().all(|w| WORDLIST.lines().any(|l| l == *w)) {
+        return None;
+    }
+    Some(words.len() as f64 * EFF_WORD_BITS)
+}
+
+fn display_duration(seconds: f64) -> String {
+    const UNITS: &[(f64, &str)] = &[
+        (60.0, "second"),
+        (60.0, "minute"),
+        (24.0, "hour"),
+        (30.0, "day"),
+        (12.0, "month"),
+        (100.0, "year"),
+    ];
+
+    if seconds < 1.0 {
+        return "less than a second".to_string();
+    }
+
+    let mut value = seconds;
+    for (size, unit) in UNITS {
+        if value < *size {
+            let n = value.round() as u64;
+            return format!("{} {}{}", n, unit, if n == 1 { "" } else { "s" });
+        }
+        value /= size;
+    }
+    "centuries".to_string()
+}
@@ -0,0 +1,105 @@
+//! Decoding plaintext that is not utf-8.
+//!
+//! The string-returning decrypt functions ([`crate::decrypt_sync`],
+//! [`crate::open_string`] and the like) take an optional [`TextDecoding`]
+//! and default to strict utf-8, as before. Data migrated from Windows apps
+//! is often UTF-16LE or Windows-1252 instead, and would otherwise have to
+//! be decrypted as bytes and decoded in JS. [`decode_text`] applies the
+//! same decodings to bytes that are already decrypted.
+
+use wasm_bindgen::prelude::*;
+use zeroize::Zeroizing;
+
+use crate::{BVaultError, ErrorCode};
+
+/// How decrypted bytes are turned into a string.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum TextDecoding {
+    /// utf-8; invalid sequences are an error.
+    #[default]
+    Utf8 = 1,
+    /// utf-8, with invalid sequences replaced by U+FFFD.
+    Utf8Lossy = 2,
+    /// UTF-16 little-endian, as written by Windows; a leading byte order
+    /// mark is dropped. An odd length or an unpaired surrogate is an error.
+    Utf16Le = 3,
+    /// Latin-1 as browsers decode it, i.e. Windows-1252: bytes 0x80-0x9F
+    /// are the Windows punctuation (`€`, curly quotes, ...) rather than C1
+    /// controls. Never fails.
+    Latin1 = 4,
+    /// One character per byte, U+0000 to U+00FF: the "binary string" of
+    /// `atob`. Never fails and is lossless, so the bytes can be recovered
+    /// with `charCodeAt`.
+    Raw = 5,
+}
+
+/// The characters of Windows-1252 bytes 0x80-0x9F; the five bytes it leaves
+/// undefined map to the C1 control of the same value, as in browsers.
+const WINDOWS_1252: [char; 32] = [
+    '\u{20AC}', '\u{0081}', '\u{201A}', '\u{0192}', '\u{201E}', '\u{2026}', '\u{2020}', '\u{2021}',
+    '\u{02C6}', '\u{2030}', '\u{0160}', '\u{2039}', '\u{0152}', '\u{008D}', '\u{017D}', '\u{008F}',
+    '\u{0090}', '\u{2018}', '\u{2019}', '\u{201C}', '\u{201D}', '\u{2022}', '\u{2013}', '\u{2014}',
+    '\u{02DC}', '\u{2122}', '\u{0161}', '\u{203A}', '\u{0153}', '\u{009D}', '\u{017E}', '\u{0178}',
+];
+
+fn decode_utf16le(bytes: &[u8]) -> Result<String, &'static str> {
+    if !bytes.len().is_multiple_of(2) {
+        return Err("invalid utf-16");
+    }
+    let units = Zeroizing::new(
+        bytes
+            .chunks_exact(2)
+            .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+            .collect::<Vec<u16>>(),
+    );
+    let units = match units.first() {
+        Some(0xFEFF) => &units[1..],
+        _ => &units[..],
+    };
+    String::from_utf16(units).map_err(|_| "invalid utf-16")
+}
+
+/// Decodes `bytes`, or says why they are invalid.
+fn decode(bytes: &[u8], decoding: TextDecoding) -> Result<String, &'static str> {
+    match decoding {
+        TextDecoding::Utf8 => std::str::from_utf8(bytes)
+            .map(str::to_string)
+            .map_err(|_| "invalid utf-8"),
+        TextDecoding::Utf8Lossy => Ok(String::from_utf8_lossy(bytes).into_owned()),
+        TextDecoding::Utf16Le => decode_utf16le(bytes),
+        TextDecoding::Latin1 => Ok(bytes
+            .iter()
+            .map(|&byte| match byte {
+                0x80..=0x9F => WINDOWS_1252[usize::from(byte - 0x80)],
+                _ => char::from(byte),
+            })
+            .collect()),
+        TextDecoding::Raw => Ok(bytes.iter().map(|&byte| char::from(byte)).collect()),
+    }
+}
+
+/// Decodes decrypted bytes, wiping them afterwards; `decoding` defaults to
+/// strict utf-8. Invalid text is reported like other decryption failures.
+pub(crate) fn decode_plaintext(
+    plaintext: Vec<u8>,
+    decoding: Option<TextDecoding>,
+) -> Result<String, BVaultError> {
+    let plaintext = Zeroizing::new(plaintext);
+    decode(&plaintext, decoding.unwrap_or_default())
+        .map_err(|message| BVaultError::decryption_failed(ErrorCode::Utf8Error, message))
+}
+
+/// Decodes `bytes` as text; `decoding` defaults to strict utf-8. For
+/// plaintext from the bytes-returning decrypt functions.
+///
+/// # Errors
+///
+/// - If `bytes` are not valid in a strict decoding, an
+///   [`ErrorCode::Utf8Error`] error is returned.
+#[wasm_bindgen]
+pub fn decode_text(bytes: &[u8], decoding: Option<TextDecoding>) -> Result<String, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    decode(bytes, decoding.unwrap_or_default())
+        .map_err(|message| BVaultError::new(ErrorCode::Utf8Error, message))
+}
@@ -0,0 +1,79 @@
+//! Optional multi-threading over a pool of Web Workers.
+//!
+//! With the `threads` feature, JS may call [`init_threads`] once on a
+//! cross-origin isolated page; batch operations such as
+//! [`crate::VaultSession::decrypt_entries`] then spread their items across
+//! the pool. Until the pool is up, and in builds without the feature,
+//! everything runs on the calling thread, so the same code works either
+//! way.
+//!
+//! Work is split between items, not within one: each Argon2 derivation
+//! still computes its lanes one after another, so a batch gains from the
+//! pool but a single unlock does not.
+
+use wasm_bindgen::prelude::*;
+
+#[cfg(feature = "threads")]
+use std::sync::atomic::{AtomicBool, Ordering};
+
+#[cfg(feature = "threads")]
+static POOL_READY: AtomicBool = AtomicBool::new(false);
+
+/// Starts a pool of `count` Web Worker threads and resolves once it is
+/// ready. Pass `navigator.hardwareConcurrency` unless there is a reason to
+/// hold cores back.
+///
+/// # Errors
+///
+/// - If the runtime has no Web Workers (Node, Cloudflare Workers; see
+///   [`crate::runtime_info`]), the promise rejects with an
+///   [`crate::ErrorCode::UnsupportedAlgorithm`] error.
+/// - If the workers cannot be started (e.g. the page is not cross-origin
+///   isolated, so `SharedArrayBuffer` is unavailable), the promise rejects
+///   and work stays on the calling thread.
+#[cfg(feature = "threads")]
+#[wasm_bindgen]
+pub async fn init_threads(count: usize) -> Result<(), JsValue> {
+    crate::utils::in_operation(async move {
+        if !crate::runtime_info().workers() {
+            return Err(crate::BVaultError::new(
+                crate::ErrorCode::UnsupportedAlgorithm,
+                format!(
+                    "{} cannot start Web Workers; work stays on the calling thread",
+                    crate::runtime::detect().name()
+                ),
+            )
+            .into());
+        }
+        wasm_bindgen_futures::JsFuture::from(wasm_bindgen_rayon::init_thread_pool(count)).await?;
+        POOL_READY.store(true, Ordering::Release);
+        Ok(())
+    })
+    .await
+}
+
+/// Whether batch operations run on a worker pool: the build has the
+/// `threads` feature and [`init_threads`] has completed.
+#[wasm_bindgen]
+pub fn threads_enabled() -> bool {
+    #[cfg(feature = "threads")]
+    return POOL_READY.load(Ordering::Acquire);
+    #[cfg(not(feature = "threads"))]
+    false
+}
+
+/// Applies `f` to every item, on the worker pool when it is running,
+/// keeping the results in order.
+pub(crate) fn map<T, R, F>(items: Vec<T>, f: F) -> Vec<R>
+where
+    T: Send,
+    R: Send,
+    F: Fn(T) -> R + Sync + Send,
+{
+    #[cfg(feature = "threads")]
+    if threads_enabled() {
+        use rayon::prelude::*;
+        return items.into_par_iter().map(f).collect();
+    }
+    items.into_iter().map(f).collect()
+}
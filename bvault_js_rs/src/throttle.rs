@@ -0,0 +1,185 @@
+//! Optional throttling of password guesses against the same blob.
+//!
+//! With [`enable_throttling`], [`crate::open`] and [`crate::Vault::unlock`]
+//! count failed attempts (wrong password or failed authentication) per
+//! envelope or vault blob. After `free_attempts` failures, each further
+//! attempt on that blob must wait twice as long as the one before, and is
+//! rejected with [`ErrorCode::Throttled`] until then. A successful
+//! attempt resets the count.
+//!
+//! This slows down scripts that guess passwords through the page; it is no
+//! defence against an attacker who copies the blob out and runs the KDF
+//! elsewhere, which only a strong password and KDF prevent.
+
+use std::collections::HashMap;
+use std::sync::{Mutex, PoisonError};
+
+use sha2::{Digest, Sha256};
+use wasm_bindgen::prelude::*;
+
+use crate::{BVaultError, ErrorCode};
+
+/// Default cap on the delay, in milliseconds (15 minutes).
+const DEFAULT_MAX_DELAY_MS: f64 = 15.0 * 60.0 * 1000.0;
+
+/// Most blobs tracked at once; the one with the oldest failure is dropped
+/// beyond that.
+const MAX_TRACKED: usize = 256;
+
+/// Bytes of the SHA-256 of a blob used to tell blobs apart.
+const FINGERPRINT_LENGTH: usize = 16;
+
+type Fingerprint = [u8; FINGERPRINT_LENGTH];
+
+static THROTTLE: Mutex<Option<Throttle>> = Mutex::new(None);
+
+struct Throttle {
+    free_attempts: u32,
+    base_delay_ms: f64,
+    max_delay_ms: f64,
+    blobs: HashMap<Fingerprint, Failures>,
+}
+
+struct Failures {
+    count: u32,
+    last_ms: f64,
+    next_allowed_ms: f64,
+}
+
+impl Throttle {
+    fn delay_after(&self, count: u32) -> f64 {
+        match count.checked_sub(self.free_attempts) {
+            None | Some(0) => 0.0,
+            Some(excess) => {
+                let delay = self.base_delay_ms * 2f64.powi(excess.min(64) as i32 - 1);
+                delay.min(self.max_delay_ms)
+            }
+        }
+    }
+
+    fn record_failure(&mut self, fingerprint: Fingerprint, now: f64) {
+        if !self.blobs.contains_key(&fingerprint) && self.blobs.len() >= MAX_TRACKED {
+            let oldest = self
+                .blobs
+                .iter()
+                .min_by(|a, b| a.1.last_ms.total_cmp(&b.1.last_ms))
+                .map(|(fingerprint, _)| *fingerprint);
+            if let Some(oldest) = oldest {
+                self.blobs.remove(&oldest);
+            }
+        }
+        let count = self.blobs.get(&fingerprint).map_or(0, |f| f.count) + 1;
+        let next_allowed_ms = now + self.delay_after(count);
+        self.blobs.insert(
+            fingerprint,
+            Failures {
+                count,
+                last_ms: now,
+                next_allowed_ms,
+            },
+        );
+    }
+}
+
+fn fingerprint(blob: &[u8]) -> Fingerprint {
+    let mut fingerprint = [0; FINGERPRINT_LENGTH];
+    fingerprint.copy_from_slice(&Sha256::digest(blob)[..FINGERPRINT_LENGTH]);
+    fingerprint
+}
+
+/// Turns on throttling: after `free_attempts` failures on a blob, the next
+/// attempt waits `base_delay_ms`, and each one after that twice as long,
+/// up to `max_delay_ms` (default 15 minutes). Any failures counted before
+/// are forgotten.
+///
+/// # Errors
+///
+/// - If a delay is negative or not a number, an
+///   [`ErrorCode::InvalidArgument`] error is returned.
+#[wasm_bindgen]
+pub fn enable_throttling(
+    free_attempts: u32,
+    base_delay_ms: f64,
+    max_delay_ms: Option<f64>,
+) -> Result<(), BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    let max_delay_ms = max_delay_ms.unwrap_or(DEFAULT_MAX_DELAY_MS);
+    if !(base_delay_ms >= 0.0 && max_delay_ms >= 0.0) {
+        return Err(BVaultError::new(
+            ErrorCode::InvalidArgument,
+            "delays must be non-negative numbers of milliseconds",
+        ));
+    }
+    *THROTTLE.lock().unwrap_or_else(PoisonError::into_inner) = Some(Throttle {
+        free_attempts,
+        base_delay_ms,
+        max_delay_ms,
+        blobs: HashMap::new(),
+    });
+    Ok(())
+}
+
+/// Turns throttling off and forgets all counted failures.
+#[wasm_bindgen]
+pub fn disable_throttling() {
+    let _operation = crate::utils::set_panic_hook();
+    *THROTTLE.lock().unwrap_or_else(PoisonError::into_inner) = None;
+}
+
+/// Milliseconds until the next attempt on `blob` will be processed; 0 if
+/// it can be attempted now or throttling is off.
+#[wasm_bindgen]
+pub fn throttle_delay(blob: &[u8]) -> f64 {
+    let _operation = crate::utils::set_panic_hook();
+    let throttle = THROTTLE.lock().unwrap_or_else(PoisonError::into_inner);
+    throttle
+        .as_ref()
+        .and_then(|throttle| throttle.blobs.get(&fingerprint(blob)))
+        .map_or(0.0, |failures| {
+            (failures.next_allowed_ms - crate::utils::now_ms()).max(0.0)
+        })
+}
+
+/// Runs the password attempt `attempt` on `blob` if the throttle allows
+/// it, and counts its outcome.
+pub(crate) fn attempt<T>(
+    blob: &[u8],
+    attempt: impl FnOnce() -> Result<T, BVaultError>,
+) -> Result<T, BVaultError> {
+    let state = {
+        let throttle = THROTTLE.lock().unwrap_or_else(PoisonError::into_inner);
+        throttle.as_ref().map(|throttle| {
+            let fingerprint = fingerprint(blob);
+            let wait = throttle.blobs.get(&fingerprint).map_or(0.0, |failures| {
+                failures.next_allowed_ms - crate::utils::now_ms()
+            });
+            (fingerprint, wait)
+        })
+    };
+    let (fingerprint, wait) = match state {
+        Some(state) => state,
+        None => return attempt(),
+    };
+    if wait > 0.0 {
+        return Err(BVaultError::new(
+            ErrorCode::Throttled,
+            format!("too many failed attempts; try again in {} ms", wait.ceil()),
+        ));
+    }
+
+    // The lock is not held while the KDF runs.
+    let result = attempt();
+    let mut throttle = THROTTLE.lock().unwrap_or_else(PoisonError::into_inner);
+    if let Some(throttle) = throttle.as_mut() {
+        match &result {
+            Ok(_) => {
+                throttle.blobs.remove(&fingerprint);
+            }
+            Err(err) if matches!(err.code(), ErrorCode::AuthFailed | ErrorCode::WrongPassword) => {
+                throttle.record_failure(fingerprint, crate::utils::now_ms());
+            }
+            Err(_) => {}
+        }
+    }
+    result
+}
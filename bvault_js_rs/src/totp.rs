@@ -0,0 +1,221 @@
+//! Time-based one-time passwords (RFC 6238), computed inside WASM so the
+//! shared secret is never decoded in JS.
+//!
+//! Secrets are the base32 strings authenticator apps exchange (RFC 4648,
+//! case-insensitive, with or without padding; spaces and dashes are
+//! ignored). Times are in seconds since the Unix epoch and default to the
+//! JS clock.
+
+use hmac::digest::KeyInit;
+use hmac::{Hmac, Mac};
+use sha1::Sha1;
+use sha2::{Sha256, Sha512};
+use subtle::ConstantTimeEq;
+use wasm_bindgen::prelude::*;
+use zeroize::{Zeroize, Zeroizing};
+
+use crate::{BVaultError, ErrorCode};
+
+/// Default time step, in seconds.
+const DEFAULT_PERIOD: u32 = 30;
+
+/// Default code length, in digits.
+const DEFAULT_DIGITS: u32 = 6;
+
+/// Longest code a 31-bit dynamic truncation can fill.
+const MAX_DIGITS: u32 = 10;
+
+/// Steps either side of the current one [`totp_verify`] accepts by default.
+const DEFAULT_WINDOW: u32 = 1;
+
+/// Widest window [`totp_verify`] accepts, in steps either side.
+const MAX_WINDOW: u32 = 10;
+
+const BASE32_ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+/// HMAC hash functions TOTP codes can be computed with.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum TotpAlgorithm {
+    /// HMAC-SHA1, what nearly every service uses. The default.
+    #[default]
+    Sha1 = 1,
+    /// HMAC-SHA256.
+    Sha256 = 2,
+    /// HMAC-SHA512.
+    Sha512 = 3,
+}
+
+impl TotpAlgorithm {
+    fn mac(self, key: &[u8], counter: u64) -> Zeroizing<Vec<u8>> {
+        fn run<M: Mac + KeyInit>(key: &[u8], counter: u64) -> Zeroizing<Vec<u8>> {
+            let mut mac = <M as Mac>::new_from_slice(key).expect("HMAC takes keys of any length");
+            mac.update(&counter.to_be_bytes());
+            Zeroizing::new(mac.finalize().into_bytes().to_vec())
+        }
+        match self {
+            TotpAlgorithm::Sha1 => run::<Hmac<Sha1>>(key, counter),
+            TotpAlgorithm::Sha256 => run::<Hmac<Sha256>>(key, counter),
+            TotpAlgorithm::Sha512 => run::<Hmac<Sha512>>(key, counter),
+        }
+    }
+}
+
+/// Validated TOTP parameters.
+struct Totp {
+    secret: Zeroizing<Vec<u8>>,
+    period: u32,
+    digits: u32,
+    algorithm: TotpAlgorithm,
+}
+
+impl Totp {
+    fn new(
+        secret_b32: &str,
+        period: Option<u32>,
+        digits: Option<u32>,
+        algorithm: Option<TotpAlgorithm>,
+    ) -> Result<Totp, BVaultError> {
+        let period = period.unwrap_or(DEFAULT_PERIOD);
+        if period == 0 {
+            return Err(BVaultError::new(
+                ErrorCode::InvalidArgument,
+                "period must be at least 1 second",
+            ));
+        }
+        let digits = digits.unwrap_or(DEFAULT_DIGITS);
+        if !(1..=MAX_DIGITS).contains(&digits) {
+            return Err(BVaultError::new(
+                ErrorCode::InvalidArgument,
+                "digits must be between 1 and 10",
+            ));
+        }
+        let secret = decode_base32(secret_b32)?;
+        if secret.is_empty() {
+            return Err(BVaultError::new(
+                ErrorCode::BadKeyLength,
+                "TOTP secret must not be empty",
+            ));
+        }
+        Ok(Totp {
+            secret,
+            period,
+            digits,
+            algorithm: algorithm.unwrap_or_default(),
+        })
+    }
+
+    /// The time step `time` falls in.
+    fn counter(&self, time: Option<f64>) -> Result<u64, BVaultError> {
+        let time = time.unwrap_or_else(|| crate::utils::now_ms() / 1000.0);
+        if !time.is_finite() || time < 0.0 {
+            return Err(BVaultError::new(
+                ErrorCode::InvalidArgument,
+                "time must be a non-negative number of seconds",
+            ));
+        }
+        Ok((time / self.period as f64).floor() as u64)
+    }
+
+    /// The HOTP value (RFC 4226 section 5.3) for `counter`.
+    fn code(&self, counter: u64) -> String {
+        let mac = self.algorithm.mac(&self.secret, counter);
+        let offset = (mac[mac.len() - 1] & 0x0f) as usize;
+        let binary = u32::from_be_bytes([
+            mac[offset] & 0x7f,
+            mac[offset + 1],
+            mac[offset + 2],
+            mac[offset + 3],
+        ]);
+        let code = binary as u64 % 10u64.pow(self.digits);
+        format!("{:0width$}", code, width = self.digits as usize)
+    }
+}
+
+/// Decodes RFC 4648 base32, ignoring case, padding, spaces and dashes.
+fn decode_base32(text: &str) -> Result<Zeroizing<Vec<u8>>, BVaultError> {
+    let mut out = Zeroizing::new(Vec::with_capacity(text.len() * 5 / 8));
+    let mut buffer = 0u64;
+    let mut bits = 0;
+    for byte in text.bytes() {
+        if matches!(byte, b' ' | b'-' | b'=') {
+            continue;
+        }
+        let value = BASE32_ALPHABET
+            .iter()
+            .position(|&c| c == byte.to_ascii_uppercase())
+            .ok_or_else(|| BVaultError::new(ErrorCode::InvalidBase32, "invalid base32"))?;
+        buffer = (buffer << 5) | value as u64;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((buffer >> bits) as u8);
+        }
+    }
+    buffer.zeroize();
+    Ok(out)
+}
+
+/// The TOTP code for `secret_b32` at `time` (default now).
+///
+/// `period` defaults to 30 seconds, `digits` to 6 and `algorithm` to
+/// HMAC-SHA1, the values authenticator apps assume when an `otpauth://`
+/// URI leaves them out.
+///
+/// # Errors
+///
+/// - If the secret is not valid base32, an [`ErrorCode::InvalidBase32`]
+///   error is returned; if it is empty, an [`ErrorCode::BadKeyLength`]
+///   error.
+/// - If `period` is 0, `digits` is outside 1..=10 or `time` is negative,
+///   an [`ErrorCode::InvalidArgument`] error is returned.
+#[wasm_bindgen]
+pub fn totp_generate(
+    secret_b32: &str,
+    period: Option<u32>,
+    digits: Option<u32>,
+    algorithm: Option<TotpAlgorithm>,
+    time: Option<f64>,
+) -> Result<String, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    let totp = Totp::new(secret_b32, period, digits, algorithm)?;
+    Ok(totp.code(totp.counter(time)?))
+}
+
+/// Whether `code` is the TOTP code for `secret_b32` at `time` (default
+/// now), or at up to `window` steps before or after it (default 1) to
+/// allow for clock drift. Every candidate is compared in constant time.
+///
+/// # Errors
+///
+/// - If `window` is above 10, an [`ErrorCode::InvalidArgument`] error is
+///   returned.
+/// - Otherwise see [`totp_generate`].
+#[wasm_bindgen]
+pub fn totp_verify(
+    code: &str,
+    secret_b32: &str,
+    period: Option<u32>,
+    digits: Option<u32>,
+    algorithm: Option<TotpAlgorithm>,
+    window: Option<u32>,
+    time: Option<f64>,
+) -> Result<bool, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    let totp = Totp::new(secret_b32, period, digits, algorithm)?;
+    let counter = totp.counter(time)?;
+    let window = window.unwrap_or(DEFAULT_WINDOW);
+    if window > MAX_WINDOW {
+        return Err(BVaultError::new(
+            ErrorCode::InvalidArgument,
+            "window must be at most 10 steps",
+        ));
+    }
+    let window = window as u64;
+
+    let mut matched = subtle::Choice::from(0);
+    for step in counter.saturating_sub(window)..=counter.saturating_add(window) {
+        matched |= totp.code(step).as_bytes().ct_eq(code.as_bytes());
+    }
+    Ok(matched.into())
+}
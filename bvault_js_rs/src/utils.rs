@@ -1,10 +1,222 @@
-pub fn set_panic_hook() {
-    // When the `console_error_panic_hook` feature is enabled, we can call the
-    // `set_panic_hook` function at least once during initialization, and then
-    // we will get better error messages if our code ever panics.
-    //
-    // For more details see
-    // https://github.com/rustwasm/console_error_panic_hook#readme
-    #[cfg(feature = "console_error_panic_hook")]
-    console_error_panic_hook::set_once();
+use std::cell::Cell;
+use std::panic::{self, Location, PanicHookInfo};
+use std::sync::Once;
+
+use wasm_bindgen::prelude::*;
+
+/// Longest panic message passed on to JS, in bytes.
+const MAX_PANIC_MESSAGE: usize = 200;
+
+thread_local! {
+    /// Where the exported function running now called [`set_panic_hook`] or
+    /// [`in_operation`].
+    static OPERATION: Cell<Option<&'static Location<'static>>> = const { Cell::new(None) };
+}
+
+/// Installs the panic hook, once, and records the calling exported
+/// function as the current operation until the returned guard is dropped.
+/// Every synchronous exported function calls this first and holds the
+/// guard to the end; async ones use [`in_operation`] instead.
+///
+/// A panic then throws a [`BVaultInternalError`] to JS instead of trapping
+/// with "unreachable executed". With the `console_error_panic_hook` feature
+/// the panic is also logged with `console.error`, and any hook installed
+/// earlier still runs. The module's state after a panic is undefined; pages
+/// should reload it rather than carry on.
+#[track_caller]
+pub(crate) fn set_panic_hook() -> Operation {
+    // `Location::caller` must be read here: inside a closure it would name
+    // this file rather than the caller.
+    let caller = Location::caller();
+    install_panic_hook();
+    enter(caller)
+}
+
+/// Runs `future` as the calling exported function's operation. The
+/// operation is set only while the future is being polled, so other calls
+/// running between its awaits neither see it nor overwrite it.
+#[cfg(any(feature = "cbc-legacy", feature = "streams", feature = "threads"))]
+#[track_caller]
+pub(crate) fn in_operation<F: std::future::Future>(
+    future: F,
+) -> impl std::future::Future<Output = F::Output> {
+    let caller = Location::caller();
+    install_panic_hook();
+    let mut future = Box::pin(future);
+    std::future::poll_fn(move |cx| {
+        let _operation = enter(caller);
+        future.as_mut().poll(cx)
+    })
+}
+
+/// [`in_operation`] for a stream returned by an exported function, whose
+/// items are produced after the function has returned.
+#[cfg(feature = "streams")]
+#[track_caller]
+pub(crate) fn stream_in_operation<S: futures_util::Stream>(
+    stream: S,
+) -> impl futures_util::Stream<Item = S::Item> {
+    let caller = Location::caller();
+    let mut stream = Box::pin(stream);
+    futures_util::stream::poll_fn(move |cx| {
+        let _operation = enter(caller);
+        stream.as_mut().poll_next(cx)
+    })
+}
+
+fn install_panic_hook() {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| {
+        #[cfg(feature = "console_error_panic_hook")]
+        console_error_panic_hook::set_once();
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            previous(info);
+            wasm_bindgen::throw_val(BVaultInternalError::from_panic(info).into());
+        }));
+    });
+}
+
+fn enter(caller: &'static Location<'static>) -> Operation {
+    let previous = OPERATION.with(|operation| operation.replace(Some(caller)));
+    Operation { previous }
+}
+
+/// The current operation of [`set_panic_hook`]. Dropping it restores the
+/// one before, which is none outside any exported function, so errors
+/// raised later by code that sets none are not blamed on this one.
+#[must_use = "the operation ends when this is dropped"]
+pub(crate) struct Operation {
+    previous: Option<&'static Location<'static>>,
+}
+
+impl Drop for Operation {
+    fn drop(&mut self) {
+        OPERATION.with(|operation| operation.set(self.previous));
+    }
+}
+
+/// A panic inside the library: a bug, never an expected failure. Thrown to
+/// JS in place of a `RuntimeError: unreachable`.
+#[wasm_bindgen]
+#[derive(Clone, Debug)]
+pub struct BVaultInternalError {
+    message: String,
+    operation: String,
+    location: String,
+}
+
+#[wasm_bindgen]
+impl BVaultInternalError {
+    /// The panic message, scrubbed of formatted values.
+    #[wasm_bindgen(getter)]
+    pub fn message(&self) -> String {
+        self.message.clone()
+    }
+
+    /// The source module of the exported function that panicked, such as
+    /// `"vault"`, or `"unknown"`.
+    #[wasm_bindgen(getter)]
+    pub fn operation(&self) -> String {
+        self.operation.clone()
+    }
+
+    /// Source file and line of the panic.
+    #[wasm_bindgen(getter)]
+    pub fn location(&self) -> String {
+        self.location.clone()
+    }
+
+    #[wasm_bindgen(js_name = toString)]
+    pub fn to_js_string(&self) -> String {
+        format!(
+            "BVaultInternalError in {} at {}: {}",
+            self.operation, self.location, self.message
+        )
+    }
+}
+
+impl BVaultInternalError {
+    fn from_panic(info: &PanicHookInfo<'_>) -> BVaultInternalError {
+        let operation = current_operation().unwrap_or("unknown");
+        BVaultInternalError {
+            message: scrub(info.payload_as_str().unwrap_or("panic")),
+            operation: operation.to_string(),
+            location: info.location().map_or_else(
+                || "unknown".to_string(),
+                |location| format!("{}:{}", location.file(), location.line()),
+            ),
+        }
+    }
+}
+
+/// The source module of the exported function running now, such as
+/// `"vault"`.
+pub(crate) fn current_operation() -> Option<&'static str> {
+    OPERATION
+        .with(Cell::get)
+        .map(|location| module_name(location.file()))
+}
+
+/// `vault` for `src/vault.rs`.
+fn module_name(file: &'static str) -> &'static str {
+    let name = file.rsplit(['/', '\\']).next().unwrap_or(file);
+    name.strip_suffix(".rs").unwrap_or(name)
+}
+
+/// The fixed part of a panic message. `unwrap`, `expect` and most
+/// assertions append the values involved after a colon, and those values
+/// can be keys or plaintexts, so everything from the first colon on is
+/// dropped.
+fn scrub(message: &str) -> String {
+    let fixed = message.split(':').next().unwrap_or_default().trim();
+    let mut end = fixed.len().min(MAX_PANIC_MESSAGE);
+    while !fixed.is_char_boundary(end) {
+        end -= 1;
+    }
+    fixed[..end].to_string()
+}
+
+/// Resolves on a later turn of the JS event loop (via `setTimeout(0)`), so
+/// long computations can let the page render and handle input in between.
+#[cfg(feature = "cbc-legacy")]
+pub async fn yield_now() {
+    use wasm_bindgen::JsCast;
+
+    let promise = js_sys::Promise::new(&mut |resolve, _reject| {
+        let global = js_sys::global();
+        let set_timeout = js_sys::Reflect::get(&global, &JsValue::from_str("setTimeout"))
+            .ok()
+            .and_then(|f| f.dyn_into::<js_sys::Function>().ok());
+
+        let scheduled = set_timeout
+            .map(|f| f.call2(&global, &resolve, &JsValue::from(0)).is_ok())
+            .unwrap_or(false);
+        if !scheduled {
+            let _ = resolve.call0(&JsValue::UNDEFINED);
+        }
+    });
+    let _ = wasm_bindgen_futures::JsFuture::from(promise).await;
+}
+
+/// Milliseconds since the Unix epoch, from the JS clock.
+pub fn now_ms() -> f64 {
+    js_sys::Date::now()
+}
+
+/// Lowercase hex encoding.
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Decodes hex of either case, with an optional `0x` prefix.
+pub fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    let hex = hex.strip_prefix("0x").unwrap_or(hex);
+    if !hex.len().is_multiple_of(2) || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+        .collect()
 }
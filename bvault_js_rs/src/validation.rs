@@ -0,0 +1,436 @@
+//! Checking inputs for every problem at once, to debug corrupted exports.
+//!
+//! A damaged envelope or legacy ciphertext usually fails with the first
+//! error hit while parsing it, or with a generic decryption failure once
+//! the key is derived. [`validate_envelope`], [`validate_envelope_string`]
+//! and [`validate_legacy`] instead walk the whole input, without a
+//! password, and list each problem they find:
+//!
+//! - base64 that is malformed (bad characters, mixed alphabets, wrong or
+//!   misplaced padding, an impossible length, non-zero trailing bits);
+//! - salts, nonces, IVs and tags whose lengths do not fit the suite;
+//! - CBC ciphertext that is not a whole number of blocks;
+//! - KDF parameters below the accepted minimum or too large to be real;
+//! - bodies shorter than their authentication tag.
+//!
+//! With [`crate::SecurityPolicy::strict_validation`] on, the decrypt
+//! functions run the same checks first and fail with an
+//! [`ErrorCode::ValidationFailed`] error listing every problem.
+//!
+//! Passing validation only means the input is well-formed: it says nothing
+//! about whether the password is right or the data was tampered with.
+
+use std::fmt::Display;
+
+use wasm_bindgen::prelude::*;
+
+use crate::cbor::{narrow, Value};
+use crate::envelope::{
+    check_envelope_version, cipher_from_id, is_cbor, kdf_from_cbor_unchecked, read_kdf_unchecked,
+    Envelope, Reader, EXT_COMPRESSION, EXT_KEY_CHECK, EXT_PADDING, EXT_ROTATION, FIELD_BODY,
+    FIELD_CIPHER, FIELD_KDF, FIELD_MAGIC, FIELD_NONCE, FIELD_SALT, FIELD_VERSION, MAGIC,
+    ROTATION_LENGTH,
+};
+use crate::kcv::KCV_LENGTH;
+use crate::{BVaultError, Cipher, ErrorCode, KdfParams};
+
+/// The problems found in an input; see the module documentation.
+#[wasm_bindgen]
+#[derive(Clone, Debug, Default)]
+pub struct ValidationReport {
+    problems: Vec<String>,
+}
+
+#[wasm_bindgen]
+impl ValidationReport {
+    /// Whether no problem was found.
+    #[wasm_bindgen(getter)]
+    pub fn ok(&self) -> bool {
+        self.problems.is_empty()
+    }
+
+    /// Each problem as `"<field>: <what is wrong>"`, in input order.
+    #[wasm_bindgen(getter)]
+    pub fn problems(&self) -> Vec<String> {
+        self.problems.clone()
+    }
+}
+
+impl ValidationReport {
+    fn add(&mut self, field: &str, message: impl Display) {
+        self.problems.push(format!("{}: {}", field, message));
+    }
+
+    /// An [`ErrorCode::ValidationFailed`] error listing the problems, if
+    /// there are any.
+    pub(crate) fn into_result(self) -> Result<(), BVaultError> {
+        match self.problems.len() {
+            0 => Ok(()),
+            1 => Err(BVaultError::new(
+                ErrorCode::ValidationFailed,
+                self.problems[0].clone(),
+            )),
+            n => Err(BVaultError::new(
+                ErrorCode::ValidationFailed,
+                format!("{} problems: {}", n, self.problems.join("; ")),
+            )),
+        }
+    }
+}
+
+/// Checks `text` as base64 the way the decoders read it, returning the
+/// bytes if it decodes.
+fn check_base64(report: &mut ValidationReport, field: &str, text: &str) -> Option<Vec<u8>> {
+    let mut standard = false;
+    let mut url_safe = false;
+    let mut padding = 0;
+    for (offset, c) in text.char_indices() {
+        match c {
+            '=' => padding += 1,
+            _ if padding > 0 => {
+                report.add(
+                    field,
+                    format!("base64 continues after padding at offset {}", offset),
+                );
+                return None;
+            }
+            'A'..='Z' | 'a'..='z' | '0'..='9' => {}
+            '+' | '/' => standard = true,
+            '-' | '_' => url_safe = true,
+            c if c.is_whitespace() => {
+                report.add(
+                    field,
+                    format!("base64 contains whitespace at offset {}", offset),
+                );
+                return None;
+            }
+            c => {
+                report.add(
+                    field,
+                    format!("invalid base64 character {:?} at offset {}", c, offset),
+                );
+                return None;
+            }
+        }
+    }
+    if standard && url_safe {
+        report.add(
+            field,
+            "base64 mixes the standard (+/) and URL-safe (-_) alphabets",
+        );
+        return None;
+    }
+    let data = text.len() - padding;
+    if padding > 2 || (padding > 0 && !text.len().is_multiple_of(4)) {
+        report.add(field, "base64 padding is malformed");
+        return None;
+    }
+    if data % 4 == 1 {
+        report.add(
+            field,
+            format!(
+                "{} base64 characters cannot be a whole number of bytes; it may be truncated",
+                data
+            ),
+        );
+        return None;
+    }
+    match crate::b64_to_bytes(text) {
+        Ok(bytes) => Some(bytes),
+        Err(_) => {
+            report.add(
+                field,
+                "base64 ends in non-zero bits; it may be truncated or corrupted",
+            );
+            None
+        }
+    }
+}
+
+/// Checks KDF parameters against the accepted minimum and maximum costs.
+fn check_kdf(report: &mut ValidationReport, kdf: &KdfParams) {
+    if let Err(err) = kdf.validate() {
+        report.add("kdf", err.message());
+    }
+}
+
+fn check_salt(report: &mut ValidationReport, kdf: Option<&KdfParams>, salt: &[u8]) {
+    match kdf {
+        Some(_) if salt.is_empty() => report.add("salt", "is empty"),
+        None if !salt.is_empty() => report.add(
+            "salt",
+            format!("is {} bytes, but a raw-key envelope has none", salt.len()),
+        ),
+        _ => {}
+    }
+}
+
+fn check_length(report: &mut ValidationReport, field: &str, length: usize, expected: usize) {
+    if length != expected {
+        report.add(field, format!("is {} bytes, expected {}", length, expected));
+    }
+}
+
+fn check_body(report: &mut ValidationReport, cipher: Option<Cipher>, body: &[u8]) {
+    let tag_length = match cipher {
+        Some(cipher) => cipher.tag_length(),
+        None => return,
+    };
+    if body.len() < tag_length {
+        report.add(
+            "body",
+            format!(
+                "is {} bytes, shorter than the {}-byte authentication tag; the envelope may be truncated",
+                body.len(),
+                tag_length
+            ),
+        );
+    }
+}
+
+fn check_cipher(report: &mut ValidationReport, id: u8) -> Option<Cipher> {
+    cipher_from_id(id)
+        .map_err(|err| report.add("cipher", err.message()))
+        .ok()
+}
+
+fn check_nonce(report: &mut ValidationReport, cipher: Option<Cipher>, nonce: &[u8]) {
+    if let Some(cipher) = cipher {
+        check_length(report, "nonce", nonce.len(), cipher.nonce_length());
+    }
+}
+
+fn walk_binary(report: &mut ValidationReport, bytes: &[u8]) {
+    let mut reader = Reader::new(bytes);
+    macro_rules! read {
+        ($read:expr, $field:expr) => {
+            match $read {
+                Ok(value) => value,
+                Err(_) => {
+                    report.add($field, "is missing; the envelope is truncated");
+                    return;
+                }
+            }
+        };
+    }
+
+    if read!(reader.take(MAGIC.len()), "magic") != MAGIC {
+        report.add("magic", "not a bvault envelope");
+        return;
+    }
+    if let Err(err) = check_envelope_version(read!(reader.u8(), "version")) {
+        report.add("version", err.message());
+    }
+    let cipher = check_cipher(report, read!(reader.u8(), "cipher"));
+    let kdf = match read_kdf_unchecked(&mut reader) {
+        Ok(kdf) => kdf,
+        Err(err) => {
+            report.add("kdf", err.message());
+            return;
+        }
+    };
+    if let Some(kdf) = &kdf {
+        check_kdf(report, kdf);
+    }
+    let salt_length = read!(reader.u8(), "salt") as usize;
+    check_salt(
+        report,
+        kdf.as_ref(),
+        read!(reader.take(salt_length), "salt"),
+    );
+    let nonce_length = read!(reader.u8(), "nonce") as usize;
+    check_nonce(report, cipher, read!(reader.take(nonce_length), "nonce"));
+    for _ in 0..read!(reader.u8(), "extensions") {
+        let tag = read!(reader.u8(), "extensions");
+        let length = read!(reader.u16(), "extensions") as usize;
+        read!(reader.take(length), "extensions");
+        let (field, expected) = match tag {
+            EXT_KEY_CHECK => ("key check value", KCV_LENGTH),
+            EXT_COMPRESSION => ("compression", 1),
+            EXT_PADDING => ("padding", 1),
+            EXT_ROTATION => ("rotation", ROTATION_LENGTH),
+            tag => {
+                report.add("extensions", format!("unknown extension {}", tag));
+                continue;
+            }
+        };
+        check_length(report, field, length, expected);
+    }
+    check_body(report, cipher, reader.rest());
+}
+
+fn walk_cbor(report: &mut ValidationReport, bytes: &[u8]) {
+    let mut fields = match Value::parse(bytes).and_then(Value::into_map) {
+        Ok(fields) => fields,
+        Err(err) => {
+            report.add("envelope", err.message());
+            return;
+        }
+    };
+    let mut field = |key: u64, name: &str, report: &mut ValidationReport| {
+        fields
+            .require(key)
+            .map_err(|err| report.add(name, err.message()))
+            .ok()
+    };
+
+    let magic = field(FIELD_MAGIC, "magic", report).and_then(|magic| magic.into_text().ok());
+    if magic.as_deref().map(str::as_bytes) != Some(&MAGIC[..]) {
+        report.add("magic", "not a bvault envelope");
+        return;
+    }
+    if let Some(version) = field(FIELD_VERSION, "version", report) {
+        if let Err(err) = narrow(version).and_then(check_envelope_version) {
+            report.add("version", err.message());
+        }
+    }
+    let cipher = field(FIELD_CIPHER, "cipher", report).and_then(|id| match narrow(id) {
+        Ok(id) => check_cipher(report, id),
+        Err(err) => {
+            report.add("cipher", err.message());
+            None
+        }
+    });
+    let salt = field(FIELD_SALT, "salt", report);
+    let nonce = field(FIELD_NONCE, "nonce", report);
+    let body = field(FIELD_BODY, "body", report);
+    let kdf = match fields
+        .take(FIELD_KDF)
+        .map(kdf_from_cbor_unchecked)
+        .transpose()
+    {
+        Ok(kdf) => kdf,
+        Err(err) => {
+            report.add("kdf", err.message());
+            return;
+        }
+    };
+    if let Some(kdf) = &kdf {
+        check_kdf(report, kdf);
+    }
+    let bytes = |value: Option<Value>, name: &str, report: &mut ValidationReport| {
+        value?
+            .into_bytes()
+            .map_err(|err| report.add(name, err.message()))
+            .ok()
+    };
+    if let Some(salt) = bytes(salt, "salt", report) {
+        check_salt(report, kdf.as_ref(), &salt);
+    }
+    if let Some(nonce) = bytes(nonce, "nonce", report) {
+        check_nonce(report, cipher, &nonce);
+    }
+    if let Some(body) = bytes(body, "body", report) {
+        check_body(report, cipher, &body);
+    }
+}
+
+fn diagnose_bytes(report: &mut ValidationReport, envelope: &[u8]) {
+    if is_cbor(envelope) {
+        walk_cbor(report, envelope);
+    } else {
+        walk_binary(report, envelope);
+    }
+    // Anything the walk does not look at, such as the values of
+    // extensions, is left to the parser.
+    if report.ok() {
+        if let Err(err) = Envelope::parse(envelope) {
+            report.add("envelope", err.message());
+        }
+    }
+}
+
+pub(crate) fn diagnose_envelope(envelope: &[u8]) -> ValidationReport {
+    let mut report = ValidationReport::default();
+    diagnose_bytes(&mut report, envelope);
+    report
+}
+
+pub(crate) fn diagnose_envelope_string(b64_envelope: &str) -> ValidationReport {
+    let mut report = ValidationReport::default();
+    if let Some(envelope) = check_base64(&mut report, "envelope", b64_envelope) {
+        diagnose_bytes(&mut report, &envelope);
+    }
+    report
+}
+
+#[cfg(feature = "cbc-legacy")]
+pub(crate) fn diagnose_legacy(
+    b64_ciphertext: &str,
+    b64_iv: &str,
+    b64_salt: &str,
+    kdf: Option<&KdfParams>,
+    b64_mac: Option<&str>,
+) -> ValidationReport {
+    const BLOCK_LENGTH: usize = 16;
+
+    let mut report = ValidationReport::default();
+    if let Some(ciphertext) = check_base64(&mut report, "ciphertext", b64_ciphertext) {
+        if ciphertext.is_empty() {
+            report.add("ciphertext", "is empty");
+        } else if !ciphertext.len().is_multiple_of(BLOCK_LENGTH) {
+            report.add(
+                "ciphertext",
+                format!(
+                    "is {} bytes, not a multiple of the {}-byte AES block; it may be truncated",
+                    ciphertext.len(),
+                    BLOCK_LENGTH
+                ),
+            );
+        }
+    }
+    if let Some(iv) = check_base64(&mut report, "iv", b64_iv) {
+        check_length(&mut report, "iv", iv.len(), crate::IV_LENGTH);
+    }
+    if let Some(salt) = check_base64(&mut report, "salt", b64_salt) {
+        if salt.is_empty() {
+            report.add("salt", "is empty");
+        }
+    }
+    if let Some(kdf) = kdf {
+        check_kdf(&mut report, kdf);
+    }
+    if let Some(b64_mac) = b64_mac {
+        if let Some(mac) = check_base64(&mut report, "mac", b64_mac) {
+            check_length(&mut report, "mac", mac.len(), crate::mac::TAG_LENGTH);
+        }
+    }
+    report
+}
+
+/// Lists every problem with the envelope `envelope` (binary or CBOR); see
+/// the module documentation. No password is needed.
+#[wasm_bindgen]
+pub fn validate_envelope(envelope: &[u8]) -> ValidationReport {
+    let _operation = crate::utils::set_panic_hook();
+    diagnose_envelope(envelope)
+}
+
+/// Like [`validate_envelope`], for the base64 envelopes of
+/// [`crate::open_string`]; the base64 itself is checked too.
+#[wasm_bindgen]
+pub fn validate_envelope_string(b64_envelope: &str) -> ValidationReport {
+    let _operation = crate::utils::set_panic_hook();
+    diagnose_envelope_string(b64_envelope)
+}
+
+/// Lists every problem with the inputs of [`crate::decrypt_sync`], taken
+/// in the same order; `kdf` is only checked when given.
+#[cfg(feature = "cbc-legacy")]
+#[wasm_bindgen]
+pub fn validate_legacy(
+    b64_ciphertext: &str,
+    b64_iv: &str,
+    b64_salt: &str,
+    kdf: Option<KdfParams>,
+    b64_mac: Option<String>,
+) -> ValidationReport {
+    let _operation = crate::utils::set_panic_hook();
+    diagnose_legacy(
+        b64_ciphertext,
+        b64_iv,
+        b64_salt,
+        kdf.as_ref(),
+        b64_mac.as_deref(),
+    )
+}
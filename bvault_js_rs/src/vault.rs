@@ -0,0 +1,776 @@
+//! A multi-entry vault document owned by the crate.
+//!
+//! A [`Vault`] keeps every entry encrypted in memory under one master key,
+//! derived once at unlock. Each entry has its own random nonce and records
+//! when it was last modified, for [`crate::merge_vaults`]. Entries are bound
+//! to their timestamp and ID as associated data (`modified || id`), so
+//! entries cannot be swapped around inside a blob or their timestamps
+//! rewritten. The whole vault serializes to a compact binary blob
+//! (big-endian):
+//!
+//! ```text
+//! magic       4      "BVLV"
+//! version     1      currently 3
+//! cipher      1      Cipher ID
+//! kdf         1 + …  as in envelopes
+//! salt        1 + n  length-prefixed
+//! kcv         8      key check value of the master key
+//! count       4      number of entries, then per entry in ID order:
+//!   id        1 + n  utf-8, length-prefixed
+//!   modified  8      ms since the Unix epoch; 0 if unknown
+//!   nonce     1 + n  length-prefixed
+//!   body      4 + n  ciphertext with the AEAD tag appended
+//!   metadata  1 + n  version 3: nonce of the metadata, length-prefixed;
+//!                    empty if the entry has none, else followed by
+//!   meta body 4 + n  the metadata ciphertext with its tag
+//! ```
+//!
+//! Version 1 vaults have no `modified` field. Their entries, and any entry
+//! whose timestamp is 0, are bound to their ID alone. They still unlock,
+//! and are written back as version 1 until an entry is changed. Vaults are
+//! written as version 2, without the `metadata` fields, until an entry is
+//! given metadata.
+//!
+//! Entry metadata (titles, URLs, anything the app wants to list) is sealed
+//! separately from the entry under a key derived from the master key with
+//! HKDF-SHA256, and bound to the entry like its body. That key alone opens
+//! only metadata, so [`crate::VaultMetadata`] can list entries while their
+//! secrets stay locked.
+//!
+//! [`Vault::to_cbor`] writes the same content as canonical CBOR, using the
+//! envelope field numbers where they overlap:
+//!
+//! ```text
+//! 0  magic    text "BVLV"
+//! 1  version  3, or 2 without metadata and 1 without timestamps
+//! 2  cipher   Cipher ID
+//! 3  kdf      [KDF ID, parameters...]
+//! 4  salt     bytes
+//! 6  kcv      bytes
+//! 8  entries  array of [id text, nonce bytes, body bytes, modified uint,
+//!             metadata nonce bytes, metadata bytes], in ID order; version
+//!             1 omits `modified`, and entries without metadata omit the
+//!             last two
+//! ```
+//!
+//! [`Vault::unlock`] accepts either form.
+
+use std::collections::BTreeMap;
+use std::convert::TryInto;
+
+use wasm_bindgen::prelude::*;
+use zeroize::Zeroizing;
+
+use crate::cbor::{narrow, Value};
+use crate::envelope::{
+    check_nonce_length, check_salt_length, check_version, cipher_from_id, invalid, is_cbor,
+    kdf_from_cbor, kdf_to_cbor, read_kdf, text_magic, write_kdf, Reader,
+};
+use crate::kcv::{self, KCV_LENGTH};
+use crate::kdf::{hkdf_sha256, Key};
+use crate::{
+    new_salted_key, random_bytes, BVaultError, Cipher, ErrorCode, KdfParams, OpenOptions,
+    SealOptions,
+};
+
+const MAGIC: &[u8; 4] = b"BVLV";
+pub(crate) const VERSION: u8 = 3;
+/// Vaults without entry metadata.
+pub(crate) const VERSION_NO_METADATA: u8 = 2;
+/// Vaults without entry timestamps.
+pub(crate) const VERSION_UNTIMED: u8 = 1;
+
+/// HKDF info of the key entry metadata is sealed under.
+pub(crate) const METADATA_KEY_INFO: &[u8] = b"bvault-vault-metadata";
+
+const FIELD_MAGIC: u64 = 0;
+const FIELD_VERSION: u64 = 1;
+const FIELD_CIPHER: u64 = 2;
+const FIELD_KDF: u64 = 3;
+const FIELD_SALT: u64 = 4;
+const FIELD_KCV: u64 = 6;
+const FIELD_ENTRIES: u64 = 8;
+
+/// Length of a generated entry ID, in random bytes (hex-encoded).
+const ID_BYTES: usize = 16;
+
+/// One encrypted entry.
+#[derive(Clone, Debug)]
+pub(crate) struct SealedEntry {
+    /// Last modification, in ms since the Unix epoch; 0 if unknown.
+    pub(crate) modified: u64,
+    pub(crate) nonce: Vec<u8>,
+    pub(crate) body: Vec<u8>,
+    pub(crate) metadata: Option<SealedMetadata>,
+}
+
+/// An entry's metadata, encrypted under the metadata key.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct SealedMetadata {
+    pub(crate) nonce: Vec<u8>,
+    pub(crate) body: Vec<u8>,
+}
+
+/// The associated data an entry is sealed with.
+fn entry_aad(id: &str, modified: u64) -> Vec<u8> {
+    if modified == 0 {
+        return id.as_bytes().to_vec();
+    }
+    let mut aad = modified.to_be_bytes().to_vec();
+    aad.extend_from_slice(id.as_bytes());
+    aad
+}
+
+/// The associated data an entry's metadata is sealed with.
+pub(crate) fn metadata_aad(id: &str, modified: u64) -> Vec<u8> {
+    let mut aad = b"metadata:".to_vec();
+    aad.extend_from_slice(&entry_aad(id, modified));
+    aad
+}
+
+fn check_vault_version(version: u8) -> Result<u8, BVaultError> {
+    if version != VERSION_UNTIMED && version != VERSION_NO_METADATA {
+        check_version("vault", version, VERSION)?;
+    }
+    Ok(version)
+}
+
+/// A vault blob's contents, before the key is derived.
+pub(crate) struct Locked {
+    pub(crate) version: u8,
+    pub(crate) cipher: Cipher,
+    pub(crate) kdf: KdfParams,
+    pub(crate) salt: Vec<u8>,
+    pub(crate) kcv: Vec<u8>,
+    pub(crate) entries: BTreeMap<String, SealedEntry>,
+}
+
+impl Locked {
+    /// Writes the binary form.
+    pub(crate) fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(MAGIC);
+        out.push(self.version);
+        out.push(self.cipher.id());
+        write_kdf(&mut out, Some(&self.kdf));
+        // The salt length is bounded by `check_salt_length` or the parser.
+        out.push(self.salt.len() as u8);
+        out.extend_from_slice(&self.salt);
+        out.extend_from_slice(&self.kcv);
+
+        out.extend_from_slice(&(self.entries.len() as u32).to_be_bytes());
+        for (id, entry) in &self.entries {
+            out.push(id.len() as u8);
+            out.extend_from_slice(id.as_bytes());
+            if self.version != VERSION_UNTIMED {
+                out.extend_from_slice(&entry.modified.to_be_bytes());
+            }
+            out.push(entry.nonce.len() as u8);
+            out.extend_from_slice(&entry.nonce);
+            out.extend_from_slice(&(entry.body.len() as u32).to_be_bytes());
+            out.extend_from_slice(&entry.body);
+            if self.version == VERSION {
+                match &entry.metadata {
+                    Some(metadata) => {
+                        out.push(metadata.nonce.len() as u8);
+                        out.extend_from_slice(&metadata.nonce);
+                        out.extend_from_slice(&(metadata.body.len() as u32).to_be_bytes());
+                        out.extend_from_slice(&metadata.body);
+                    }
+                    None => out.push(0),
+                }
+            }
+        }
+        out
+    }
+
+    /// Writes the canonical CBOR form.
+    pub(crate) fn to_cbor(&self) -> Vec<u8> {
+        let entries = self
+            .entries
+            .iter()
+            .map(|(id, entry)| {
+                let mut items = vec![
+                    Value::Text(id.clone()),
+                    Value::Bytes(entry.nonce.clone()),
+                    Value::Bytes(entry.body.clone()),
+                ];
+                if self.version != VERSION_UNTIMED {
+                    items.push(entry.modified.into());
+                }
+                if let Some(metadata) = entry.metadata.as_ref().filter(|_| self.version == VERSION)
+                {
+                    items.push(Value::Bytes(metadata.nonce.clone()));
+                    items.push(Value::Bytes(metadata.body.clone()));
+                }
+                Value::Array(items)
+            })
+            .collect();
+        Value::map(vec![
+            (FIELD_MAGIC, Value::Text(text_magic(MAGIC))),
+            (FIELD_VERSION, self.version.into()),
+            (FIELD_CIPHER, self.cipher.id().into()),
+            (FIELD_KDF, kdf_to_cbor(&self.kdf)),
+            (FIELD_SALT, Value::Bytes(self.salt.clone())),
+            (FIELD_KCV, Value::Bytes(self.kcv.clone())),
+            (FIELD_ENTRIES, Value::Array(entries)),
+        ])
+        .to_bytes()
+    }
+
+    pub(crate) fn parse(blob: &[u8]) -> Result<Locked, BVaultError> {
+        if is_cbor(blob) {
+            Locked::parse_cbor(blob)
+        } else {
+            Locked::parse_binary(blob)
+        }
+    }
+
+    fn parse_binary(blob: &[u8]) -> Result<Locked, BVaultError> {
+        let mut reader = Reader::new(blob);
+
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err(invalid("not a bvault vault"));
+        }
+        let version = check_vault_version(reader.u8()?)?;
+        let cipher = cipher_from_id(reader.u8()?)?;
+        let kdf = read_kdf(&mut reader)?.ok_or_else(|| invalid("vault has no KDF"))?;
+        let salt_len = reader.u8()? as usize;
+        let salt = reader.take(salt_len)?.to_vec();
+        let kcv = reader.take(KCV_LENGTH)?.to_vec();
+
+        let mut entries = BTreeMap::new();
+        for _ in 0..reader.u32()? {
+            let id_len = reader.u8()? as usize;
+            let id = String::from_utf8(reader.take(id_len)?.to_vec())
+                .map_err(|_| invalid("entry ID is not valid utf-8"))?;
+            let modified = if version == VERSION_UNTIMED {
+                0
+            } else {
+                let bytes = reader.take(8)?;
+                u64::from_be_bytes(bytes.try_into().expect("took 8 bytes"))
+            };
+            let nonce_len = reader.u8()? as usize;
+            let nonce = reader.take(nonce_len)?.to_vec();
+            check_nonce_length(cipher, &nonce)?;
+            let body_len = reader.u32()? as usize;
+            let body = reader.take(body_len)?.to_vec();
+            let metadata = match version {
+                VERSION => match reader.u8()? as usize {
+                    0 => None,
+                    nonce_len => {
+                        let nonce = reader.take(nonce_len)?.to_vec();
+                        check_nonce_length(cipher, &nonce)?;
+                        let body_len = reader.u32()? as usize;
+                        let body = reader.take(body_len)?.to_vec();
+                        Some(SealedMetadata { nonce, body })
+                    }
+                },
+                _ => None,
+            };
+            let entry = SealedEntry {
+                modified,
+                nonce,
+                body,
+                metadata,
+            };
+            if entries.insert(id, entry).is_some() {
+                return Err(invalid("duplicate entry ID"));
+            }
+        }
+        if !reader.rest().is_empty() {
+            return Err(invalid("trailing bytes after vault"));
+        }
+
+        Ok(Locked {
+            version,
+            cipher,
+            kdf,
+            salt,
+            kcv,
+            entries,
+        })
+    }
+
+    fn parse_cbor(blob: &[u8]) -> Result<Locked, BVaultError> {
+        let mut fields = Value::parse(blob)?.into_map()?;
+        if fields.require(FIELD_MAGIC)?.into_text()?.as_bytes() != MAGIC {
+            return Err(invalid("not a bvault vault"));
+        }
+        let version = check_vault_version(narrow(fields.require(FIELD_VERSION)?)?)?;
+        let cipher = cipher_from_id(narrow(fields.require(FIELD_CIPHER)?)?)?;
+        let kdf = kdf_from_cbor(fields.require(FIELD_KDF)?)?;
+        let salt = fields.require(FIELD_SALT)?.into_bytes()?;
+        check_salt_length(salt.len()).map_err(|_| invalid("salt is too long"))?;
+        let kcv = fields.require(FIELD_KCV)?.into_bytes()?;
+        if kcv.len() != KCV_LENGTH {
+            return Err(invalid("malformed key check value"));
+        }
+
+        let mut entries = BTreeMap::new();
+        for entry in fields.require(FIELD_ENTRIES)?.into_array()? {
+            let mut items = entry.into_array()?.into_iter();
+            let (id, nonce, body) = match (items.next(), items.next(), items.next()) {
+                (Some(id), Some(nonce), Some(body)) => {
+                    (id.into_text()?, nonce.into_bytes()?, body.into_bytes()?)
+                }
+                _ => return Err(invalid("vault entry must be [id, nonce, body, modified]")),
+            };
+            let malformed = || invalid("vault entry must be [id, nonce, body, modified]");
+            let modified = match (version, items.next()) {
+                (VERSION_UNTIMED, None) => 0,
+                (VERSION_UNTIMED, Some(_)) | (_, None) => return Err(malformed()),
+                (_, Some(modified)) => modified.into_uint()?,
+            };
+            let metadata = match (version, items.next(), items.next(), items.next()) {
+                (_, None, _, _) => None,
+                (VERSION, Some(nonce), Some(body), None) => {
+                    let nonce = nonce.into_bytes()?;
+                    check_nonce_length(cipher, &nonce)?;
+                    Some(SealedMetadata {
+                        nonce,
+                        body: body.into_bytes()?,
+                    })
+                }
+                _ => return Err(malformed()),
+            };
+            if id.len() > u8::MAX as usize {
+                return Err(invalid("entry ID is too long"));
+            }
+            check_nonce_length(cipher, &nonce)?;
+            // Canonical order: strictly ascending IDs, which also rules out
+            // duplicates.
+            if entries.keys().next_back().is_some_and(|last| *last >= id) {
+                return Err(invalid("vault entries are not in canonical order"));
+            }
+            entries.insert(
+                id,
+                SealedEntry {
+                    modified,
+                    nonce,
+                    body,
+                    metadata,
+                },
+            );
+        }
+        fields.finish()?;
+
+        Ok(Locked {
+            version,
+            cipher,
+            kdf,
+            salt,
+            kcv,
+            entries,
+        })
+    }
+}
+
+/// An unlocked vault: a set of encrypted entries under one master key.
+///
+/// The key is wiped when the vault is dropped; call `free()` from JS to
+/// lock it.
+#[wasm_bindgen]
+pub struct Vault {
+    key: Key,
+    version: u8,
+    salt: Vec<u8>,
+    kdf: KdfParams,
+    cipher: Cipher,
+    entries: BTreeMap<String, SealedEntry>,
+}
+
+#[wasm_bindgen]
+impl Vault {
+    /// Creates an empty vault protected by `password`.
+    ///
+    /// # Errors
+    ///
+    /// See [`crate::seal`].
+    pub fn create(password: &str, options: Option<SealOptions>) -> Result<Vault, BVaultError> {
+        let _operation = crate::utils::set_panic_hook();
+        let options = options.unwrap_or_default();
+        check_salt_length(options.salt_length())?;
+
+        let kdf = options.kdf();
+        let (salt, key) = new_salted_key(
+            password,
+            options.salt_length(),
+            &kdf,
+            Some(options.cipher()),
+        )?;
+        Ok(Vault {
+            key,
+            version: VERSION_NO_METADATA,
+            salt,
+            kdf,
+            cipher: options.cipher(),
+            entries: BTreeMap::new(),
+        })
+    }
+
+    /// Unlocks a vault blob produced by [`Vault::to_bytes`] or
+    /// [`Vault::to_cbor`].
+    ///
+    /// # Errors
+    ///
+    /// - If the blob is malformed or uses an unknown version, cipher or KDF,
+    ///   an error is returned.
+    /// - If its cipher or KDF is below the floor in `options`, an
+    ///   [`ErrorCode::BelowMinimum`] error is returned.
+    /// - If the password (or keyfile) is wrong, an
+    ///   [`ErrorCode::WrongPassword`] error is returned.
+    /// - If throttling is on and the blob has seen too many failed attempts,
+    ///   an [`ErrorCode::Throttled`] error is returned.
+    pub fn unlock(
+        blob: &[u8],
+        password: &str,
+        options: Option<OpenOptions>,
+    ) -> Result<Vault, BVaultError> {
+        let _operation = crate::utils::set_panic_hook();
+        let Locked {
+            version,
+            cipher,
+            kdf,
+            salt,
+            kcv,
+            entries,
+        } = Locked::parse(blob)?;
+
+        let options = options.unwrap_or_default();
+        options.check_floor(cipher, &kdf)?;
+        let kdf = match options.keyfile() {
+            Some(keyfile) => kdf.with_keyfile(keyfile),
+            None => kdf,
+        };
+        crate::policy::check_decrypt(password, salt.len(), &kdf);
+        let key = crate::throttle::attempt(blob, || {
+            let key = kdf.derive(password.as_bytes(), &salt)?;
+            if !kcv::matches(&key[..], &kcv)? {
+                return Err(kcv::wrong_password());
+            }
+            Ok(key)
+        })?;
+
+        Ok(Vault {
+            key,
+            version,
+            salt,
+            kdf,
+            cipher,
+            entries,
+        })
+    }
+
+    /// Encrypts `plaintext` as a new entry and returns its generated ID.
+    ///
+    /// # Errors
+    ///
+    /// - If the random number generator is unavailable, an error is returned.
+    pub fn add_entry(&mut self, plaintext: &[u8]) -> Result<String, BVaultError> {
+        let id = loop {
+            let id = crate::utils::to_hex(&random_bytes(ID_BYTES)?);
+            if !self.entries.contains_key(&id) {
+                break id;
+            }
+        };
+        self.put_entry(&id, plaintext, None, crate::utils::now_ms() as u64)?;
+        Ok(id)
+    }
+
+    /// [`Vault::add_entry`], with `metadata` sealed separately so that
+    /// [`crate::VaultMetadata`] can read it without the entry.
+    ///
+    /// # Errors
+    ///
+    /// See [`Vault::add_entry`].
+    pub fn add_entry_with_metadata(
+        &mut self,
+        plaintext: &[u8],
+        metadata: &[u8],
+    ) -> Result<String, BVaultError> {
+        let id = self.add_entry(plaintext)?;
+        self.set_entry_metadata(&id, Some(metadata.to_vec()))?;
+        Ok(id)
+    }
+
+    /// Decrypts the metadata of the entry with the given ID; `undefined` if
+    /// it has none.
+    ///
+    /// # Errors
+    ///
+    /// - If there is no such entry, an error is returned.
+    /// - If the metadata fails authentication, an error is returned.
+    pub fn get_entry_metadata(&self, id: &str) -> Result<Option<Vec<u8>>, BVaultError> {
+        let entry = self.entries.get(id).ok_or_else(|| not_found(id))?;
+        self.open_entry_metadata(id, entry)
+    }
+
+    /// Replaces the metadata of an existing entry, or removes it if
+    /// `metadata` is `undefined`. This counts as a modification: the entry
+    /// is resealed under a new timestamp.
+    ///
+    /// # Errors
+    ///
+    /// - If there is no such entry, an error is returned.
+    /// - If the entry fails authentication, an error is returned.
+    pub fn set_entry_metadata(
+        &mut self,
+        id: &str,
+        metadata: Option<Vec<u8>>,
+    ) -> Result<(), BVaultError> {
+        let metadata = metadata.map(Zeroizing::new);
+        let plaintext = Zeroizing::new(self.get_entry(id)?);
+        self.put_entry(
+            id,
+            &plaintext,
+            metadata.as_ref().map(|metadata| &metadata[..]),
+            crate::utils::now_ms() as u64,
+        )
+    }
+
+    /// The key entry metadata is sealed under, for
+    /// [`crate::VaultMetadata::open_with_key`]. It opens no entry, so an app
+    /// can keep it while the vault is locked to go on listing entries.
+    ///
+    /// # Errors
+    ///
+    /// - If the key derivation fails, an error is returned.
+    pub fn metadata_key(&self) -> Result<Vec<u8>, BVaultError> {
+        Ok(self.metadata_subkey()?.to_vec())
+    }
+
+    /// Decrypts the entry with the given ID.
+    ///
+    /// # Errors
+    ///
+    /// - If there is no such entry, an error is returned.
+    /// - If the entry fails authentication, an error is returned.
+    pub fn get_entry(&self, id: &str) -> Result<Vec<u8>, BVaultError> {
+        let entry = self.entries.get(id).ok_or_else(|| not_found(id))?;
+        self.open_entry(id, entry)
+    }
+
+    /// [`Vault::get_entry`], returning the plaintext as a
+    /// [`crate::Secret`] that stays in WASM memory.
+    ///
+    /// # Errors
+    ///
+    /// See [`Vault::get_entry`].
+    pub fn get_entry_secret(&self, id: &str) -> Result<crate::Secret, BVaultError> {
+        Ok(crate::secret::Secret::new(self.get_entry(id)?))
+    }
+
+    /// When the entry with the given ID was last added or updated, in ms
+    /// since the Unix epoch; 0 for entries from vaults that predate
+    /// timestamps.
+    ///
+    /// # Errors
+    ///
+    /// - If there is no such entry, an error is returned.
+    pub fn entry_modified(&self, id: &str) -> Result<f64, BVaultError> {
+        let entry = self.entries.get(id).ok_or_else(|| not_found(id))?;
+        Ok(entry.modified as f64)
+    }
+
+    /// Replaces the plaintext of an existing entry, under a fresh nonce.
+    /// Its metadata, if any, is kept.
+    ///
+    /// # Errors
+    ///
+    /// - If there is no such entry, an error is returned.
+    /// - If the random number generator is unavailable, an error is returned.
+    pub fn update_entry(&mut self, id: &str, plaintext: &[u8]) -> Result<(), BVaultError> {
+        let metadata = self.get_entry_metadata(id)?.map(Zeroizing::new);
+        self.put_entry(
+            id,
+            plaintext,
+            metadata.as_ref().map(|metadata| &metadata[..]),
+            crate::utils::now_ms() as u64,
+        )
+    }
+
+    /// Removes an entry.
+    ///
+    /// # Errors
+    ///
+    /// - If there is no such entry, an error is returned.
+    pub fn delete_entry(&mut self, id: &str) -> Result<(), BVaultError> {
+        self.entries
+            .remove(id)
+            .map(|_| ())
+            .ok_or_else(|| not_found(id))
+    }
+
+    /// The IDs of all entries, in sorted order.
+    pub fn list_ids(&self) -> Vec<String> {
+        self.entries.keys().cloned().collect()
+    }
+
+    /// Serializes the vault, entries still encrypted, into a binary blob.
+    ///
+    /// # Errors
+    ///
+    /// - If the key check value cannot be computed, an error is returned.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, BVaultError> {
+        Ok(self.locked()?.to_bytes())
+    }
+
+    /// Serializes the vault, entries still encrypted, as canonical CBOR.
+    ///
+    /// The same vault state always yields the same bytes.
+    ///
+    /// # Errors
+    ///
+    /// See [`Vault::to_bytes`].
+    pub fn to_cbor(&self) -> Result<Vec<u8>, BVaultError> {
+        Ok(self.locked()?.to_cbor())
+    }
+}
+
+impl Vault {
+    /// The serializable state: everything but the key itself.
+    pub(crate) fn locked(&self) -> Result<Locked, BVaultError> {
+        Ok(Locked {
+            version: self.version,
+            cipher: self.cipher,
+            kdf: self.kdf.clone(),
+            salt: self.salt.clone(),
+            kcv: kcv::compute(&self.key[..])?.to_vec(),
+            entries: self.entries.clone(),
+        })
+    }
+
+    /// Fails with [`ErrorCode::WrongPassword`] unless `password` (with the
+    /// keyfile the vault was unlocked with, if any) derives its key.
+    pub(crate) fn check_password(&self, password: &str) -> Result<(), BVaultError> {
+        let key = self.kdf.derive(password.as_bytes(), &self.salt)?;
+        if !crate::constant_time_eq(&key[..], &self.key[..]) {
+            return Err(kcv::wrong_password());
+        }
+        Ok(())
+    }
+
+    /// A key for the purpose `info`, derived from the master key.
+    pub(crate) fn subkey(&self, info: &[u8]) -> Result<Key, BVaultError> {
+        let mut key = Key::default();
+        hkdf_sha256(&self.key[..], None, info, &mut key[..])?;
+        Ok(key)
+    }
+
+    /// The entries, still encrypted.
+    pub(crate) fn entries(&self) -> &BTreeMap<String, SealedEntry> {
+        &self.entries
+    }
+
+    pub(crate) fn cipher(&self) -> Cipher {
+        self.cipher
+    }
+
+    /// The key entry metadata is sealed under.
+    pub(crate) fn metadata_subkey(&self) -> Result<Key, BVaultError> {
+        self.subkey(METADATA_KEY_INFO)
+    }
+
+    /// Decrypts `entry`, stored under `id`.
+    pub(crate) fn open_entry(&self, id: &str, entry: &SealedEntry) -> Result<Vec<u8>, BVaultError> {
+        self.cipher.decrypt_with_aad(
+            &self.key[..],
+            &entry.nonce,
+            &entry.body,
+            &entry_aad(id, entry.modified),
+        )
+    }
+
+    /// Decrypts the metadata of `entry`, stored under `id`, if it has any.
+    pub(crate) fn open_entry_metadata(
+        &self,
+        id: &str,
+        entry: &SealedEntry,
+    ) -> Result<Option<Vec<u8>>, BVaultError> {
+        if entry.metadata.is_none() {
+            return Ok(None);
+        }
+        open_metadata(&self.metadata_subkey()?[..], self.cipher, id, entry)
+    }
+
+    /// Encrypts `plaintext` (and `metadata`, if any) under fresh nonces
+    /// and stores it as `id`, last modified at `modified`. A timestamped
+    /// entry makes this at least a version 2 vault, and metadata a version 3
+    /// one.
+    pub(crate) fn put_entry(
+        &mut self,
+        id: &str,
+        plaintext: &[u8],
+        metadata: Option<&[u8]>,
+        modified: u64,
+    ) -> Result<(), BVaultError> {
+        let plaintext = Zeroizing::new(plaintext.to_vec());
+        let nonce = random_bytes(self.cipher.nonce_length())?;
+        let body = self.cipher.encrypt_with_aad(
+            &self.key[..],
+            &nonce,
+            &plaintext,
+            &entry_aad(id, modified),
+        )?;
+        let metadata = match metadata {
+            Some(metadata) => {
+                let nonce = random_bytes(self.cipher.nonce_length())?;
+                let body = self.cipher.encrypt_with_aad(
+                    &self.metadata_subkey()?[..],
+                    &nonce,
+                    metadata,
+                    &metadata_aad(id, modified),
+                )?;
+                Some(SealedMetadata { nonce, body })
+            }
+            None => None,
+        };
+        if metadata.is_some() {
+            self.version = VERSION;
+        } else if modified != 0 {
+            self.version = self.version.max(VERSION_NO_METADATA);
+        }
+        self.entries.insert(
+            id.to_string(),
+            SealedEntry {
+                modified,
+                nonce,
+                body,
+                metadata,
+            },
+        );
+        Ok(())
+    }
+
+    /// Removes the entry `id`, if there is one.
+    pub(crate) fn remove_entry(&mut self, id: &str) {
+        self.entries.remove(id);
+    }
+}
+
+/// Decrypts the metadata of `entry`, stored under `id`, with the metadata
+/// key `key`.
+pub(crate) fn open_metadata(
+    key: &[u8],
+    cipher: Cipher,
+    id: &str,
+    entry: &SealedEntry,
+) -> Result<Option<Vec<u8>>, BVaultError> {
+    entry
+        .metadata
+        .as_ref()
+        .map(|metadata| {
+            cipher.decrypt_with_aad(
+                key,
+                &metadata.nonce,
+                &metadata.body,
+                &metadata_aad(id, entry.modified),
+            )
+        })
+        .transpose()
+}
+
+pub(crate) fn not_found(id: &str) -> BVaultError {
+    BVaultError::new(ErrorCode::EntryNotFound, format!("no entry with ID {}", id))
+}
@@ -0,0 +1,262 @@
+//! Deterministic test vectors, for keeping other implementations of the
+//! formats byte-compatible with this one.
+//!
+//! [`generate_test_vectors`] returns JSON with hex byte strings. Inputs
+//! (salts, nonces, keys) are derived from a fixed seed with HKDF-SHA256, so
+//! the output is identical on every run and build; implementations only
+//! need to read the inputs from the vectors, not reproduce them:
+//!
+//! ```text
+//! {
+//!   "format": "bvault-test-vectors",
+//!   "version": 1,
+//!   "kdf": [ { "kdf": { "algorithm": …, … }, "password": "…",
+//!              "salt": "<hex>", "key": "<hex>" } ],
+//!   "cipher": [ { "cipher": "aes-256-gcm", "key": "<hex>", "nonce": "<hex>",
+//!                 "aad": "<hex>", "plaintext": "<hex>", "ciphertext": "<hex>" } ],
+//!   "envelope": [ { "version": 2, "cipher": …, "kdf": { … }, "password": "…",
+//!                   "key_check": true, "padding": "none" | "padme",
+//!                   "aad": "<hex>", "plaintext": "<hex>",
+//!                   "envelope": "<hex>", "cbor": "<hex>" } ]
+//! }
+//! ```
+//!
+//! KDF and cipher names are those of the vault JSON export (see `json`).
+//! Suites missing from a build, such as Argon2id without the `argon2`
+//! feature, are left out. Compressed envelopes are not included: DEFLATE
+//! output differs between compressors, though every one must decompress it.
+
+use serde::Serialize;
+use wasm_bindgen::prelude::*;
+
+use crate::envelope::{seal_with_nonce, Envelope, VERSION, VERSION_UNBOUND};
+use crate::json::{CipherJson, KdfJson};
+use crate::kdf::{hkdf_sha256, KEY_LENGTH};
+use crate::utils::to_hex;
+use crate::{BVaultError, Cipher, ErrorCode, KdfParams, Padding, SealOptions};
+
+const FORMAT: &str = "bvault-test-vectors";
+const VERSION_VECTORS: u8 = 1;
+
+/// Input keying material for every generated input.
+const SEED: &[u8] = b"bvault test vectors";
+
+const PASSWORD: &str = "correct horse battery staple";
+const PLAINTEXT: &[u8] = b"The quick brown fox jumps over the lazy dog";
+const AAD: &[u8] = b"vault:42";
+
+const CIPHERS: [Cipher; 5] = [
+    Cipher::Aes256Gcm,
+    Cipher::ChaCha20Poly1305,
+    Cipher::XChaCha20Poly1305,
+    Cipher::Aes256GcmSiv,
+    Cipher::Cascade,
+];
+
+#[derive(Serialize)]
+struct Vectors {
+    format: &'static str,
+    version: u8,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    kdf: Option<Vec<KdfVector>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cipher: Option<Vec<CipherVector>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    envelope: Option<Vec<EnvelopeVector>>,
+}
+
+#[derive(Serialize)]
+struct KdfVector {
+    kdf: KdfJson,
+    password: &'static str,
+    salt: String,
+    key: String,
+}
+
+#[derive(Serialize)]
+struct CipherVector {
+    cipher: CipherJson,
+    key: String,
+    nonce: String,
+    aad: String,
+    plaintext: String,
+    ciphertext: String,
+}
+
+#[derive(Serialize)]
+struct EnvelopeVector {
+    version: u8,
+    cipher: CipherJson,
+    kdf: KdfJson,
+    password: &'static str,
+    key_check: bool,
+    padding: &'static str,
+    aad: String,
+    plaintext: String,
+    envelope: String,
+    cbor: String,
+}
+
+/// `length` bytes for the input named `label`.
+fn fixed(label: &str, length: usize) -> Vec<u8> {
+    let mut out = vec![0; length];
+    hkdf_sha256(SEED, None, label.as_bytes(), &mut out).expect("inputs are short");
+    out
+}
+
+/// `result`, or `None` if this build lacks the algorithm.
+fn if_supported<T>(result: Result<T, BVaultError>) -> Result<Option<T>, BVaultError> {
+    match result {
+        Ok(value) => Ok(Some(value)),
+        Err(err) if err.code() == ErrorCode::UnsupportedAlgorithm => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+fn kdf_vectors() -> Result<Vec<KdfVector>, BVaultError> {
+    let kdfs = [
+        KdfParams::pbkdf2(10_000),
+        KdfParams::pbkdf2_sha512(10_000),
+        KdfParams::argon2id(Some(8 * 1024), Some(1), Some(1)),
+        KdfParams::scrypt(Some(14), Some(8), Some(1)),
+    ];
+    let mut vectors = Vec::new();
+    for (i, kdf) in kdfs.iter().enumerate() {
+        let salt = fixed(&format!("kdf salt {}", i), 16);
+        if let Some(key) = if_supported(kdf.derive(PASSWORD.as_bytes(), &salt))? {
+            vectors.push(KdfVector {
+                kdf: kdf.into(),
+                password: PASSWORD,
+                salt: to_hex(&salt),
+                key: to_hex(&key[..]),
+            });
+        }
+    }
+    Ok(vectors)
+}
+
+fn cipher_vectors() -> Result<Vec<CipherVector>, BVaultError> {
+    let mut vectors = Vec::new();
+    for cipher in CIPHERS {
+        let key = fixed(&format!("cipher key {}", cipher.id()), KEY_LENGTH);
+        let nonce = fixed(
+            &format!("cipher nonce {}", cipher.id()),
+            cipher.nonce_length(),
+        );
+        let ciphertext = cipher.encrypt_with_aad(&key, &nonce, PLAINTEXT, AAD);
+        if let Some(ciphertext) = if_supported(ciphertext)? {
+            vectors.push(CipherVector {
+                cipher: cipher.into(),
+                key: to_hex(&key),
+                nonce: to_hex(&nonce),
+                aad: to_hex(AAD),
+                plaintext: to_hex(PLAINTEXT),
+                ciphertext: to_hex(&ciphertext),
+            });
+        }
+    }
+    Ok(vectors)
+}
+
+fn envelope_vectors() -> Result<Vec<EnvelopeVector>, BVaultError> {
+    // Version 1 has no associated data; version 2 is shown with and
+    // without the optional header fields.
+    let mut cases = Vec::new();
+    for cipher in CIPHERS {
+        cases.push((VERSION_UNBOUND, cipher, false, Padding::None, &[][..]));
+        cases.push((VERSION, cipher, true, Padding::None, AAD));
+    }
+    cases.push((VERSION, Cipher::default(), false, Padding::Padme, &[][..]));
+
+    let kdf = KdfParams::pbkdf2(10_000);
+    let mut vectors = Vec::new();
+    for (i, (version, cipher, key_check, padding, aad)) in cases.into_iter().enumerate() {
+        let salt = fixed(&format!("envelope salt {}", i), 16);
+        let nonce = fixed(&format!("envelope nonce {}", i), cipher.nonce_length());
+        let key = kdf.derive(PASSWORD.as_bytes(), &salt)?;
+        let mut options = SealOptions::for_cipher(cipher);
+        options.set_key_check(key_check);
+        options.set_padding(padding);
+        options.set_aad(aad);
+        let envelope = seal_with_nonce(
+            version,
+            Some(kdf.clone()),
+            salt,
+            nonce,
+            &key[..],
+            &options,
+            PLAINTEXT,
+        );
+        if let Some(envelope) = if_supported(envelope)? {
+            vectors.push(envelope_vector(&envelope, &kdf, key_check, padding, aad));
+        }
+    }
+    Ok(vectors)
+}
+
+fn envelope_vector(
+    envelope: &Envelope,
+    kdf: &KdfParams,
+    key_check: bool,
+    padding: Padding,
+    aad: &[u8],
+) -> EnvelopeVector {
+    EnvelopeVector {
+        version: envelope.version,
+        cipher: envelope.cipher.into(),
+        kdf: kdf.into(),
+        password: PASSWORD,
+        key_check,
+        padding: match padding {
+            Padding::Padme => "padme",
+            _ => "none",
+        },
+        aad: to_hex(aad),
+        plaintext: to_hex(PLAINTEXT),
+        envelope: to_hex(&envelope.to_bytes()),
+        cbor: to_hex(&envelope.to_cbor()),
+    }
+}
+
+/// Generates the test vectors of `suite`: `"kdf"`, `"cipher"`,
+/// `"envelope"` or `"all"`, as JSON. The output is deterministic, so it can
+/// be checked in alongside other implementations and regenerated to detect
+/// format changes.
+///
+/// # Errors
+///
+/// - If `suite` is not one of the above, an [`ErrorCode::InvalidArgument`]
+///   error is returned.
+#[wasm_bindgen]
+pub fn generate_test_vectors(suite: &str) -> Result<String, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    let (kdf, cipher, envelope) = match suite {
+        "kdf" => (true, false, false),
+        "cipher" => (false, true, false),
+        "envelope" => (false, false, true),
+        "all" => (true, true, true),
+        _ => {
+            return Err(BVaultError::new(
+                ErrorCode::InvalidArgument,
+                format!("unknown test vector suite {:?}", suite),
+            ))
+        }
+    };
+    let vectors = Vectors {
+        format: FORMAT,
+        version: VERSION_VECTORS,
+        kdf: if kdf { Some(kdf_vectors()?) } else { None },
+        cipher: if cipher {
+            Some(cipher_vectors()?)
+        } else {
+            None
+        },
+        envelope: if envelope {
+            Some(envelope_vectors()?)
+        } else {
+            None
+        },
+    };
+    serde_json::to_string_pretty(&vectors)
+        .map_err(|e| BVaultError::new(ErrorCode::InvalidJson, e.to_string()))
+}
@@ -0,0 +1,64 @@
+//! Handing derived keys to WebCrypto.
+//!
+//! The KDF stays in Rust, so every client derives keys from the same
+//! parameters, while bulk encryption of large payloads can run natively in
+//! `SubtleCrypto`. Exported key material leaves WASM memory for good: the
+//! caller is responsible for not persisting it.
+
+use wasm_bindgen::prelude::*;
+
+use crate::{b64_to_bytes, derive_key, BVaultError, KdfParams};
+
+/// Derives the 32-byte key for `password` and returns it as raw bytes,
+/// ready for `crypto.subtle.importKey("raw", ...)`.
+///
+/// The key is the one every `encrypt_*`/`decrypt_*` function would derive
+/// from the same salt and `kdf`.
+///
+/// # Errors
+///
+/// - If the salt is invalid base64, an error is returned.
+/// - If the KDF parameters are below the accepted minimum, an error is
+///   returned.
+#[wasm_bindgen]
+pub fn derive_key_raw(
+    password: &str,
+    b64_salt: &str,
+    kdf: Option<KdfParams>,
+) -> Result<Vec<u8>, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    let key = derive_key(password, &b64_to_bytes(b64_salt)?, kdf)?;
+    Ok(key.to_vec())
+}
+
+/// Like [`derive_key_raw`], but returns the key as an AES-256-GCM JSON Web
+/// Key object (`{ kty: "oct", alg: "A256GCM", k, ext, key_ops }`), ready for
+/// `crypto.subtle.importKey("jwk", ...)`.
+///
+/// # Errors
+///
+/// See [`derive_key_raw`].
+#[wasm_bindgen]
+pub fn derive_key_jwk(
+    password: &str,
+    b64_salt: &str,
+    kdf: Option<KdfParams>,
+) -> Result<js_sys::Object, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    let key = derive_key(password, &b64_to_bytes(b64_salt)?, kdf)?;
+    let k = base64::Engine::encode(&base64::engine::general_purpose::URL_SAFE_NO_PAD, &key[..]);
+
+    let key_ops = js_sys::Array::of2(&"encrypt".into(), &"decrypt".into());
+    let jwk = js_sys::Object::new();
+    for (name, value) in [
+        ("kty", JsValue::from_str("oct")),
+        ("alg", JsValue::from_str("A256GCM")),
+        ("k", JsValue::from_str(&k)),
+        ("ext", JsValue::TRUE),
+        ("key_ops", key_ops.into()),
+    ] {
+        js_sys::Reflect::set(&jwk, &JsValue::from_str(name), &value)
+            .expect("setting a property on a plain object cannot fail");
+    }
+    Ok(jwk)
+}
@@ -0,0 +1,196 @@
+//! Web Streams bindings for [`crate::ChunkedEncryptor`] and
+//! [`crate::ChunkedDecryptor`], so a file can be piped from `fetch()` or a
+//! `Blob` through encryption or decryption to an upload or a download
+//! without buffering it whole:
+//!
+//! ```text
+//! const response = await fetch(url);
+//! const plain = await decrypt_stream(response.body, password);
+//! await plain.pipeTo(writable);
+//! ```
+//!
+//! Inputs are a `ReadableStream` of `Uint8Array` chunks or a `Blob`; the
+//! outputs are `ReadableStream`s of `Uint8Array`s. An error, including a
+//! failed authentication or a truncated file, errors the output stream.
+//! Each function takes an optional `AbortSignal`, checked before every
+//! chunk; aborting errors the output with an [`ErrorCode::Aborted`] error.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use futures_util::stream::{self, Stream, StreamExt};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use wasm_streams::ReadableStream;
+use zeroize::Zeroizing;
+
+use crate::cancel::{self, AbortSignal};
+use crate::chunked::header_complete;
+use crate::{BVaultError, ChunkedDecryptor, ChunkedEncryptor, ErrorCode, OpenOptions};
+
+/// Most bytes read while waiting for a header to parse; real headers are
+/// under 320 bytes.
+const MAX_HEADER_LENGTH: usize = 1024;
+
+fn bad_input() -> BVaultError {
+    BVaultError::new(
+        ErrorCode::InvalidArgument,
+        "input must be a ReadableStream or a Blob",
+    )
+}
+
+/// `input` as a stream of byte chunks.
+fn chunks(input: JsValue) -> Result<impl Stream<Item = Result<Vec<u8>, JsValue>>, BVaultError> {
+    let raw = match input.dyn_into::<web_sys::Blob>() {
+        Ok(blob) => blob.stream(),
+        Err(input) => input
+            .dyn_into::<web_sys::ReadableStream>()
+            .map_err(|_| bad_input())?,
+    };
+    Ok(ReadableStream::from_raw(raw).into_stream().map(|chunk| {
+        let chunk = chunk?;
+        let chunk = chunk.dyn_ref::<js_sys::Uint8Array>().ok_or_else(|| {
+            JsValue::from(BVaultError::new(
+                ErrorCode::InvalidArgument,
+                "stream chunks must be Uint8Arrays",
+            ))
+        })?;
+        Ok(chunk.to_vec())
+    }))
+}
+
+fn output(bytes: Vec<u8>) -> JsValue {
+    js_sys::Uint8Array::from(&bytes[..]).into()
+}
+
+/// Encrypts `input`, a `ReadableStream` or `Blob`, with `encryptor`. The
+/// output starts with the header, so it is a complete chunked file. The
+/// encryptor is consumed, and `signal` cancels the encryption.
+///
+/// # Errors
+///
+/// - If `input` is neither a `ReadableStream` nor a `Blob`, an
+///   [`ErrorCode::InvalidArgument`] error is returned.
+/// - Errors while streaming error the output stream.
+#[wasm_bindgen]
+pub fn encrypt_stream(
+    input: JsValue,
+    encryptor: ChunkedEncryptor,
+    signal: Option<AbortSignal>,
+) -> Result<web_sys::ReadableStream, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    let input = chunks(input)?;
+    let header = encryptor.header();
+    let encryptor = Rc::new(RefCell::new(encryptor));
+    let body = {
+        let encryptor = Rc::clone(&encryptor);
+        let signal = signal.clone();
+        input.map(move |chunk| {
+            let chunk = Zeroizing::new(chunk?);
+            cancel::check(signal.as_ref())?;
+            Ok(output(encryptor.borrow_mut().push(&chunk)?))
+        })
+    };
+    let last = stream::once(async move {
+        cancel::check(signal.as_ref())?;
+        Ok(output(encryptor.borrow_mut().finish()?))
+    });
+    let encrypted = stream::once(async move { Ok(output(header)) })
+        .chain(body)
+        .chain(last);
+    Ok(ReadableStream::from_stream(crate::utils::stream_in_operation(encrypted)).into_raw())
+}
+
+/// Decrypts `input`, a chunked file as a `ReadableStream` or `Blob`, with
+/// `password`. Resolves once the header has arrived and the key is
+/// derived, so a wrong password rejects the promise; later errors error
+/// the output stream, which ends only after the final chunk
+/// authenticates. `signal` cancels the decryption.
+///
+/// # Errors
+///
+/// - If `input` is neither a `ReadableStream` nor a `Blob`, an
+///   [`ErrorCode::InvalidArgument`] error is returned.
+/// - Otherwise see [`ChunkedDecryptor::new`].
+#[wasm_bindgen]
+pub async fn decrypt_stream(
+    input: JsValue,
+    password: String,
+    options: Option<OpenOptions>,
+    signal: Option<AbortSignal>,
+) -> Result<web_sys::ReadableStream, BVaultError> {
+    crate::utils::in_operation(async move {
+        let password = Zeroizing::new(password);
+        decrypt_with(input, signal, |header| {
+            ChunkedDecryptor::new(header, &password, options)
+        })
+        .await
+    })
+    .await
+}
+
+/// [`decrypt_stream`] for a file encrypted under a raw 32-byte key.
+///
+/// # Errors
+///
+/// - If `input` is neither a `ReadableStream` nor a `Blob`, an
+///   [`ErrorCode::InvalidArgument`] error is returned.
+/// - Otherwise see [`ChunkedDecryptor::with_raw_key`].
+#[wasm_bindgen]
+pub async fn decrypt_stream_with_raw_key(
+    input: JsValue,
+    key: Vec<u8>,
+    signal: Option<AbortSignal>,
+) -> Result<web_sys::ReadableStream, BVaultError> {
+    crate::utils::in_operation(async move {
+        let key = Zeroizing::new(key);
+        decrypt_with(input, signal, |header| {
+            ChunkedDecryptor::with_raw_key(header, &key)
+        })
+        .await
+    })
+    .await
+}
+
+/// Reads the header off `input`, opens it with `open` and decrypts the
+/// rest.
+async fn decrypt_with(
+    input: JsValue,
+    signal: Option<AbortSignal>,
+    open: impl FnOnce(&[u8]) -> Result<ChunkedDecryptor, BVaultError>,
+) -> Result<web_sys::ReadableStream, BVaultError> {
+    let mut input = Box::pin(chunks(input)?);
+    let mut start = Vec::new();
+    // Read until the header parses, or cannot: `open` then reports why.
+    while !header_complete(&start) && start.len() < MAX_HEADER_LENGTH {
+        cancel::check(signal.as_ref())?;
+        match input.next().await {
+            Some(chunk) => start.extend_from_slice(&chunk.map_err(|_| {
+                BVaultError::new(ErrorCode::InvalidArgument, "input stream failed")
+            })?),
+            None => break,
+        }
+    }
+    let decryptor = open(&start)?;
+    cancel::check(signal.as_ref())?;
+
+    let rest = start.split_off(decryptor.header_length() as usize);
+    let decryptor = Rc::new(RefCell::new(decryptor));
+    let body = {
+        let decryptor = Rc::clone(&decryptor);
+        let signal = signal.clone();
+        stream::once(async move { Ok::<_, JsValue>(rest) })
+            .chain(input)
+            .map(move |chunk| {
+                let chunk = chunk?;
+                cancel::check(signal.as_ref())?;
+                Ok(output(decryptor.borrow_mut().push(&chunk)?))
+            })
+    };
+    let last = stream::once(async move {
+        cancel::check(signal.as_ref())?;
+        Ok(output(decryptor.borrow_mut().finish()?))
+    });
+    let decrypted = crate::utils::stream_in_operation(body.chain(last));
+    Ok(ReadableStream::from_stream(decrypted).into_raw())
+}
@@ -0,0 +1,300 @@
+//! An API surface for driving the module from a Web Worker.
+//!
+//! Class instances such as [`SealOptions`] or [`BVaultError`] are handles
+//! into this module's memory, so they cannot be posted between threads.
+//! The functions here take and return only `ArrayBuffer`s (which can be
+//! transferred without copying), strings, numbers and plain descriptor
+//! objects, all structured-cloneable:
+//!
+//! ```text
+//! seal options  { cipher?: "aes-256-gcm" | "chacha20-poly1305"
+//!                        | "xchacha20-poly1305" | "aes-256-gcm-siv"
+//!                        | "aes-256-gcm+xchacha20-poly1305",
+//!                 kdf?: { algorithm: "pbkdf2-sha256", iterations } | …,
+//!                 aad?: ArrayBuffer, keyfile?: ArrayBuffer }
+//! open options  { aad?: ArrayBuffer, keyfile?: ArrayBuffer }
+//! ```
+//!
+//! KDF descriptors are those of the vault JSON export (see `json`).
+//! Typed arrays are accepted wherever an `ArrayBuffer` is.
+//!
+//! [`process_message`] dispatches a whole request, so a worker needs no
+//! per-function glue:
+//!
+//! ```text
+//! onmessage = ({ data }) => {
+//!   const reply = process_message(data.op, data.payload);
+//!   postMessage(reply, reply.ok ? [reply.result] : []);
+//! };
+//! ```
+
+use js_sys::{ArrayBuffer, Object, Reflect, Uint8Array};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use zeroize::Zeroizing;
+
+use crate::json::{CipherJson, KdfJson};
+use crate::{
+    hash, open, open_with_raw_key, random_bytes, seal, seal_with_raw_key, BVaultError, Cipher,
+    ErrorCode, HashAlgorithm, KdfParams, OpenOptions, SealOptions,
+};
+
+fn invalid_argument(message: impl Into<String>) -> BVaultError {
+    BVaultError::new(ErrorCode::InvalidArgument, message)
+}
+
+fn to_buffer(bytes: &[u8]) -> ArrayBuffer {
+    Uint8Array::from(bytes).buffer()
+}
+
+/// The member `name` of `object`; `undefined` if `object` is not an object.
+fn member(object: &JsValue, name: &str) -> JsValue {
+    if !object.is_object() {
+        return JsValue::UNDEFINED;
+    }
+    Reflect::get(object, &JsValue::from_str(name)).unwrap_or(JsValue::UNDEFINED)
+}
+
+/// The bytes of an `ArrayBuffer` or typed array, or `None` if `value` is
+/// `undefined` or `null`.
+fn optional_bytes(value: &JsValue, what: &str) -> Result<Option<Vec<u8>>, BVaultError> {
+    if value.is_undefined() || value.is_null() {
+        return Ok(None);
+    }
+    if let Some(buffer) = value.dyn_ref::<ArrayBuffer>() {
+        return Ok(Some(Uint8Array::new(buffer).to_vec()));
+    }
+    if ArrayBuffer::is_view(value) {
+        let offset = member(value, "byteOffset").as_f64().unwrap_or(0.0) as u32;
+        let length = member(value, "byteLength").as_f64().unwrap_or(0.0) as u32;
+        let view =
+            Uint8Array::new_with_byte_offset_and_length(&member(value, "buffer"), offset, length);
+        return Ok(Some(view.to_vec()));
+    }
+    Err(invalid_argument(format!(
+        "{} must be an ArrayBuffer or a typed array",
+        what
+    )))
+}
+
+fn bytes(value: &JsValue, what: &str) -> Result<Vec<u8>, BVaultError> {
+    optional_bytes(value, what)?.ok_or_else(|| invalid_argument(format!("{} is missing", what)))
+}
+
+fn string(value: &JsValue, what: &str) -> Result<String, BVaultError> {
+    value
+        .as_string()
+        .ok_or_else(|| invalid_argument(format!("{} must be a string", what)))
+}
+
+/// Deserializes the descriptor member `name` of `options`, if present.
+fn descriptor<T: serde::de::DeserializeOwned>(
+    options: &JsValue,
+    name: &str,
+) -> Result<Option<T>, BVaultError> {
+    let value = member(options, name);
+    if value.is_undefined() || value.is_null() {
+        return Ok(None);
+    }
+    serde_wasm_bindgen::from_value(value)
+        .map(Some)
+        .map_err(|e| invalid_argument(format!("options.{}: {}", name, e)))
+}
+
+fn cipher(options: &JsValue) -> Result<Option<Cipher>, BVaultError> {
+    Ok(descriptor::<CipherJson>(options, "cipher")?.map(Cipher::from))
+}
+
+fn seal_options(options: &JsValue) -> Result<SealOptions, BVaultError> {
+    let mut seal_options = SealOptions::new();
+    if let Some(cipher) = cipher(options)? {
+        seal_options.set_cipher(cipher);
+    }
+    let kdf =
+        descriptor::<KdfJson>(options, "kdf")?.map_or_else(KdfParams::default, KdfParams::from);
+    let keyfile = optional_bytes(&member(options, "keyfile"), "options.keyfile")?;
+    seal_options.set_kdf(&match keyfile {
+        Some(keyfile) => kdf.with_keyfile(&keyfile),
+        None => kdf,
+    });
+    if let Some(aad) = optional_bytes(&member(options, "aad"), "options.aad")? {
+        seal_options.set_aad(&aad);
+    }
+    Ok(seal_options)
+}
+
+fn open_options(options: &JsValue) -> Result<OpenOptions, BVaultError> {
+    let mut open_options = OpenOptions::new();
+    if let Some(keyfile) = optional_bytes(&member(options, "keyfile"), "options.keyfile")? {
+        open_options = open_options.with_keyfile(&keyfile);
+    }
+    if let Some(aad) = optional_bytes(&member(options, "aad"), "options.aad")? {
+        open_options = open_options.with_aad(&aad);
+    }
+    Ok(open_options)
+}
+
+/// [`seal`] over `ArrayBuffer`s, with a seal options descriptor.
+///
+/// # Errors
+///
+/// - If a descriptor member has the wrong type, an
+///   [`ErrorCode::InvalidArgument`] error is returned.
+/// - Otherwise see [`seal`].
+#[wasm_bindgen]
+pub fn seal_buffer(
+    plaintext: &ArrayBuffer,
+    password: &str,
+    options: JsValue,
+) -> Result<ArrayBuffer, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    let plaintext = Zeroizing::new(Uint8Array::new(plaintext).to_vec());
+    let envelope = seal(&plaintext, password, Some(seal_options(&options)?))?;
+    Ok(to_buffer(&envelope))
+}
+
+/// [`open`] over `ArrayBuffer`s, with an open options descriptor.
+///
+/// # Errors
+///
+/// - If a descriptor member has the wrong type, an
+///   [`ErrorCode::InvalidArgument`] error is returned.
+/// - Otherwise see [`open`].
+#[wasm_bindgen]
+pub fn open_buffer(
+    envelope: &ArrayBuffer,
+    password: &str,
+    options: JsValue,
+) -> Result<ArrayBuffer, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    let envelope = Uint8Array::new(envelope).to_vec();
+    let plaintext = Zeroizing::new(open(&envelope, password, Some(open_options(&options)?))?);
+    Ok(to_buffer(&plaintext))
+}
+
+/// [`seal_with_raw_key`] over `ArrayBuffer`s, with a seal options
+/// descriptor; its `kdf` and `keyfile` are ignored.
+///
+/// # Errors
+///
+/// See [`seal_buffer`] and [`seal_with_raw_key`].
+#[wasm_bindgen]
+pub fn seal_buffer_with_raw_key(
+    plaintext: &ArrayBuffer,
+    key: &ArrayBuffer,
+    options: JsValue,
+) -> Result<ArrayBuffer, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    let plaintext = Zeroizing::new(Uint8Array::new(plaintext).to_vec());
+    let key = Zeroizing::new(Uint8Array::new(key).to_vec());
+    let envelope = seal_with_raw_key(&plaintext, &key, Some(seal_options(&options)?))?;
+    Ok(to_buffer(&envelope))
+}
+
+/// [`open_with_raw_key`] over `ArrayBuffer`s, with an open options
+/// descriptor; its `keyfile` is ignored.
+///
+/// # Errors
+///
+/// See [`open_with_raw_key`].
+#[wasm_bindgen]
+pub fn open_buffer_with_raw_key(
+    envelope: &ArrayBuffer,
+    key: &ArrayBuffer,
+    options: JsValue,
+) -> Result<ArrayBuffer, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    let envelope = Uint8Array::new(envelope).to_vec();
+    let key = Zeroizing::new(Uint8Array::new(key).to_vec());
+    let plaintext = Zeroizing::new(open_with_raw_key(
+        &envelope,
+        &key,
+        Some(open_options(&options)?),
+    )?);
+    Ok(to_buffer(&plaintext))
+}
+
+fn dispatch(op: &str, payload: &JsValue) -> Result<Vec<u8>, BVaultError> {
+    let field = |name: &str| member(payload, name);
+    let options = field("options");
+    match op {
+        "seal" => {
+            let plaintext = Zeroizing::new(bytes(&field("plaintext"), "plaintext")?);
+            let password = Zeroizing::new(string(&field("password"), "password")?);
+            seal(&plaintext, &password, Some(seal_options(&options)?))
+        }
+        "open" => open(
+            &bytes(&field("envelope"), "envelope")?,
+            &Zeroizing::new(string(&field("password"), "password")?),
+            Some(open_options(&options)?),
+        ),
+        "seal_with_raw_key" => seal_with_raw_key(
+            &Zeroizing::new(bytes(&field("plaintext"), "plaintext")?),
+            &Zeroizing::new(bytes(&field("key"), "key")?),
+            Some(seal_options(&options)?),
+        ),
+        "open_with_raw_key" => open_with_raw_key(
+            &bytes(&field("envelope"), "envelope")?,
+            &Zeroizing::new(bytes(&field("key"), "key")?),
+            Some(open_options(&options)?),
+        ),
+        "hash" => {
+            let algorithm = match string(&field("algorithm"), "algorithm")?.as_str() {
+                "sha256" => HashAlgorithm::Sha256,
+                "sha512" => HashAlgorithm::Sha512,
+                "blake3" => HashAlgorithm::Blake3,
+                other => {
+                    return Err(BVaultError::new(
+                        ErrorCode::UnsupportedAlgorithm,
+                        format!("unknown hash algorithm {}", other),
+                    ))
+                }
+            };
+            Ok(hash(algorithm, &bytes(&field("data"), "data")?).bytes())
+        }
+        "random_bytes" => {
+            let length = field("length")
+                .as_f64()
+                .filter(|length| length.fract() == 0.0 && *length >= 0.0)
+                .ok_or_else(|| invalid_argument("length must be a non-negative integer"))?;
+            random_bytes(length as usize)
+        }
+        _ => Err(invalid_argument(format!("unknown operation {}", op))),
+    }
+}
+
+/// Runs the operation `op` on a plain `payload` object and returns a plain
+/// reply object; errors are returned as data rather than thrown, since an
+/// error instance cannot be posted back.
+///
+/// | `op`                | payload                                 |
+/// |---------------------|-----------------------------------------|
+/// | `seal`              | `{ plaintext, password, options? }`     |
+/// | `open`              | `{ envelope, password, options? }`      |
+/// | `seal_with_raw_key` | `{ plaintext, key, options? }`          |
+/// | `open_with_raw_key` | `{ envelope, key, options? }`           |
+/// | `hash`              | `{ algorithm: "sha256" \| "sha512" \| "blake3", data }` |
+/// | `random_bytes`      | `{ length }`                            |
+///
+/// The reply is `{ ok: true, result: ArrayBuffer }` or `{ ok: false, code,
+/// message }`, with `code` an [`ErrorCode`] value.
+#[wasm_bindgen]
+pub fn process_message(op: &str, payload: JsValue) -> JsValue {
+    let _operation = crate::utils::set_panic_hook();
+    let reply = Object::new();
+    let set = |name: &str, value: JsValue| {
+        Reflect::set(&reply, &JsValue::from_str(name), &value).expect("reply is an object");
+    };
+    match dispatch(op, &payload) {
+        Ok(result) => {
+            set("ok", JsValue::TRUE);
+            set("result", to_buffer(&Zeroizing::new(result)).into());
+        }
+        Err(err) => {
+            set("ok", JsValue::FALSE);
+            set("code", JsValue::from(err.code() as u32));
+            set("message", JsValue::from_str(&err.message()));
+        }
+    }
+    reply.into()
+}
@@ -0,0 +1,233 @@
+//! X25519 key agreement, for sharing vault entries between users without
+//! sending the master password.
+//!
+//! [`seal_for_recipient`] authenticates the sender as well as encrypting to
+//! the recipient: the AEAD key comes from HKDF-SHA256 over two
+//! Diffie-Hellman results, one with a fresh ephemeral key and one with the
+//! sender's long-term key,
+//!
+//! ```text
+//! ikm  = DH(ephemeral, recipient) || DH(sender, recipient)
+//! salt = ephemeral public || sender public || recipient public
+//! key  = HKDF-SHA256(ikm, salt, "bvault-x25519-seal")
+//! ```
+//!
+//! so only the holder of the sender's secret key could have produced a
+//! message that opens under the sender's public key. The sealed message is
+//!
+//! ```text
+//! magic      4   "BVXS"
+//! version    1   currently 1
+//! cipher     1   Cipher ID
+//! ephemeral  32  ephemeral public key
+//! nonce      n   the cipher's nonce length
+//! body       rest ciphertext with the AEAD tag appended
+//! ```
+//!
+//! and everything before the body is the AEAD's associated data.
+
+use std::convert::TryInto;
+
+use wasm_bindgen::prelude::*;
+use x25519_dalek::{PublicKey, StaticSecret};
+use zeroize::Zeroizing;
+
+use crate::envelope::{check_version, cipher_from_id, invalid, Reader};
+use crate::kdf::{hkdf_sha256, Key, KEY_LENGTH};
+use crate::{random_bytes, BVaultError, Cipher, ErrorCode};
+
+const MAGIC: &[u8; 4] = b"BVXS";
+const VERSION: u8 = 1;
+const SEAL_INFO: &[u8] = b"bvault-x25519-seal";
+
+/// An X25519 key pair.
+#[wasm_bindgen]
+#[derive(Clone)]
+pub struct X25519KeyPair {
+    secret_key: Zeroizing<[u8; 32]>,
+    public_key: [u8; 32],
+}
+
+#[wasm_bindgen]
+impl X25519KeyPair {
+    /// The 32-byte secret key. Store it like a password.
+    #[wasm_bindgen(getter)]
+    pub fn secret_key(&self) -> Vec<u8> {
+        self.secret_key.to_vec()
+    }
+
+    /// The 32-byte public key, safe to publish.
+    #[wasm_bindgen(getter)]
+    pub fn public_key(&self) -> Vec<u8> {
+        self.public_key.to_vec()
+    }
+}
+
+pub(crate) fn secret_key(bytes: &[u8]) -> Result<StaticSecret, BVaultError> {
+    let bytes: [u8; 32] = bytes.try_into().map_err(|_| {
+        BVaultError::new(
+            ErrorCode::BadKeyLength,
+            "X25519 secret keys must be 32 bytes",
+        )
+    })?;
+    Ok(StaticSecret::from(bytes))
+}
+
+pub(crate) fn public_key(bytes: &[u8]) -> Result<PublicKey, BVaultError> {
+    let bytes: [u8; 32] = bytes.try_into().map_err(|_| {
+        BVaultError::new(
+            ErrorCode::BadKeyLength,
+            "X25519 public keys must be 32 bytes",
+        )
+    })?;
+    Ok(PublicKey::from(bytes))
+}
+
+/// DH(secret, public), rejecting low-order public keys.
+pub(crate) fn diffie_hellman(
+    secret: &StaticSecret,
+    public: &PublicKey,
+) -> Result<Zeroizing<[u8; 32]>, BVaultError> {
+    let shared = secret.diffie_hellman(public);
+    if !shared.was_contributory() {
+        return Err(BVaultError::new(
+            ErrorCode::InvalidArgument,
+            "X25519 public key is a low-order point",
+        ));
+    }
+    Ok(Zeroizing::new(shared.to_bytes()))
+}
+
+fn seal_key(
+    ephemeral_dh: &[u8],
+    static_dh: &[u8],
+    ephemeral: &PublicKey,
+    sender: &PublicKey,
+    recipient: &PublicKey,
+) -> Result<Key, BVaultError> {
+    let mut ikm = Zeroizing::new([0u8; 64]);
+    ikm[..32].copy_from_slice(ephemeral_dh);
+    ikm[32..].copy_from_slice(static_dh);
+    let mut salt = Vec::with_capacity(96);
+    salt.extend_from_slice(ephemeral.as_bytes());
+    salt.extend_from_slice(sender.as_bytes());
+    salt.extend_from_slice(recipient.as_bytes());
+
+    let mut key = Key::default();
+    hkdf_sha256(&ikm[..], Some(&salt), SEAL_INFO, &mut key[..])?;
+    Ok(key)
+}
+
+/// Generates a random X25519 key pair.
+///
+/// # Errors
+///
+/// - If the random number generator is unavailable, an error is returned.
+#[wasm_bindgen]
+pub fn generate_x25519_keypair() -> Result<X25519KeyPair, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    let bytes = Zeroizing::new(random_bytes(KEY_LENGTH)?);
+    let secret = secret_key(&bytes)?;
+    Ok(X25519KeyPair {
+        secret_key: Zeroizing::new(secret.to_bytes()),
+        public_key: PublicKey::from(&secret).to_bytes(),
+    })
+}
+
+/// The public key belonging to a 32-byte X25519 secret key.
+///
+/// # Errors
+///
+/// - If `secret_key` is not 32 bytes, an error is returned.
+#[wasm_bindgen]
+pub fn x25519_public_key(secret_key: &[u8]) -> Result<Vec<u8>, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    let secret = self::secret_key(secret_key)?;
+    Ok(PublicKey::from(&secret).to_bytes().to_vec())
+}
+
+/// Encrypts `plaintext` so that only the holder of `recipient_public_key`
+/// can open it, and only with `sender_secret_key`'s public key.
+///
+/// `cipher` defaults to AES-256-GCM.
+///
+/// # Errors
+///
+/// - If a key is not 32 bytes, or the recipient key is a low-order point,
+///   an error is returned.
+/// - If the random number generator is unavailable, an error is returned.
+#[wasm_bindgen]
+pub fn seal_for_recipient(
+    plaintext: &[u8],
+    sender_secret_key: &[u8],
+    recipient_public_key: &[u8],
+    cipher: Option<Cipher>,
+) -> Result<Vec<u8>, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    let cipher = cipher.unwrap_or_default();
+    let sender = secret_key(sender_secret_key)?;
+    let recipient = public_key(recipient_public_key)?;
+
+    let ephemeral_bytes = Zeroizing::new(random_bytes(KEY_LENGTH)?);
+    let ephemeral = secret_key(&ephemeral_bytes)?;
+    let ephemeral_public = PublicKey::from(&ephemeral);
+    let key = seal_key(
+        &diffie_hellman(&ephemeral, &recipient)?[..],
+        &diffie_hellman(&sender, &recipient)?[..],
+        &ephemeral_public,
+        &PublicKey::from(&sender),
+        &recipient,
+    )?;
+
+    let nonce = random_bytes(cipher.nonce_length())?;
+    let mut out = Vec::with_capacity(6 + 32 + nonce.len() + plaintext.len() + 16);
+    out.extend_from_slice(MAGIC);
+    out.push(VERSION);
+    out.push(cipher.id());
+    out.extend_from_slice(ephemeral_public.as_bytes());
+    out.extend_from_slice(&nonce);
+    let body = cipher.encrypt_with_aad(&key[..], &nonce, plaintext, &out)?;
+    out.extend_from_slice(&body);
+    Ok(out)
+}
+
+/// Decrypts a message from [`seal_for_recipient`], checking that it came
+/// from the holder of `sender_public_key`.
+///
+/// # Errors
+///
+/// - If a key is not 32 bytes, or the sender key is a low-order point, an
+///   error is returned.
+/// - If the message is malformed, an error is returned.
+/// - If the message was not sealed by this sender for this recipient, or
+///   was tampered with, an [`ErrorCode::AuthFailed`] error is returned.
+#[wasm_bindgen]
+pub fn open_from_sender(
+    sealed: &[u8],
+    recipient_secret_key: &[u8],
+    sender_public_key: &[u8],
+) -> Result<Vec<u8>, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    let recipient = secret_key(recipient_secret_key)?;
+    let sender = public_key(sender_public_key)?;
+
+    let mut reader = Reader::new(sealed);
+    if reader.take(MAGIC.len())? != MAGIC {
+        return Err(invalid("not an X25519 sealed message"));
+    }
+    check_version("sealed message", reader.u8()?, VERSION)?;
+    let cipher = cipher_from_id(reader.u8()?)?;
+    let ephemeral = public_key(reader.take(32)?)?;
+    let nonce = reader.take(cipher.nonce_length())?;
+    let body = reader.rest();
+    let header = &sealed[..sealed.len() - body.len()];
+
+    let key = seal_key(
+        &diffie_hellman(&recipient, &ephemeral)?[..],
+        &diffie_hellman(&recipient, &sender)?[..],
+        &ephemeral,
+        &sender,
+        &PublicKey::from(&recipient),
+    )?;
+    cipher.decrypt_with_aad(&key[..], nonce, body, header)
+}
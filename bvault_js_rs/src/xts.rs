@@ -0,0 +1,122 @@
+//! AES-256-XTS (IEEE 1619), for random-access block storage such as an
+//! encrypted filesystem on OPFS: each sector encrypts on its own under a
+//! tweak derived from its number, so one 4 KiB block can be rewritten
+//! without touching the rest of the file.
+//!
+//! XTS keeps ciphertexts the size of the plaintext, and pays for it: there
+//! is no authentication, so tampering goes unnoticed (though it garbles a
+//! whole 16-byte block instead of flipping chosen bits), and rewriting a
+//! sector shows which 16-byte blocks changed. Pair it with a MAC or an
+//! authenticated index of sector hashes where integrity matters.
+//!
+//! The 64-byte key is two AES-256 keys, for the data and the tweak, which
+//! must differ. The tweak of sector `n` is `n` as 16 little-endian bytes.
+//! Sectors that are not a multiple of 16 bytes use ciphertext stealing, but
+//! every sector, the last included, must be at least 16 bytes.
+
+use aes::cipher::KeyInit;
+use aes::Aes256;
+use subtle::ConstantTimeEq;
+use wasm_bindgen::prelude::*;
+use xts_mode::{get_tweak_default, Xts128};
+
+use crate::{BVaultError, ErrorCode};
+
+/// Length of an AES-256-XTS key: two AES-256 keys.
+const XTS_KEY_LENGTH: usize = 64;
+/// The AES block size, and the shortest sector.
+const BLOCK_SIZE: usize = 16;
+const DEFAULT_SECTOR_SIZE: u32 = 4096;
+/// IEEE 1619 allows at most 2^20 blocks per sector.
+const MAX_SECTOR_SIZE: u32 = 16 * 1024 * 1024;
+
+fn xts(key: &[u8]) -> Result<Xts128<Aes256>, BVaultError> {
+    if key.len() != XTS_KEY_LENGTH {
+        return Err(BVaultError::new(
+            ErrorCode::BadKeyLength,
+            "AES-256-XTS keys must be 64 bytes",
+        ));
+    }
+    let (data_key, tweak_key) = key.split_at(XTS_KEY_LENGTH / 2);
+    if bool::from(data_key.ct_eq(tweak_key)) {
+        return Err(BVaultError::new(
+            ErrorCode::InvalidKey,
+            "the two halves of an AES-256-XTS key must differ",
+        ));
+    }
+    let cipher = |key| Aes256::new_from_slice(key).expect("AES-256 keys are 32 bytes");
+    Ok(Xts128::new(cipher(data_key), cipher(tweak_key)))
+}
+
+/// The sector size to use, and whether `data` splits into sectors of at
+/// least one block.
+fn sector_size(data: &[u8], sector_size: Option<u32>) -> Result<usize, BVaultError> {
+    let size = sector_size.unwrap_or(DEFAULT_SECTOR_SIZE);
+    if !(BLOCK_SIZE as u32..=MAX_SECTOR_SIZE).contains(&size) {
+        return Err(BVaultError::new(
+            ErrorCode::InvalidArgument,
+            "sector size must be between 16 bytes and 16 MiB",
+        ));
+    }
+    let size = size as usize;
+    let last = data.len() % size;
+    if data.is_empty() || (last != 0 && last < BLOCK_SIZE) {
+        return Err(BVaultError::new(
+            ErrorCode::BadLength,
+            "every sector, the last included, must be at least 16 bytes",
+        ));
+    }
+    Ok(size)
+}
+
+/// Encrypts `plaintext`, one or more consecutive sectors starting at
+/// sector number `first_sector`, with AES-256-XTS. `sector_size` defaults
+/// to 4096 bytes; the last sector may be shorter.
+///
+/// # Errors
+///
+/// - If `key` is not 64 bytes, an [`ErrorCode::BadKeyLength`] error is
+///   returned; if its halves are equal, an [`ErrorCode::InvalidKey`] error.
+/// - If `sector_size` is outside 16 bytes to 16 MiB, an
+///   [`ErrorCode::InvalidArgument`] error is returned.
+/// - If the plaintext is empty or its last sector is shorter than 16
+///   bytes, an [`ErrorCode::BadLength`] error is returned.
+#[wasm_bindgen]
+pub fn aes_xts_encrypt(
+    plaintext: &[u8],
+    key: &[u8],
+    first_sector: u64,
+    sector_size: Option<u32>,
+) -> Result<Vec<u8>, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    let xts = xts(key)?;
+    let size = self::sector_size(plaintext, sector_size)?;
+    let mut out = plaintext.to_vec();
+    xts.encrypt_area(&mut out, size, first_sector.into(), get_tweak_default);
+    Ok(out)
+}
+
+/// Decrypts sectors encrypted by [`aes_xts_encrypt`], given the same key,
+/// first sector number and sector size. Any run of whole sectors can be
+/// decrypted on its own.
+///
+/// Nothing is verified: a wrong key or tampered data yields garbage rather
+/// than an error.
+///
+/// # Errors
+///
+/// See [`aes_xts_encrypt`].
+#[wasm_bindgen]
+pub fn aes_xts_decrypt(
+    ciphertext: &[u8],
+    key: &[u8],
+    first_sector: u64,
+    sector_size: Option<u32>,
+) -> Result<Vec<u8>, BVaultError> {
+    let _operation = crate::utils::set_panic_hook();
+    let xts = xts(key)?;
+    let size = self::sector_size(ciphertext, sector_size)?;
+    let mut out = ciphertext.to_vec();
+    xts.decrypt_area(&mut out, size, first_sector.into(), get_tweak_default);
+    Ok(out)
+}